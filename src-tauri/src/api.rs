@@ -32,15 +32,48 @@ fn get_db_config(app: tauri::AppHandle) -> Result<(String, String), String> {
 // API команды для работы с БД
 
 /// Создание счёта
+/// 
+/// Если класс счёта не указан, он выводится из типа
 #[tauri::command]
 pub async fn create_account(
     app: tauri::AppHandle,
     name: String,
     acc_type: String,
+    acc_class: Option<String>,
 ) -> Result<i64, String> {
     let (db_path, key) = get_db_config(app)?;
-    db::create_account(&db_path, &key, name, acc_type)
-        .map_err(|e| format!("Failed to create account: {}", e))
+    let result = match acc_class {
+        Some(acc_class) => db::AccountClass::parse(&acc_class)
+            .and_then(|acc_class| db::create_account_with_class(&db_path, &key, name, acc_class, acc_type)),
+        None => db::create_account(&db_path, &key, name, acc_type),
+    };
+    result.map_err(|e| format!("Failed to create account: {}", e))
+}
+
+/// Изменение класса и подтипа счёта
+#[tauri::command]
+pub async fn set_account_class(
+    app: tauri::AppHandle,
+    account_id: i64,
+    acc_class: String,
+    acc_type: String,
+) -> Result<(), String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::AccountClass::parse(&acc_class)
+        .and_then(|acc_class| db::set_account_class(&db_path, &key, account_id, acc_class, acc_type))
+        .map_err(|e| format!("Failed to set account class: {}", e))
+}
+
+/// Закрытие счёта
+#[tauri::command]
+pub async fn close_account(
+    app: tauri::AppHandle,
+    account_id: i64,
+    ts: Option<i64>,
+) -> Result<(), String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::close_account(&db_path, &key, account_id, ts)
+        .map_err(|e| format!("Failed to close account: {}", e))
 }
 
 /// Получение списка счетов
@@ -112,6 +145,27 @@ pub async fn get_net_worth(app: tauri::AppHandle) -> Result<f64, String> {
         .map_err(|e| format!("Failed to get net worth: {}", e))
 }
 
+/// Получение Net Worth с отдельными суммами активов и обязательств
+#[tauri::command]
+pub async fn get_net_worth_breakdown(app: tauri::AppHandle) -> Result<db::NetWorth, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_net_worth_breakdown(&db_path, &key)
+        .map_err(|e| format!("Failed to get net worth breakdown: {}", e))
+}
+
+/// История Net Worth на регулярной сетке (day, week, month)
+#[tauri::command]
+pub async fn get_net_worth_history(
+    app: tauri::AppHandle,
+    from: i64,
+    to: i64,
+    interval: db::HistoryInterval,
+) -> Result<Vec<db::NetWorthPoint>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_net_worth_history(&db_path, &key, from, to, interval)
+        .map_err(|e| format!("Failed to get net worth history: {}", e))
+}
+
 /// Получение временного ряда балансов для аккаунта
 #[tauri::command]
 pub async fn get_balance_history(
@@ -123,6 +177,278 @@ pub async fn get_balance_history(
         .map_err(|e| format!("Failed to get balance history: {}", e))
 }
 
+/// Ресемплированная история баланса счёта (day, week, month, quarter)
+#[tauri::command]
+pub async fn get_balance_history_resampled(
+    app: tauri::AppHandle,
+    account_id: i64,
+    from: i64,
+    to: i64,
+    interval: db::HistoryInterval,
+    aggregation: db::BalanceAggregation,
+) -> Result<Vec<db::BalanceBucket>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_balance_history_resampled(&db_path, &key, account_id, from, to, interval, aggregation)
+        .map_err(|e| format!("Failed to get resampled balance history: {}", e))
+}
+
+/// Создание регулярного платежа или поступления
+#[tauri::command]
+pub async fn create_recurring_item(
+    app: tauri::AppHandle,
+    input: db::RecurringItemInput,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::create_recurring_item(&db_path, &key, input)
+        .map_err(|e| format!("Failed to create recurring item: {}", e))
+}
+
+/// Получение списка регулярных элементов
+#[tauri::command]
+pub async fn list_recurring_items(app: tauri::AppHandle) -> Result<Vec<db::RecurringItem>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::list_recurring_items(&db_path, &key)
+        .map_err(|e| format!("Failed to list recurring items: {}", e))
+}
+
+/// Удаление регулярного элемента
+#[tauri::command]
+pub async fn delete_recurring_item(app: tauri::AppHandle, item_id: i64) -> Result<(), String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::delete_recurring_item(&db_path, &key, item_id)
+        .map_err(|e| format!("Failed to delete recurring item: {}", e))
+}
+
+/// Прогноз балансов счетов на горизонт в днях
+#[tauri::command]
+pub async fn forecast_balances(
+    app: tauri::AppHandle,
+    horizon_days: i64,
+) -> Result<Vec<db::AccountForecast>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::forecast_balances(&db_path, &key, horizon_days)
+        .map_err(|e| format!("Failed to forecast balances: {}", e))
+}
+
+/// Предпросмотр импорта CSV-выписки
+#[tauri::command]
+pub async fn preview_csv_import(
+    app: tauri::AppHandle,
+    account_id: i64,
+    content: Vec<u8>,
+    options: db::CsvImportOptions,
+) -> Result<db::ImportPreview, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::preview_csv_import(&db_path, &key, account_id, &content, &options)
+        .map_err(|e| format!("Failed to preview CSV import: {}", e))
+}
+
+/// Импорт CSV-выписки в одной транзакции
+#[tauri::command]
+pub async fn import_csv(
+    app: tauri::AppHandle,
+    account_id: i64,
+    file_name: String,
+    content: Vec<u8>,
+    options: db::CsvImportOptions,
+) -> Result<db::ImportResult, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::import_csv(&db_path, &key, account_id, file_name, &content, &options)
+        .map_err(|e| format!("Failed to import CSV: {}", e))
+}
+
+/// Предпросмотр импорта выписки OFX
+#[tauri::command]
+pub async fn preview_ofx_import(
+    app: tauri::AppHandle,
+    account_id: i64,
+    content: Vec<u8>,
+) -> Result<db::StatementImportPreview, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::preview_ofx_import(&db_path, &key, account_id, &content)
+        .map_err(|e| format!("Failed to preview OFX import: {}", e))
+}
+
+/// Импорт выписки OFX со сверкой остатка
+#[tauri::command]
+pub async fn import_ofx(
+    app: tauri::AppHandle,
+    account_id: i64,
+    file_name: String,
+    content: Vec<u8>,
+) -> Result<db::StatementImportResult, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::import_ofx(&db_path, &key, account_id, file_name, &content)
+        .map_err(|e| format!("Failed to import OFX: {}", e))
+}
+
+/// Предпросмотр импорта выписки camt.053
+#[tauri::command]
+pub async fn preview_camt053_import(
+    app: tauri::AppHandle,
+    account_id: i64,
+    content: Vec<u8>,
+) -> Result<db::StatementImportPreview, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::preview_camt053_import(&db_path, &key, account_id, &content)
+        .map_err(|e| format!("Failed to preview camt.053 import: {}", e))
+}
+
+/// Импорт выписки camt.053 со сверкой остатка
+#[tauri::command]
+pub async fn import_camt053(
+    app: tauri::AppHandle,
+    account_id: i64,
+    file_name: String,
+    content: Vec<u8>,
+) -> Result<db::StatementImportResult, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::import_camt053(&db_path, &key, account_id, file_name, &content)
+        .map_err(|e| format!("Failed to import camt.053: {}", e))
+}
+
+/// Предпросмотр импорта выписки MT940
+#[tauri::command]
+pub async fn preview_mt940_import(
+    app: tauri::AppHandle,
+    account_id: i64,
+    content: Vec<u8>,
+) -> Result<db::StatementImportPreview, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::preview_mt940_import(&db_path, &key, account_id, &content)
+        .map_err(|e| format!("Failed to preview MT940 import: {}", e))
+}
+
+/// Импорт выписки MT940 со сверкой остатка
+#[tauri::command]
+pub async fn import_mt940(
+    app: tauri::AppHandle,
+    account_id: i64,
+    file_name: String,
+    content: Vec<u8>,
+) -> Result<db::StatementImportResult, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::import_mt940(&db_path, &key, account_id, file_name, &content)
+        .map_err(|e| format!("Failed to import MT940: {}", e))
+}
+
+/// Экспорт учёта в beancount или ledger/hledger
+#[tauri::command]
+pub async fn export_plain_text(
+    app: tauri::AppHandle,
+    format: db::PlainTextFormat,
+    currency: String,
+) -> Result<String, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::export_plain_text(&db_path, &key, format, currency)
+        .map_err(|e| format!("Failed to export plain text: {}", e))
+}
+
+/// Импорт файла beancount или ledger/hledger
+#[tauri::command]
+pub async fn import_plain_text(
+    app: tauri::AppHandle,
+    format: db::PlainTextFormat,
+    content: Vec<u8>,
+) -> Result<db::PlainTextImportResult, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::import_plain_text(&db_path, &key, format, &content)
+        .map_err(|e| format!("Failed to import plain text: {}", e))
+}
+
+/// Получение списка пакетов импорта
+#[tauri::command]
+pub async fn list_import_batches(
+    app: tauri::AppHandle,
+    account_id: Option<i64>,
+) -> Result<Vec<db::ImportBatch>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::list_import_batches(&db_path, &key, account_id)
+        .map_err(|e| format!("Failed to list import batches: {}", e))
+}
+
+/// Сохранение профиля импорта
+#[tauri::command]
+pub async fn save_import_profile(
+    app: tauri::AppHandle,
+    name: String,
+    options: db::CsvImportOptions,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::save_import_profile(&db_path, &key, name, options)
+        .map_err(|e| format!("Failed to save import profile: {}", e))
+}
+
+/// Получение списка профилей импорта
+#[tauri::command]
+pub async fn list_import_profiles(app: tauri::AppHandle) -> Result<Vec<db::ImportProfile>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::list_import_profiles(&db_path, &key)
+        .map_err(|e| format!("Failed to list import profiles: {}", e))
+}
+
+/// Удаление профиля импорта
+#[tauri::command]
+pub async fn delete_import_profile(app: tauri::AppHandle, profile_id: i64) -> Result<(), String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::delete_import_profile(&db_path, &key, profile_id)
+        .map_err(|e| format!("Failed to delete import profile: {}", e))
+}
+
+/// Установка налогового признака категории (deductible, interest)
+#[tauri::command]
+pub async fn set_category_tax_kind(
+    app: tauri::AppHandle,
+    category_id: i64,
+    tax_kind: Option<String>,
+) -> Result<(), String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::set_category_tax_kind(&db_path, &key, category_id, tax_kind)
+        .map_err(|e| format!("Failed to set category tax kind: {}", e))
+}
+
+/// Налоговая сводка за год с подписанной выгрузкой в CSV и JSON
+#[tauri::command]
+pub async fn generate_tax_summary(
+    app: tauri::AppHandle,
+    year: i32,
+) -> Result<db::TaxSummaryExport, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::generate_tax_summary(&db_path, &key, year)
+        .map_err(|e| format!("Failed to generate tax summary: {}", e))
+}
+
+/// Проверка подписи выгруженного документа
+#[tauri::command]
+pub async fn verify_signed_export(export: db::SignedExport) -> Result<bool, String> {
+    db::verify_signed_export(&export)
+        .map_err(|e| format!("Failed to verify export: {}", e))
+}
+
+/// Поиск аномалий в расходах
+#[tauri::command]
+pub async fn list_anomalies(
+    app: tauri::AppHandle,
+    options: Option<db::AnomalyOptions>,
+) -> Result<Vec<db::Anomaly>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::list_anomalies(&db_path, &key, options.unwrap_or_default())
+        .map_err(|e| format!("Failed to list anomalies: {}", e))
+}
+
+/// Установка статуса аномалии (open, acknowledged, dismissed)
+#[tauri::command]
+pub async fn set_anomaly_status(
+    app: tauri::AppHandle,
+    operation_id: i64,
+    kind: String,
+    status: String,
+) -> Result<(), String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::set_anomaly_status(&db_path, &key, operation_id, kind, status)
+        .map_err(|e| format!("Failed to set anomaly status: {}", e))
+}
+
 /// Получение структуры активов (распределение по типам)
 #[tauri::command]
 pub async fn get_asset_allocation(
@@ -133,6 +459,839 @@ pub async fn get_asset_allocation(
         .map_err(|e| format!("Failed to get asset allocation: {}", e))
 }
 
+/// Установка целевой доли группы активов
+#[tauri::command]
+pub async fn set_allocation_target(
+    app: tauri::AppHandle,
+    asset_type: String,
+    target_percent: f64,
+    tolerance_percent: f64,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::set_allocation_target(&db_path, &key, asset_type, target_percent, tolerance_percent)
+        .map_err(|e| format!("Failed to set allocation target: {}", e))
+}
+
+/// Получение целевых долей
+#[tauri::command]
+pub async fn list_allocation_targets(app: tauri::AppHandle) -> Result<Vec<db::AllocationTarget>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::list_allocation_targets(&db_path, &key)
+        .map_err(|e| format!("Failed to list allocation targets: {}", e))
+}
+
+/// Удаление целевой доли группы активов
+#[tauri::command]
+pub async fn delete_allocation_target(app: tauri::AppHandle, asset_type: String) -> Result<(), String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::delete_allocation_target(&db_path, &key, asset_type)
+        .map_err(|e| format!("Failed to delete allocation target: {}", e))
+}
+
+/// План ребалансировки к целевой структуре активов
+#[tauri::command]
+pub async fn get_rebalance_plan(
+    app: tauri::AppHandle,
+    contribution: Option<f64>,
+    contributions_only: bool,
+) -> Result<db::RebalancePlan, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_rebalance_plan(&db_path, &key, contribution, contributions_only)
+        .map_err(|e| format!("Failed to get rebalance plan: {}", e))
+}
+
+/// Перевод между собственными счетами
+#[tauri::command]
+pub async fn create_transfer(
+    app: tauri::AppHandle,
+    from_account_id: i64,
+    to_account_id: i64,
+    amount: f64,
+    description: String,
+    ts: Option<i64>,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::create_transfer(&db_path, &key, from_account_id, to_account_id, amount, description, ts)
+        .map_err(|e| format!("Failed to create transfer: {}", e))
+}
+
+/// Получение переводов за период
+#[tauri::command]
+pub async fn list_transfers(
+    app: tauri::AppHandle,
+    from_ts: Option<i64>,
+    to_ts: Option<i64>,
+) -> Result<Vec<db::Transfer>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::list_transfers(&db_path, &key, from_ts, to_ts)
+        .map_err(|e| format!("Failed to list transfers: {}", e))
+}
+
+/// Отчёт о денежном потоке по периодам и группам (account, category, payee)
+#[tauri::command]
+pub async fn get_cash_flow(
+    app: tauri::AppHandle,
+    from: i64,
+    to: i64,
+    interval: db::HistoryInterval,
+    group_by: db::CashFlowGroupBy,
+) -> Result<Vec<db::CashFlow>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_cash_flow(&db_path, &key, from, to, interval, group_by)
+        .map_err(|e| format!("Failed to get cash flow: {}", e))
+}
+
+// Начальные остатки и сверки баланса
+
+/// Установка начального остатка счёта
+#[tauri::command]
+pub async fn set_opening_balance(
+    app: tauri::AppHandle,
+    account_id: i64,
+    balance: f64,
+    ts: i64,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::set_opening_balance(&db_path, &key, account_id, balance, ts)
+        .map_err(|e| format!("Failed to set opening balance: {}", e))
+}
+
+/// Получение начального остатка счёта
+#[tauri::command]
+pub async fn get_opening_balance(
+    app: tauri::AppHandle,
+    account_id: i64,
+) -> Result<Option<db::OpeningBalance>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_opening_balance(&db_path, &key, account_id)
+        .map_err(|e| format!("Failed to get opening balance: {}", e))
+}
+
+/// Добавление сверки баланса
+#[tauri::command]
+pub async fn add_balance_assertion(
+    app: tauri::AppHandle,
+    account_id: i64,
+    expected_balance: f64,
+    ts: i64,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::add_balance_assertion(&db_path, &key, account_id, expected_balance, ts)
+        .map_err(|e| format!("Failed to add balance assertion: {}", e))
+}
+
+/// Получение списка сверок баланса
+#[tauri::command]
+pub async fn list_balance_assertions(
+    app: tauri::AppHandle,
+    account_id: Option<i64>,
+) -> Result<Vec<db::BalanceAssertion>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::list_balance_assertions(&db_path, &key, account_id)
+        .map_err(|e| format!("Failed to list balance assertions: {}", e))
+}
+
+/// Проверка сверок баланса (отчёт с первой не прошедшей сверкой)
+#[tauri::command]
+pub async fn check_balance_assertions(
+    app: tauri::AppHandle,
+    account_id: Option<i64>,
+) -> Result<db::BalanceAssertionReport, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::check_balance_assertions(&db_path, &key, account_id)
+        .map_err(|e| format!("Failed to check balance assertions: {}", e))
+}
+
+// Сверка с банковской выпиской
+
+/// Начало сверки счёта с выпиской
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn start_reconciliation(
+    app: tauri::AppHandle,
+    account_id: i64,
+    statement_name: String,
+    period_start: i64,
+    period_end: i64,
+    closing_balance: Option<f64>,
+    lines: Vec<db::StatementLineInput>,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::start_reconciliation(&db_path, &key, account_id, statement_name, period_start, period_end, closing_balance, lines)
+        .map_err(|e| format!("Failed to start reconciliation: {}", e))
+}
+
+/// Получение сессии сверки
+#[tauri::command]
+pub async fn get_reconciliation(
+    app: tauri::AppHandle,
+    session_id: i64,
+) -> Result<db::ReconciliationSession, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_reconciliation(&db_path, &key, session_id)
+        .map_err(|e| format!("Failed to get reconciliation: {}", e))
+}
+
+/// Получение списка сессий сверки
+#[tauri::command]
+pub async fn list_reconciliations(
+    app: tauri::AppHandle,
+    account_id: Option<i64>,
+) -> Result<Vec<db::ReconciliationSession>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::list_reconciliations(&db_path, &key, account_id)
+        .map_err(|e| format!("Failed to list reconciliations: {}", e))
+}
+
+/// Автоматическое сопоставление строк выписки с операциями
+#[tauri::command]
+pub async fn auto_match_statement(
+    app: tauri::AppHandle,
+    session_id: i64,
+    amount_tolerance: f64,
+    date_tolerance_days: i64,
+) -> Result<Vec<db::StatementMatch>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::auto_match_statement(&db_path, &key, session_id, amount_tolerance, date_tolerance_days)
+        .map_err(|e| format!("Failed to match statement: {}", e))
+}
+
+/// Подтверждение сопоставления строки выписки с операцией
+#[tauri::command]
+pub async fn confirm_statement_match(
+    app: tauri::AppHandle,
+    line_id: i64,
+    operation_id: i64,
+) -> Result<(), String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::confirm_statement_match(&db_path, &key, line_id, operation_id)
+        .map_err(|e| format!("Failed to confirm statement match: {}", e))
+}
+
+/// Создание недостающей операции по строке выписки
+#[tauri::command]
+pub async fn create_operation_from_statement_line(
+    app: tauri::AppHandle,
+    line_id: i64,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::create_operation_from_statement_line(&db_path, &key, line_id)
+        .map_err(|e| format!("Failed to create operation: {}", e))
+}
+
+/// Завершение сверки
+#[tauri::command]
+pub async fn complete_reconciliation(
+    app: tauri::AppHandle,
+    session_id: i64,
+) -> Result<db::ReconciliationSession, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::complete_reconciliation(&db_path, &key, session_id)
+        .map_err(|e| format!("Failed to complete reconciliation: {}", e))
+}
+
+// Ценные бумаги и инвестиционные счета
+
+/// Регистрация ценной бумаги
+#[tauri::command]
+pub async fn create_security(
+    app: tauri::AppHandle,
+    ticker: String,
+    isin: Option<String>,
+    name: String,
+    asset_class: String,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::create_security(&db_path, &key, ticker, isin, name, asset_class)
+        .map_err(|e| format!("Failed to create security: {}", e))
+}
+
+/// Получение списка ценных бумаг
+#[tauri::command]
+pub async fn list_securities(app: tauri::AppHandle) -> Result<Vec<db::Security>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::list_securities(&db_path, &key)
+        .map_err(|e| format!("Failed to list securities: {}", e))
+}
+
+/// Добавление котировки ценной бумаги
+#[tauri::command]
+pub async fn add_security_price(
+    app: tauri::AppHandle,
+    security_id: i64,
+    price: f64,
+    ts: i64,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::add_security_price(&db_path, &key, security_id, price, ts)
+        .map_err(|e| format!("Failed to add security price: {}", e))
+}
+
+/// Получение истории цен ценной бумаги
+#[tauri::command]
+pub async fn get_price_history(
+    app: tauri::AppHandle,
+    security_id: i64,
+) -> Result<Vec<db::SecurityPrice>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_price_history(&db_path, &key, security_id)
+        .map_err(|e| format!("Failed to get price history: {}", e))
+}
+
+/// Добавление инвестиционной сделки (buy, sell, dividend)
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn add_investment_transaction(
+    app: tauri::AppHandle,
+    account_id: i64,
+    security_id: i64,
+    kind: String,
+    quantity: f64,
+    price: f64,
+    fee: f64,
+    ts: i64,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::add_investment_transaction(&db_path, &key, account_id, security_id, kind, quantity, price, fee, ts)
+        .map_err(|e| format!("Failed to add investment transaction: {}", e))
+}
+
+/// Получение инвестиционных сделок по счёту
+#[tauri::command]
+pub async fn get_investment_transactions(
+    app: tauri::AppHandle,
+    account_id: i64,
+) -> Result<Vec<db::InvestmentTransaction>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_investment_transactions(&db_path, &key, account_id)
+        .map_err(|e| format!("Failed to get investment transactions: {}", e))
+}
+
+/// Получение позиций по ценным бумагам с рыночной оценкой
+#[tauri::command]
+pub async fn get_holdings(
+    app: tauri::AppHandle,
+    account_id: Option<i64>,
+) -> Result<Vec<db::Holding>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_holdings(&db_path, &key, account_id)
+        .map_err(|e| format!("Failed to get holdings: {}", e))
+}
+
+// Налоговые лоты и реализованный доход
+
+/// Продажа ценной бумаги с выбором метода списания лотов (fifo, lifo, specific)
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn sell_security(
+    app: tauri::AppHandle,
+    account_id: i64,
+    security_id: i64,
+    quantity: f64,
+    price: f64,
+    fee: f64,
+    ts: i64,
+    lot_method: String,
+    lot_selection: Option<Vec<db::LotSelection>>,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::sell_security(&db_path, &key, account_id, security_id, quantity, price, fee, ts, lot_method, lot_selection)
+        .map_err(|e| format!("Failed to sell security: {}", e))
+}
+
+/// Получение налоговых лотов счёта
+#[tauri::command]
+pub async fn get_tax_lots(
+    app: tauri::AppHandle,
+    account_id: i64,
+    security_id: Option<i64>,
+) -> Result<Vec<db::TaxLot>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_tax_lots(&db_path, &key, account_id, security_id)
+        .map_err(|e| format!("Failed to get tax lots: {}", e))
+}
+
+/// Отчёт о реализованном доходе за год
+#[tauri::command]
+pub async fn get_realized_gains(
+    app: tauri::AppHandle,
+    year: i32,
+) -> Result<db::RealizedGainsReport, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_realized_gains(&db_path, &key, year)
+        .map_err(|e| format!("Failed to get realized gains: {}", e))
+}
+
+/// Доходность инвестиционных счетов и портфеля (XIRR и TWR)
+#[tauri::command]
+pub async fn get_investment_returns(
+    app: tauri::AppHandle,
+    from: i64,
+    to: i64,
+) -> Result<db::PortfolioReturns, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_investment_returns(&db_path, &key, from, to)
+        .map_err(|e| format!("Failed to get investment returns: {}", e))
+}
+
+// Кредиты и ипотеки
+
+/// Регистрация кредита или ипотеки
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn create_loan(
+    app: tauri::AppHandle,
+    account_id: i64,
+    name: String,
+    principal: f64,
+    annual_rate: f64,
+    term_months: i64,
+    start_ts: i64,
+    funding_account_id: Option<i64>,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::create_loan(&db_path, &key, account_id, name, principal, annual_rate, term_months, start_ts, funding_account_id)
+        .map_err(|e| format!("Failed to create loan: {}", e))
+}
+
+/// Получение списка кредитов
+#[tauri::command]
+pub async fn list_loans(app: tauri::AppHandle) -> Result<Vec<db::Loan>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::list_loans(&db_path, &key)
+        .map_err(|e| format!("Failed to list loans: {}", e))
+}
+
+/// Проведение очередного платежа по кредиту
+#[tauri::command]
+pub async fn record_loan_payment(
+    app: tauri::AppHandle,
+    loan_id: i64,
+    from_account_id: i64,
+    extra_principal: f64,
+    ts: i64,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::record_loan_payment(&db_path, &key, loan_id, from_account_id, extra_principal, ts)
+        .map_err(|e| format!("Failed to record loan payment: {}", e))
+}
+
+/// Получение проведённых платежей по кредиту
+#[tauri::command]
+pub async fn get_loan_payments(
+    app: tauri::AppHandle,
+    loan_id: i64,
+) -> Result<Vec<db::LoanPayment>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_loan_payments(&db_path, &key, loan_id)
+        .map_err(|e| format!("Failed to get loan payments: {}", e))
+}
+
+/// График погашения кредита
+#[tauri::command]
+pub async fn get_amortization_schedule(
+    app: tauri::AppHandle,
+    loan_id: i64,
+    extra_monthly: Option<f64>,
+) -> Result<Vec<db::AmortizationRow>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_amortization_schedule(&db_path, &key, loan_id, extra_monthly)
+        .map_err(|e| format!("Failed to get amortization schedule: {}", e))
+}
+
+/// Прогноз погашения кредита с досрочными платежами
+#[tauri::command]
+pub async fn get_loan_payoff_projection(
+    app: tauri::AppHandle,
+    loan_id: i64,
+    extra_monthly: f64,
+) -> Result<db::LoanPayoffProjection, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_loan_payoff_projection(&db_path, &key, loan_id, extra_monthly)
+        .map_err(|e| format!("Failed to get loan payoff projection: {}", e))
+}
+
+// Контрагенты и нормализация описаний
+
+/// Создание контрагента
+#[tauri::command]
+pub async fn create_payee(app: tauri::AppHandle, name: String) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::create_payee(&db_path, &key, name)
+        .map_err(|e| format!("Failed to create payee: {}", e))
+}
+
+/// Получение списка контрагентов
+#[tauri::command]
+pub async fn list_payees(app: tauri::AppHandle) -> Result<Vec<db::Payee>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::list_payees(&db_path, &key)
+        .map_err(|e| format!("Failed to list payees: {}", e))
+}
+
+/// Добавление правила нормализации для контрагента
+#[tauri::command]
+pub async fn add_payee_rule(
+    app: tauri::AppHandle,
+    payee_id: i64,
+    kind: String,
+    pattern: String,
+    priority: i64,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::add_payee_rule(&db_path, &key, payee_id, kind, pattern, priority)
+        .map_err(|e| format!("Failed to add payee rule: {}", e))
+}
+
+/// Получение правил нормализации
+#[tauri::command]
+pub async fn list_payee_rules(
+    app: tauri::AppHandle,
+    payee_id: Option<i64>,
+) -> Result<Vec<db::PayeeRule>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::list_payee_rules(&db_path, &key, payee_id)
+        .map_err(|e| format!("Failed to list payee rules: {}", e))
+}
+
+/// Удаление правила нормализации
+#[tauri::command]
+pub async fn delete_payee_rule(app: tauri::AppHandle, rule_id: i64) -> Result<(), String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::delete_payee_rule(&db_path, &key, rule_id)
+        .map_err(|e| format!("Failed to delete payee rule: {}", e))
+}
+
+/// Ручное назначение контрагента операции
+#[tauri::command]
+pub async fn set_operation_payee(
+    app: tauri::AppHandle,
+    operation_id: i64,
+    payee_id: Option<i64>,
+) -> Result<(), String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::set_operation_payee(&db_path, &key, operation_id, payee_id)
+        .map_err(|e| format!("Failed to set operation payee: {}", e))
+}
+
+/// Повторное применение правил нормализации к существующим операциям
+#[tauri::command]
+pub async fn apply_payee_rules(app: tauri::AppHandle, overwrite: bool) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::apply_payee_rules(&db_path, &key, overwrite)
+        .map_err(|e| format!("Failed to apply payee rules: {}", e))
+}
+
+/// Слияние контрагентов
+#[tauri::command]
+pub async fn merge_payees(
+    app: tauri::AppHandle,
+    target_id: i64,
+    source_ids: Vec<i64>,
+) -> Result<Vec<db::PayeeMerge>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::merge_payees(&db_path, &key, target_id, source_ids)
+        .map_err(|e| format!("Failed to merge payees: {}", e))
+}
+
+/// Расходы по контрагентам за период
+#[tauri::command]
+pub async fn get_spending_by_payee(
+    app: tauri::AppHandle,
+    from_ts: Option<i64>,
+    to_ts: Option<i64>,
+) -> Result<Vec<db::PayeeSpending>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_spending_by_payee(&db_path, &key, from_ts, to_ts)
+        .map_err(|e| format!("Failed to get spending by payee: {}", e))
+}
+
+// Категории, теги и правила автокатегоризации
+
+/// Создание категории
+#[tauri::command]
+pub async fn create_category(app: tauri::AppHandle, name: String) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::create_category(&db_path, &key, name)
+        .map_err(|e| format!("Failed to create category: {}", e))
+}
+
+/// Получение списка категорий
+#[tauri::command]
+pub async fn list_categories(app: tauri::AppHandle) -> Result<Vec<db::Category>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::list_categories(&db_path, &key)
+        .map_err(|e| format!("Failed to list categories: {}", e))
+}
+
+/// Создание тега
+#[tauri::command]
+pub async fn create_tag(app: tauri::AppHandle, name: String) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::create_tag(&db_path, &key, name)
+        .map_err(|e| format!("Failed to create tag: {}", e))
+}
+
+/// Получение списка тегов
+#[tauri::command]
+pub async fn list_tags(app: tauri::AppHandle) -> Result<Vec<db::Tag>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::list_tags(&db_path, &key)
+        .map_err(|e| format!("Failed to list tags: {}", e))
+}
+
+/// Получение тегов операции
+#[tauri::command]
+pub async fn get_operation_tags(app: tauri::AppHandle, operation_id: i64) -> Result<Vec<db::Tag>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_operation_tags(&db_path, &key, operation_id)
+        .map_err(|e| format!("Failed to get operation tags: {}", e))
+}
+
+/// Добавление тега операции
+#[tauri::command]
+pub async fn add_operation_tag(app: tauri::AppHandle, operation_id: i64, tag_id: i64) -> Result<(), String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::add_operation_tag(&db_path, &key, operation_id, tag_id)
+        .map_err(|e| format!("Failed to add operation tag: {}", e))
+}
+
+/// Снятие тега с операции
+#[tauri::command]
+pub async fn remove_operation_tag(app: tauri::AppHandle, operation_id: i64, tag_id: i64) -> Result<(), String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::remove_operation_tag(&db_path, &key, operation_id, tag_id)
+        .map_err(|e| format!("Failed to remove operation tag: {}", e))
+}
+
+/// Ручное назначение категории операции
+#[tauri::command]
+pub async fn set_operation_category(
+    app: tauri::AppHandle,
+    operation_id: i64,
+    category_id: Option<i64>,
+) -> Result<(), String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::set_operation_category(&db_path, &key, operation_id, category_id)
+        .map_err(|e| format!("Failed to set operation category: {}", e))
+}
+
+/// Создание правила автокатегоризации
+#[tauri::command]
+pub async fn create_categorization_rule(
+    app: tauri::AppHandle,
+    rule: db::CategorizationRuleInput,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::create_categorization_rule(&db_path, &key, rule)
+        .map_err(|e| format!("Failed to create categorization rule: {}", e))
+}
+
+/// Изменение правила автокатегоризации
+#[tauri::command]
+pub async fn update_categorization_rule(
+    app: tauri::AppHandle,
+    rule_id: i64,
+    rule: db::CategorizationRuleInput,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::update_categorization_rule(&db_path, &key, rule_id, rule)
+        .map_err(|e| format!("Failed to update categorization rule: {}", e))
+}
+
+/// Удаление правила автокатегоризации
+#[tauri::command]
+pub async fn delete_categorization_rule(app: tauri::AppHandle, rule_id: i64) -> Result<(), String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::delete_categorization_rule(&db_path, &key, rule_id)
+        .map_err(|e| format!("Failed to delete categorization rule: {}", e))
+}
+
+/// Получение правил автокатегоризации
+#[tauri::command]
+pub async fn list_categorization_rules(app: tauri::AppHandle) -> Result<Vec<db::CategorizationRule>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::list_categorization_rules(&db_path, &key)
+        .map_err(|e| format!("Failed to list categorization rules: {}", e))
+}
+
+/// Применение правил к операциям за период (с предпросмотром)
+#[tauri::command]
+pub async fn apply_categorization_rules(
+    app: tauri::AppHandle,
+    from_ts: i64,
+    to_ts: i64,
+    dry_run: bool,
+) -> Result<Vec<db::RuleChange>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::apply_categorization_rules(&db_path, &key, from_ts, to_ts, dry_run)
+        .map_err(|e| format!("Failed to apply categorization rules: {}", e))
+}
+
+// Разбивка операций
+
+/// Разбивка операции на строки по категориям
+#[tauri::command]
+pub async fn set_operation_splits(
+    app: tauri::AppHandle,
+    operation_id: i64,
+    splits: Vec<db::SplitLineInput>,
+) -> Result<Vec<db::SplitLine>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::set_operation_splits(&db_path, &key, operation_id, splits)
+        .map_err(|e| format!("Failed to set operation splits: {}", e))
+}
+
+/// Получение строк разбивки операции
+#[tauri::command]
+pub async fn get_operation_splits(
+    app: tauri::AppHandle,
+    operation_id: i64,
+) -> Result<Vec<db::SplitLine>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_operation_splits(&db_path, &key, operation_id)
+        .map_err(|e| format!("Failed to get operation splits: {}", e))
+}
+
+/// Расходы по категориям за период (с учётом разбивки)
+#[tauri::command]
+pub async fn get_spending_by_category(
+    app: tauri::AppHandle,
+    from_ts: Option<i64>,
+    to_ts: Option<i64>,
+) -> Result<Vec<db::CategorySpending>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_spending_by_category(&db_path, &key, from_ts, to_ts)
+        .map_err(|e| format!("Failed to get spending by category: {}", e))
+}
+
+// Полнотекстовый поиск по операциям
+
+/// Установка заметки к операции
+#[tauri::command]
+pub async fn set_operation_notes(
+    app: tauri::AppHandle,
+    operation_id: i64,
+    notes: Option<String>,
+) -> Result<(), String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::set_operation_notes(&db_path, &key, operation_id, notes)
+        .map_err(|e| format!("Failed to set operation notes: {}", e))
+}
+
+/// Полнотекстовый поиск по операциям
+#[tauri::command]
+pub async fn search_operations(
+    app: tauri::AppHandle,
+    query: String,
+    filters: Option<db::OperationFilters>,
+    limit: Option<i64>,
+) -> Result<Vec<db::OperationSearchHit>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::search_operations(&db_path, &key, query, filters.unwrap_or_default(), limit)
+        .map_err(|e| format!("Failed to search operations: {}", e))
+}
+
+/// Постраничная выборка операций с фильтрами и итогами
+#[tauri::command]
+pub async fn query_operations(
+    app: tauri::AppHandle,
+    query: db::OperationQuery,
+) -> Result<db::OperationPage, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::query_operations(&db_path, &key, query)
+        .map_err(|e| format!("Failed to query operations: {}", e))
+}
+
+// Вложения
+
+/// Прикрепление файла к операции или счёту
+#[tauri::command]
+pub async fn add_attachment(
+    app: tauri::AppHandle,
+    owner_type: String,
+    owner_id: i64,
+    file_name: String,
+    mime_type: String,
+    data: Vec<u8>,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::add_attachment(&db_path, &key, owner_type, owner_id, file_name, mime_type, data)
+        .map_err(|e| format!("Failed to add attachment: {}", e))
+}
+
+/// Получение списка вложений сущности
+#[tauri::command]
+pub async fn list_attachments(
+    app: tauri::AppHandle,
+    owner_type: String,
+    owner_id: i64,
+) -> Result<Vec<db::Attachment>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::list_attachments(&db_path, &key, owner_type, owner_id)
+        .map_err(|e| format!("Failed to list attachments: {}", e))
+}
+
+/// Получение вложения с содержимым и проверкой целостности
+#[tauri::command]
+pub async fn get_attachment(
+    app: tauri::AppHandle,
+    attachment_id: i64,
+) -> Result<db::AttachmentContent, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_attachment(&db_path, &key, attachment_id)
+        .map_err(|e| format!("Failed to get attachment: {}", e))
+}
+
+/// Удаление вложения
+#[tauri::command]
+pub async fn delete_attachment(app: tauri::AppHandle, attachment_id: i64) -> Result<(), String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::delete_attachment(&db_path, &key, attachment_id)
+        .map_err(|e| format!("Failed to delete attachment: {}", e))
+}
+
+// Цели накоплений
+
+/// Создание цели накоплений
+#[tauri::command]
+pub async fn create_goal(
+    app: tauri::AppHandle,
+    name: String,
+    target_amount: f64,
+    target_ts: i64,
+    account_ids: Vec<i64>,
+    tag_ids: Vec<i64>,
+) -> Result<i64, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::create_goal(&db_path, &key, name, target_amount, target_ts, account_ids, tag_ids)
+        .map_err(|e| format!("Failed to create goal: {}", e))
+}
+
+/// Получение списка целей
+#[tauri::command]
+pub async fn list_goals(app: tauri::AppHandle) -> Result<Vec<db::Goal>, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::list_goals(&db_path, &key)
+        .map_err(|e| format!("Failed to list goals: {}", e))
+}
+
+/// Удаление цели
+#[tauri::command]
+pub async fn delete_goal(app: tauri::AppHandle, goal_id: i64) -> Result<(), String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::delete_goal(&db_path, &key, goal_id)
+        .map_err(|e| format!("Failed to delete goal: {}", e))
+}
+
+/// Прогресс по цели накоплений
+#[tauri::command]
+pub async fn get_goal_progress(app: tauri::AppHandle, goal_id: i64) -> Result<db::GoalProgress, String> {
+    let (db_path, key) = get_db_config(app)?;
+    db::get_goal_progress(&db_path, &key, goal_id)
+        .map_err(|e| format!("Failed to get goal progress: {}", e))
+}
+
 // Команды верификации подписей
 
 /// Верификация подписи одной записи version_log
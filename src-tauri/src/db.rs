@@ -1,8 +1,91 @@
-use rusqlite::{Connection, Result as SqlResult};
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use thiserror::Error;
 
+/// Допустимое расхождение при сравнении денежных сумм (полкопейки)
+const AMOUNT_EPSILON: f64 = 0.005;
+
+/// Количество секунд в сутках
+const SECONDS_PER_DAY: i64 = 86400;
+
+/// Средняя длина месяца в днях (для пересчёта темпов в месячные)
+const DAYS_PER_MONTH: f64 = 30.4375;
+
+/// Окно (в днях), по которому оценивается темп пополнения цели
+const GOAL_VELOCITY_WINDOW_DAYS: i64 = 90;
+
+/// Максимальное количество периодов в регулярной сетке временного ряда
+const HISTORY_MAX_BUCKETS: usize = 10_000;
+
+/// Окно истории для модели тренда прогноза балансов
+const FORECAST_LOOKBACK_DAYS: i64 = 90;
+
+/// Максимальный горизонт прогноза балансов
+const FORECAST_MAX_HORIZON_DAYS: i64 = 730;
+
+/// Квантиль нормального распределения для 90% доверительного интервала прогноза
+const FORECAST_BAND_Z: f64 = 1.645;
+
+/// Порог робастной z-оценки суммы для аномалии
+const ANOMALY_SCORE_THRESHOLD: f64 = 3.5;
+
+/// Минимум предыдущих операций группы для оценки типичного диапазона
+const ANOMALY_MIN_HISTORY: usize = 5;
+
+/// Окно поиска похожих списаний-дубликатов по умолчанию
+const ANOMALY_DUPLICATE_WINDOW_DAYS: i64 = 3;
+
+/// Длина года в днях для годовой доходности
+const DAYS_PER_YEAR: f64 = 365.0;
+
+/// Точность и верхняя граница поиска ставки XIRR
+const XIRR_TOLERANCE: f64 = 1e-10;
+const XIRR_MAX_RATE: f64 = 1e6;
+
+/// Количество результатов полнотекстового поиска по умолчанию
+const SEARCH_DEFAULT_LIMIT: i64 = 50;
+
+/// Размер страницы выборки операций по умолчанию и максимальный
+const OPERATION_PAGE_DEFAULT_LIMIT: i64 = 100;
+const OPERATION_PAGE_MAX_LIMIT: i64 = 1000;
+
+/// Допустимая погрешность при сравнении количества ценных бумаг
+const QUANTITY_EPSILON: f64 = 1e-9;
+
+/// Максимальный срок кредита в месяцах (100 лет)
+const MAX_LOAN_TERM_MONTHS: i64 = 1200;
+
+/// Срок владения (в днях), после которого доход считается долгосрочным
+const LONG_TERM_HOLDING_DAYS: i64 = 365;
+
+/// Допустимые классы активов для ценных бумаг
+pub const SECURITY_ASSET_CLASSES: [&str; 7] = ["stock", "bond", "etf", "fund", "crypto", "commodity", "other"];
+
+/// Методы списания налоговых лотов при продаже
+pub const LOT_METHODS: [&str; 3] = ["fifo", "lifo", "specific"];
+
+/// Виды правил нормализации описаний операций в контрагентов
+pub const PAYEE_RULE_KINDS: [&str; 2] = ["alias", "regex"];
+
+/// Виды аномалий в расходах
+pub const ANOMALY_KINDS: [&str; 3] = ["outlier_amount", "duplicate", "new_payee_large"];
+
+/// Статусы аномалий (open - состояние по умолчанию, без записи в anomaly_states)
+pub const ANOMALY_STATUSES: [&str; 3] = ["open", "acknowledged", "dismissed"];
+
+/// Налоговые признаки категорий: вычитаемые расходы и процентный доход
+pub const TAX_CATEGORY_KINDS: [&str; 2] = ["deductible", "interest"];
+
+/// Сущности, к которым можно прикреплять файлы
+pub const ATTACHMENT_OWNER_TYPES: [&str; 2] = ["operation", "account"];
+
+/// Максимальный размер одного вложения (10 МБ)
+pub const ATTACHMENT_MAX_SIZE: usize = 10 * 1024 * 1024;
+
+/// Максимальный суммарный размер вложений одной сущности (50 МБ)
+pub const ATTACHMENT_MAX_TOTAL_SIZE: usize = 50 * 1024 * 1024;
+
 #[derive(Debug, Error)]
 pub enum DbError {
     #[error("Database error: {0}")]
@@ -13,6 +96,9 @@ pub enum DbError {
     
     #[error("Migration error: {0}")]
     MigrationError(String),
+    
+    #[error("Validation error: {0}")]
+    ValidationError(String),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -26,13 +112,83 @@ pub struct DbResult {
     pub message: String,
 }
 
+/// Класс счёта в плане счетов
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountClass {
+    #[default]
+    Asset,
+    Liability,
+    Equity,
+    Income,
+    Expense,
+}
+
+impl AccountClass {
+    /// Строковое представление класса (как в БД)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountClass::Asset => "asset",
+            AccountClass::Liability => "liability",
+            AccountClass::Equity => "equity",
+            AccountClass::Income => "income",
+            AccountClass::Expense => "expense",
+        }
+    }
+    
+    /// Разбор строкового представления класса
+    pub fn parse(value: &str) -> Result<Self, DbError> {
+        match value {
+            "asset" => Ok(AccountClass::Asset),
+            "liability" => Ok(AccountClass::Liability),
+            "equity" => Ok(AccountClass::Equity),
+            "income" => Ok(AccountClass::Income),
+            "expense" => Ok(AccountClass::Expense),
+            _ => Err(DbError::ValidationError(format!("Unknown account class: {}", value))),
+        }
+    }
+    
+    /// Класс по умолчанию для строкового типа счёта
+    /// 
+    /// Используется при миграции старых счетов и при создании счёта без явного класса.
+    /// Неизвестные типы считаются активами.
+    pub fn infer_from_type(acc_type: &str) -> Self {
+        match acc_type.to_lowercase().as_str() {
+            "credit" | "credit_card" | "creditcard" | "loan" | "mortgage" | "debt" | "liability" => AccountClass::Liability,
+            "equity" => AccountClass::Equity,
+            "income" => AccountClass::Income,
+            "expense" => AccountClass::Expense,
+            _ => AccountClass::Asset,
+        }
+    }
+}
+
+impl rusqlite::types::ToSql for AccountClass {
+    fn to_sql(&self) -> SqlResult<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl rusqlite::types::FromSql for AccountClass {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        let text = value.as_str()?;
+        AccountClass::parse(text).map_err(|e| rusqlite::types::FromSqlError::Other(Box::new(e)))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Account {
     pub id: i64,
     pub name: String,
+    #[serde(rename = "class", default)]
+    pub acc_class: AccountClass,
+    /// Подтип счёта внутри класса (cash, bank, credit_card, ...)
     #[serde(rename = "type")]
     pub acc_type: String,
     pub created_at: i64,
+    /// Момент закрытия счёта; операции позже него не принимаются
+    #[serde(default)]
+    pub closed_at: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,6 +198,13 @@ pub struct Operation {
     pub amount: f64,
     pub description: String,
     pub ts: i64,
+    /// Контрагент, определённый по описанию операции
+    #[serde(default)]
+    pub payee_id: Option<i64>,
+    #[serde(default)]
+    pub category_id: Option<i64>,
+    #[serde(default)]
+    pub notes: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -52,6 +215,96 @@ pub struct State {
     pub ts: i64,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NetWorth {
+    pub assets: f64,
+    pub liabilities: f64,
+    pub net_worth: f64,
+}
+
+/// Шаг регулярной сетки временных рядов
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryInterval {
+    Day,
+    Week,
+    Month,
+    Quarter,
+}
+
+impl HistoryInterval {
+    /// Строковое представление интервала (как в БД)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HistoryInterval::Day => "day",
+            HistoryInterval::Week => "week",
+            HistoryInterval::Month => "month",
+            HistoryInterval::Quarter => "quarter",
+        }
+    }
+    
+    /// Разбор строкового представления интервала
+    pub fn parse(value: &str) -> Result<Self, DbError> {
+        match value {
+            "day" => Ok(HistoryInterval::Day),
+            "week" => Ok(HistoryInterval::Week),
+            "month" => Ok(HistoryInterval::Month),
+            "quarter" => Ok(HistoryInterval::Quarter),
+            _ => Err(DbError::ValidationError(format!("Unknown interval: {}", value))),
+        }
+    }
+}
+
+impl rusqlite::types::ToSql for HistoryInterval {
+    fn to_sql(&self) -> SqlResult<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl rusqlite::types::FromSql for HistoryInterval {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        let text = value.as_str()?;
+        HistoryInterval::parse(text).map_err(|e| rusqlite::types::FromSqlError::Other(Box::new(e)))
+    }
+}
+
+/// Агрегация баланса внутри периода при ресемплинге
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BalanceAggregation {
+    /// Последний баланс на конец периода
+    Last,
+    /// Открытие, максимум, минимум и закрытие за период
+    Ohlc,
+    /// Среднее, взвешенное по времени
+    Average,
+}
+
+/// Период ресемплированной истории баланса
+/// 
+/// `value` - значение выбранной агрегации (для ohlc - закрытие);
+/// `open`, `high`, `low`, `close` заполняются только для ohlc.
+/// `filled` - в периоде не было снимков, значение перенесено из предыдущего.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BalanceBucket {
+    pub ts: i64,
+    pub value: f64,
+    pub open: Option<f64>,
+    pub high: Option<f64>,
+    pub low: Option<f64>,
+    pub close: Option<f64>,
+    pub filled: bool,
+}
+
+/// Точка истории Net Worth: состояние на конец периода [ts, следующая точка)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NetWorthPoint {
+    pub ts: i64,
+    pub assets: f64,
+    pub liabilities: f64,
+    pub net_worth: f64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AssetAllocation {
     #[serde(rename = "type")]
@@ -60,762 +313,11993 @@ pub struct AssetAllocation {
     pub account_count: i64,
 }
 
+/// Доходность инвестиционного счёта (или портфеля) за период
+/// 
+/// `account_id = None` - весь портфель. `xirr` - денежно-взвешенная
+/// годовая доходность, `twr` - взвешенная по времени доходность за период,
+/// `twr_annualized` - в пересчёте на год (для периодов от года).
+/// Если доходность не определена, значение `None`, а причина - в `*_error`.
 #[derive(Debug, Serialize, Deserialize)]
-pub struct VersionLogRecord {
-    pub id: i64,
-    pub entity: String,
-    pub entity_id: i64,
-    pub action: String,
-    pub payload: String,
-    pub ts: i64,
+pub struct InvestmentReturn {
+    pub account_id: Option<i64>,
+    pub from: i64,
+    pub to: i64,
+    pub start_value: f64,
+    pub end_value: f64,
+    pub net_contributions: f64,
+    pub gain: f64,
+    pub xirr: Option<f64>,
+    pub xirr_error: Option<String>,
+    pub twr: Option<f64>,
+    pub twr_annualized: Option<f64>,
+    pub twr_error: Option<String>,
 }
 
-// Вспомогательные функции для сериализации
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PortfolioReturns {
+    pub accounts: Vec<InvestmentReturn>,
+    pub portfolio: InvestmentReturn,
+}
 
-/// Сериализация сущности в JSON-строку
-/// 
-/// Используется для создания payload в version_log
-/// 
-/// # Примеры
+/// Целевая доля группы активов с допустимым отклонением
 /// 
-/// ```
-/// let account = Account { id: 1, name: "Test".to_string(), acc_type: "cash".to_string(), created_at: 123456 };
-/// let json = serialize_entity(&account).unwrap();
-/// // json = '{"id":1,"name":"Test","type":"cash","created_at":123456}'
-/// ```
-pub fn serialize_entity<T: Serialize>(entity: &T) -> Result<String, DbError> {
-    serde_json::to_string(entity)
-        .map_err(|e| DbError::InitError(format!("Serialization error: {}", e)))
+/// `asset_type` - тип из `get_asset_allocation` (тип счёта или класс бумаг).
+/// Доли в процентах; группа в полосе, если |доля - цель| <= `tolerance_percent`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AllocationTarget {
+    pub id: i64,
+    pub asset_type: String,
+    pub target_percent: f64,
+    pub tolerance_percent: f64,
+    pub updated_at: i64,
 }
 
-/// Инициализация базы данных с шифрованием
-pub fn init_db(path: &str, key: &str) -> Result<(), DbError> {
-    // Проверяем и создаем директорию если нужно
-    if let Some(parent) = Path::new(path).parent() {
-        if !parent.exists() {
-            std::fs::create_dir_all(parent)
-                .map_err(|e| DbError::InitError(format!("Failed to create directory: {}", e)))?;
-        }
-    }
-
-    // Открываем или создаём базу данных
-    let conn = Connection::open(path)?;
-    
-    // Включаем PRAGMA key для шифрования через SQLCipher
-    conn.pragma_update(None, "key", key)?;
-    
-    // Включаем внешние ключи
-    conn.execute("PRAGMA foreign_keys = ON", [])?;
-    
-    // Выполняем миграции
-    run_migrations(&conn)?;
-    
-    // Генерируем Ed25519 ключи при первом запуске
-    ensure_ed25519_keys(path, key)?;
-    
-    Ok(())
+/// Строка плана ребалансировки по группе активов
+/// 
+/// `amount` - изменение стоимости группы (покупка > 0, продажа < 0).
+/// Для групп без цели `target_percent = None`, они не ребалансируются.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RebalanceLine {
+    pub asset_type: String,
+    pub current_value: f64,
+    pub current_percent: f64,
+    pub target_percent: Option<f64>,
+    pub tolerance_percent: Option<f64>,
+    pub drift_percent: Option<f64>,
+    pub amount: f64,
+    pub projected_value: f64,
+    pub projected_percent: f64,
 }
 
-/// Запуск всех миграций
-fn run_migrations(conn: &Connection) -> Result<(), DbError> {
-    // Создаём таблицу meta для отслеживания версии БД
-    create_meta_table(conn)?;
-    
-    // Получаем текущую версию
-    let version = get_current_version(conn)?;
-    
-    // Выполняем миграции по порядку
-    if version < 1 {
-        migration_v1(conn)?;
-        update_version(conn, 1)?;
-    }
-    
-    if version < 2 {
-        migration_v2_accounts(conn)?;
-        update_version(conn, 2)?;
-    }
-    
-    if version < 3 {
-        migration_v3_operations(conn)?;
-        update_version(conn, 3)?;
-    }
-    
-    if version < 4 {
-        migration_v4_states(conn)?;
-        update_version(conn, 4)?;
-    }
-    
-    if version < 5 {
-        migration_v5_version_log(conn)?;
-        update_version(conn, 5)?;
-    }
-    
-    if version < 6 {
-        migration_v6_keystore(conn)?;
-        update_version(conn, 6)?;
-    }
-    
-    if version < 7 {
-        migration_v7_version_signatures(conn)?;
-        update_version(conn, 7)?;
-    }
-    
-    Ok(())
+/// Перемещение средств между группами; `from_asset_type = None` - новый взнос
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RebalanceTransfer {
+    pub from_asset_type: Option<String>,
+    pub to_asset_type: String,
+    pub amount: f64,
 }
 
-/// Получение текущей версии БД
-fn get_current_version(conn: &Connection) -> Result<i32, DbError> {
-    let version: Result<String, _> = conn.query_row(
-        "SELECT version FROM meta LIMIT 1",
-        [],
-        |row| row.get(0),
-    );
-    
-    match version {
-        Ok(v) => v.parse::<i32>().map_err(|_| DbError::MigrationError("Invalid version format".to_string())),
-        Err(_) => Ok(0),
-    }
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RebalancePlan {
+    pub total_value: f64,
+    pub contribution: f64,
+    pub contributions_only: bool,
+    pub lines: Vec<RebalanceLine>,
+    pub transfers: Vec<RebalanceTransfer>,
+    /// Взнос, который не удалось распределить (все группы на верхней границе)
+    pub unallocated: f64,
+    pub within_bands_before: bool,
+    pub within_bands_after: bool,
 }
 
-/// Обновление версии БД
-fn update_version(conn: &Connection, version: i32) -> SqlResult<()> {
-    conn.execute(
-        "UPDATE meta SET version = ?1",
-        [version.to_string()],
-    )?;
-    Ok(())
+/// Перевод между собственными счетами: две связанные операции
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Transfer {
+    pub id: i64,
+    pub from_account_id: i64,
+    pub to_account_id: i64,
+    pub from_operation_id: i64,
+    pub to_operation_id: i64,
+    pub amount: f64,
+    pub description: String,
+    pub ts: i64,
 }
 
-/// Создание таблицы meta
-fn create_meta_table(conn: &Connection) -> SqlResult<()> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS meta (
-            version TEXT NOT NULL
-        )",
-        [],
-    )?;
-    
-    // Проверяем, есть ли уже версия, если нет - вставляем начальную
-    let count: i64 = conn.query_row(
-        "SELECT COUNT(*) FROM meta",
-        [],
-        |row| row.get(0),
-    )?;
-    
-    if count == 0 {
-        conn.execute(
-            "INSERT INTO meta (version) VALUES (?1)",
-            ["0"],
-        )?;
-    }
-    
-    Ok(())
+/// Разрез отчёта о денежном потоке
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CashFlowGroupBy {
+    Account,
+    Category,
+    Payee,
 }
 
-/// Миграция v1: начальная настройка (уже выполнена через create_meta_table)
-fn migration_v1(_conn: &Connection) -> SqlResult<()> {
-    // Таблица meta уже создана, просто обновляем версию
-    Ok(())
+/// Строка отчёта о денежном потоке: период и группа
+/// 
+/// `expenses` - положительная сумма списаний, `net = income - expenses`.
+/// Группа без категории/контрагента имеет `group_id = None`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CashFlow {
+    pub ts: i64,
+    pub group_id: Option<i64>,
+    pub group_name: Option<String>,
+    pub income: f64,
+    pub expenses: f64,
+    pub net: f64,
+    pub line_count: i64,
 }
 
-/// Миграция v2: создание таблицы accounts
-fn migration_v2_accounts(conn: &Connection) -> SqlResult<()> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS accounts (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL,
-            type TEXT NOT NULL,
-            created_at INTEGER NOT NULL
-        )",
-        [],
-    )?;
-    
-    // Создаём индексы для ускорения поиска
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_accounts_type ON accounts(type)",
-        [],
-    )?;
-    
-    Ok(())
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatementLineInput {
+    pub ts: i64,
+    pub amount: f64,
+    pub description: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatementLine {
+    pub id: i64,
+    pub session_id: i64,
+    pub ts: i64,
+    pub amount: f64,
+    pub description: String,
+    pub operation_id: Option<i64>,
+    pub match_status: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReconciliationSession {
+    pub id: i64,
+    pub account_id: i64,
+    pub statement_name: String,
+    pub period_start: i64,
+    pub period_end: i64,
+    pub closing_balance: Option<f64>,
+    pub ledger_balance: Option<f64>,
+    pub status: String,
+    pub created_at: i64,
+    pub reconciled_at: Option<i64>,
+    pub lines: Vec<StatementLine>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatementMatch {
+    pub line_id: i64,
+    pub operation_id: i64,
+    pub score: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Security {
+    pub id: i64,
+    pub ticker: String,
+    pub isin: Option<String>,
+    pub name: String,
+    pub asset_class: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecurityPrice {
+    pub id: i64,
+    pub security_id: i64,
+    pub price: f64,
+    pub ts: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InvestmentTransaction {
+    pub id: i64,
+    pub account_id: i64,
+    pub security_id: i64,
+    pub kind: String,
+    pub quantity: f64,
+    pub price: f64,
+    pub fee: f64,
+    pub operation_id: i64,
+    pub ts: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Holding {
+    pub account_id: i64,
+    pub security_id: i64,
+    pub ticker: String,
+    pub asset_class: String,
+    pub quantity: f64,
+    pub price: Option<f64>,
+    pub price_ts: Option<i64>,
+    pub market_value: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaxLot {
+    pub id: i64,
+    pub account_id: i64,
+    pub security_id: i64,
+    pub transaction_id: i64,
+    pub quantity: f64,
+    pub remaining_quantity: f64,
+    pub cost_per_unit: f64,
+    pub acquired_ts: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LotSelection {
+    pub lot_id: i64,
+    pub quantity: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LotAssignment {
+    pub id: i64,
+    pub sale_transaction_id: i64,
+    pub lot_id: i64,
+    pub quantity: f64,
+    pub cost_basis: f64,
+    pub proceeds: f64,
+    pub acquired_ts: i64,
+    pub disposed_ts: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RealizedGain {
+    pub sale_transaction_id: i64,
+    pub lot_id: i64,
+    pub account_id: i64,
+    pub security_id: i64,
+    pub ticker: String,
+    pub quantity: f64,
+    pub acquired_ts: i64,
+    pub disposed_ts: i64,
+    pub holding_period_days: i64,
+    pub long_term: bool,
+    pub cost_basis: f64,
+    pub proceeds: f64,
+    pub gain: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RealizedGainsReport {
+    pub year: i32,
+    pub gains: Vec<RealizedGain>,
+    pub total_proceeds: f64,
+    pub total_cost_basis: f64,
+    pub total_gain: f64,
+    pub short_term_gain: f64,
+    pub long_term_gain: f64,
+}
+
+/// Строка раздела налоговой сводки
+/// 
+/// `id` - контрагент (доходы), категория (вычеты), счёт (проценты)
+/// или бумага (дивиденды и реализованный доход).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaxSummaryLine {
+    pub id: Option<i64>,
+    pub name: String,
+    pub amount: f64,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaxSummary {
+    pub year: i32,
+    pub from_ts: i64,
+    pub to_ts: i64,
+    pub income: Vec<TaxSummaryLine>,
+    pub total_income: f64,
+    pub deductible_expenses: Vec<TaxSummaryLine>,
+    pub total_deductible: f64,
+    pub interest: Vec<TaxSummaryLine>,
+    pub total_interest: f64,
+    pub dividends: Vec<TaxSummaryLine>,
+    pub total_dividends: f64,
+    pub realized_gains: RealizedGainsReport,
+}
+
+/// Экспортированный документ с отдельной подписью Ed25519
+/// 
+/// Подписываются байты `content`; подпись и публичный ключ - в hex.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignedExport {
+    pub format: String,
+    pub content: String,
+    pub signature: String,
+    pub public_key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaxSummaryExport {
+    pub summary: TaxSummary,
+    pub csv: SignedExport,
+    pub json: SignedExport,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionLogRecord {
+    pub id: i64,
+    pub entity: String,
+    pub entity_id: i64,
+    pub action: String,
+    pub payload: String,
+    pub ts: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OpeningBalance {
+    pub id: i64,
+    pub account_id: i64,
+    pub balance: f64,
+    pub ts: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BalanceAssertion {
+    pub id: i64,
+    pub account_id: i64,
+    pub expected_balance: f64,
+    pub ts: i64,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceAssertionResult {
+    pub assertion_id: i64,
+    pub account_id: i64,
+    pub ts: i64,
+    pub expected_balance: f64,
+    pub actual_balance: f64,
+    pub difference: f64,
+    pub passed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BalanceAssertionReport {
+    pub results: Vec<BalanceAssertionResult>,
+    pub first_failure: Option<BalanceAssertionResult>,
+    pub passed_count: i64,
+    pub failed_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Loan {
+    pub id: i64,
+    pub account_id: i64,
+    pub name: String,
+    pub principal: f64,
+    pub annual_rate: f64,
+    pub term_months: i64,
+    pub start_ts: i64,
+    pub monthly_payment: f64,
+    pub created_at: i64,
+    pub funding_operation_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoanPayment {
+    pub id: i64,
+    pub loan_id: i64,
+    pub payment_number: i64,
+    pub from_account_id: i64,
+    pub principal: f64,
+    pub interest: f64,
+    pub extra_principal: f64,
+    pub principal_operation_id: i64,
+    pub interest_operation_id: Option<i64>,
+    pub loan_operation_id: i64,
+    pub ts: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AmortizationRow {
+    pub payment_number: i64,
+    pub due_ts: i64,
+    pub payment: f64,
+    pub principal: f64,
+    pub interest: f64,
+    pub extra_principal: f64,
+    pub remaining_balance: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoanPayoffProjection {
+    pub loan_id: i64,
+    pub outstanding_principal: f64,
+    pub extra_monthly: f64,
+    pub baseline_payoff_ts: Option<i64>,
+    pub baseline_total_interest: f64,
+    pub baseline_payments: i64,
+    pub projected_payoff_ts: Option<i64>,
+    pub projected_total_interest: f64,
+    pub projected_payments: i64,
+    pub months_saved: i64,
+    pub interest_saved: f64,
+    pub schedule: Vec<AmortizationRow>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Payee {
+    pub id: i64,
+    pub name: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PayeeRule {
+    pub id: i64,
+    pub payee_id: i64,
+    pub kind: String,
+    pub pattern: String,
+    pub priority: i64,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PayeeMerge {
+    pub source_id: i64,
+    pub target_id: i64,
+    pub operations_moved: i64,
+    pub rules_moved: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PayeeSpending {
+    pub payee_id: Option<i64>,
+    pub payee_name: Option<String>,
+    pub total_spent: f64,
+    pub operation_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Category {
+    pub id: i64,
+    pub name: String,
+    pub created_at: i64,
+    pub tax_kind: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    pub created_at: i64,
+}
+
+/// Условия и действия правила автокатегоризации
+/// 
+/// Условия объединяются через И; незаданное условие не проверяется.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategorizationRuleInput {
+    pub name: String,
+    /// Регулярное выражение по описанию операции (без учёта регистра)
+    pub description_pattern: Option<String>,
+    pub account_id: Option<i64>,
+    /// Сумма операции строго больше
+    pub amount_gt: Option<f64>,
+    /// Сумма операции строго меньше
+    pub amount_lt: Option<f64>,
+    pub set_category_id: Option<i64>,
+    pub set_payee_id: Option<i64>,
+    pub add_tag_id: Option<i64>,
+    pub priority: i64,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategorizationRule {
+    pub id: i64,
+    pub name: String,
+    pub description_pattern: Option<String>,
+    pub account_id: Option<i64>,
+    pub amount_gt: Option<f64>,
+    pub amount_lt: Option<f64>,
+    pub set_category_id: Option<i64>,
+    pub set_payee_id: Option<i64>,
+    pub add_tag_id: Option<i64>,
+    pub priority: i64,
+    pub enabled: bool,
+    pub version: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Изменение операции, сделанное (или предлагаемое) правилом
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RuleChange {
+    pub rule_id: i64,
+    pub rule_version: i64,
+    pub operation_id: i64,
+    /// Изменяемое поле: category, payee или tag
+    pub field: String,
+    pub old_value: Option<i64>,
+    pub new_value: i64,
+}
+
+/// Метаданные вложения; содержимое хранится в зашифрованной БД
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: i64,
+    pub owner_type: String,
+    pub owner_id: i64,
+    pub file_name: String,
+    pub mime_type: String,
+    pub size: i64,
+    /// SHA-256 содержимого (hex), попадает в подписанный version_log
+    pub sha256: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AttachmentContent {
+    pub attachment: Attachment,
+    pub data: Vec<u8>,
+    /// Хэш содержимого совпадает с подписанной записью version_log
+    pub integrity_ok: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SplitLineInput {
+    pub category_id: Option<i64>,
+    pub amount: f64,
+    pub memo: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SplitLine {
+    pub id: i64,
+    pub operation_id: i64,
+    pub category_id: Option<i64>,
+    pub amount: f64,
+    pub memo: String,
+}
+
+/// Операция вместе со строками разбивки (payload для version_log)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SplitOperation {
+    #[serde(flatten)]
+    pub operation: Operation,
+    pub splits: Vec<SplitLine>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CategorySpending {
+    pub category_id: Option<i64>,
+    pub category_name: Option<String>,
+    pub total_spent: f64,
+    pub line_count: i64,
+}
+
+/// Параметры регулярного платежа или поступления
+/// 
+/// Операции счёта с тем же контрагентом (или тем же нормализованным
+/// описанием) считаются повторениями этого элемента.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurringItemInput {
+    pub account_id: i64,
+    /// Сумма со знаком операции (зарплата положительна, аренда отрицательна)
+    pub amount: f64,
+    pub description: String,
+    pub payee_id: Option<i64>,
+    pub interval: HistoryInterval,
+    /// Момент ближайшего повторения
+    pub next_ts: i64,
+    /// Повторения после этого момента не учитываются
+    pub end_ts: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurringItem {
+    pub id: i64,
+    pub account_id: i64,
+    pub amount: f64,
+    pub description: String,
+    pub payee_id: Option<i64>,
+    pub interval: HistoryInterval,
+    pub next_ts: i64,
+    pub end_ts: Option<i64>,
+    pub created_at: i64,
+}
+
+/// Точка прогноза: ожидаемый баланс и 90% доверительный интервал
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ForecastPoint {
+    pub ts: i64,
+    pub expected: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+/// Прогноз баланса счёта
+/// 
+/// `daily_trend` и `daily_volatility` - среднее и стандартное отклонение
+/// дневного нерегулярного потока. `negative_before_income` - ожидаемый
+/// баланс уходит в минус раньше ближайшего регулярного поступления
+/// (или поступлений на горизонте нет).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountForecast {
+    pub account_id: i64,
+    pub current_balance: f64,
+    pub daily_trend: f64,
+    pub daily_volatility: f64,
+    pub points: Vec<ForecastPoint>,
+    pub first_negative_ts: Option<i64>,
+    pub next_income_ts: Option<i64>,
+    pub negative_before_income: bool,
+}
+
+/// Кодировка импортируемого файла
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportEncoding {
+    #[default]
+    Utf8,
+    Cp1251,
+}
+
+/// Знак суммы в колонке `amount` выписки
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AmountSign {
+    /// Поступления положительные, списания отрицательные
+    #[default]
+    Signed,
+    /// Списания положительные (типично для выписок по кредитным картам)
+    Inverted,
+}
+
+/// Сопоставление колонок CSV (индексы с нуля)
+/// 
+/// Сумма берётся либо из колонки `amount` (с учётом `AmountSign`),
+/// либо из пары колонок `debit` (списание) и `credit` (поступление) -
+/// тогда знак определяется колонкой, а не значением.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CsvColumnMapping {
+    pub date: usize,
+    /// Колонки описания, непустые значения объединяются через пробел
+    #[serde(default)]
+    pub description: Vec<usize>,
+    pub amount: Option<usize>,
+    pub debit: Option<usize>,
+    pub credit: Option<usize>,
+}
+
+/// Параметры разбора CSV-выписки
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CsvImportOptions {
+    pub delimiter: char,
+    #[serde(default)]
+    pub encoding: ImportEncoding,
+    /// Формат даты chrono: `%d.%m.%Y`, `%Y-%m-%d %H:%M:%S` и т.п.
+    /// Даты без времени относятся к началу локальных суток.
+    pub date_format: String,
+    /// Десятичный разделитель: `.` или `,` (второй символ считается разделителем разрядов)
+    pub decimal_separator: char,
+    pub has_header: bool,
+    /// Количество строк преамбулы перед заголовком
+    #[serde(default)]
+    pub skip_rows: usize,
+    pub columns: CsvColumnMapping,
+    #[serde(default)]
+    pub sign: AmountSign,
+}
+
+/// Сохранённый профиль импорта (настройки выписки конкретного банка)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportProfile {
+    pub id: i64,
+    pub name: String,
+    pub options: CsvImportOptions,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportLineStatus {
+    New,
+    /// Строка уже импортировалась (или повторяется в файле)
+    Duplicate,
+}
+
+/// Строка выписки после разбора
+/// 
+/// `ts` - дата проводки (booking date); дата валютирования, контрагент,
+/// назначение платежа и end-to-end ID заполняются, если формат их содержит.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportLine {
+    pub line_number: usize,
+    pub ts: i64,
+    pub amount: f64,
+    pub description: String,
+    /// Идентификатор операции в банке (FITID и т.п.), если формат его содержит
+    pub external_id: Option<String>,
+    pub value_ts: Option<i64>,
+    pub counterparty: Option<String>,
+    pub remittance_info: Option<String>,
+    pub end_to_end_id: Option<String>,
+    pub import_hash: String,
+    pub status: ImportLineStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportLineError {
+    pub line_number: usize,
+    pub message: String,
+}
+
+/// Предпросмотр импорта - в БД ничего не записывается
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportPreview {
+    pub account_id: i64,
+    pub lines: Vec<ImportLine>,
+    pub errors: Vec<ImportLineError>,
+    pub new_count: usize,
+    pub duplicate_count: usize,
+    /// Сумма новых строк
+    pub new_amount: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportBatch {
+    pub id: i64,
+    pub account_id: i64,
+    /// Формат источника (csv, ...)
+    pub source: String,
+    pub file_name: String,
+    pub file_sha256: String,
+    pub line_count: usize,
+    pub imported_count: usize,
+    pub duplicate_count: usize,
+    pub imported_at: i64,
+}
+
+/// Результат импорта; сериализуется в подписанную запись version_log
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportResult {
+    pub batch: ImportBatch,
+    /// Операции, созданные из новых строк (в порядке строк)
+    pub operation_ids: Vec<i64>,
+    pub lines: Vec<ImportLine>,
+}
+
+/// Сверка итогового остатка выписки с балансом счёта в states
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatementBalanceCheck {
+    pub as_of: i64,
+    /// Остаток по данным банка (в знаках счёта)
+    pub statement_balance: f64,
+    /// Баланс счёта на момент `as_of` (в предпросмотре - с учётом новых строк)
+    pub book_balance: f64,
+    pub difference: f64,
+    pub matches: bool,
+}
+
+/// Предпросмотр импорта выписки с итоговым остатком (OFX и т.п.)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatementImportPreview {
+    pub preview: ImportPreview,
+    /// `None`, если выписка не содержит остатка
+    pub balance_check: Option<StatementBalanceCheck>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatementImportResult {
+    pub result: ImportResult,
+    pub balance_check: Option<StatementBalanceCheck>,
+}
+
+/// Синтаксис текстового учёта для экспорта и импорта
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlainTextFormat {
+    Beancount,
+    /// ledger-cli; совместим с hledger
+    Ledger,
+}
+
+/// Итог импорта файла текстового учёта
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PlainTextImportResult {
+    pub accounts_created: usize,
+    pub accounts_closed: usize,
+    pub categories_created: usize,
+    pub operations_created: usize,
+    pub transfers_created: usize,
+    pub opening_balances_set: usize,
+    pub assertions_created: usize,
+    /// Записи, которые уже есть в БД (совпали по идентификатору из метаданных)
+    pub skipped_count: usize,
+    /// Пропущенные неподдерживаемые директивы и проводки
+    pub warnings: Vec<ImportLineError>,
+}
+
+/// Параметры поиска аномалий
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnomalyOptions {
+    /// Проверяемые операции: [from_ts, to_ts); история для сравнения - вся
+    pub from_ts: Option<i64>,
+    pub to_ts: Option<i64>,
+    /// Окно поиска дубликатов в днях (по умолчанию `ANOMALY_DUPLICATE_WINDOW_DAYS`)
+    pub duplicate_window_days: Option<i64>,
+    /// Порог крупного списания нового контрагента
+    /// (по умолчанию - 90-й перцентиль всех списаний)
+    pub large_amount: Option<f64>,
+    #[serde(default)]
+    pub include_dismissed: bool,
+}
+
+/// Найденная аномалия
+/// 
+/// `score` - для outlier_amount робастная z-оценка, для duplicate -
+/// расстояние в днях, для new_payee_large - отношение суммы к порогу.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Anomaly {
+    pub operation: Operation,
+    pub kind: String,
+    pub score: f64,
+    pub related_operation_id: Option<i64>,
+    pub reason: String,
+    pub status: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnomalyState {
+    pub id: i64,
+    pub operation_id: i64,
+    pub kind: String,
+    pub status: String,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OperationTag {
+    pub operation_id: i64,
+    pub tag_id: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Goal {
+    pub id: i64,
+    pub name: String,
+    pub target_amount: f64,
+    pub target_ts: i64,
+    pub account_ids: Vec<i64>,
+    pub tag_ids: Vec<i64>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GoalProgress {
+    pub goal_id: i64,
+    pub current_amount: f64,
+    pub target_amount: f64,
+    pub remaining_amount: f64,
+    pub months_remaining: f64,
+    pub required_monthly_contribution: f64,
+    /// Средний месячный прирост за последние `GOAL_VELOCITY_WINDOW_DAYS` дней
+    pub recent_monthly_contribution: f64,
+    /// Ожидаемая сумма к дате цели при сохранении текущего темпа
+    pub projected_amount: f64,
+    /// reached, on_track, behind или overdue
+    pub status: String,
+}
+
+/// Фильтры выборки операций
+/// 
+/// Незаданный фильтр не применяется. Период - [from_ts, to_ts),
+/// границы суммы включаются.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OperationFilters {
+    pub account_ids: Option<Vec<i64>>,
+    pub from_ts: Option<i64>,
+    pub to_ts: Option<i64>,
+    pub min_amount: Option<f64>,
+    pub max_amount: Option<f64>,
+}
+
+/// Порядок сортировки выборки операций
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationSort {
+    #[default]
+    TsDesc,
+    TsAsc,
+    AmountDesc,
+    AmountAsc,
+}
+
+impl OperationSort {
+    /// Строковое представление (используется в курсоре)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OperationSort::TsDesc => "ts_desc",
+            OperationSort::TsAsc => "ts_asc",
+            OperationSort::AmountDesc => "amount_desc",
+            OperationSort::AmountAsc => "amount_asc",
+        }
+    }
+}
+
+/// Параметры постраничной выборки операций
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OperationQuery {
+    #[serde(flatten)]
+    pub filters: OperationFilters,
+    /// Текстовый фильтр (синтаксис как у `search_operations`)
+    pub text: Option<String>,
+    #[serde(default)]
+    pub sort: OperationSort,
+    /// Курсор из `OperationPage::next_cursor` предыдущей страницы
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OperationPage {
+    pub operations: Vec<Operation>,
+    /// Курсор следующей страницы; `None` - страница последняя
+    pub next_cursor: Option<String>,
+    /// Количество операций во всей отфильтрованной выборке
+    pub total_count: i64,
+    /// Сумма операций во всей отфильтрованной выборке
+    pub total_amount: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OperationSearchHit {
+    pub operation: Operation,
+    /// Релевантность (чем больше, тем лучше)
+    pub rank: f64,
+    /// Фрагмент с подсвеченными совпадениями в [квадратных скобках]
+    pub snippet: String,
+}
+
+// Вспомогательные функции для сериализации
+
+/// Сериализация сущности в JSON-строку
+/// 
+/// Используется для создания payload в version_log
+/// 
+/// # Примеры
+/// 
+/// ```
+/// let account = Account { id: 1, name: "Test".to_string(), acc_class: AccountClass::Asset, acc_type: "cash".to_string(), created_at: 123456, closed_at: None };
+/// let json = serialize_entity(&account).unwrap();
+/// // json = '{"id":1,"name":"Test","class":"asset","type":"cash","created_at":123456,"closed_at":null}'
+/// ```
+pub fn serialize_entity<T: Serialize>(entity: &T) -> Result<String, DbError> {
+    serde_json::to_string(entity)
+        .map_err(|e| DbError::InitError(format!("Serialization error: {}", e)))
+}
+
+/// Текущий Unix timestamp в секундах
+fn current_timestamp() -> Result<i64, DbError> {
+    let ts = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| DbError::InitError(format!("Failed to get timestamp: {}", e)))?
+        .as_secs() as i64;
+    Ok(ts)
+}
+
+/// Инициализация базы данных с шифрованием
+pub fn init_db(path: &str, key: &str) -> Result<(), DbError> {
+    // Проверяем и создаем директорию если нужно
+    if let Some(parent) = Path::new(path).parent() {
+        if !parent.exists() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| DbError::InitError(format!("Failed to create directory: {}", e)))?;
+        }
+    }
+
+    // Открываем или создаём базу данных
+    let conn = Connection::open(path)?;
+    
+    // Включаем PRAGMA key для шифрования через SQLCipher
+    conn.pragma_update(None, "key", key)?;
+    
+    // Включаем внешние ключи
+    conn.execute("PRAGMA foreign_keys = ON", [])?;
+    
+    // Выполняем миграции
+    run_migrations(&conn)?;
+    
+    // Генерируем Ed25519 ключи при первом запуске
+    ensure_ed25519_keys(path, key)?;
+    
+    Ok(())
+}
+
+/// Запуск всех миграций
+fn run_migrations(conn: &Connection) -> Result<(), DbError> {
+    // Создаём таблицу meta для отслеживания версии БД
+    create_meta_table(conn)?;
+    
+    // Получаем текущую версию
+    let version = get_current_version(conn)?;
+    
+    // Выполняем миграции по порядку
+    if version < 1 {
+        migration_v1(conn)?;
+        update_version(conn, 1)?;
+    }
+    
+    if version < 2 {
+        migration_v2_accounts(conn)?;
+        update_version(conn, 2)?;
+    }
+    
+    if version < 3 {
+        migration_v3_operations(conn)?;
+        update_version(conn, 3)?;
+    }
+    
+    if version < 4 {
+        migration_v4_states(conn)?;
+        update_version(conn, 4)?;
+    }
+    
+    if version < 5 {
+        migration_v5_version_log(conn)?;
+        update_version(conn, 5)?;
+    }
+    
+    if version < 6 {
+        migration_v6_keystore(conn)?;
+        update_version(conn, 6)?;
+    }
+    
+    if version < 7 {
+        migration_v7_version_signatures(conn)?;
+        update_version(conn, 7)?;
+    }
+    
+    if version < 8 {
+        migration_v8_balance_assertions(conn)?;
+        update_version(conn, 8)?;
+    }
+    
+    if version < 9 {
+        migration_v9_reconciliation(conn)?;
+        update_version(conn, 9)?;
+    }
+    
+    if version < 10 {
+        migration_v10_investments(conn)?;
+        update_version(conn, 10)?;
+    }
+    
+    if version < 11 {
+        // Перенос истории сделок в лоты применяется целиком или не применяется вовсе
+        let tx = conn.unchecked_transaction()?;
+        migration_v11_tax_lots(&tx)?;
+        update_version(&tx, 11)?;
+        tx.commit()?;
+    }
+    
+    if version < 12 {
+        migration_v12_account_classes(conn)?;
+        update_version(conn, 12)?;
+    }
+    
+    if version < 13 {
+        migration_v13_loans(conn)?;
+        update_version(conn, 13)?;
+    }
+    
+    if version < 14 {
+        migration_v14_payees(conn)?;
+        update_version(conn, 14)?;
+    }
+    
+    if version < 15 {
+        migration_v15_categorization(conn)?;
+        update_version(conn, 15)?;
+    }
+    
+    if version < 16 {
+        migration_v16_attachments(conn)?;
+        update_version(conn, 16)?;
+    }
+    
+    if version < 17 {
+        migration_v17_operation_splits(conn)?;
+        update_version(conn, 17)?;
+    }
+    
+    if version < 18 {
+        migration_v18_goals(conn)?;
+        update_version(conn, 18)?;
+    }
+    
+    if version < 19 {
+        migration_v19_operation_search(conn)?;
+        update_version(conn, 19)?;
+    }
+    
+    if version < 20 {
+        migration_v20_transfers(conn)?;
+        update_version(conn, 20)?;
+    }
+    
+    if version < 21 {
+        migration_v21_recurring_items(conn)?;
+        update_version(conn, 21)?;
+    }
+    
+    if version < 22 {
+        migration_v22_allocation_targets(conn)?;
+        update_version(conn, 22)?;
+    }
+    
+    if version < 23 {
+        migration_v23_anomaly_states(conn)?;
+        update_version(conn, 23)?;
+    }
+    
+    if version < 24 {
+        migration_v24_category_tax_kind(conn)?;
+        update_version(conn, 24)?;
+    }
+    
+    if version < 25 {
+        migration_v25_imports(conn)?;
+        update_version(conn, 25)?;
+    }
+    
+    if version < 26 {
+        migration_v26_account_closing(conn)?;
+        update_version(conn, 26)?;
+    }
+    
+    if version < 27 {
+        migration_v27_loan_funding(conn)?;
+        update_version(conn, 27)?;
+    }
+    
+    Ok(())
+}
+
+/// Получение текущей версии БД
+fn get_current_version(conn: &Connection) -> Result<i32, DbError> {
+    let version: Result<String, _> = conn.query_row(
+        "SELECT version FROM meta LIMIT 1",
+        [],
+        |row| row.get(0),
+    );
+    
+    match version {
+        Ok(v) => v.parse::<i32>().map_err(|_| DbError::MigrationError("Invalid version format".to_string())),
+        Err(_) => Ok(0),
+    }
+}
+
+/// Обновление версии БД
+fn update_version(conn: &Connection, version: i32) -> SqlResult<()> {
+    conn.execute(
+        "UPDATE meta SET version = ?1",
+        [version.to_string()],
+    )?;
+    Ok(())
+}
+
+/// Создание таблицы meta
+fn create_meta_table(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS meta (
+            version TEXT NOT NULL
+        )",
+        [],
+    )?;
+    
+    // Проверяем, есть ли уже версия, если нет - вставляем начальную
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM meta",
+        [],
+        |row| row.get(0),
+    )?;
+    
+    if count == 0 {
+        conn.execute(
+            "INSERT INTO meta (version) VALUES (?1)",
+            ["0"],
+        )?;
+    }
+    
+    Ok(())
+}
+
+/// Миграция v1: начальная настройка (уже выполнена через create_meta_table)
+fn migration_v1(_conn: &Connection) -> SqlResult<()> {
+    // Таблица meta уже создана, просто обновляем версию
+    Ok(())
+}
+
+/// Миграция v2: создание таблицы accounts
+fn migration_v2_accounts(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS accounts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            type TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    
+    // Создаём индексы для ускорения поиска
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_accounts_type ON accounts(type)",
+        [],
+    )?;
+    
+    Ok(())
 }
 
 /// Миграция v3: создание таблицы operations
 fn migration_v3_operations(conn: &Connection) -> SqlResult<()> {
     conn.execute(
-        "CREATE TABLE IF NOT EXISTS operations (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            account_id INTEGER NOT NULL,
-            amount REAL NOT NULL,
-            description TEXT NOT NULL,
-            ts INTEGER NOT NULL,
-            FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
-        )",
-        [],
+        "CREATE TABLE IF NOT EXISTS operations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id INTEGER NOT NULL,
+            amount REAL NOT NULL,
+            description TEXT NOT NULL,
+            ts INTEGER NOT NULL,
+            FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    // Создаём индексы
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_operations_account_id ON operations(account_id)",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_operations_ts ON operations(ts)",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+/// Миграция v4: создание таблицы states
+fn migration_v4_states(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS states (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id INTEGER NOT NULL,
+            balance REAL NOT NULL,
+            ts INTEGER NOT NULL,
+            FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    // Создаём индексы
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_states_account_id ON states(account_id)",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_states_ts ON states(ts)",
+        [],
+    )?;
+    
+    // Уникальный индекс для предотвращения дублирования снимков
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_states_account_ts ON states(account_id, ts)",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+/// Миграция v5: создание таблицы version_log для аудита изменений
+fn migration_v5_version_log(conn: &Connection) -> SqlResult<()> {
+    // Создаём таблицу version_log
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS version_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            entity TEXT NOT NULL,
+            entity_id INTEGER NOT NULL,
+            action TEXT NOT NULL,
+            payload TEXT NOT NULL,
+            ts INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    
+    // Создаём индексы для быстрого поиска
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_version_log_entity ON version_log(entity, entity_id)",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_version_log_ts ON version_log(ts)",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_version_log_action ON version_log(action)",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+/// Миграция M6: Таблица keystore для хранения криптографических ключей
+fn migration_v6_keystore(conn: &Connection) -> SqlResult<()> {
+    // Создаём таблицу keystore для хранения ключей
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS keystore (
+            key TEXT PRIMARY KEY,
+            value BLOB NOT NULL
+        )",
+        [],
+    )?;
+    
+    // Индекс уже есть благодаря PRIMARY KEY
+    Ok(())
+}
+
+/// Миграция M7: Таблица version_signatures для подписей версий
+fn migration_v7_version_signatures(conn: &Connection) -> SqlResult<()> {
+    // Создаём таблицу version_signatures
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS version_signatures (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            version_id INTEGER NOT NULL,
+            signature BLOB NOT NULL,
+            public_key BLOB NOT NULL,
+            ts INTEGER NOT NULL,
+            FOREIGN KEY (version_id) REFERENCES version_log(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    // Создаём индексы для быстрого поиска
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_version_signatures_version_id ON version_signatures(version_id)",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_version_signatures_ts ON version_signatures(ts)",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+/// Миграция M8: Начальные остатки и сверки баланса
+fn migration_v8_balance_assertions(conn: &Connection) -> SqlResult<()> {
+    // Начальный остаток счёта (не более одного на счёт)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS opening_balances (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id INTEGER NOT NULL UNIQUE,
+            balance REAL NOT NULL,
+            ts INTEGER NOT NULL,
+            FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    // Утверждения вида "на дату ts баланс счёта равен expected_balance"
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS balance_assertions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id INTEGER NOT NULL,
+            expected_balance REAL NOT NULL,
+            ts INTEGER NOT NULL,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_balance_assertions_account_ts ON balance_assertions(account_id, ts)",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+/// Миграция M9: Сессии сверки счёта с банковской выпиской
+fn migration_v9_reconciliation(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS reconciliation_sessions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id INTEGER NOT NULL,
+            statement_name TEXT NOT NULL,
+            period_start INTEGER NOT NULL,
+            period_end INTEGER NOT NULL,
+            closing_balance REAL,
+            ledger_balance REAL,
+            status TEXT NOT NULL DEFAULT 'open',
+            created_at INTEGER NOT NULL,
+            reconciled_at INTEGER,
+            FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_reconciliation_sessions_account_id ON reconciliation_sessions(account_id)",
+        [],
+    )?;
+    
+    // Строки выписки и их сопоставление с операциями
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS statement_lines (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id INTEGER NOT NULL,
+            ts INTEGER NOT NULL,
+            amount REAL NOT NULL,
+            description TEXT NOT NULL,
+            operation_id INTEGER,
+            match_status TEXT NOT NULL DEFAULT 'unmatched',
+            FOREIGN KEY (session_id) REFERENCES reconciliation_sessions(id) ON DELETE CASCADE,
+            FOREIGN KEY (operation_id) REFERENCES operations(id) ON DELETE SET NULL
+        )",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_statement_lines_session_id ON statement_lines(session_id)",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_statement_lines_operation_id ON statement_lines(operation_id)",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+/// Миграция M10: Ценные бумаги, история цен и инвестиционные сделки
+fn migration_v10_investments(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS securities (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            ticker TEXT NOT NULL,
+            isin TEXT UNIQUE,
+            name TEXT NOT NULL,
+            asset_class TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_securities_ticker ON securities(ticker)",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS security_prices (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            security_id INTEGER NOT NULL,
+            price REAL NOT NULL,
+            ts INTEGER NOT NULL,
+            FOREIGN KEY (security_id) REFERENCES securities(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    // Одна котировка бумаги на момент времени
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_security_prices_security_ts ON security_prices(security_id, ts)",
+        [],
+    )?;
+    
+    // Сделки с бумагами; денежная часть сделки хранится как обычная операция счёта
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS investment_transactions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id INTEGER NOT NULL,
+            security_id INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            quantity REAL NOT NULL,
+            price REAL NOT NULL,
+            fee REAL NOT NULL DEFAULT 0,
+            operation_id INTEGER NOT NULL,
+            ts INTEGER NOT NULL,
+            FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE,
+            FOREIGN KEY (security_id) REFERENCES securities(id),
+            FOREIGN KEY (operation_id) REFERENCES operations(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_investment_transactions_account_security
+         ON investment_transactions(account_id, security_id)",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_investment_transactions_ts ON investment_transactions(ts)",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+/// Миграция M11: Налоговые лоты и их списание при продажах
+fn migration_v11_tax_lots(conn: &Connection) -> Result<(), DbError> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tax_lots (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id INTEGER NOT NULL,
+            security_id INTEGER NOT NULL,
+            transaction_id INTEGER NOT NULL UNIQUE,
+            quantity REAL NOT NULL,
+            remaining_quantity REAL NOT NULL,
+            cost_per_unit REAL NOT NULL,
+            acquired_ts INTEGER NOT NULL,
+            FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE,
+            FOREIGN KEY (security_id) REFERENCES securities(id),
+            FOREIGN KEY (transaction_id) REFERENCES investment_transactions(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_tax_lots_account_security ON tax_lots(account_id, security_id)",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS lot_assignments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            sale_transaction_id INTEGER NOT NULL,
+            lot_id INTEGER NOT NULL,
+            quantity REAL NOT NULL,
+            cost_basis REAL NOT NULL,
+            proceeds REAL NOT NULL,
+            acquired_ts INTEGER NOT NULL,
+            disposed_ts INTEGER NOT NULL,
+            FOREIGN KEY (sale_transaction_id) REFERENCES investment_transactions(id) ON DELETE CASCADE,
+            FOREIGN KEY (lot_id) REFERENCES tax_lots(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_lot_assignments_disposed_ts ON lot_assignments(disposed_ts)",
+        [],
+    )?;
+    
+    // Лоты для сделок, внесённых до появления учёта лотов. Перенос не зависит
+    // от текущих open_tax_lot/assign_tax_lots: комиссия покупки входит в стоимость
+    // лота, продажи списываются по FIFO. Продажа сверх открытых лотов списывает
+    // только доступное количество, чтобы старая история не блокировала миграцию.
+    let transactions = {
+        let mut stmt = conn.prepare(
+            "SELECT id, account_id, security_id, kind, quantity, price, fee, operation_id, ts
+             FROM investment_transactions
+             WHERE kind IN ('buy', 'sell') AND id NOT IN (SELECT transaction_id FROM tax_lots)
+             ORDER BY ts ASC, id ASC"
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(InvestmentTransaction {
+                id: row.get(0)?,
+                account_id: row.get(1)?,
+                security_id: row.get(2)?,
+                kind: row.get(3)?,
+                quantity: row.get(4)?,
+                price: row.get(5)?,
+                fee: row.get(6)?,
+                operation_id: row.get(7)?,
+                ts: row.get(8)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    for transaction in &transactions {
+        if transaction.kind == "buy" {
+            conn.execute(
+                "INSERT INTO tax_lots (account_id, security_id, transaction_id, quantity, remaining_quantity, cost_per_unit, acquired_ts)
+                 VALUES (?1, ?2, ?3, ?4, ?4, ?5, ?6)",
+                rusqlite::params![
+                    transaction.account_id,
+                    transaction.security_id,
+                    transaction.id,
+                    transaction.quantity,
+                    transaction.price + transaction.fee / transaction.quantity,
+                    transaction.ts,
+                ],
+            )?;
+            continue;
+        }
+        
+        let lots = {
+            let mut stmt = conn.prepare(
+                "SELECT id, remaining_quantity, cost_per_unit, acquired_ts
+                 FROM tax_lots
+                 WHERE account_id = ?1 AND security_id = ?2 AND acquired_ts <= ?3 AND remaining_quantity > 1e-9
+                 ORDER BY acquired_ts ASC, id ASC"
+            )?;
+            let rows = stmt.query_map(
+                rusqlite::params![transaction.account_id, transaction.security_id, transaction.ts],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?, row.get::<_, f64>(2)?, row.get::<_, i64>(3)?)),
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+            rows
+        };
+        
+        let proceeds_per_unit = transaction.price - transaction.fee / transaction.quantity;
+        let mut remaining = transaction.quantity;
+        for (lot_id, lot_remaining, cost_per_unit, acquired_ts) in lots {
+            if remaining <= 1e-9 {
+                break;
+            }
+            let quantity = remaining.min(lot_remaining);
+            remaining -= quantity;
+            
+            conn.execute(
+                "UPDATE tax_lots SET remaining_quantity = MAX(remaining_quantity - ?1, 0.0) WHERE id = ?2",
+                rusqlite::params![quantity, lot_id],
+            )?;
+            conn.execute(
+                "INSERT INTO lot_assignments (sale_transaction_id, lot_id, quantity, cost_basis, proceeds, acquired_ts, disposed_ts)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                rusqlite::params![
+                    transaction.id,
+                    lot_id,
+                    quantity,
+                    quantity * cost_per_unit,
+                    quantity * proceeds_per_unit,
+                    acquired_ts,
+                    transaction.ts,
+                ],
+            )?;
+        }
+    }
+    
+    Ok(())
+}
+
+/// Миграция M12: Класс счёта (asset, liability, equity, income, expense)
+/// 
+/// Существующие строковые типы становятся подтипами, класс выводится из типа.
+fn migration_v12_account_classes(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "ALTER TABLE accounts ADD COLUMN class TEXT NOT NULL DEFAULT 'asset'",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_accounts_class ON accounts(class)",
+        [],
+    )?;
+    
+    let accounts = {
+        let mut stmt = conn.prepare("SELECT id, type FROM accounts")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    for (account_id, acc_type) in accounts {
+        conn.execute(
+            "UPDATE accounts SET class = ?1 WHERE id = ?2",
+            rusqlite::params![AccountClass::infer_from_type(&acc_type), account_id],
+        )?;
+    }
+    
+    Ok(())
+}
+
+/// Миграция M13: Кредиты, ипотеки и платежи по ним
+fn migration_v13_loans(conn: &Connection) -> SqlResult<()> {
+    // Остаток долга ведётся на счёте-обязательстве account_id
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS loans (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id INTEGER NOT NULL UNIQUE,
+            name TEXT NOT NULL,
+            principal REAL NOT NULL,
+            annual_rate REAL NOT NULL,
+            term_months INTEGER NOT NULL,
+            start_ts INTEGER NOT NULL,
+            monthly_payment REAL NOT NULL,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    // Платёж раскладывается на операции: тело и проценты со счёта списания,
+    // уменьшение долга на счёте кредита
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS loan_payments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            loan_id INTEGER NOT NULL,
+            payment_number INTEGER NOT NULL,
+            from_account_id INTEGER NOT NULL,
+            principal REAL NOT NULL,
+            interest REAL NOT NULL,
+            extra_principal REAL NOT NULL DEFAULT 0,
+            principal_operation_id INTEGER NOT NULL,
+            interest_operation_id INTEGER,
+            loan_operation_id INTEGER NOT NULL,
+            ts INTEGER NOT NULL,
+            FOREIGN KEY (loan_id) REFERENCES loans(id) ON DELETE CASCADE,
+            FOREIGN KEY (from_account_id) REFERENCES accounts(id),
+            UNIQUE (loan_id, payment_number)
+        )",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+/// Миграция M14: Контрагенты и правила нормализации описаний
+fn migration_v14_payees(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS payees (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    
+    // Правила сопоставления описания операции с контрагентом
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS payee_rules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            payee_id INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            pattern TEXT NOT NULL,
+            priority INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (payee_id) REFERENCES payees(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    conn.execute(
+        "ALTER TABLE operations ADD COLUMN payee_id INTEGER REFERENCES payees(id) ON DELETE SET NULL",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_operations_payee ON operations(payee_id)",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+/// Миграция M15: Категории, теги и правила автокатегоризации
+fn migration_v15_categorization(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS categories (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tags (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS operation_tags (
+            operation_id INTEGER NOT NULL,
+            tag_id INTEGER NOT NULL,
+            PRIMARY KEY (operation_id, tag_id),
+            FOREIGN KEY (operation_id) REFERENCES operations(id) ON DELETE CASCADE,
+            FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    conn.execute(
+        "ALTER TABLE operations ADD COLUMN category_id INTEGER REFERENCES categories(id) ON DELETE SET NULL",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_operations_category ON operations(category_id)",
+        [],
+    )?;
+    
+    // Правила версионируются: каждое изменение увеличивает version
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS categorization_rules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            description_pattern TEXT,
+            account_id INTEGER,
+            amount_gt REAL,
+            amount_lt REAL,
+            set_category_id INTEGER,
+            set_payee_id INTEGER,
+            add_tag_id INTEGER,
+            priority INTEGER NOT NULL DEFAULT 0,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            version INTEGER NOT NULL DEFAULT 1,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE,
+            FOREIGN KEY (set_category_id) REFERENCES categories(id) ON DELETE SET NULL,
+            FOREIGN KEY (set_payee_id) REFERENCES payees(id) ON DELETE SET NULL,
+            FOREIGN KEY (add_tag_id) REFERENCES tags(id) ON DELETE SET NULL
+        )",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+/// Миграция M16: Вложения (чеки, документы)
+/// 
+/// Содержимое файлов хранится в BLOB внутри зашифрованной SQLCipher БД.
+fn migration_v16_attachments(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS attachments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            owner_type TEXT NOT NULL,
+            owner_id INTEGER NOT NULL,
+            file_name TEXT NOT NULL,
+            mime_type TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            sha256 TEXT NOT NULL,
+            data BLOB NOT NULL,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_attachments_owner ON attachments(owner_type, owner_id)",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+/// Миграция M17: Разбивка операций по категориям
+fn migration_v17_operation_splits(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS operation_splits (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            operation_id INTEGER NOT NULL,
+            category_id INTEGER,
+            amount REAL NOT NULL,
+            memo TEXT NOT NULL DEFAULT '',
+            FOREIGN KEY (operation_id) REFERENCES operations(id) ON DELETE CASCADE,
+            FOREIGN KEY (category_id) REFERENCES categories(id) ON DELETE SET NULL
+        )",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_operation_splits_operation ON operation_splits(operation_id)",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+/// Миграция M18: Цели накоплений
+fn migration_v18_goals(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS goals (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            target_amount REAL NOT NULL,
+            target_ts INTEGER NOT NULL,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    
+    // Цель привязывается к счетам (баланс) и/или тегам (помеченные операции)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS goal_links (
+            goal_id INTEGER NOT NULL,
+            link_type TEXT NOT NULL,
+            link_id INTEGER NOT NULL,
+            PRIMARY KEY (goal_id, link_type, link_id),
+            FOREIGN KEY (goal_id) REFERENCES goals(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+/// Миграция M19: Заметки к операциям и полнотекстовый индекс FTS5
+/// 
+/// Индекс хранится внутри зашифрованной БД и обновляется функциями записи
+/// (см. `refresh_operation_search`); существующие операции индексируются сразу.
+fn migration_v19_operation_search(conn: &Connection) -> Result<(), DbError> {
+    conn.execute("ALTER TABLE operations ADD COLUMN notes TEXT", [])?;
+    
+    conn.execute(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS operations_fts USING fts5(
+            description, payee, category, tags, notes,
+            tokenize = 'unicode61 remove_diacritics 2'
+        )",
+        [],
+    )?;
+    
+    let operation_ids = {
+        let mut stmt = conn.prepare("SELECT id FROM operations")?;
+        let rows = stmt.query_map([], |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    for operation_id in operation_ids {
+        refresh_operation_search(conn, operation_id)?;
+    }
+    
+    Ok(())
+}
+
+/// Миграция M20: Переводы между собственными счетами
+fn migration_v20_transfers(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS transfers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            from_account_id INTEGER NOT NULL,
+            to_account_id INTEGER NOT NULL,
+            from_operation_id INTEGER NOT NULL UNIQUE,
+            to_operation_id INTEGER NOT NULL UNIQUE,
+            amount REAL NOT NULL,
+            description TEXT NOT NULL,
+            ts INTEGER NOT NULL,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (from_operation_id) REFERENCES operations(id) ON DELETE CASCADE,
+            FOREIGN KEY (to_operation_id) REFERENCES operations(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_transfers_ts ON transfers(ts)", [])?;
+    
+    Ok(())
+}
+
+/// Миграция M21: Регулярные платежи и поступления
+fn migration_v21_recurring_items(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS recurring_items (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id INTEGER NOT NULL,
+            amount REAL NOT NULL,
+            description TEXT NOT NULL,
+            payee_id INTEGER,
+            interval TEXT NOT NULL,
+            next_ts INTEGER NOT NULL,
+            end_ts INTEGER,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE,
+            FOREIGN KEY (payee_id) REFERENCES payees(id) ON DELETE SET NULL
+        )",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+/// Миграция M22: Целевая структура активов
+fn migration_v22_allocation_targets(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS allocation_targets (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            asset_type TEXT NOT NULL UNIQUE,
+            target_percent REAL NOT NULL,
+            tolerance_percent REAL NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+/// Миграция M23: Решения пользователя по найденным аномалиям
+/// 
+/// Сами аномалии не хранятся - они вычисляются по истории операций.
+fn migration_v23_anomaly_states(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS anomaly_states (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            operation_id INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            status TEXT NOT NULL,
+            updated_at INTEGER NOT NULL,
+            UNIQUE (operation_id, kind),
+            FOREIGN KEY (operation_id) REFERENCES operations(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+/// Миграция M24: Налоговый признак категорий
+fn migration_v24_category_tax_kind(conn: &Connection) -> SqlResult<()> {
+    conn.execute("ALTER TABLE categories ADD COLUMN tax_kind TEXT", [])?;
+    
+    Ok(())
+}
+
+/// Миграция M25: Импорт выписок
+/// 
+/// Хеши импортированных строк переживают удаление операции, чтобы
+/// удалённая пользователем операция не вернулась при повторном импорте.
+fn migration_v25_imports(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS import_profiles (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            options TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS import_batches (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id INTEGER NOT NULL,
+            source TEXT NOT NULL,
+            file_name TEXT NOT NULL,
+            file_sha256 TEXT NOT NULL,
+            line_count INTEGER NOT NULL,
+            imported_count INTEGER NOT NULL,
+            duplicate_count INTEGER NOT NULL,
+            imported_at INTEGER NOT NULL,
+            FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS import_hashes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id INTEGER NOT NULL,
+            hash TEXT NOT NULL,
+            operation_id INTEGER,
+            batch_id INTEGER NOT NULL,
+            UNIQUE (account_id, hash),
+            FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE,
+            FOREIGN KEY (operation_id) REFERENCES operations(id) ON DELETE SET NULL,
+            FOREIGN KEY (batch_id) REFERENCES import_batches(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+/// Миграция M26: Закрытие счетов
+fn migration_v26_account_closing(conn: &Connection) -> SqlResult<()> {
+    conn.execute("ALTER TABLE accounts ADD COLUMN closed_at INTEGER", [])?;
+    
+    Ok(())
+}
+
+/// Миграция M27: Зачисление суммы кредита на счёт получателя
+/// 
+/// Операция зачисления - перемещение денег, а не доход; ссылка на неё
+/// позволяет исключить её из отчёта о денежных потоках.
+fn migration_v27_loan_funding(conn: &Connection) -> SqlResult<()> {
+    conn.execute("ALTER TABLE loans ADD COLUMN funding_operation_id INTEGER", [])?;
+    
+    Ok(())
+}
+
+/// Получение версии БД
+pub fn get_db_version(path: &str, key: &str) -> Result<String, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let version: String = conn.query_row(
+        "SELECT version FROM meta LIMIT 1",
+        [],
+        |row| row.get(0),
+    )?;
+    
+    Ok(version)
+}
+
+/// Обновление версии БД
+pub fn update_db_version(path: &str, key: &str, new_version: &str) -> Result<(), DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    conn.execute(
+        "UPDATE meta SET version = ?1",
+        [new_version],
+    )?;
+    
+    Ok(())
+}
+
+// Функции для version_log (аудит изменений)
+
+/// Ключи подписи записей version_log
+/// 
+/// Загружаются из keystore один раз на пакет записей, чтобы не открывать
+/// новое соединение с БД на каждую запись.
+struct VersionSigner {
+    private_key: Vec<u8>,
+    public_key: Vec<u8>,
+}
+
+impl VersionSigner {
+    /// Загрузка ключей Ed25519 из keystore
+    fn load(path: &str, db_key: &str) -> Result<Self, DbError> {
+        let private_key = load_key_from_keystore(path, db_key, "ed25519_private")?
+            .ok_or_else(|| DbError::InitError("Ed25519 private key not found in keystore".to_string()))?;
+        
+        let public_key = load_key_from_keystore(path, db_key, "ed25519_public")?
+            .ok_or_else(|| DbError::InitError("Ed25519 public key not found in keystore".to_string()))?;
+        
+        Ok(VersionSigner { private_key, public_key })
+    }
+}
+
+/// Запись изменения в version_log
+/// 
+/// Функция должна вызываться внутри транзакций для обеспечения атомарности
+/// 
+/// # Параметры
+/// - `conn` - ссылка на транзакцию или соединение
+/// - `entity` - тип сущности (account, operation, state)
+/// - `entity_id` - ID сущности
+/// - `action` - тип действия (create, update, delete)
+/// - `payload_json` - JSON-снимок состояния сущности
+fn write_version_log(
+    conn: &Connection,
+    path: &str,
+    db_key: &str,
+    entity: &str,
+    entity_id: i64,
+    action: &str,
+    payload_json: &str,
+) -> Result<(), DbError> {
+    let signer = VersionSigner::load(path, db_key)?;
+    write_signed_version_log(conn, &signer, entity, entity_id, action, payload_json)
+}
+
+/// Запись изменения в version_log с уже загруженными ключами подписи
+fn write_signed_version_log(
+    conn: &Connection,
+    signer: &VersionSigner,
+    entity: &str,
+    entity_id: i64,
+    action: &str,
+    payload_json: &str,
+) -> Result<(), DbError> {
+    // Получаем текущий timestamp в секундах
+    let ts = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| DbError::InitError(format!("Failed to get timestamp: {}", e)))?
+        .as_secs() as i64;
+    
+    // Записываем в version_log
+    conn.execute(
+        "INSERT INTO version_log (entity, entity_id, action, payload, ts) VALUES (?1, ?2, ?3, ?4, ?5)",
+        [
+            entity,
+            &entity_id.to_string(),
+            action,
+            payload_json,
+            &ts.to_string(),
+        ],
+    )?;
+    
+    // Получаем id вставленной записи
+    let version_id = conn.last_insert_rowid();
+    
+    // Сериализуем payload в bytes
+    let payload_bytes = payload_json.as_bytes();
+    
+    // Подписываем payload
+    let signature = crate::crypto::sign_payload(payload_bytes, &signer.private_key)
+        .map_err(|e| DbError::InitError(format!("Failed to sign payload: {}", e)))?;
+    
+    // Записываем подпись в version_signatures
+    conn.execute(
+        "INSERT INTO version_signatures (version_id, signature, public_key, ts) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![version_id, &signature, &signer.public_key, ts],
+    )?;
+    
+    Ok(())
+}
+
+/// Верификация подписи записи version_log
+///
+/// # Arguments
+/// - `path` - путь к базе данных
+/// - `key` - ключ шифрования
+/// - `version_id` - ID записи в version_log
+///
+/// # Returns
+/// - `Ok(true)` - подпись валидна
+/// - `Ok(false)` - подпись невалидна
+/// - `Err` - ошибка при выполнении (запись не найдена, отсутствует подпись и т.д.)
+pub fn verify_version_signature(path: &str, key: &str, version_id: i64) -> Result<bool, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    // Извлекаем payload из version_log
+    let payload: String = conn.query_row(
+        "SELECT payload FROM version_log WHERE id = ?1",
+        [version_id],
+        |row| row.get(0),
+    ).map_err(|e| DbError::InitError(format!("Failed to get version_log payload: {}", e)))?;
+    
+    // Извлекаем signature и public_key из version_signatures
+    let (signature, public_key): (Vec<u8>, Vec<u8>) = conn.query_row(
+        "SELECT signature, public_key FROM version_signatures WHERE version_id = ?1",
+        [version_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).map_err(|e| DbError::InitError(format!("Failed to get signature: {}", e)))?;
+    
+    // Верифицируем подпись
+    let payload_bytes = payload.as_bytes();
+    let is_valid = crate::crypto::verify_payload(payload_bytes, &signature, &public_key)
+        .map_err(|e| DbError::InitError(format!("Failed to verify signature: {}", e)))?;
+    
+    Ok(is_valid)
+}
+
+// Функции работы со счетами
+
+/// Создание нового счёта
+/// 
+/// Класс счёта выводится из типа (см. `AccountClass::infer_from_type`)
+pub fn create_account(path: &str, key: &str, name: String, acc_type: String) -> Result<i64, DbError> {
+    let acc_class = AccountClass::infer_from_type(&acc_type);
+    create_account_with_class(path, key, name, acc_class, acc_type)
+}
+
+/// Создание нового счёта с явным классом
+/// 
+/// # Параметры
+/// - `acc_class` - класс счёта (актив, обязательство, ...)
+/// - `acc_type` - подтип счёта внутри класса
+pub fn create_account_with_class(
+    path: &str,
+    key: &str,
+    name: String,
+    acc_class: AccountClass,
+    acc_type: String,
+) -> Result<i64, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    insert_account(&conn, path, key, None, name, acc_class, acc_type)
+}
+
+/// Вставка счёта с логированием в version_log
+/// 
+/// `id` задаётся при импорте с сохранением идентификаторов; `None` - новый ID.
+fn insert_account(
+    conn: &Connection,
+    path: &str,
+    key: &str,
+    id: Option<i64>,
+    name: String,
+    acc_class: AccountClass,
+    acc_type: String,
+) -> Result<i64, DbError> {
+    // Получаем текущий timestamp в секундах
+    let created_at = current_timestamp()?;
+    
+    conn.execute(
+        "INSERT INTO accounts (id, name, class, type, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![id, name, acc_class, acc_type, created_at],
+    )?;
+    
+    let account_id = conn.last_insert_rowid();
+    
+    // Создаём объект Account для логирования
+    let account = Account {
+        id: account_id,
+        name,
+        acc_class,
+        acc_type,
+        created_at,
+        closed_at: None,
+    };
+    
+    // Сериализуем аккаунт в JSON
+    let payload_json = serialize_entity(&account)?;
+    
+    // Логируем создание аккаунта
+    write_version_log(conn, path, key, "account", account_id, "create", &payload_json)?;
+    
+    Ok(account_id)
+}
+
+/// Изменение класса и подтипа счёта
+pub fn set_account_class(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    acc_class: AccountClass,
+    acc_type: String,
+) -> Result<(), DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    ensure_account_exists(&tx, account_id)?;
+    
+    tx.execute(
+        "UPDATE accounts SET class = ?1, type = ?2 WHERE id = ?3",
+        rusqlite::params![acc_class, acc_type, account_id],
+    )?;
+    
+    let account = load_account(&tx, account_id)?;
+    write_version_log(&tx, path, key, "account", account_id, "update", &serialize_entity(&account)?)?;
+    
+    tx.commit()?;
+    
+    Ok(())
+}
+
+/// Закрытие счёта
+/// 
+/// После закрытия операции с датой позже `ts` на счёт не принимаются.
+/// 
+/// # Параметры
+/// - `ts` - момент закрытия (если не указан - текущий); не раньше последней операции
+pub fn close_account(path: &str, key: &str, account_id: i64, ts: Option<i64>) -> Result<(), DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let ts = match ts {
+        Some(ts) => ts,
+        None => current_timestamp()?,
+    };
+    mark_account_closed(&tx, path, key, account_id, ts)?;
+    
+    tx.commit()?;
+    
+    Ok(())
+}
+
+/// Запись момента закрытия счёта в рамках транзакции вызывающего
+fn mark_account_closed(conn: &Connection, path: &str, key: &str, account_id: i64, ts: i64) -> Result<(), DbError> {
+    let account = load_account(conn, account_id)?;
+    if let Some(closed_at) = account.closed_at {
+        return Err(DbError::ValidationError(format!(
+            "Account {} is already closed (ts {})",
+            account_id, closed_at
+        )));
+    }
+    
+    let last_operation_ts: Option<i64> = conn.query_row(
+        "SELECT MAX(ts) FROM operations WHERE account_id = ?1",
+        [account_id],
+        |row| row.get(0),
+    )?;
+    if let Some(last_ts) = last_operation_ts {
+        if ts < last_ts {
+            return Err(DbError::ValidationError(format!(
+                "Account closing must not be earlier than the last operation (ts {})",
+                last_ts
+            )));
+        }
+    }
+    
+    conn.execute(
+        "UPDATE accounts SET closed_at = ?1 WHERE id = ?2",
+        rusqlite::params![ts, account_id],
+    )?;
+    
+    let account = Account { closed_at: Some(ts), ..account };
+    write_version_log(conn, path, key, "account", account_id, "update", &serialize_entity(&account)?)?;
+    
+    Ok(())
+}
+
+/// Маппинг строки accounts в структуру
+fn account_from_row(row: &rusqlite::Row) -> SqlResult<Account> {
+    Ok(Account {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        acc_class: row.get(2)?,
+        acc_type: row.get(3)?,
+        created_at: row.get(4)?,
+        closed_at: row.get(5)?,
+    })
+}
+
+/// Загрузка счёта по ID
+fn load_account(conn: &Connection, account_id: i64) -> Result<Account, DbError> {
+    conn.query_row(
+        "SELECT id, name, class, type, created_at, closed_at FROM accounts WHERE id = ?1",
+        [account_id],
+        account_from_row,
+    ).optional()?
+    .ok_or_else(|| DbError::ValidationError(format!("Account {} not found", account_id)))
+}
+
+/// Получение списка всех счетов
+pub fn list_accounts(path: &str, key: &str) -> Result<Vec<Account>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, name, class, type, created_at, closed_at FROM accounts ORDER BY created_at DESC"
+    )?;
+    
+    let accounts = stmt.query_map([], account_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(accounts)
+}
+
+// Функции работы с операциями
+
+/// Проверка существования счёта
+fn ensure_account_exists(conn: &Connection, account_id: i64) -> Result<(), DbError> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM accounts WHERE id = ?1",
+        [account_id],
+        |row| row.get(0),
+    )?;
+    
+    if count == 0 {
+        return Err(DbError::ValidationError(format!("Account {} not found", account_id)));
+    }
+    
+    Ok(())
+}
+
+/// Баланс счёта на момент ts (последний снимок states с ts <= заданного)
+fn get_balance_at(conn: &Connection, account_id: i64, ts: i64) -> SqlResult<f64> {
+    let balance: Result<f64, _> = conn.query_row(
+        "SELECT balance FROM states WHERE account_id = ?1 AND ts <= ?2 ORDER BY ts DESC LIMIT 1",
+        [account_id, ts],
+        |row| row.get(0),
+    );
+    
+    Ok(balance.unwrap_or(0.0))
+}
+
+/// Применение изменения баланса к цепочке states начиная с момента ts
+/// 
+/// Нужна для записей задним числом: снимок на момент ts создаётся
+/// (или корректируется, если уже существует), а все более поздние снимки
+/// сдвигаются на `delta`. Каждое изменение states логируется в version_log.
+fn apply_balance_delta(
+    conn: &Connection,
+    path: &str,
+    key: &str,
+    account_id: i64,
+    ts: i64,
+    delta: f64,
+) -> Result<(), DbError> {
+    apply_balance_deltas(conn, &VersionSigner::load(path, key)?, account_id, &[(ts, delta)])
+}
+
+/// Пакетное применение изменений баланса `(ts, delta)` к цепочке states
+/// 
+/// Для каждого момента из `deltas` создаётся снимок (если его нет), затем
+/// каждый снимок начиная с самого раннего момента пересчитывается один раз
+/// с учётом всех изменений не позже него. Так вставка N записей задним
+/// числом стоит O(N) записей в version_log, а не O(N²).
+fn apply_balance_deltas(
+    conn: &Connection,
+    signer: &VersionSigner,
+    account_id: i64,
+    deltas: &[(i64, f64)],
+) -> Result<(), DbError> {
+    let mut totals: std::collections::BTreeMap<i64, f64> = std::collections::BTreeMap::new();
+    for &(ts, delta) in deltas {
+        *totals.entry(ts).or_insert(0.0) += delta;
+    }
+    
+    let Some(&first_ts) = totals.keys().next() else {
+        return Ok(());
+    };
+    
+    let states = {
+        let mut stmt = conn.prepare(
+            "SELECT id, balance, ts FROM states WHERE account_id = ?1 AND ts >= ?2 ORDER BY ts ASC"
+        )?;
+        let rows = stmt.query_map([account_id, first_ts], |row| {
+            Ok(State {
+                id: row.get(0)?,
+                account_id,
+                balance: row.get(1)?,
+                ts: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    // Новые снимки берут баланс предыдущего снимка до изменений
+    let mut timeline: Vec<(i64, Option<State>)> = states.into_iter().map(|state| (state.ts, Some(state))).collect();
+    for &ts in totals.keys() {
+        if let Err(position) = timeline.binary_search_by_key(&ts, |(state_ts, _)| *state_ts) {
+            timeline.insert(position, (ts, None));
+        }
+    }
+    
+    let mut shift = 0.0;
+    let mut pending = totals.into_iter().peekable();
+    let mut previous_balance = get_balance_at(conn, account_id, first_ts - 1)?;
+    for (ts, existing) in timeline {
+        while let Some((_, delta)) = pending.next_if(|(delta_ts, _)| *delta_ts <= ts) {
+            shift += delta;
+        }
+        
+        match existing {
+            Some(mut state) => {
+                previous_balance = state.balance;
+                state.balance += shift;
+                conn.execute(
+                    "UPDATE states SET balance = ?1 WHERE id = ?2",
+                    rusqlite::params![state.balance, state.id],
+                )?;
+                write_signed_version_log(conn, signer, "state", state.id, "update", &serialize_entity(&state)?)?;
+            }
+            None => {
+                let balance = previous_balance + shift;
+                conn.execute(
+                    "INSERT INTO states (account_id, balance, ts) VALUES (?1, ?2, ?3)",
+                    rusqlite::params![account_id, balance, ts],
+                )?;
+                let state = State { id: conn.last_insert_rowid(), account_id, balance, ts };
+                write_signed_version_log(conn, signer, "state", state.id, "create", &serialize_entity(&state)?)?;
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+/// Вставка операции с обновлением цепочки states
+/// 
+/// Вызывается внутри транзакции. Операция и изменённые снимки states
+/// логируются в version_log.
+fn insert_operation(
+    conn: &Connection,
+    path: &str,
+    key: &str,
+    account_id: i64,
+    amount: f64,
+    description: &str,
+    ts: i64,
+) -> Result<i64, DbError> {
+    insert_operation_with_id(conn, path, key, None, account_id, amount, description, ts)
+}
+
+/// Вставка операции с заданным ID (`None` - новый ID), см. `insert_operation`
+#[allow(clippy::too_many_arguments)]
+fn insert_operation_with_id(
+    conn: &Connection,
+    path: &str,
+    key: &str,
+    id: Option<i64>,
+    account_id: i64,
+    amount: f64,
+    description: &str,
+    ts: i64,
+) -> Result<i64, DbError> {
+    let signer = VersionSigner::load(path, key)?;
+    let rules = load_active_rules(conn)?;
+    let operation_id = insert_operation_record(conn, &signer, &rules, id, account_id, amount, description, ts)?;
+    
+    // Обновляем баланс в states (с учётом снимков после ts)
+    apply_balance_deltas(conn, &signer, account_id, &[(ts, amount)])?;
+    
+    Ok(operation_id)
+}
+
+/// Вставка строки операции без обновления states
+/// 
+/// Применяет переданные активные правила автокатегоризации и правила
+/// нормализации контрагентов. Баланс обновляет вызывающая сторона: для одной
+/// операции сразу, для пакета импорта - один раз после вставки всех строк.
+#[allow(clippy::too_many_arguments)]
+fn insert_operation_record(
+    conn: &Connection,
+    signer: &VersionSigner,
+    rules: &[ActiveRule],
+    id: Option<i64>,
+    account_id: i64,
+    amount: f64,
+    description: &str,
+    ts: i64,
+) -> Result<i64, DbError> {
+    // Закрытый счёт не принимает операции позже момента закрытия
+    let closed_at: Option<i64> = conn.query_row(
+        "SELECT closed_at FROM accounts WHERE id = ?1",
+        [account_id],
+        |row| row.get(0),
+    ).optional()?.flatten();
+    if let Some(closed_at) = closed_at {
+        if ts > closed_at {
+            return Err(DbError::ValidationError(format!(
+                "Account {} is closed since ts {}",
+                account_id, closed_at
+            )));
+        }
+    }
+    
+    let mut operation = Operation {
+        id: 0,
+        account_id,
+        amount,
+        description: description.to_string(),
+        ts,
+        payee_id: None,
+        category_id: None,
+        notes: None,
+    };
+    
+    // Правила автокатегоризации; контрагент из правила важнее нормализации
+    let mut changes = plan_rule_changes(rules, &operation, &[]);
+    for change in &changes {
+        match change.field.as_str() {
+            "category" => operation.category_id = Some(change.new_value),
+            "payee" => operation.payee_id = Some(change.new_value),
+            _ => {}
+        }
+    }
+    
+    // Определяем контрагента по правилам нормализации
+    if operation.payee_id.is_none() {
+        operation.payee_id = resolve_payee(conn, description)?;
+    }
+    
+    // Вставляем операцию
+    conn.execute(
+        "INSERT INTO operations (id, account_id, amount, description, ts, payee_id, category_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![id, account_id, amount, description, ts, operation.payee_id, operation.category_id],
+    )?;
+    
+    let operation_id = conn.last_insert_rowid();
+    operation.id = operation_id;
+    
+    // Сериализуем операцию в JSON
+    let operation_json = serialize_entity(&operation)?;
+    
+    // Логируем создание операции
+    write_signed_version_log(conn, signer, "operation", operation_id, "create", &operation_json)?;
+    
+    // Теги из правил и записи о сработавших правилах
+    for change in &mut changes {
+        change.operation_id = operation_id;
+        apply_rule_change(conn, signer, change)?;
+    }
+    
+    refresh_operation_search(conn, operation_id)?;
+    
+    Ok(operation_id)
+}
+
+/// Добавление операции с автоматическим обновлением баланса
+pub fn add_operation(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    amount: f64,
+    description: String,
+) -> Result<i64, DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    // Начинаем транзакцию
+    let tx = conn.transaction()?;
+    
+    // Получаем текущий timestamp
+    let ts = current_timestamp()?;
+    
+    let operation_id = insert_operation(&tx, path, key, account_id, amount, &description, ts)?;
+    
+    // Коммитим транзакцию (включая операцию, баланс и оба лога)
+    tx.commit()?;
+    
+    Ok(operation_id)
+}
+
+/// Добавление операции с заданной датой (в том числе задним числом)
+/// 
+/// Баланс на момент `ts` и все более поздние снимки states пересчитываются.
+pub fn add_operation_at(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    amount: f64,
+    description: String,
+    ts: i64,
+) -> Result<i64, DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    ensure_account_exists(&tx, account_id)?;
+    
+    let operation_id = insert_operation(&tx, path, key, account_id, amount, &description, ts)?;
+    
+    tx.commit()?;
+    
+    Ok(operation_id)
+}
+
+/// Получение списка операций по счёту
+pub fn get_operations(path: &str, key: &str, account_id: i64) -> Result<Vec<Operation>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, account_id, amount, description, ts, payee_id, category_id, notes FROM operations 
+         WHERE account_id = ?1 ORDER BY ts DESC"
+    )?;
+    
+    let operations = stmt.query_map([account_id], operation_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(operations)
+}
+
+/// Маппинг строки operations в структуру
+/// 
+/// Ожидает колонки: id, account_id, amount, description, ts, payee_id, category_id, notes
+fn operation_from_row(row: &rusqlite::Row) -> SqlResult<Operation> {
+    Ok(Operation {
+        id: row.get(0)?,
+        account_id: row.get(1)?,
+        amount: row.get(2)?,
+        description: row.get(3)?,
+        ts: row.get(4)?,
+        payee_id: row.get(5)?,
+        category_id: row.get(6)?,
+        notes: row.get(7)?,
+    })
+}
+
+// Функции для начальных остатков и сверок баланса
+
+/// Установка начального остатка счёта
+/// 
+/// Начальный остаток не является операцией и не попадает в аналитику доходов:
+/// он только записывается в цепочку states на момент `ts`. Повторный вызов
+/// заменяет ранее установленный остаток.
+/// 
+/// # Параметры
+/// - `path` - путь к базе данных
+/// - `key` - ключ шифрования
+/// - `account_id` - ID аккаунта
+/// - `balance` - сумма начального остатка
+/// - `ts` - момент, на который остаток действителен (не позже первой операции)
+pub fn set_opening_balance(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    balance: f64,
+    ts: i64,
+) -> Result<i64, DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let opening_id = upsert_opening_balance(&tx, path, key, account_id, balance, ts)?;
+    
+    tx.commit()?;
+    
+    Ok(opening_id)
+}
+
+/// Установка начального остатка в рамках транзакции вызывающего
+fn upsert_opening_balance(
+    conn: &Connection,
+    path: &str,
+    key: &str,
+    account_id: i64,
+    balance: f64,
+    ts: i64,
+) -> Result<i64, DbError> {
+    ensure_account_exists(conn, account_id)?;
+    
+    // Начальный остаток не может быть позже первой операции счёта
+    let first_operation_ts: Option<i64> = conn.query_row(
+        "SELECT MIN(ts) FROM operations WHERE account_id = ?1",
+        [account_id],
+        |row| row.get(0),
+    )?;
+    
+    if let Some(first_ts) = first_operation_ts {
+        if ts > first_ts {
+            return Err(DbError::ValidationError(format!(
+                "Opening balance must not be later than the first operation (ts {})",
+                first_ts
+            )));
+        }
+    }
+    
+    let existing: Option<(i64, f64, i64)> = conn.query_row(
+        "SELECT id, balance, ts FROM opening_balances WHERE account_id = ?1",
+        [account_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    ).optional()?;
+    
+    let (opening_id, action) = match existing {
+        Some((opening_id, old_balance, old_ts)) => {
+            // Откатываем влияние прежнего остатка на цепочку states
+            apply_balance_delta(conn, path, key, account_id, old_ts, -old_balance)?;
+            
+            conn.execute(
+                "UPDATE opening_balances SET balance = ?1, ts = ?2 WHERE id = ?3",
+                rusqlite::params![balance, ts, opening_id],
+            )?;
+            (opening_id, "update")
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO opening_balances (account_id, balance, ts) VALUES (?1, ?2, ?3)",
+                rusqlite::params![account_id, balance, ts],
+            )?;
+            (conn.last_insert_rowid(), "create")
+        }
+    };
+    
+    apply_balance_delta(conn, path, key, account_id, ts, balance)?;
+    
+    let opening = OpeningBalance {
+        id: opening_id,
+        account_id,
+        balance,
+        ts,
+    };
+    
+    write_version_log(conn, path, key, "opening_balance", opening_id, action, &serialize_entity(&opening)?)?;
+    
+    Ok(opening_id)
+}
+
+/// Получение начального остатка счёта
+pub fn get_opening_balance(
+    path: &str,
+    key: &str,
+    account_id: i64,
+) -> Result<Option<OpeningBalance>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let opening = conn.query_row(
+        "SELECT id, account_id, balance, ts FROM opening_balances WHERE account_id = ?1",
+        [account_id],
+        |row| {
+            Ok(OpeningBalance {
+                id: row.get(0)?,
+                account_id: row.get(1)?,
+                balance: row.get(2)?,
+                ts: row.get(3)?,
+            })
+        },
+    ).optional()?;
+    
+    Ok(opening)
+}
+
+/// Добавление сверки баланса ("на момент ts баланс счёта должен быть равен expected_balance")
+/// 
+/// # Параметры
+/// - `path` - путь к базе данных
+/// - `key` - ключ шифрования
+/// - `account_id` - ID аккаунта
+/// - `expected_balance` - ожидаемый баланс
+/// - `ts` - момент, на который проверяется баланс
+pub fn add_balance_assertion(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    expected_balance: f64,
+    ts: i64,
+) -> Result<i64, DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let assertion_id = insert_balance_assertion(&tx, path, key, None, account_id, expected_balance, ts)?;
+    
+    tx.commit()?;
+    
+    Ok(assertion_id)
+}
+
+/// Вставка сверки баланса с заданным ID (`None` - новый ID)
+fn insert_balance_assertion(
+    conn: &Connection,
+    path: &str,
+    key: &str,
+    id: Option<i64>,
+    account_id: i64,
+    expected_balance: f64,
+    ts: i64,
+) -> Result<i64, DbError> {
+    ensure_account_exists(conn, account_id)?;
+    
+    let created_at = current_timestamp()?;
+    
+    conn.execute(
+        "INSERT INTO balance_assertions (id, account_id, expected_balance, ts, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![id, account_id, expected_balance, ts, created_at],
+    )?;
+    
+    let assertion = BalanceAssertion {
+        id: conn.last_insert_rowid(),
+        account_id,
+        expected_balance,
+        ts,
+        created_at,
+    };
+    
+    write_version_log(conn, path, key, "balance_assertion", assertion.id, "create", &serialize_entity(&assertion)?)?;
+    
+    Ok(assertion.id)
+}
+
+/// Получение списка сверок баланса (в хронологическом порядке)
+/// 
+/// # Параметры
+/// - `account_id` - фильтр по счёту; если не указан - возвращаются сверки всех счетов
+pub fn list_balance_assertions(
+    path: &str,
+    key: &str,
+    account_id: Option<i64>,
+) -> Result<Vec<BalanceAssertion>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, account_id, expected_balance, ts, created_at FROM balance_assertions
+         WHERE ?1 IS NULL OR account_id = ?1
+         ORDER BY ts ASC, id ASC"
+    )?;
+    
+    let assertions = stmt.query_map([account_id], |row| {
+        Ok(BalanceAssertion {
+            id: row.get(0)?,
+            account_id: row.get(1)?,
+            expected_balance: row.get(2)?,
+            ts: row.get(3)?,
+            created_at: row.get(4)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(assertions)
+}
+
+/// Проверка сверок баланса по цепочке states
+/// 
+/// Для каждой сверки фактический баланс берётся из последнего снимка states
+/// с ts не позже момента сверки. Сверки проверяются в хронологическом порядке,
+/// первая не прошедшая сверка возвращается в `first_failure`.
+/// 
+/// # Параметры
+/// - `account_id` - фильтр по счёту; если не указан - проверяются все счета
+pub fn check_balance_assertions(
+    path: &str,
+    key: &str,
+    account_id: Option<i64>,
+) -> Result<BalanceAssertionReport, DbError> {
+    let assertions = list_balance_assertions(path, key, account_id)?;
+    
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut results = Vec::with_capacity(assertions.len());
+    for assertion in assertions {
+        let actual_balance = get_balance_at(&conn, assertion.account_id, assertion.ts)?;
+        let difference = actual_balance - assertion.expected_balance;
+        
+        results.push(BalanceAssertionResult {
+            assertion_id: assertion.id,
+            account_id: assertion.account_id,
+            ts: assertion.ts,
+            expected_balance: assertion.expected_balance,
+            actual_balance,
+            difference,
+            passed: difference.abs() <= AMOUNT_EPSILON,
+        });
+    }
+    
+    let first_failure = results.iter().find(|r| !r.passed).cloned();
+    let failed_count = results.iter().filter(|r| !r.passed).count() as i64;
+    let passed_count = results.len() as i64 - failed_count;
+    
+    Ok(BalanceAssertionReport {
+        results,
+        first_failure,
+        passed_count,
+        failed_count,
+    })
+}
+
+// Функции сверки с банковской выпиской
+
+/// Загрузка сессии сверки вместе со строками выписки
+fn load_reconciliation_session(conn: &Connection, session_id: i64) -> Result<ReconciliationSession, DbError> {
+    let mut session = conn.query_row(
+        "SELECT id, account_id, statement_name, period_start, period_end, closing_balance,
+                ledger_balance, status, created_at, reconciled_at
+         FROM reconciliation_sessions WHERE id = ?1",
+        [session_id],
+        |row| {
+            Ok(ReconciliationSession {
+                id: row.get(0)?,
+                account_id: row.get(1)?,
+                statement_name: row.get(2)?,
+                period_start: row.get(3)?,
+                period_end: row.get(4)?,
+                closing_balance: row.get(5)?,
+                ledger_balance: row.get(6)?,
+                status: row.get(7)?,
+                created_at: row.get(8)?,
+                reconciled_at: row.get(9)?,
+                lines: Vec::new(),
+            })
+        },
+    ).optional()?
+    .ok_or_else(|| DbError::ValidationError(format!("Reconciliation session {} not found", session_id)))?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, session_id, ts, amount, description, operation_id, match_status
+         FROM statement_lines WHERE session_id = ?1 ORDER BY ts ASC, id ASC"
+    )?;
+    
+    session.lines = stmt.query_map([session_id], |row| {
+        Ok(StatementLine {
+            id: row.get(0)?,
+            session_id: row.get(1)?,
+            ts: row.get(2)?,
+            amount: row.get(3)?,
+            description: row.get(4)?,
+            operation_id: row.get(5)?,
+            match_status: row.get(6)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(session)
+}
+
+/// Загрузка строки выписки вместе с её сессией (сессия должна быть открыта)
+fn load_open_statement_line(conn: &Connection, line_id: i64) -> Result<(ReconciliationSession, usize), DbError> {
+    let session_id: i64 = conn.query_row(
+        "SELECT session_id FROM statement_lines WHERE id = ?1",
+        [line_id],
+        |row| row.get(0),
+    ).optional()?
+    .ok_or_else(|| DbError::ValidationError(format!("Statement line {} not found", line_id)))?;
+    
+    let session = load_reconciliation_session(conn, session_id)?;
+    if session.status != "open" {
+        return Err(DbError::ValidationError(format!(
+            "Reconciliation session {} is already {}",
+            session_id, session.status
+        )));
+    }
+    
+    let index = session.lines.iter().position(|line| line.id == line_id)
+        .ok_or_else(|| DbError::ValidationError(format!("Statement line {} not found", line_id)))?;
+    
+    Ok((session, index))
+}
+
+/// Логирование изменения строки выписки
+fn log_statement_line(conn: &Connection, path: &str, key: &str, line: &StatementLine) -> Result<(), DbError> {
+    write_version_log(conn, path, key, "statement_line", line.id, "update", &serialize_entity(line)?)
+}
+
+/// Нормализованные слова описания для нечёткого сравнения
+fn description_tokens(description: &str) -> std::collections::HashSet<String> {
+    description
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_string())
+        .collect()
+}
+
+/// Схожесть описаний (коэффициент Жаккара по словам, от 0.0 до 1.0)
+fn description_similarity(a: &str, b: &str) -> f64 {
+    let tokens_a = description_tokens(a);
+    let tokens_b = description_tokens(b);
+    
+    if tokens_a.is_empty() && tokens_b.is_empty() {
+        return 1.0;
+    }
+    
+    let common = tokens_a.intersection(&tokens_b).count() as f64;
+    let total = tokens_a.union(&tokens_b).count() as f64;
+    common / total
+}
+
+/// Начало сверки счёта с банковской выпиской
+/// 
+/// Создаёт сессию сверки и загружает строки выписки. Все строки изначально
+/// не сопоставлены с операциями.
+/// 
+/// # Параметры
+/// - `account_id` - ID сверяемого счёта
+/// - `statement_name` - идентификатор выписки (имя файла, номер выписки и т.п.)
+/// - `period_start`, `period_end` - период выписки
+/// - `closing_balance` - конечный остаток по выписке (если известен)
+/// - `lines` - строки выписки
+#[allow(clippy::too_many_arguments)]
+pub fn start_reconciliation(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    statement_name: String,
+    period_start: i64,
+    period_end: i64,
+    closing_balance: Option<f64>,
+    lines: Vec<StatementLineInput>,
+) -> Result<i64, DbError> {
+    if period_end < period_start {
+        return Err(DbError::ValidationError("Statement period end is before its start".to_string()));
+    }
+    
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    ensure_account_exists(&tx, account_id)?;
+    
+    let created_at = current_timestamp()?;
+    
+    tx.execute(
+        "INSERT INTO reconciliation_sessions
+            (account_id, statement_name, period_start, period_end, closing_balance, status, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, 'open', ?6)",
+        rusqlite::params![account_id, statement_name, period_start, period_end, closing_balance, created_at],
+    )?;
+    
+    let session_id = tx.last_insert_rowid();
+    
+    for line in &lines {
+        tx.execute(
+            "INSERT INTO statement_lines (session_id, ts, amount, description, match_status)
+             VALUES (?1, ?2, ?3, ?4, 'unmatched')",
+            rusqlite::params![session_id, line.ts, line.amount, line.description],
+        )?;
+    }
+    
+    // В payload попадает вся выписка, чтобы подпись фиксировала её содержимое
+    let session = load_reconciliation_session(&tx, session_id)?;
+    write_version_log(&tx, path, key, "reconciliation", session_id, "create", &serialize_entity(&session)?)?;
+    
+    tx.commit()?;
+    
+    Ok(session_id)
+}
+
+/// Получение сессии сверки со строками выписки
+pub fn get_reconciliation(path: &str, key: &str, session_id: i64) -> Result<ReconciliationSession, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    load_reconciliation_session(&conn, session_id)
+}
+
+/// Получение списка сессий сверки (новые первыми)
+/// 
+/// # Параметры
+/// - `account_id` - фильтр по счёту; если не указан - возвращаются сессии всех счетов
+pub fn list_reconciliations(
+    path: &str,
+    key: &str,
+    account_id: Option<i64>,
+) -> Result<Vec<ReconciliationSession>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let session_ids = {
+        let mut stmt = conn.prepare(
+            "SELECT id FROM reconciliation_sessions
+             WHERE ?1 IS NULL OR account_id = ?1
+             ORDER BY period_end DESC, id DESC"
+        )?;
+        let ids = stmt.query_map([account_id], |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        ids
+    };
+    
+    session_ids
+        .into_iter()
+        .map(|session_id| load_reconciliation_session(&conn, session_id))
+        .collect()
+}
+
+/// Автоматическое сопоставление строк выписки с операциями
+/// 
+/// Кандидатами считаются операции счёта, сумма которых отличается не больше чем
+/// на `amount_tolerance`, а дата - не больше чем на `date_tolerance_days` дней.
+/// Кандидаты ранжируются по близости суммы, даты и схожести описания; каждая
+/// операция предлагается не более чем одной строке. Найденные пары сохраняются
+/// со статусом `suggested` и требуют подтверждения через `confirm_statement_match`.
+pub fn auto_match_statement(
+    path: &str,
+    key: &str,
+    session_id: i64,
+    amount_tolerance: f64,
+    date_tolerance_days: i64,
+) -> Result<Vec<StatementMatch>, DbError> {
+    if amount_tolerance < 0.0 || date_tolerance_days < 0 {
+        return Err(DbError::ValidationError("Match tolerances must not be negative".to_string()));
+    }
+    
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let session = load_reconciliation_session(&tx, session_id)?;
+    if session.status != "open" {
+        return Err(DbError::ValidationError(format!(
+            "Reconciliation session {} is already {}",
+            session_id, session.status
+        )));
+    }
+    
+    let date_tolerance = date_tolerance_days * SECONDS_PER_DAY;
+    
+    // Операции, ещё не привязанные к подтверждённым строкам выписок
+    let operations = {
+        let mut stmt = tx.prepare(
+            "SELECT id, account_id, amount, description, ts, payee_id, category_id, notes FROM operations
+             WHERE account_id = ?1 AND ts >= ?2 AND ts <= ?3
+               AND id NOT IN (
+                   SELECT operation_id FROM statement_lines
+                   WHERE operation_id IS NOT NULL AND match_status IN ('confirmed', 'created')
+               )"
+        )?;
+        let rows = stmt.query_map(
+            [session.account_id, session.period_start - date_tolerance, session.period_end + date_tolerance],
+            operation_from_row,
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    // Все допустимые пары (строка, операция) с оценкой
+    let mut candidates = Vec::new();
+    for line in session.lines.iter().filter(|line| line.match_status == "unmatched" || line.match_status == "suggested") {
+        for operation in &operations {
+            let amount_diff = (line.amount - operation.amount).abs();
+            let date_diff = (line.ts - operation.ts).abs();
+            if amount_diff > amount_tolerance + AMOUNT_EPSILON || date_diff > date_tolerance {
+                continue;
+            }
+            
+            let amount_score = 1.0 - amount_diff / (amount_tolerance + AMOUNT_EPSILON);
+            let date_score = 1.0 - date_diff as f64 / (date_tolerance + SECONDS_PER_DAY) as f64;
+            let description_score = description_similarity(&line.description, &operation.description);
+            
+            candidates.push(StatementMatch {
+                line_id: line.id,
+                operation_id: operation.id,
+                score: 0.5 * amount_score + 0.3 * date_score + 0.2 * description_score,
+            });
+        }
+    }
+    
+    // Жадное назначение: сначала пары с наибольшей оценкой
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    
+    let mut used_lines = std::collections::HashSet::new();
+    let mut used_operations = std::collections::HashSet::new();
+    let mut matches = Vec::new();
+    for candidate in candidates {
+        if used_lines.contains(&candidate.line_id) || used_operations.contains(&candidate.operation_id) {
+            continue;
+        }
+        used_lines.insert(candidate.line_id);
+        used_operations.insert(candidate.operation_id);
+        matches.push(candidate);
+    }
+    
+    // Сбрасываем прежние предложения и сохраняем новые
+    tx.execute(
+        "UPDATE statement_lines SET operation_id = NULL, match_status = 'unmatched'
+         WHERE session_id = ?1 AND match_status = 'suggested'",
+        [session_id],
+    )?;
+    
+    for m in &matches {
+        tx.execute(
+            "UPDATE statement_lines SET operation_id = ?1, match_status = 'suggested' WHERE id = ?2",
+            [m.operation_id, m.line_id],
+        )?;
+    }
+    
+    tx.commit()?;
+    
+    matches.sort_by_key(|m| m.line_id);
+    Ok(matches)
+}
+
+/// Подтверждение сопоставления строки выписки с операцией
+/// 
+/// # Параметры
+/// - `line_id` - ID строки выписки
+/// - `operation_id` - ID операции того же счёта
+pub fn confirm_statement_match(
+    path: &str,
+    key: &str,
+    line_id: i64,
+    operation_id: i64,
+) -> Result<(), DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let (session, index) = load_open_statement_line(&tx, line_id)?;
+    let mut line = session.lines.into_iter().nth(index)
+        .ok_or_else(|| DbError::ValidationError(format!("Statement line {} not found", line_id)))?;
+    
+    if line.match_status == "confirmed" || line.match_status == "created" {
+        return Err(DbError::ValidationError(format!(
+            "Statement line {} is already matched",
+            line_id
+        )));
+    }
+    
+    let operation_account: i64 = tx.query_row(
+        "SELECT account_id FROM operations WHERE id = ?1",
+        [operation_id],
+        |row| row.get(0),
+    ).optional()?
+    .ok_or_else(|| DbError::ValidationError(format!("Operation {} not found", operation_id)))?;
+    
+    if operation_account != session.account_id {
+        return Err(DbError::ValidationError(format!(
+            "Operation {} belongs to another account",
+            operation_id
+        )));
+    }
+    
+    let already_matched: i64 = tx.query_row(
+        "SELECT COUNT(*) FROM statement_lines
+         WHERE operation_id = ?1 AND id != ?2 AND match_status IN ('confirmed', 'created')",
+        [operation_id, line_id],
+        |row| row.get(0),
+    )?;
+    
+    if already_matched > 0 {
+        return Err(DbError::ValidationError(format!(
+            "Operation {} is already matched to another statement line",
+            operation_id
+        )));
+    }
+    
+    tx.execute(
+        "UPDATE statement_lines SET operation_id = ?1, match_status = 'confirmed' WHERE id = ?2",
+        [operation_id, line_id],
+    )?;
+    
+    line.operation_id = Some(operation_id);
+    line.match_status = "confirmed".to_string();
+    log_statement_line(&tx, path, key, &line)?;
+    
+    tx.commit()?;
+    
+    Ok(())
+}
+
+/// Создание недостающей операции по строке выписки
+/// 
+/// Операция создаётся с датой, суммой и описанием строки выписки и сразу
+/// привязывается к ней.
+pub fn create_operation_from_statement_line(
+    path: &str,
+    key: &str,
+    line_id: i64,
+) -> Result<i64, DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let (session, index) = load_open_statement_line(&tx, line_id)?;
+    let mut line = session.lines.into_iter().nth(index)
+        .ok_or_else(|| DbError::ValidationError(format!("Statement line {} not found", line_id)))?;
+    
+    if line.match_status == "confirmed" || line.match_status == "created" {
+        return Err(DbError::ValidationError(format!(
+            "Statement line {} is already matched",
+            line_id
+        )));
+    }
+    
+    let operation_id = insert_operation(&tx, path, key, session.account_id, line.amount, &line.description, line.ts)?;
+    
+    tx.execute(
+        "UPDATE statement_lines SET operation_id = ?1, match_status = 'created' WHERE id = ?2",
+        [operation_id, line_id],
+    )?;
+    
+    line.operation_id = Some(operation_id);
+    line.match_status = "created".to_string();
+    log_statement_line(&tx, path, key, &line)?;
+    
+    tx.commit()?;
+    
+    Ok(operation_id)
+}
+
+/// Завершение сверки: период отмечается как сверенный
+/// 
+/// Все строки выписки должны быть подтверждены или созданы как операции.
+/// Если в выписке указан конечный остаток, он должен совпадать с балансом
+/// счёта из states на конец периода. Итог сверки (включая строки выписки)
+/// записывается в подписанный version_log.
+pub fn complete_reconciliation(
+    path: &str,
+    key: &str,
+    session_id: i64,
+) -> Result<ReconciliationSession, DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let session = load_reconciliation_session(&tx, session_id)?;
+    if session.status != "open" {
+        return Err(DbError::ValidationError(format!(
+            "Reconciliation session {} is already {}",
+            session_id, session.status
+        )));
+    }
+    
+    let unmatched = session.lines.iter()
+        .filter(|line| line.match_status != "confirmed" && line.match_status != "created")
+        .count();
+    if unmatched > 0 {
+        return Err(DbError::ValidationError(format!(
+            "{} statement lines are not matched",
+            unmatched
+        )));
+    }
+    
+    let ledger_balance = get_balance_at(&tx, session.account_id, session.period_end)?;
+    if let Some(closing_balance) = session.closing_balance {
+        if (ledger_balance - closing_balance).abs() > AMOUNT_EPSILON {
+            return Err(DbError::ValidationError(format!(
+                "Ledger balance {:.2} does not match statement closing balance {:.2}",
+                ledger_balance, closing_balance
+            )));
+        }
+    }
+    
+    let reconciled_at = current_timestamp()?;
+    
+    tx.execute(
+        "UPDATE reconciliation_sessions SET status = 'reconciled', ledger_balance = ?1, reconciled_at = ?2
+         WHERE id = ?3",
+        rusqlite::params![ledger_balance, reconciled_at, session_id],
+    )?;
+    
+    let session = load_reconciliation_session(&tx, session_id)?;
+    write_version_log(&tx, path, key, "reconciliation", session_id, "reconcile", &serialize_entity(&session)?)?;
+    
+    tx.commit()?;
+    
+    Ok(session)
+}
+
+// Функции работы с ценными бумагами и инвестиционными счетами
+
+/// Регистрация ценной бумаги
+/// 
+/// # Параметры
+/// - `ticker` - тикер бумаги
+/// - `isin` - ISIN (опционально, уникален)
+/// - `name` - название
+/// - `asset_class` - класс актива (см. `SECURITY_ASSET_CLASSES`)
+pub fn create_security(
+    path: &str,
+    key: &str,
+    ticker: String,
+    isin: Option<String>,
+    name: String,
+    asset_class: String,
+) -> Result<i64, DbError> {
+    if ticker.trim().is_empty() {
+        return Err(DbError::ValidationError("Ticker must not be empty".to_string()));
+    }
+    
+    if !SECURITY_ASSET_CLASSES.contains(&asset_class.as_str()) {
+        return Err(DbError::ValidationError(format!("Unknown asset class: {}", asset_class)));
+    }
+    
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let created_at = current_timestamp()?;
+    
+    tx.execute(
+        "INSERT INTO securities (ticker, isin, name, asset_class, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![ticker, isin, name, asset_class, created_at],
+    )?;
+    
+    let security = Security {
+        id: tx.last_insert_rowid(),
+        ticker,
+        isin,
+        name,
+        asset_class,
+        created_at,
+    };
+    
+    write_version_log(&tx, path, key, "security", security.id, "create", &serialize_entity(&security)?)?;
+    
+    tx.commit()?;
+    
+    Ok(security.id)
+}
+
+/// Получение списка ценных бумаг
+pub fn list_securities(path: &str, key: &str) -> Result<Vec<Security>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, ticker, isin, name, asset_class, created_at FROM securities ORDER BY ticker ASC"
+    )?;
+    
+    let securities = stmt.query_map([], |row| {
+        Ok(Security {
+            id: row.get(0)?,
+            ticker: row.get(1)?,
+            isin: row.get(2)?,
+            name: row.get(3)?,
+            asset_class: row.get(4)?,
+            created_at: row.get(5)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(securities)
+}
+
+/// Добавление котировки ценной бумаги
+/// 
+/// Котировка на тот же момент времени заменяет существующую.
+pub fn add_security_price(
+    path: &str,
+    key: &str,
+    security_id: i64,
+    price: f64,
+    ts: i64,
+) -> Result<i64, DbError> {
+    if price < 0.0 {
+        return Err(DbError::ValidationError("Price must not be negative".to_string()));
+    }
+    
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    ensure_security_exists(&tx, security_id)?;
+    
+    tx.execute(
+        "INSERT INTO security_prices (security_id, price, ts) VALUES (?1, ?2, ?3)
+         ON CONFLICT(security_id, ts) DO UPDATE SET price = excluded.price",
+        rusqlite::params![security_id, price, ts],
+    )?;
+    
+    let price_id: i64 = tx.query_row(
+        "SELECT id FROM security_prices WHERE security_id = ?1 AND ts = ?2",
+        [security_id, ts],
+        |row| row.get(0),
+    )?;
+    
+    let security_price = SecurityPrice {
+        id: price_id,
+        security_id,
+        price,
+        ts,
+    };
+    
+    write_version_log(&tx, path, key, "security_price", price_id, "create", &serialize_entity(&security_price)?)?;
+    
+    tx.commit()?;
+    
+    Ok(price_id)
+}
+
+/// Получение истории цен ценной бумаги (по возрастанию времени)
+pub fn get_price_history(
+    path: &str,
+    key: &str,
+    security_id: i64,
+) -> Result<Vec<SecurityPrice>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, security_id, price, ts FROM security_prices WHERE security_id = ?1 ORDER BY ts ASC"
+    )?;
+    
+    let prices = stmt.query_map([security_id], |row| {
+        Ok(SecurityPrice {
+            id: row.get(0)?,
+            security_id: row.get(1)?,
+            price: row.get(2)?,
+            ts: row.get(3)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(prices)
+}
+
+/// Проверка существования ценной бумаги
+fn ensure_security_exists(conn: &Connection, security_id: i64) -> Result<(), DbError> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM securities WHERE id = ?1",
+        [security_id],
+        |row| row.get(0),
+    )?;
+    
+    if count == 0 {
+        return Err(DbError::ValidationError(format!("Security {} not found", security_id)));
+    }
+    
+    Ok(())
+}
+
+/// Цена бумаги на момент ts
+/// 
+/// Берётся последняя котировка не позже ts; если котировок нет -
+/// цена последней сделки с бумагой не позже ts.
+fn get_security_price_at(conn: &Connection, security_id: i64, ts: i64) -> SqlResult<Option<(f64, i64)>> {
+    let quote = conn.query_row(
+        "SELECT price, ts FROM security_prices WHERE security_id = ?1 AND ts <= ?2 ORDER BY ts DESC LIMIT 1",
+        [security_id, ts],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional()?;
+    
+    if quote.is_some() {
+        return Ok(quote);
+    }
+    
+    conn.query_row(
+        "SELECT price, ts FROM investment_transactions
+         WHERE security_id = ?1 AND kind IN ('buy', 'sell') AND ts <= ?2
+         ORDER BY ts DESC, id DESC LIMIT 1",
+        [security_id, ts],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional()
+}
+
+/// Количество бумаги на счёте на момент ts
+fn get_held_quantity(conn: &Connection, account_id: i64, security_id: i64, ts: i64) -> SqlResult<f64> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(CASE kind WHEN 'buy' THEN quantity WHEN 'sell' THEN -quantity ELSE 0 END), 0.0)
+         FROM investment_transactions WHERE account_id = ?1 AND security_id = ?2 AND ts <= ?3",
+        [account_id, security_id, ts],
+        |row| row.get(0),
+    )
+}
+
+/// Позиции по бумагам на момент ts с рыночной оценкой
+fn load_holdings_at(conn: &Connection, account_id: Option<i64>, ts: i64) -> SqlResult<Vec<Holding>> {
+    let positions = {
+        let mut stmt = conn.prepare(
+            "SELECT t.account_id, t.security_id, s.ticker, s.asset_class,
+                    SUM(CASE t.kind WHEN 'buy' THEN t.quantity WHEN 'sell' THEN -t.quantity ELSE 0 END) AS quantity
+             FROM investment_transactions t
+             INNER JOIN securities s ON s.id = t.security_id
+             WHERE (?1 IS NULL OR t.account_id = ?1) AND t.ts <= ?2
+             GROUP BY t.account_id, t.security_id
+             ORDER BY t.account_id ASC, s.ticker ASC"
+        )?;
+        let rows = stmt.query_map(rusqlite::params![account_id, ts], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, f64>(4)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    let mut holdings = Vec::new();
+    for (account_id, security_id, ticker, asset_class, quantity) in positions {
+        if quantity.abs() <= QUANTITY_EPSILON {
+            continue;
+        }
+        
+        let quote = get_security_price_at(conn, security_id, ts)?;
+        holdings.push(Holding {
+            account_id,
+            security_id,
+            ticker,
+            asset_class,
+            quantity,
+            price: quote.map(|(price, _)| price),
+            price_ts: quote.map(|(_, price_ts)| price_ts),
+            market_value: quote.map(|(price, _)| price * quantity).unwrap_or(0.0),
+        });
+    }
+    
+    Ok(holdings)
+}
+
+/// Вставка инвестиционной сделки (внутри транзакции)
+/// 
+/// Проводит денежную часть сделки, для покупки открывает налоговый лот,
+/// для продажи списывает лоты методом `lot_method`.
+#[allow(clippy::too_many_arguments)]
+fn insert_investment_transaction(
+    conn: &Connection,
+    path: &str,
+    key: &str,
+    account_id: i64,
+    security_id: i64,
+    kind: String,
+    quantity: f64,
+    price: f64,
+    fee: f64,
+    ts: i64,
+    lot_method: &str,
+    lot_selection: Option<&[LotSelection]>,
+) -> Result<i64, DbError> {
+    if quantity <= 0.0 {
+        return Err(DbError::ValidationError("Quantity must be positive".to_string()));
+    }
+    
+    if price < 0.0 || fee < 0.0 {
+        return Err(DbError::ValidationError("Price and fee must not be negative".to_string()));
+    }
+    
+    ensure_account_exists(conn, account_id)?;
+    ensure_security_exists(conn, security_id)?;
+    
+    let ticker: String = conn.query_row(
+        "SELECT ticker FROM securities WHERE id = ?1",
+        [security_id],
+        |row| row.get(0),
+    )?;
+    
+    let gross = quantity * price;
+    let (amount, description) = match kind.as_str() {
+        "buy" => (-(gross + fee), format!("Buy {} {} @ {}", quantity, ticker, price)),
+        "sell" => {
+            let held = get_held_quantity(conn, account_id, security_id, ts)?;
+            if quantity > held + QUANTITY_EPSILON {
+                return Err(DbError::ValidationError(format!(
+                    "Cannot sell {} {}: only {} held",
+                    quantity, ticker, held
+                )));
+            }
+            (gross - fee, format!("Sell {} {} @ {}", quantity, ticker, price))
+        }
+        "dividend" => (gross - fee, format!("Dividend {} x {}", ticker, quantity)),
+        _ => {
+            return Err(DbError::ValidationError(format!("Unknown investment transaction kind: {}", kind)));
+        }
+    };
+    
+    let operation_id = insert_operation(conn, path, key, account_id, amount, &description, ts)?;
+    
+    conn.execute(
+        "INSERT INTO investment_transactions (account_id, security_id, kind, quantity, price, fee, operation_id, ts)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        rusqlite::params![account_id, security_id, kind, quantity, price, fee, operation_id, ts],
+    )?;
+    
+    let transaction = InvestmentTransaction {
+        id: conn.last_insert_rowid(),
+        account_id,
+        security_id,
+        kind,
+        quantity,
+        price,
+        fee,
+        operation_id,
+        ts,
+    };
+    
+    write_version_log(conn, path, key, "investment_transaction", transaction.id, "create", &serialize_entity(&transaction)?)?;
+    
+    match transaction.kind.as_str() {
+        "buy" => {
+            let lot = open_tax_lot(conn, &transaction)?;
+            write_version_log(conn, path, key, "tax_lot", lot.id, "create", &serialize_entity(&lot)?)?;
+        }
+        "sell" => {
+            for assignment in assign_tax_lots(conn, &transaction, lot_method, lot_selection)? {
+                write_version_log(conn, path, key, "lot_assignment", assignment.id, "create", &serialize_entity(&assignment)?)?;
+            }
+        }
+        _ => {}
+    }
+    
+    Ok(transaction.id)
+}
+
+/// Добавление инвестиционной сделки
+/// 
+/// Денежная часть сделки проводится как операция по счёту:
+/// - `buy` - списание `quantity * price + fee`, позиция увеличивается
+/// - `sell` - зачисление `quantity * price - fee`, позиция уменьшается
+///   (лоты списываются методом FIFO, другой метод - через `sell_security`)
+/// - `dividend` - зачисление `quantity * price - fee` (количество бумаг × дивиденд на бумагу),
+///   позиция не меняется
+/// 
+/// # Параметры
+/// - `account_id` - инвестиционный счёт
+/// - `security_id` - ценная бумага
+/// - `kind` - тип сделки (buy, sell, dividend)
+/// - `quantity` - количество бумаг
+/// - `price` - цена за бумагу (для дивиденда - выплата на бумагу)
+/// - `fee` - комиссия
+/// - `ts` - момент сделки
+#[allow(clippy::too_many_arguments)]
+pub fn add_investment_transaction(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    security_id: i64,
+    kind: String,
+    quantity: f64,
+    price: f64,
+    fee: f64,
+    ts: i64,
+) -> Result<i64, DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let transaction_id = insert_investment_transaction(
+        &tx, path, key, account_id, security_id, kind, quantity, price, fee, ts, "fifo", None,
+    )?;
+    
+    tx.commit()?;
+    
+    Ok(transaction_id)
+}
+
+/// Продажа ценной бумаги с выбором метода списания лотов
+/// 
+/// # Параметры
+/// - `lot_method` - метод списания: `fifo`, `lifo` или `specific`
+/// - `lot_selection` - лоты и количества для метода `specific`
+///   (сумма количеств должна совпадать с `quantity`)
+#[allow(clippy::too_many_arguments)]
+pub fn sell_security(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    security_id: i64,
+    quantity: f64,
+    price: f64,
+    fee: f64,
+    ts: i64,
+    lot_method: String,
+    lot_selection: Option<Vec<LotSelection>>,
+) -> Result<i64, DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let transaction_id = insert_investment_transaction(
+        &tx, path, key, account_id, security_id, "sell".to_string(), quantity, price, fee, ts,
+        &lot_method, lot_selection.as_deref(),
+    )?;
+    
+    tx.commit()?;
+    
+    Ok(transaction_id)
+}
+
+/// Получение инвестиционных сделок по счёту (по возрастанию времени)
+pub fn get_investment_transactions(
+    path: &str,
+    key: &str,
+    account_id: i64,
+) -> Result<Vec<InvestmentTransaction>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, account_id, security_id, kind, quantity, price, fee, operation_id, ts
+         FROM investment_transactions WHERE account_id = ?1 ORDER BY ts ASC, id ASC"
+    )?;
+    
+    let transactions = stmt.query_map([account_id], |row| {
+        Ok(InvestmentTransaction {
+            id: row.get(0)?,
+            account_id: row.get(1)?,
+            security_id: row.get(2)?,
+            kind: row.get(3)?,
+            quantity: row.get(4)?,
+            price: row.get(5)?,
+            fee: row.get(6)?,
+            operation_id: row.get(7)?,
+            ts: row.get(8)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(transactions)
+}
+
+/// Получение текущих позиций по бумагам с рыночной оценкой
+/// 
+/// # Параметры
+/// - `account_id` - фильтр по счёту; если не указан - позиции по всем счетам
+pub fn get_holdings(
+    path: &str,
+    key: &str,
+    account_id: Option<i64>,
+) -> Result<Vec<Holding>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let holdings = load_holdings_at(&conn, account_id, current_timestamp()?)?;
+    
+    Ok(holdings)
+}
+
+// Функции налоговых лотов и реализованного дохода
+
+/// Границы календарного года [начало, конец) в локальном часовом поясе
+fn year_bounds(year: i32) -> Result<(i64, i64), DbError> {
+    use chrono::TimeZone;
+    
+    let start_of = |y: i32| {
+        chrono::Local
+            .with_ymd_and_hms(y, 1, 1, 0, 0, 0)
+            .earliest()
+            .map(|dt| dt.timestamp())
+            .ok_or_else(|| DbError::ValidationError(format!("Invalid year: {}", year)))
+    };
+    
+    Ok((start_of(year)?, start_of(year + 1)?))
+}
+
+/// Открытие налогового лота по сделке покупки
+/// 
+/// Стоимость единицы включает комиссию покупки.
+fn open_tax_lot(conn: &Connection, purchase: &InvestmentTransaction) -> SqlResult<TaxLot> {
+    let cost_per_unit = purchase.price + purchase.fee / purchase.quantity;
+    
+    conn.execute(
+        "INSERT INTO tax_lots (account_id, security_id, transaction_id, quantity, remaining_quantity, cost_per_unit, acquired_ts)
+         VALUES (?1, ?2, ?3, ?4, ?4, ?5, ?6)",
+        rusqlite::params![
+            purchase.account_id,
+            purchase.security_id,
+            purchase.id,
+            purchase.quantity,
+            cost_per_unit,
+            purchase.ts,
+        ],
+    )?;
+    
+    Ok(TaxLot {
+        id: conn.last_insert_rowid(),
+        account_id: purchase.account_id,
+        security_id: purchase.security_id,
+        transaction_id: purchase.id,
+        quantity: purchase.quantity,
+        remaining_quantity: purchase.quantity,
+        cost_per_unit,
+        acquired_ts: purchase.ts,
+    })
+}
+
+/// Маппинг строки tax_lots в структуру
+fn tax_lot_from_row(row: &rusqlite::Row) -> SqlResult<TaxLot> {
+    Ok(TaxLot {
+        id: row.get(0)?,
+        account_id: row.get(1)?,
+        security_id: row.get(2)?,
+        transaction_id: row.get(3)?,
+        quantity: row.get(4)?,
+        remaining_quantity: row.get(5)?,
+        cost_per_unit: row.get(6)?,
+        acquired_ts: row.get(7)?,
+    })
+}
+
+/// Списание налоговых лотов по сделке продажи
+/// 
+/// Выручка на единицу уменьшается на комиссию продажи. Возвращает
+/// созданные записи списания (логирование - на стороне вызывающего).
+fn assign_tax_lots(
+    conn: &Connection,
+    sale: &InvestmentTransaction,
+    lot_method: &str,
+    lot_selection: Option<&[LotSelection]>,
+) -> Result<Vec<LotAssignment>, DbError> {
+    let mut picks: Vec<(TaxLot, f64)> = Vec::new();
+    
+    match lot_method {
+        "fifo" | "lifo" => {
+            let order = if lot_method == "fifo" { "ASC" } else { "DESC" };
+            let lots = {
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT id, account_id, security_id, transaction_id, quantity, remaining_quantity, cost_per_unit, acquired_ts
+                     FROM tax_lots
+                     WHERE account_id = ?1 AND security_id = ?2 AND acquired_ts <= ?3 AND remaining_quantity > ?4
+                     ORDER BY acquired_ts {order}, id {order}"
+                ))?;
+                let rows = stmt.query_map(
+                    rusqlite::params![sale.account_id, sale.security_id, sale.ts, QUANTITY_EPSILON],
+                    tax_lot_from_row,
+                )?
+                .collect::<Result<Vec<_>, _>>()?;
+                rows
+            };
+            
+            let mut remaining = sale.quantity;
+            for lot in lots {
+                if remaining <= QUANTITY_EPSILON {
+                    break;
+                }
+                let quantity = remaining.min(lot.remaining_quantity);
+                remaining -= quantity;
+                picks.push((lot, quantity));
+            }
+            
+            if remaining > QUANTITY_EPSILON {
+                return Err(DbError::ValidationError(format!(
+                    "Not enough open lots to sell {} units",
+                    sale.quantity
+                )));
+            }
+        }
+        "specific" => {
+            let selection = lot_selection.ok_or_else(|| {
+                DbError::ValidationError("Specific identification requires a lot selection".to_string())
+            })?;
+            
+            let selected_total: f64 = selection.iter().map(|s| s.quantity).sum();
+            if (selected_total - sale.quantity).abs() > QUANTITY_EPSILON {
+                return Err(DbError::ValidationError(format!(
+                    "Selected lots cover {} units, sale is {} units",
+                    selected_total, sale.quantity
+                )));
+            }
+            
+            for item in selection {
+                let lot = conn.query_row(
+                    "SELECT id, account_id, security_id, transaction_id, quantity, remaining_quantity, cost_per_unit, acquired_ts
+                     FROM tax_lots WHERE id = ?1",
+                    [item.lot_id],
+                    tax_lot_from_row,
+                ).optional()?
+                .ok_or_else(|| DbError::ValidationError(format!("Tax lot {} not found", item.lot_id)))?;
+                
+                if lot.account_id != sale.account_id || lot.security_id != sale.security_id || lot.acquired_ts > sale.ts {
+                    return Err(DbError::ValidationError(format!(
+                        "Tax lot {} cannot be used for this sale",
+                        item.lot_id
+                    )));
+                }
+                
+                let already_picked: f64 = picks.iter()
+                    .filter(|(picked, _)| picked.id == lot.id)
+                    .map(|(_, quantity)| quantity)
+                    .sum();
+                if item.quantity <= 0.0 || item.quantity + already_picked > lot.remaining_quantity + QUANTITY_EPSILON {
+                    return Err(DbError::ValidationError(format!(
+                        "Invalid quantity {} for tax lot {} ({} remaining)",
+                        item.quantity, item.lot_id, lot.remaining_quantity
+                    )));
+                }
+                
+                picks.push((lot, item.quantity));
+            }
+        }
+        _ => {
+            return Err(DbError::ValidationError(format!("Unknown lot method: {}", lot_method)));
+        }
+    }
+    
+    let proceeds_per_unit = sale.price - sale.fee / sale.quantity;
+    
+    let mut assignments = Vec::with_capacity(picks.len());
+    for (lot, quantity) in picks {
+        conn.execute(
+            "UPDATE tax_lots SET remaining_quantity = MAX(remaining_quantity - ?1, 0.0) WHERE id = ?2",
+            rusqlite::params![quantity, lot.id],
+        )?;
+        
+        let cost_basis = quantity * lot.cost_per_unit;
+        let proceeds = quantity * proceeds_per_unit;
+        
+        conn.execute(
+            "INSERT INTO lot_assignments (sale_transaction_id, lot_id, quantity, cost_basis, proceeds, acquired_ts, disposed_ts)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            rusqlite::params![sale.id, lot.id, quantity, cost_basis, proceeds, lot.acquired_ts, sale.ts],
+        )?;
+        
+        assignments.push(LotAssignment {
+            id: conn.last_insert_rowid(),
+            sale_transaction_id: sale.id,
+            lot_id: lot.id,
+            quantity,
+            cost_basis,
+            proceeds,
+            acquired_ts: lot.acquired_ts,
+            disposed_ts: sale.ts,
+        });
+    }
+    
+    Ok(assignments)
+}
+
+/// Получение налоговых лотов счёта (включая полностью списанные)
+/// 
+/// # Параметры
+/// - `account_id` - инвестиционный счёт
+/// - `security_id` - фильтр по ценной бумаге
+pub fn get_tax_lots(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    security_id: Option<i64>,
+) -> Result<Vec<TaxLot>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, account_id, security_id, transaction_id, quantity, remaining_quantity, cost_per_unit, acquired_ts
+         FROM tax_lots
+         WHERE account_id = ?1 AND (?2 IS NULL OR security_id = ?2)
+         ORDER BY acquired_ts ASC, id ASC"
+    )?;
+    
+    let lots = stmt.query_map(rusqlite::params![account_id, security_id], tax_lot_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(lots)
+}
+
+/// Отчёт о реализованном доходе по ценным бумагам за календарный год
+/// 
+/// Каждая строка соответствует списанию одного лота: себестоимость,
+/// выручка, срок владения и доход. Доход считается долгосрочным, если
+/// лот удерживался дольше `LONG_TERM_HOLDING_DAYS` дней.
+/// 
+/// # Параметры
+/// - `year` - налоговый год (границы - по локальному времени)
+pub fn get_realized_gains(
+    path: &str,
+    key: &str,
+    year: i32,
+) -> Result<RealizedGainsReport, DbError> {
+    let (start, end) = year_bounds(year)?;
+    
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT la.sale_transaction_id, la.lot_id, tl.account_id, tl.security_id, s.ticker,
+                la.quantity, la.acquired_ts, la.disposed_ts, la.cost_basis, la.proceeds
+         FROM lot_assignments la
+         INNER JOIN tax_lots tl ON tl.id = la.lot_id
+         INNER JOIN securities s ON s.id = tl.security_id
+         WHERE la.disposed_ts >= ?1 AND la.disposed_ts < ?2
+         ORDER BY la.disposed_ts ASC, la.id ASC"
+    )?;
+    
+    let gains = stmt.query_map([start, end], |row| {
+        let acquired_ts: i64 = row.get(6)?;
+        let disposed_ts: i64 = row.get(7)?;
+        let cost_basis: f64 = row.get(8)?;
+        let proceeds: f64 = row.get(9)?;
+        let holding_period_days = (disposed_ts - acquired_ts) / SECONDS_PER_DAY;
+        
+        Ok(RealizedGain {
+            sale_transaction_id: row.get(0)?,
+            lot_id: row.get(1)?,
+            account_id: row.get(2)?,
+            security_id: row.get(3)?,
+            ticker: row.get(4)?,
+            quantity: row.get(5)?,
+            acquired_ts,
+            disposed_ts,
+            holding_period_days,
+            long_term: holding_period_days > LONG_TERM_HOLDING_DAYS,
+            cost_basis,
+            proceeds,
+            gain: proceeds - cost_basis,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    let total_proceeds = gains.iter().map(|g| g.proceeds).sum();
+    let total_cost_basis = gains.iter().map(|g| g.cost_basis).sum();
+    let total_gain = gains.iter().map(|g| g.gain).sum();
+    let long_term_gain = gains.iter().filter(|g| g.long_term).map(|g| g.gain).sum();
+    let short_term_gain = gains.iter().filter(|g| !g.long_term).map(|g| g.gain).sum();
+    
+    Ok(RealizedGainsReport {
+        year,
+        gains,
+        total_proceeds,
+        total_cost_basis,
+        total_gain,
+        short_term_gain,
+        long_term_gain,
+    })
+}
+
+// Функции налоговой сводки
+
+/// Суммирование строк налоговой сводки по группам
+/// 
+/// Строки упорядочены по убыванию суммы, затем по имени.
+fn group_tax_lines(rows: Vec<(Option<i64>, String, f64)>) -> (Vec<TaxSummaryLine>, f64) {
+    let mut groups: std::collections::HashMap<Option<i64>, TaxSummaryLine> = std::collections::HashMap::new();
+    for (id, name, amount) in rows {
+        let line = groups.entry(id).or_insert_with(|| TaxSummaryLine { id, name, amount: 0.0, count: 0 });
+        line.amount += amount;
+        line.count += 1;
+    }
+    
+    let mut lines: Vec<TaxSummaryLine> = groups
+        .into_values()
+        .map(|line| TaxSummaryLine { amount: round_cents(line.amount), ..line })
+        .collect();
+    lines.sort_by(|a, b| b.amount.total_cmp(&a.amount).then_with(|| a.name.cmp(&b.name)));
+    let total = round_cents(lines.iter().map(|line| line.amount).sum());
+    
+    (lines, total)
+}
+
+/// Экранирование поля CSV (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Налоговая сводка в CSV: раздел, идентификатор, название, сумма, количество
+fn tax_summary_csv(summary: &TaxSummary) -> String {
+    let mut csv = String::from("section,id,name,amount,count\n");
+    let mut push_row = |section: &str, id: Option<i64>, name: &str, amount: f64, count: Option<i64>| {
+        csv.push_str(&format!(
+            "{},{},{},{:.2},{}\n",
+            section,
+            id.map(|id| id.to_string()).unwrap_or_default(),
+            csv_field(name),
+            amount,
+            count.map(|count| count.to_string()).unwrap_or_default(),
+        ));
+    };
+    
+    let sections = [
+        ("income", &summary.income, summary.total_income),
+        ("deductible_expense", &summary.deductible_expenses, summary.total_deductible),
+        ("interest", &summary.interest, summary.total_interest),
+        ("dividend", &summary.dividends, summary.total_dividends),
+    ];
+    for (section, lines, total) in sections {
+        for line in lines {
+            push_row(section, line.id, &line.name, line.amount, Some(line.count));
+        }
+        push_row(&format!("{}_total", section), None, "", total, None);
+    }
+    
+    let gains = &summary.realized_gains;
+    let (gain_lines, _) = group_tax_lines(
+        gains.gains.iter().map(|g| (Some(g.security_id), g.ticker.clone(), g.gain)).collect(),
+    );
+    for line in &gain_lines {
+        push_row("realized_gain", line.id, &line.name, line.amount, Some(line.count));
+    }
+    push_row("realized_gain_short_term", None, "", round_cents(gains.short_term_gain), None);
+    push_row("realized_gain_long_term", None, "", round_cents(gains.long_term_gain), None);
+    push_row("realized_gain_total", None, "", round_cents(gains.total_gain), None);
+    
+    csv
+}
+
+/// Подпись экспортируемого документа ключом хранилища
+fn sign_export(path: &str, key: &str, format: &str, content: String) -> Result<SignedExport, DbError> {
+    let private_key = load_key_from_keystore(path, key, "ed25519_private")?
+        .ok_or_else(|| DbError::InitError("Ed25519 private key not found in keystore".to_string()))?;
+    let public_key = load_key_from_keystore(path, key, "ed25519_public")?
+        .ok_or_else(|| DbError::InitError("Ed25519 public key not found in keystore".to_string()))?;
+    
+    let signature = crate::crypto::sign_payload(content.as_bytes(), &private_key)
+        .map_err(|e| DbError::InitError(format!("Failed to sign payload: {}", e)))?;
+    
+    Ok(SignedExport {
+        format: format.to_string(),
+        content,
+        signature: hex_encode(&signature),
+        public_key: hex_encode(&public_key),
+    })
+}
+
+/// Проверка отдельной подписи экспортированного документа
+pub fn verify_signed_export(export: &SignedExport) -> Result<bool, DbError> {
+    let signature = hex_decode(&export.signature)?;
+    let public_key = hex_decode(&export.public_key)?;
+    
+    crate::crypto::verify_payload(export.content.as_bytes(), &signature, &public_key)
+        .map_err(|e| DbError::ValidationError(format!("Failed to verify signature: {}", e)))
+}
+
+/// Налоговая сводка за календарный год
+/// 
+/// Разделы:
+/// - доходы по источникам (контрагентам) - поступления на счета-активы,
+///   кроме процентов, переводов, сделок с бумагами и кредитных операций
+/// - вычитаемые расходы по категориям с признаком `deductible`
+///   (включая расходы по кредитным картам)
+/// - проценты по счетам - поступления в категориях с признаком `interest`
+/// - дивиденды по бумагам (за вычетом комиссии)
+/// - реализованный доход (см. `get_realized_gains`)
+/// 
+/// Сводка выгружается в CSV и JSON; каждый документ подписан
+/// ключом Ed25519 хранилища отдельной подписью.
+pub fn generate_tax_summary(path: &str, key: &str, year: i32) -> Result<TaxSummaryExport, DbError> {
+    let (from_ts, to_ts) = year_bounds(year)?;
+    
+    let realized_gains = get_realized_gains(path, key, year)?;
+    
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    // Строки операций с учётом разбивки, без внутренних перемещений
+    let lines = {
+        let mut stmt = conn.prepare(
+            "SELECT lines.amount, a.class, lines.account_id, a.name, lines.payee_id, p.name,
+                    lines.category_id, c.name, c.tax_kind
+             FROM (
+                 SELECT o.id, s.category_id, o.payee_id, s.amount, o.account_id, o.ts
+                 FROM operation_splits s
+                 INNER JOIN operations o ON o.id = s.operation_id
+                 UNION ALL
+                 SELECT o.id, o.category_id, o.payee_id, o.amount, o.account_id, o.ts
+                 FROM operations o
+                 WHERE NOT EXISTS (SELECT 1 FROM operation_splits s WHERE s.operation_id = o.id)
+             ) lines
+             INNER JOIN accounts a ON a.id = lines.account_id
+             LEFT JOIN categories c ON c.id = lines.category_id
+             LEFT JOIN payees p ON p.id = lines.payee_id
+             WHERE a.class IN ('asset', 'liability')
+               AND lines.ts >= ?1 AND lines.ts < ?2
+               AND lines.id NOT IN (SELECT from_operation_id FROM transfers UNION SELECT to_operation_id FROM transfers)
+               AND lines.id NOT IN (SELECT operation_id FROM investment_transactions)
+               AND lines.id NOT IN (SELECT principal_operation_id FROM loan_payments
+                                    UNION SELECT loan_operation_id FROM loan_payments)
+             ORDER BY lines.ts ASC, lines.id ASC"
+        )?;
+        let rows = stmt.query_map([from_ts, to_ts], |row| {
+            Ok((
+                row.get::<_, f64>(0)?,
+                row.get::<_, AccountClass>(1)?,
+                (row.get::<_, i64>(2)?, row.get::<_, String>(3)?),
+                (row.get::<_, Option<i64>>(4)?, row.get::<_, Option<String>>(5)?),
+                (row.get::<_, Option<i64>>(6)?, row.get::<_, Option<String>>(7)?),
+                row.get::<_, Option<String>>(8)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    let mut income = Vec::new();
+    let mut deductible = Vec::new();
+    let mut interest = Vec::new();
+    for (amount, class, (account_id, account_name), (payee_id, payee_name), (category_id, category_name), tax_kind) in lines {
+        // Расход - списание с актива или рост долга по обязательству;
+        // возвраты в вычитаемых категориях уменьшают сумму вычета
+        match (class, tax_kind.as_deref()) {
+            (AccountClass::Asset, Some("deductible")) => {
+                deductible.push((category_id, category_name.unwrap_or_default(), -amount));
+            }
+            (AccountClass::Liability, Some("deductible")) => {
+                deductible.push((category_id, category_name.unwrap_or_default(), amount));
+            }
+            (AccountClass::Asset, Some("interest")) if amount > 0.0 => {
+                interest.push((Some(account_id), account_name, amount));
+            }
+            (AccountClass::Asset, _) if amount > 0.0 => {
+                income.push((payee_id, payee_name.unwrap_or_else(|| "Unassigned".to_string()), amount));
+            }
+            _ => {}
+        }
+    }
+    
+    let dividends = {
+        let mut stmt = conn.prepare(
+            "SELECT it.security_id, s.ticker, o.amount
+             FROM investment_transactions it
+             INNER JOIN securities s ON s.id = it.security_id
+             INNER JOIN operations o ON o.id = it.operation_id
+             WHERE it.kind = 'dividend' AND it.ts >= ?1 AND it.ts < ?2
+             ORDER BY it.ts ASC, it.id ASC"
+        )?;
+        let rows = stmt.query_map([from_ts, to_ts], |row| {
+            Ok((Some(row.get::<_, i64>(0)?), row.get::<_, String>(1)?, row.get::<_, f64>(2)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    let (income, total_income) = group_tax_lines(income);
+    let (deductible_expenses, total_deductible) = group_tax_lines(deductible);
+    let (interest, total_interest) = group_tax_lines(interest);
+    let (dividends, total_dividends) = group_tax_lines(dividends);
+    
+    let summary = TaxSummary {
+        year,
+        from_ts,
+        to_ts,
+        income,
+        total_income,
+        deductible_expenses,
+        total_deductible,
+        interest,
+        total_interest,
+        dividends,
+        total_dividends,
+        realized_gains,
+    };
+    
+    let csv = sign_export(path, key, "csv", tax_summary_csv(&summary))?;
+    let json = sign_export(path, key, "json", serialize_entity(&summary)?)?;
+    
+    Ok(TaxSummaryExport { summary, csv, json })
+}
+
+// Функции доходности инвестиций
+
+/// Денежно-взвешенная годовая доходность (XIRR)
+/// 
+/// `cash_flows` - пары (момент, сумма) с точки зрения инвестора: вложения
+/// отрицательны, стоимость на конец периода и изъятия положительны.
+/// Корень ищется методом Ньютона с защитой бисекцией внутри интервала,
+/// на концах которого NPV имеет разные знаки, поэтому решение не уходит
+/// за пределы ставки -100% и не расходится.
+pub fn xirr(cash_flows: &[(i64, f64)]) -> Result<f64, DbError> {
+    let has_inflow = cash_flows.iter().any(|(_, amount)| *amount > AMOUNT_EPSILON);
+    let has_outflow = cash_flows.iter().any(|(_, amount)| *amount < -AMOUNT_EPSILON);
+    if !has_inflow || !has_outflow {
+        return Err(DbError::ValidationError(
+            "XIRR is undefined: cash flows must include both investments and returns".to_string(),
+        ));
+    }
+    
+    let first_ts = cash_flows.iter().map(|(ts, _)| *ts).min().unwrap_or(0);
+    let flows: Vec<(f64, f64)> = cash_flows
+        .iter()
+        .filter(|(_, amount)| amount.abs() > AMOUNT_EPSILON)
+        .map(|(ts, amount)| ((ts - first_ts) as f64 / (SECONDS_PER_DAY as f64 * DAYS_PER_YEAR), *amount))
+        .collect();
+    
+    let npv = |rate: f64| -> (f64, f64) {
+        flows.iter().fold((0.0, 0.0), |(value, derivative), (years, amount)| {
+            let discount = (1.0 + rate).powf(-years);
+            (value + amount * discount, derivative - years * amount * discount / (1.0 + rate))
+        })
+    };
+    
+    // Интервал со сменой знака NPV. Дисконтирование за десятилетия по ставке
+    // около -100% переполняет f64, поэтому нижняя граница поднимается,
+    // пока NPV на ней не станет конечной
+    let mut low = -1.0 + 1e-9;
+    while !npv(low).0.is_finite() {
+        low = -1.0 + (low + 1.0) * 10.0;
+        if low >= 0.0 {
+            return Err(DbError::ValidationError(
+                "XIRR is undefined: net present value overflows near -100%".to_string(),
+            ));
+        }
+    }
+    let mut high = 1.0;
+    let low_sign = npv(low).0.signum();
+    while npv(high).0.signum() == low_sign {
+        high *= 2.0;
+        if high > XIRR_MAX_RATE {
+            return Err(DbError::ValidationError(
+                "XIRR is undefined: no rate makes the net present value zero".to_string(),
+            ));
+        }
+    }
+    
+    let mut rate = 0.1_f64.clamp(low, high);
+    for _ in 0..200 {
+        let (value, derivative) = npv(rate);
+        if value.abs() < XIRR_TOLERANCE {
+            return Ok(rate);
+        }
+        
+        if value.signum() == low_sign {
+            low = rate;
+        } else {
+            high = rate;
+        }
+        
+        let newton = rate - value / derivative;
+        let next = if newton.is_finite() && newton > low && newton < high {
+            newton
+        } else {
+            (low + high) / 2.0
+        };
+        
+        if (next - rate).abs() < XIRR_TOLERANCE {
+            return Ok(next);
+        }
+        rate = next;
+    }
+    
+    Err(DbError::ValidationError("XIRR did not converge".to_string()))
+}
+
+/// Стоимость набора счетов на момент ts: денежный остаток и позиции по рынку
+fn investment_value_at(conn: &Connection, account_ids: &[i64], ts: i64) -> SqlResult<f64> {
+    let mut value = 0.0;
+    for account_id in account_ids {
+        value += get_balance_at(conn, *account_id, ts)?;
+        value += load_holdings_at(conn, Some(*account_id), ts)?
+            .iter()
+            .map(|holding| holding.market_value)
+            .sum::<f64>();
+    }
+    
+    Ok(value)
+}
+
+/// Внешние денежные потоки счетов за (from, to], сгруппированные по моментам
+/// 
+/// Внешними считаются операции, кроме денежных частей сделок с бумагами
+/// (покупки, продажи, дивиденды - это часть доходности), и начальные остатки.
+fn load_external_flows(conn: &Connection, account_ids: &[i64], from: i64, to: i64) -> SqlResult<Vec<(i64, f64)>> {
+    let ids_json = format!("[{}]", account_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(","));
+    
+    let mut stmt = conn.prepare(
+        "SELECT ts, SUM(amount) FROM (
+             SELECT o.ts, o.amount FROM operations o
+             WHERE o.account_id IN (SELECT value FROM json_each(?1))
+               AND o.id NOT IN (SELECT operation_id FROM investment_transactions)
+             UNION ALL
+             SELECT b.ts, b.balance FROM opening_balances b
+             WHERE b.account_id IN (SELECT value FROM json_each(?1))
+         )
+         WHERE ts > ?2 AND ts <= ?3
+         GROUP BY ts
+         ORDER BY ts ASC"
+    )?;
+    
+    let flows = stmt.query_map(rusqlite::params![ids_json, from, to], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(flows)
+}
+
+/// Взвешенная по времени доходность (TWR) за период
+/// 
+/// Период делится моментами внешних потоков; доходность подпериода -
+/// отношение стоимости до потока в его конце к стоимости после потока
+/// в его начале. Подпериоды с нулевой стоимостью пропускаются.
+fn time_weighted_return(
+    conn: &Connection,
+    account_ids: &[i64],
+    flows: &[(i64, f64)],
+    from: i64,
+    to: i64,
+) -> Result<f64, DbError> {
+    let mut growth = 1.0;
+    let mut invested = false;
+    let mut period_start = from;
+    let mut start_value = investment_value_at(conn, account_ids, from)?;
+    
+    let mut boundaries: Vec<(i64, f64)> = flows.to_vec();
+    if boundaries.last().map(|(ts, _)| *ts) != Some(to) {
+        boundaries.push((to, 0.0));
+    }
+    
+    for (ts, flow) in boundaries {
+        let value = investment_value_at(conn, account_ids, ts)?;
+        let end_value = value - flow;
+        
+        if start_value.abs() <= AMOUNT_EPSILON {
+            if end_value.abs() > AMOUNT_EPSILON {
+                return Err(DbError::ValidationError(format!(
+                    "TWR is undefined: value changed from zero between {} and {}",
+                    period_start, ts
+                )));
+            }
+        } else if start_value < 0.0 {
+            return Err(DbError::ValidationError(format!(
+                "TWR is undefined: negative account value at {}",
+                period_start
+            )));
+        } else {
+            growth *= end_value / start_value;
+            invested = true;
+        }
+        
+        period_start = ts;
+        start_value = value;
+    }
+    
+    if !invested {
+        return Err(DbError::ValidationError(
+            "TWR is undefined: nothing was invested during the period".to_string(),
+        ));
+    }
+    
+    Ok(growth - 1.0)
+}
+
+/// Доходность набора счетов за период [from, to]
+fn compute_investment_return(
+    conn: &Connection,
+    account_id: Option<i64>,
+    account_ids: &[i64],
+    from: i64,
+    to: i64,
+) -> Result<InvestmentReturn, DbError> {
+    let start_value = investment_value_at(conn, account_ids, from)?;
+    let end_value = investment_value_at(conn, account_ids, to)?;
+    let flows = load_external_flows(conn, account_ids, from, to)?;
+    let net_contributions: f64 = flows.iter().map(|(_, amount)| amount).sum();
+    
+    // Потоки с точки зрения инвестора: начальная стоимость и взносы - вложения
+    let mut cash_flows = vec![(from, -start_value)];
+    cash_flows.extend(flows.iter().map(|(ts, amount)| (*ts, -amount)));
+    cash_flows.push((to, end_value));
+    
+    let (xirr, xirr_error) = match xirr(&cash_flows) {
+        Ok(rate) => (Some(rate), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    
+    let years = (to - from) as f64 / (SECONDS_PER_DAY as f64 * DAYS_PER_YEAR);
+    let (twr, twr_annualized, twr_error) = match time_weighted_return(conn, account_ids, &flows, from, to) {
+        Ok(twr) => {
+            let annualized = (years >= 1.0).then(|| (1.0 + twr).powf(1.0 / years) - 1.0);
+            (Some(twr), annualized, None)
+        }
+        Err(e) => (None, None, Some(e.to_string())),
+    };
+    
+    Ok(InvestmentReturn {
+        account_id,
+        from,
+        to,
+        start_value,
+        end_value,
+        net_contributions,
+        gain: end_value - start_value - net_contributions,
+        xirr,
+        xirr_error,
+        twr,
+        twr_annualized,
+        twr_error,
+    })
+}
+
+/// Доходность инвестиционных счетов и портфеля за период
+/// 
+/// Инвестиционными считаются счета со сделками по ценным бумагам.
+/// Стоимость счёта - денежный остаток по states плюс рыночная стоимость
+/// позиций; внешние потоки - операции счёта, кроме денежных частей сделок.
+/// Переводы между инвестиционными счетами в портфеле взаимно гасятся.
+/// 
+/// # Параметры
+/// - `path` - путь к базе данных
+/// - `key` - ключ шифрования
+/// - `from`, `to` - период: оценка на моменты from и to, потоки в (from, to]
+pub fn get_investment_returns(path: &str, key: &str, from: i64, to: i64) -> Result<PortfolioReturns, DbError> {
+    if from >= to {
+        return Err(DbError::ValidationError("Period start must be before its end".to_string()));
+    }
+    
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let account_ids = {
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT account_id FROM investment_transactions WHERE ts <= ?1 ORDER BY account_id ASC"
+        )?;
+        let rows = stmt.query_map([to], |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    let accounts = account_ids
+        .iter()
+        .map(|account_id| compute_investment_return(&conn, Some(*account_id), &[*account_id], from, to))
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    let portfolio = compute_investment_return(&conn, None, &account_ids, from, to)?;
+    
+    Ok(PortfolioReturns { accounts, portfolio })
+}
+
+// Функции кредитов и ипотек
+
+/// Округление денежной суммы до копеек
+fn round_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+/// Сдвиг момента времени на заданное число календарных месяцев (в локальном часовом поясе)
+fn add_months(ts: i64, months: i64) -> Result<i64, DbError> {
+    use chrono::TimeZone;
+    
+    let invalid = || DbError::ValidationError(format!("Cannot shift {} by {} months", ts, months));
+    
+    let base = chrono::Local.timestamp_opt(ts, 0).earliest().ok_or_else(invalid)?;
+    let shifted = base
+        .naive_local()
+        .checked_add_months(chrono::Months::new(u32::try_from(months).map_err(|_| invalid())?))
+        .ok_or_else(invalid)?;
+    
+    chrono::Local
+        .from_local_datetime(&shifted)
+        .earliest()
+        .map(|dt| dt.timestamp())
+        .ok_or_else(invalid)
+}
+
+/// Аннуитетный ежемесячный платёж, округлённый до копеек
+/// 
+/// Срок ограничен `MAX_LOAN_TERM_MONTHS` при создании кредита.
+fn annuity_payment(principal: f64, annual_rate: f64, term_months: i64) -> f64 {
+    let rate = annual_rate / 100.0 / 12.0;
+    
+    if rate.abs() < f64::EPSILON {
+        return round_cents(principal / term_months as f64);
+    }
+    
+    round_cents(principal * rate / (1.0 - (1.0 + rate).powi(-(term_months as i32))))
+}
+
+/// Маппинг строки loans в структуру
+fn loan_from_row(row: &rusqlite::Row) -> SqlResult<Loan> {
+    Ok(Loan {
+        id: row.get(0)?,
+        account_id: row.get(1)?,
+        name: row.get(2)?,
+        principal: row.get(3)?,
+        annual_rate: row.get(4)?,
+        term_months: row.get(5)?,
+        start_ts: row.get(6)?,
+        monthly_payment: row.get(7)?,
+        created_at: row.get(8)?,
+        funding_operation_id: row.get(9)?,
+    })
+}
+
+/// Загрузка кредита по ID
+fn load_loan(conn: &Connection, loan_id: i64) -> Result<Loan, DbError> {
+    conn.query_row(
+        "SELECT id, account_id, name, principal, annual_rate, term_months, start_ts, monthly_payment, created_at, funding_operation_id
+         FROM loans WHERE id = ?1",
+        [loan_id],
+        loan_from_row,
+    ).optional()?
+    .ok_or_else(|| DbError::ValidationError(format!("Loan {} not found", loan_id)))
+}
+
+/// Непогашенное тело кредита и номер следующего платежа
+fn loan_outstanding(conn: &Connection, loan: &Loan) -> SqlResult<(f64, i64)> {
+    let (repaid, payments): (f64, i64) = conn.query_row(
+        "SELECT COALESCE(SUM(principal + extra_principal), 0.0), COUNT(*) FROM loan_payments WHERE loan_id = ?1",
+        [loan.id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    
+    Ok((round_cents(loan.principal - repaid).max(0.0), payments + 1))
+}
+
+/// Разбивка очередного платежа на проценты, плановое тело и досрочное погашение
+fn split_loan_payment(loan: &Loan, balance: f64, extra_principal: f64) -> (f64, f64, f64) {
+    let interest = round_cents(balance * loan.annual_rate / 100.0 / 12.0);
+    let principal = (loan.monthly_payment - interest).clamp(0.0, balance);
+    let extra = extra_principal.clamp(0.0, balance - principal);
+    
+    (interest, principal, round_cents(extra))
+}
+
+/// Построение графика погашения от текущего остатка долга
+/// 
+/// Ежемесячный платёж фиксирован; `extra_monthly` каждый месяц
+/// дополнительно уменьшает тело кредита. Последний платёж закрывает остаток.
+fn build_amortization(
+    loan: &Loan,
+    outstanding: f64,
+    first_payment_number: i64,
+    extra_monthly: f64,
+) -> Result<Vec<AmortizationRow>, DbError> {
+    let mut rows = Vec::new();
+    let mut balance = outstanding;
+    let mut payment_number = first_payment_number;
+    
+    while balance > AMOUNT_EPSILON {
+        let (interest, principal, extra) = split_loan_payment(loan, balance, extra_monthly);
+        
+        if principal + extra <= 0.0 {
+            return Err(DbError::ValidationError(format!(
+                "Monthly payment {} does not cover interest {}",
+                loan.monthly_payment, interest
+            )));
+        }
+        
+        balance = round_cents(balance - principal - extra);
+        
+        rows.push(AmortizationRow {
+            payment_number,
+            due_ts: add_months(loan.start_ts, payment_number)?,
+            payment: round_cents(principal + interest + extra),
+            principal: round_cents(principal),
+            interest,
+            extra_principal: extra,
+            remaining_balance: balance,
+        });
+        
+        payment_number += 1;
+    }
+    
+    Ok(rows)
+}
+
+/// Регистрация кредита или ипотеки
+/// 
+/// Счёт кредита должен иметь класс `liability`. Сумма кредита проводится
+/// на этот счёт операцией на дату выдачи, ежемесячный аннуитетный платёж
+/// рассчитывается из суммы, ставки и срока.
+/// 
+/// Если указан `funding_account_id`, та же сумма зачисляется на этот счёт
+/// (класс `asset`), и Net Worth на дату выдачи не меняется. Без него
+/// поступление денег вызывающая сторона проводит сама.
+/// 
+/// # Параметры
+/// - `account_id` - счёт-обязательство, на котором ведётся остаток долга
+/// - `principal` - сумма кредита
+/// - `annual_rate` - годовая ставка в процентах
+/// - `term_months` - срок в месяцах, не больше `MAX_LOAN_TERM_MONTHS`
+/// - `start_ts` - дата выдачи; первый платёж через месяц
+/// - `funding_account_id` - счёт, на который поступает сумма кредита
+#[allow(clippy::too_many_arguments)]
+pub fn create_loan(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    name: String,
+    principal: f64,
+    annual_rate: f64,
+    term_months: i64,
+    start_ts: i64,
+    funding_account_id: Option<i64>,
+) -> Result<i64, DbError> {
+    if principal <= 0.0 {
+        return Err(DbError::ValidationError("Loan principal must be positive".to_string()));
+    }
+    
+    if annual_rate < 0.0 {
+        return Err(DbError::ValidationError("Interest rate must not be negative".to_string()));
+    }
+    
+    if term_months <= 0 {
+        return Err(DbError::ValidationError("Loan term must be positive".to_string()));
+    }
+    
+    if term_months > MAX_LOAN_TERM_MONTHS {
+        return Err(DbError::ValidationError(format!(
+            "Loan term must not exceed {} months",
+            MAX_LOAN_TERM_MONTHS
+        )));
+    }
+    
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let account = load_account(&tx, account_id)?;
+    if account.acc_class != AccountClass::Liability {
+        return Err(DbError::ValidationError(format!(
+            "Loan account {} must be a liability, got {}",
+            account_id,
+            account.acc_class.as_str()
+        )));
+    }
+    
+    let existing: Option<i64> = tx.query_row(
+        "SELECT id FROM loans WHERE account_id = ?1",
+        [account_id],
+        |row| row.get(0),
+    ).optional()?;
+    if let Some(loan_id) = existing {
+        return Err(DbError::ValidationError(format!(
+            "Account {} is already used by loan {}",
+            account_id, loan_id
+        )));
+    }
+    
+    if let Some(funding_account_id) = funding_account_id {
+        let funding_account = load_account(&tx, funding_account_id)?;
+        if funding_account.acc_class != AccountClass::Asset {
+            return Err(DbError::ValidationError(format!(
+                "Funding account {} must be an asset, got {}",
+                funding_account_id,
+                funding_account.acc_class.as_str()
+            )));
+        }
+    }
+    
+    let created_at = current_timestamp()?;
+    let monthly_payment = annuity_payment(principal, annual_rate, term_months);
+    
+    // Сумма кредита поступает на счёт получателя
+    let funding_operation_id = match funding_account_id {
+        Some(funding_account_id) => Some(insert_operation(
+            &tx, path, key, funding_account_id, principal, &format!("{}: disbursement", name), start_ts,
+        )?),
+        None => None,
+    };
+    
+    tx.execute(
+        "INSERT INTO loans (account_id, name, principal, annual_rate, term_months, start_ts, monthly_payment, created_at, funding_operation_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        rusqlite::params![account_id, name, principal, annual_rate, term_months, start_ts, monthly_payment, created_at, funding_operation_id],
+    )?;
+    
+    let loan = Loan {
+        id: tx.last_insert_rowid(),
+        account_id,
+        name,
+        principal,
+        annual_rate,
+        term_months,
+        start_ts,
+        monthly_payment,
+        created_at,
+        funding_operation_id,
+    };
+    
+    write_version_log(&tx, path, key, "loan", loan.id, "create", &serialize_entity(&loan)?)?;
+    
+    // Выдача кредита увеличивает долг на счёте кредита
+    insert_operation(&tx, path, key, account_id, principal, &format!("{}: disbursement", loan.name), start_ts)?;
+    
+    tx.commit()?;
+    
+    Ok(loan.id)
+}
+
+/// Получение списка кредитов
+pub fn list_loans(path: &str, key: &str) -> Result<Vec<Loan>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, account_id, name, principal, annual_rate, term_months, start_ts, monthly_payment, created_at, funding_operation_id
+         FROM loans ORDER BY start_ts ASC, id ASC"
+    )?;
+    
+    let loans = stmt.query_map([], loan_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(loans)
+}
+
+/// Проведение очередного платежа по кредиту
+/// 
+/// Проценты начисляются на непогашенное тело за месяц. Платёж проводится
+/// отдельными операциями:
+/// - со счёта списания: тело (включая досрочное погашение) и проценты
+/// - на счёте кредита: уменьшение долга на сумму тела
+/// 
+/// # Параметры
+/// - `from_account_id` - счёт, с которого оплачивается кредит
+/// - `extra_principal` - досрочное погашение сверх планового платежа
+/// - `ts` - дата платежа
+pub fn record_loan_payment(
+    path: &str,
+    key: &str,
+    loan_id: i64,
+    from_account_id: i64,
+    extra_principal: f64,
+    ts: i64,
+) -> Result<i64, DbError> {
+    if extra_principal < 0.0 {
+        return Err(DbError::ValidationError("Extra principal must not be negative".to_string()));
+    }
+    
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let loan = load_loan(&tx, loan_id)?;
+    ensure_account_exists(&tx, from_account_id)?;
+    
+    if from_account_id == loan.account_id {
+        return Err(DbError::ValidationError("Loan cannot be paid from its own account".to_string()));
+    }
+    
+    if ts < loan.start_ts {
+        return Err(DbError::ValidationError("Payment date is before the loan start".to_string()));
+    }
+    
+    let (outstanding, payment_number) = loan_outstanding(&tx, &loan)?;
+    if outstanding <= AMOUNT_EPSILON {
+        return Err(DbError::ValidationError(format!("Loan {} is already paid off", loan_id)));
+    }
+    
+    let (interest, principal, extra) = split_loan_payment(&loan, outstanding, extra_principal);
+    let repaid = round_cents(principal + extra);
+    
+    let principal_operation_id = insert_operation(
+        &tx, path, key, from_account_id, -repaid,
+        &format!("{}: principal #{}", loan.name, payment_number), ts,
+    )?;
+    
+    let interest_operation_id = if interest > AMOUNT_EPSILON {
+        Some(insert_operation(
+            &tx, path, key, from_account_id, -interest,
+            &format!("{}: interest #{}", loan.name, payment_number), ts,
+        )?)
+    } else {
+        None
+    };
+    
+    let loan_operation_id = insert_operation(
+        &tx, path, key, loan.account_id, -repaid,
+        &format!("{}: repayment #{}", loan.name, payment_number), ts,
+    )?;
+    
+    tx.execute(
+        "INSERT INTO loan_payments (loan_id, payment_number, from_account_id, principal, interest, extra_principal,
+                                    principal_operation_id, interest_operation_id, loan_operation_id, ts)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        rusqlite::params![
+            loan_id, payment_number, from_account_id, round_cents(principal), interest, extra,
+            principal_operation_id, interest_operation_id, loan_operation_id, ts
+        ],
+    )?;
+    
+    let payment = LoanPayment {
+        id: tx.last_insert_rowid(),
+        loan_id,
+        payment_number,
+        from_account_id,
+        principal: round_cents(principal),
+        interest,
+        extra_principal: extra,
+        principal_operation_id,
+        interest_operation_id,
+        loan_operation_id,
+        ts,
+    };
+    
+    write_version_log(&tx, path, key, "loan_payment", payment.id, "create", &serialize_entity(&payment)?)?;
+    
+    tx.commit()?;
+    
+    Ok(payment.id)
+}
+
+/// Получение проведённых платежей по кредиту
+pub fn get_loan_payments(path: &str, key: &str, loan_id: i64) -> Result<Vec<LoanPayment>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, loan_id, payment_number, from_account_id, principal, interest, extra_principal,
+                principal_operation_id, interest_operation_id, loan_operation_id, ts
+         FROM loan_payments WHERE loan_id = ?1 ORDER BY payment_number ASC"
+    )?;
+    
+    let payments = stmt.query_map([loan_id], |row| {
+        Ok(LoanPayment {
+            id: row.get(0)?,
+            loan_id: row.get(1)?,
+            payment_number: row.get(2)?,
+            from_account_id: row.get(3)?,
+            principal: row.get(4)?,
+            interest: row.get(5)?,
+            extra_principal: row.get(6)?,
+            principal_operation_id: row.get(7)?,
+            interest_operation_id: row.get(8)?,
+            loan_operation_id: row.get(9)?,
+            ts: row.get(10)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(payments)
+}
+
+/// Полный график погашения кредита по исходным условиям
+/// 
+/// # Параметры
+/// - `extra_monthly` - ежемесячное досрочное погашение (по умолчанию 0)
+pub fn get_amortization_schedule(
+    path: &str,
+    key: &str,
+    loan_id: i64,
+    extra_monthly: Option<f64>,
+) -> Result<Vec<AmortizationRow>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let loan = load_loan(&conn, loan_id)?;
+    
+    build_amortization(&loan, loan.principal, 1, extra_monthly.unwrap_or(0.0).max(0.0))
+}
+
+/// Прогноз погашения кредита от текущего остатка
+/// 
+/// Сравнивает график без досрочных платежей с графиком, в котором каждый
+/// месяц вносится `extra_monthly` сверх планового платежа: дату полного
+/// погашения, сумму процентов и количество платежей.
+pub fn get_loan_payoff_projection(
+    path: &str,
+    key: &str,
+    loan_id: i64,
+    extra_monthly: f64,
+) -> Result<LoanPayoffProjection, DbError> {
+    if extra_monthly < 0.0 {
+        return Err(DbError::ValidationError("Extra payment must not be negative".to_string()));
+    }
+    
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let loan = load_loan(&conn, loan_id)?;
+    let (outstanding, next_payment_number) = loan_outstanding(&conn, &loan)?;
+    
+    let baseline = build_amortization(&loan, outstanding, next_payment_number, 0.0)?;
+    let projected = build_amortization(&loan, outstanding, next_payment_number, extra_monthly)?;
+    
+    let total_interest = |rows: &[AmortizationRow]| round_cents(rows.iter().map(|row| row.interest).sum());
+    let baseline_total_interest = total_interest(&baseline);
+    let projected_total_interest = total_interest(&projected);
+    
+    Ok(LoanPayoffProjection {
+        loan_id,
+        outstanding_principal: outstanding,
+        extra_monthly,
+        baseline_payoff_ts: baseline.last().map(|row| row.due_ts),
+        baseline_total_interest,
+        baseline_payments: baseline.len() as i64,
+        projected_payoff_ts: projected.last().map(|row| row.due_ts),
+        projected_total_interest,
+        projected_payments: projected.len() as i64,
+        months_saved: baseline.len() as i64 - projected.len() as i64,
+        interest_saved: round_cents(baseline_total_interest - projected_total_interest),
+        schedule: projected,
+    })
+}
+
+// Функции контрагентов и нормализации описаний
+
+/// Нормализация описания операции для сопоставления с псевдонимами
+/// 
+/// Регистр приводится к нижнему, цифры и знаки препинания (номера
+/// магазинов, `#88`, `*1234`) отбрасываются, пробелы схлопываются.
+pub fn normalize_description(description: &str) -> String {
+    description
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphabetic() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Проверка соответствия описания правилу контрагента
+/// 
+/// - `alias` - нормализованный псевдоним входит в нормализованное описание целыми словами
+/// - `regex` - регулярное выражение (без учёта регистра) находит совпадение в исходном описании
+fn payee_rule_matches(kind: &str, pattern: &str, description: &str, normalized: &str) -> bool {
+    match kind {
+        "alias" => {
+            let alias = normalize_description(pattern);
+            !alias.is_empty() && format!(" {} ", normalized).contains(&format!(" {} ", alias))
+        }
+        "regex" => regex::RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map(|re| re.is_match(description))
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// Определение контрагента по описанию операции
+/// 
+/// Правила проверяются по убыванию приоритета; первое совпадение выигрывает.
+/// Если ни одно правило не подошло, название контрагента используется
+/// как псевдоним.
+fn resolve_payee(conn: &Connection, description: &str) -> SqlResult<Option<i64>> {
+    if description.trim().is_empty() {
+        return Ok(None);
+    }
+    
+    let normalized = normalize_description(description);
+    
+    let rules = {
+        let mut stmt = conn.prepare(
+            "SELECT payee_id, kind, pattern FROM payee_rules ORDER BY priority DESC, id ASC"
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    if let Some((payee_id, _, _)) = rules
+        .iter()
+        .find(|(_, kind, pattern)| payee_rule_matches(kind, pattern, description, &normalized))
+    {
+        return Ok(Some(*payee_id));
+    }
+    
+    let payees = {
+        let mut stmt = conn.prepare("SELECT id, name FROM payees ORDER BY id ASC")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    Ok(payees
+        .into_iter()
+        .find(|(_, name)| payee_rule_matches("alias", name, description, &normalized))
+        .map(|(payee_id, _)| payee_id))
+}
+
+/// Проверка существования контрагента
+fn ensure_payee_exists(conn: &Connection, payee_id: i64) -> Result<(), DbError> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM payees WHERE id = ?1",
+        [payee_id],
+        |row| row.get(0),
+    )?;
+    
+    if count == 0 {
+        return Err(DbError::ValidationError(format!("Payee {} not found", payee_id)));
+    }
+    
+    Ok(())
+}
+
+/// Загрузка операции по ID
+fn load_operation(conn: &Connection, operation_id: i64) -> Result<Operation, DbError> {
+    conn.query_row(
+        "SELECT id, account_id, amount, description, ts, payee_id, category_id, notes FROM operations WHERE id = ?1",
+        [operation_id],
+        operation_from_row,
+    ).optional()?
+    .ok_or_else(|| DbError::ValidationError(format!("Operation {} not found", operation_id)))
+}
+
+/// Смена контрагента операции с записью в version_log
+fn update_operation_payee(
+    conn: &Connection,
+    path: &str,
+    key: &str,
+    operation: &mut Operation,
+    payee_id: Option<i64>,
+) -> Result<(), DbError> {
+    conn.execute(
+        "UPDATE operations SET payee_id = ?1 WHERE id = ?2",
+        rusqlite::params![payee_id, operation.id],
+    )?;
+    
+    operation.payee_id = payee_id;
+    write_version_log(conn, path, key, "operation", operation.id, "update", &serialize_entity(operation)?)?;
+    refresh_operation_search(conn, operation.id)?;
+    
+    Ok(())
+}
+
+/// Создание контрагента
+/// 
+/// Название уникально без учёта регистра и само служит псевдонимом.
+pub fn create_payee(path: &str, key: &str, name: String) -> Result<i64, DbError> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(DbError::ValidationError("Payee name must not be empty".to_string()));
+    }
+    
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let existing: Option<i64> = tx.query_row(
+        "SELECT id FROM payees WHERE name = ?1",
+        [&name],
+        |row| row.get(0),
+    ).optional()?;
+    if let Some(payee_id) = existing {
+        return Err(DbError::ValidationError(format!("Payee '{}' already exists ({})", name, payee_id)));
+    }
+    
+    let created_at = current_timestamp()?;
+    
+    tx.execute(
+        "INSERT INTO payees (name, created_at) VALUES (?1, ?2)",
+        rusqlite::params![name, created_at],
+    )?;
+    
+    let payee = Payee {
+        id: tx.last_insert_rowid(),
+        name,
+        created_at,
+    };
+    
+    write_version_log(&tx, path, key, "payee", payee.id, "create", &serialize_entity(&payee)?)?;
+    
+    tx.commit()?;
+    
+    Ok(payee.id)
+}
+
+/// Получение списка контрагентов
+pub fn list_payees(path: &str, key: &str) -> Result<Vec<Payee>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, name, created_at FROM payees ORDER BY name COLLATE NOCASE ASC"
+    )?;
+    
+    let payees = stmt.query_map([], |row| {
+        Ok(Payee {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            created_at: row.get(2)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(payees)
+}
+
+/// Добавление правила нормализации для контрагента
+/// 
+/// Правило применяется к новым операциям (в том числе импортированным);
+/// для уже существующих операций - через `apply_payee_rules`.
+/// 
+/// # Параметры
+/// - `kind` - вид правила (см. `PAYEE_RULE_KINDS`)
+/// - `pattern` - псевдоним или регулярное выражение
+/// - `priority` - правила с большим приоритетом проверяются раньше
+pub fn add_payee_rule(
+    path: &str,
+    key: &str,
+    payee_id: i64,
+    kind: String,
+    pattern: String,
+    priority: i64,
+) -> Result<i64, DbError> {
+    match kind.as_str() {
+        "alias" => {
+            if normalize_description(&pattern).is_empty() {
+                return Err(DbError::ValidationError("Alias must contain letters".to_string()));
+            }
+        }
+        "regex" => {
+            regex::Regex::new(&pattern)
+                .map_err(|e| DbError::ValidationError(format!("Invalid regex '{}': {}", pattern, e)))?;
+        }
+        _ => return Err(DbError::ValidationError(format!("Unknown payee rule kind: {}", kind))),
+    }
+    
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    ensure_payee_exists(&tx, payee_id)?;
+    
+    let created_at = current_timestamp()?;
+    
+    tx.execute(
+        "INSERT INTO payee_rules (payee_id, kind, pattern, priority, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![payee_id, kind, pattern, priority, created_at],
+    )?;
+    
+    let rule = PayeeRule {
+        id: tx.last_insert_rowid(),
+        payee_id,
+        kind,
+        pattern,
+        priority,
+        created_at,
+    };
+    
+    write_version_log(&tx, path, key, "payee_rule", rule.id, "create", &serialize_entity(&rule)?)?;
+    
+    tx.commit()?;
+    
+    Ok(rule.id)
+}
+
+/// Получение правил нормализации (в порядке применения)
+/// 
+/// # Параметры
+/// - `payee_id` - фильтр по контрагенту; если не указан - все правила
+pub fn list_payee_rules(
+    path: &str,
+    key: &str,
+    payee_id: Option<i64>,
+) -> Result<Vec<PayeeRule>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, payee_id, kind, pattern, priority, created_at FROM payee_rules
+         WHERE ?1 IS NULL OR payee_id = ?1
+         ORDER BY priority DESC, id ASC"
+    )?;
+    
+    let rules = stmt.query_map([payee_id], |row| {
+        Ok(PayeeRule {
+            id: row.get(0)?,
+            payee_id: row.get(1)?,
+            kind: row.get(2)?,
+            pattern: row.get(3)?,
+            priority: row.get(4)?,
+            created_at: row.get(5)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(rules)
+}
+
+/// Удаление правила нормализации
+pub fn delete_payee_rule(path: &str, key: &str, rule_id: i64) -> Result<(), DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let rule = tx.query_row(
+        "SELECT id, payee_id, kind, pattern, priority, created_at FROM payee_rules WHERE id = ?1",
+        [rule_id],
+        |row| {
+            Ok(PayeeRule {
+                id: row.get(0)?,
+                payee_id: row.get(1)?,
+                kind: row.get(2)?,
+                pattern: row.get(3)?,
+                priority: row.get(4)?,
+                created_at: row.get(5)?,
+            })
+        },
+    ).optional()?
+    .ok_or_else(|| DbError::ValidationError(format!("Payee rule {} not found", rule_id)))?;
+    
+    tx.execute("DELETE FROM payee_rules WHERE id = ?1", [rule_id])?;
+    
+    write_version_log(&tx, path, key, "payee_rule", rule_id, "delete", &serialize_entity(&rule)?)?;
+    
+    tx.commit()?;
+    
+    Ok(())
+}
+
+/// Ручное назначение контрагента операции
+/// 
+/// # Параметры
+/// - `payee_id` - контрагент; `None` снимает привязку
+pub fn set_operation_payee(
+    path: &str,
+    key: &str,
+    operation_id: i64,
+    payee_id: Option<i64>,
+) -> Result<(), DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let mut operation = load_operation(&tx, operation_id)?;
+    if let Some(payee_id) = payee_id {
+        ensure_payee_exists(&tx, payee_id)?;
+    }
+    
+    update_operation_payee(&tx, path, key, &mut operation, payee_id)?;
+    
+    tx.commit()?;
+    
+    Ok(())
+}
+
+/// Повторное применение правил нормализации к существующим операциям
+/// 
+/// # Параметры
+/// - `overwrite` - пересчитать контрагента и у операций, где он уже задан
+/// 
+/// Возвращает количество операций, у которых изменился контрагент.
+pub fn apply_payee_rules(path: &str, key: &str, overwrite: bool) -> Result<i64, DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let operations = {
+        let mut stmt = tx.prepare(
+            "SELECT id, account_id, amount, description, ts, payee_id, category_id, notes FROM operations
+             WHERE ?1 OR payee_id IS NULL ORDER BY id ASC"
+        )?;
+        let rows = stmt.query_map([overwrite], operation_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    let mut changed = 0;
+    for mut operation in operations {
+        let payee_id = resolve_payee(&tx, &operation.description)?;
+        if payee_id.is_some() && payee_id != operation.payee_id {
+            update_operation_payee(&tx, path, key, &mut operation, payee_id)?;
+            changed += 1;
+        }
+    }
+    
+    tx.commit()?;
+    
+    Ok(changed)
+}
+
+/// Слияние контрагентов
+/// 
+/// Операции и правила исходных контрагентов переносятся на целевого,
+/// названия исходных контрагентов становятся его псевдонимами,
+/// сами исходные контрагенты удаляются.
+pub fn merge_payees(
+    path: &str,
+    key: &str,
+    target_id: i64,
+    source_ids: Vec<i64>,
+) -> Result<Vec<PayeeMerge>, DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    ensure_payee_exists(&tx, target_id)?;
+    
+    let mut merges = Vec::new();
+    for source_id in source_ids {
+        if source_id == target_id {
+            return Err(DbError::ValidationError("Cannot merge a payee into itself".to_string()));
+        }
+        
+        let source_name: String = tx.query_row(
+            "SELECT name FROM payees WHERE id = ?1",
+            [source_id],
+            |row| row.get(0),
+        ).optional()?
+        .ok_or_else(|| DbError::ValidationError(format!("Payee {} not found", source_id)))?;
+        
+        let operations = {
+            let mut stmt = tx.prepare(
+                "SELECT id, account_id, amount, description, ts, payee_id, category_id, notes FROM operations WHERE payee_id = ?1"
+            )?;
+            let rows = stmt.query_map([source_id], operation_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            rows
+        };
+        
+        let operations_moved = operations.len() as i64;
+        for mut operation in operations {
+            update_operation_payee(&tx, path, key, &mut operation, Some(target_id))?;
+        }
+        
+        let rules_moved = tx.execute(
+            "UPDATE payee_rules SET payee_id = ?1 WHERE payee_id = ?2",
+            [target_id, source_id],
+        )? as i64;
+        
+        // Правила автокатегоризации назначают контрагента-получателя
+        let categorization_rule_ids = {
+            let mut stmt = tx.prepare("SELECT id FROM categorization_rules WHERE set_payee_id = ?1")?;
+            let rows = stmt.query_map([source_id], |row| row.get::<_, i64>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            rows
+        };
+        
+        let updated_at = current_timestamp()?;
+        for rule_id in categorization_rule_ids {
+            tx.execute(
+                "UPDATE categorization_rules SET set_payee_id = ?1, version = version + 1, updated_at = ?2 WHERE id = ?3",
+                rusqlite::params![target_id, updated_at, rule_id],
+            )?;
+            let rule = load_categorization_rule(&tx, rule_id)?;
+            write_version_log(&tx, path, key, "categorization_rule", rule_id, "update", &serialize_entity(&rule)?)?;
+        }
+        
+        tx.execute(
+            "UPDATE recurring_items SET payee_id = ?1 WHERE payee_id = ?2",
+            [target_id, source_id],
+        )?;
+        
+        // Название исходного контрагента продолжает распознаваться
+        let created_at = current_timestamp()?;
+        tx.execute(
+            "INSERT INTO payee_rules (payee_id, kind, pattern, priority, created_at) VALUES (?1, 'alias', ?2, 0, ?3)",
+            rusqlite::params![target_id, source_name, created_at],
+        )?;
+        let alias_rule = PayeeRule {
+            id: tx.last_insert_rowid(),
+            payee_id: target_id,
+            kind: "alias".to_string(),
+            pattern: source_name,
+            priority: 0,
+            created_at,
+        };
+        write_version_log(&tx, path, key, "payee_rule", alias_rule.id, "create", &serialize_entity(&alias_rule)?)?;
+        
+        tx.execute("DELETE FROM payees WHERE id = ?1", [source_id])?;
+        
+        let merge = PayeeMerge {
+            source_id,
+            target_id,
+            operations_moved,
+            rules_moved,
+        };
+        write_version_log(&tx, path, key, "payee", source_id, "merge", &serialize_entity(&merge)?)?;
+        
+        merges.push(merge);
+    }
+    
+    tx.commit()?;
+    
+    Ok(merges)
+}
+
+/// Расходы по контрагентам за период
+/// 
+/// Учитываются списания (отрицательные операции) со счетов класса asset,
+/// кроме перемещений между своими счетами (как в `get_cash_flow`).
+/// Операции без контрагента собираются в группу с `payee_id = None`.
+/// 
+/// # Параметры
+/// - `from_ts` - начало периода включительно (если не указано - с начала истории)
+/// - `to_ts` - конец периода, не включая (если не указано - до текущего момента)
+pub fn get_spending_by_payee(
+    path: &str,
+    key: &str,
+    from_ts: Option<i64>,
+    to_ts: Option<i64>,
+) -> Result<Vec<PayeeSpending>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT o.payee_id, p.name, SUM(-o.amount), COUNT(*)
+         FROM operations o
+         INNER JOIN accounts a ON a.id = o.account_id
+         LEFT JOIN payees p ON p.id = o.payee_id
+         WHERE o.amount < 0 AND a.class = 'asset'
+           AND (?1 IS NULL OR o.ts >= ?1)
+           AND (?2 IS NULL OR o.ts < ?2)
+           AND o.id NOT IN (SELECT from_operation_id FROM transfers UNION SELECT to_operation_id FROM transfers)
+           AND o.id NOT IN (SELECT principal_operation_id FROM loan_payments)
+           AND o.id NOT IN (SELECT operation_id FROM investment_transactions)
+         GROUP BY o.payee_id
+         ORDER BY SUM(-o.amount) DESC"
+    )?;
+    
+    let spending = stmt.query_map([from_ts, to_ts], |row| {
+        Ok(PayeeSpending {
+            payee_id: row.get(0)?,
+            payee_name: row.get(1)?,
+            total_spent: row.get(2)?,
+            operation_count: row.get(3)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(spending)
+}
+
+// Функции категорий, тегов и правил автокатегоризации
+
+/// Вставка записи с уникальным названием (категории, теги)
+/// 
+/// Возвращает ID и момент создания.
+fn insert_named_entity(conn: &Connection, table: &str, name: &str) -> Result<(i64, i64), DbError> {
+    if name.is_empty() {
+        return Err(DbError::ValidationError(format!("Name must not be empty ({})", table)));
+    }
+    
+    let existing: Option<i64> = conn.query_row(
+        &format!("SELECT id FROM {} WHERE name = ?1", table),
+        [name],
+        |row| row.get(0),
+    ).optional()?;
+    if let Some(id) = existing {
+        return Err(DbError::ValidationError(format!("'{}' already exists in {} ({})", name, table, id)));
+    }
+    
+    let created_at = current_timestamp()?;
+    
+    conn.execute(
+        &format!("INSERT INTO {} (name, created_at) VALUES (?1, ?2)", table),
+        rusqlite::params![name, created_at],
+    )?;
+    
+    Ok((conn.last_insert_rowid(), created_at))
+}
+
+/// Проверка существования записи по ID
+fn ensure_row_exists(conn: &Connection, table: &str, entity: &str, id: i64) -> Result<(), DbError> {
+    let count: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM {} WHERE id = ?1", table),
+        [id],
+        |row| row.get(0),
+    )?;
+    
+    if count == 0 {
+        return Err(DbError::ValidationError(format!("{} {} not found", entity, id)));
+    }
+    
+    Ok(())
+}
+
+/// Создание категории
+pub fn create_category(path: &str, key: &str, name: String) -> Result<i64, DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let id = insert_category(&tx, path, key, name)?;
+    
+    tx.commit()?;
+    
+    Ok(id)
+}
+
+/// Вставка категории с логированием в version_log
+fn insert_category(conn: &Connection, path: &str, key: &str, name: String) -> Result<i64, DbError> {
+    let name = name.trim().to_string();
+    let (id, created_at) = insert_named_entity(conn, "categories", &name)?;
+    let category = Category { id, name, created_at, tax_kind: None };
+    
+    write_version_log(conn, path, key, "category", id, "create", &serialize_entity(&category)?)?;
+    
+    Ok(id)
+}
+
+/// Получение списка категорий
+pub fn list_categories(path: &str, key: &str) -> Result<Vec<Category>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, name, created_at, tax_kind FROM categories ORDER BY name COLLATE NOCASE ASC"
+    )?;
+    
+    let categories = stmt.query_map([], |row| {
+        Ok(Category {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            created_at: row.get(2)?,
+            tax_kind: row.get(3)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(categories)
+}
+
+/// Установка налогового признака категории
+/// 
+/// `deductible` - расходы категории попадают в вычитаемые,
+/// `interest` - доходы категории считаются процентным доходом.
+/// `None` снимает признак.
+pub fn set_category_tax_kind(
+    path: &str,
+    key: &str,
+    category_id: i64,
+    tax_kind: Option<String>,
+) -> Result<(), DbError> {
+    if let Some(kind) = &tax_kind {
+        if !TAX_CATEGORY_KINDS.contains(&kind.as_str()) {
+            return Err(DbError::ValidationError(format!("Unknown tax kind: {}", kind)));
+        }
+    }
+    
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let category = tx.query_row(
+        "SELECT id, name, created_at FROM categories WHERE id = ?1",
+        [category_id],
+        |row| Ok(Category {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            created_at: row.get(2)?,
+            tax_kind: tax_kind.clone(),
+        }),
+    ).optional()?
+    .ok_or_else(|| DbError::ValidationError(format!("Category {} not found", category_id)))?;
+    
+    tx.execute(
+        "UPDATE categories SET tax_kind = ?1 WHERE id = ?2",
+        rusqlite::params![tax_kind, category_id],
+    )?;
+    
+    write_version_log(&tx, path, key, "category", category_id, "update", &serialize_entity(&category)?)?;
+    
+    tx.commit()?;
+    
+    Ok(())
+}
+
+/// Создание тега
+pub fn create_tag(path: &str, key: &str, name: String) -> Result<i64, DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let name = name.trim().to_string();
+    let (id, created_at) = insert_named_entity(&tx, "tags", &name)?;
+    let tag = Tag { id, name, created_at };
+    
+    write_version_log(&tx, path, key, "tag", id, "create", &serialize_entity(&tag)?)?;
+    
+    tx.commit()?;
+    
+    Ok(id)
+}
+
+/// Получение списка тегов
+pub fn list_tags(path: &str, key: &str) -> Result<Vec<Tag>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, name, created_at FROM tags ORDER BY name COLLATE NOCASE ASC"
+    )?;
+    
+    let tags = stmt.query_map([], |row| {
+        Ok(Tag {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            created_at: row.get(2)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(tags)
+}
+
+/// Получение тегов операции
+pub fn get_operation_tags(path: &str, key: &str, operation_id: i64) -> Result<Vec<Tag>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.name, t.created_at FROM tags t
+         INNER JOIN operation_tags ot ON ot.tag_id = t.id
+         WHERE ot.operation_id = ?1
+         ORDER BY t.name COLLATE NOCASE ASC"
+    )?;
+    
+    let tags = stmt.query_map([operation_id], |row| {
+        Ok(Tag {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            created_at: row.get(2)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(tags)
+}
+
+/// Добавление тега операции
+pub fn add_operation_tag(path: &str, key: &str, operation_id: i64, tag_id: i64) -> Result<(), DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    load_operation(&tx, operation_id)?;
+    ensure_row_exists(&tx, "tags", "Tag", tag_id)?;
+    
+    let inserted = tx.execute(
+        "INSERT OR IGNORE INTO operation_tags (operation_id, tag_id) VALUES (?1, ?2)",
+        [operation_id, tag_id],
+    )?;
+    
+    if inserted > 0 {
+        let operation_tag = OperationTag { operation_id, tag_id };
+        write_version_log(&tx, path, key, "operation_tag", operation_id, "create", &serialize_entity(&operation_tag)?)?;
+        refresh_operation_search(&tx, operation_id)?;
+    }
+    
+    tx.commit()?;
+    
+    Ok(())
+}
+
+/// Снятие тега с операции
+pub fn remove_operation_tag(path: &str, key: &str, operation_id: i64, tag_id: i64) -> Result<(), DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let removed = tx.execute(
+        "DELETE FROM operation_tags WHERE operation_id = ?1 AND tag_id = ?2",
+        [operation_id, tag_id],
+    )?;
+    
+    if removed > 0 {
+        let operation_tag = OperationTag { operation_id, tag_id };
+        write_version_log(&tx, path, key, "operation_tag", operation_id, "delete", &serialize_entity(&operation_tag)?)?;
+        refresh_operation_search(&tx, operation_id)?;
+    }
+    
+    tx.commit()?;
+    
+    Ok(())
+}
+
+/// Ручное назначение категории операции
+/// 
+/// # Параметры
+/// - `category_id` - категория; `None` снимает категорию
+pub fn set_operation_category(
+    path: &str,
+    key: &str,
+    operation_id: i64,
+    category_id: Option<i64>,
+) -> Result<(), DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    assign_operation_category(&tx, path, key, operation_id, category_id)?;
+    
+    tx.commit()?;
+    
+    Ok(())
+}
+
+/// Назначение категории операции в рамках транзакции вызывающего
+fn assign_operation_category(
+    conn: &Connection,
+    path: &str,
+    key: &str,
+    operation_id: i64,
+    category_id: Option<i64>,
+) -> Result<(), DbError> {
+    let mut operation = load_operation(conn, operation_id)?;
+    if let Some(category_id) = category_id {
+        ensure_row_exists(conn, "categories", "Category", category_id)?;
+    }
+    
+    conn.execute(
+        "UPDATE operations SET category_id = ?1 WHERE id = ?2",
+        rusqlite::params![category_id, operation_id],
+    )?;
+    
+    operation.category_id = category_id;
+    write_version_log(conn, path, key, "operation", operation_id, "update", &serialize_entity(&operation)?)?;
+    refresh_operation_search(conn, operation_id)?;
+    
+    Ok(())
+}
+
+/// Правило автокатегоризации с скомпилированным шаблоном описания
+struct ActiveRule {
+    rule: CategorizationRule,
+    pattern: Option<regex::Regex>,
+}
+
+/// Компиляция шаблона описания правила (без учёта регистра)
+fn compile_rule_pattern(pattern: &str) -> Result<regex::Regex, DbError> {
+    regex::RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| DbError::ValidationError(format!("Invalid regex '{}': {}", pattern, e)))
+}
+
+/// Маппинг строки categorization_rules в структуру
+fn categorization_rule_from_row(row: &rusqlite::Row) -> SqlResult<CategorizationRule> {
+    Ok(CategorizationRule {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description_pattern: row.get(2)?,
+        account_id: row.get(3)?,
+        amount_gt: row.get(4)?,
+        amount_lt: row.get(5)?,
+        set_category_id: row.get(6)?,
+        set_payee_id: row.get(7)?,
+        add_tag_id: row.get(8)?,
+        priority: row.get(9)?,
+        enabled: row.get(10)?,
+        version: row.get(11)?,
+        created_at: row.get(12)?,
+        updated_at: row.get(13)?,
+    })
+}
+
+/// Загрузка правила автокатегоризации по ID
+fn load_categorization_rule(conn: &Connection, rule_id: i64) -> Result<CategorizationRule, DbError> {
+    conn.query_row(
+        "SELECT id, name, description_pattern, account_id, amount_gt, amount_lt,
+                set_category_id, set_payee_id, add_tag_id, priority, enabled, version, created_at, updated_at
+         FROM categorization_rules WHERE id = ?1",
+        [rule_id],
+        categorization_rule_from_row,
+    ).optional()?
+    .ok_or_else(|| DbError::ValidationError(format!("Categorization rule {} not found", rule_id)))
+}
+
+/// Загрузка включённых правил в порядке применения
+fn load_active_rules(conn: &Connection) -> Result<Vec<ActiveRule>, DbError> {
+    let rules = {
+        let mut stmt = conn.prepare(
+            "SELECT id, name, description_pattern, account_id, amount_gt, amount_lt,
+                    set_category_id, set_payee_id, add_tag_id, priority, enabled, version, created_at, updated_at
+             FROM categorization_rules WHERE enabled = 1
+             ORDER BY priority DESC, id ASC"
+        )?;
+        let rows = stmt.query_map([], categorization_rule_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    rules
+        .into_iter()
+        .map(|rule| {
+            let pattern = rule.description_pattern.as_deref().map(compile_rule_pattern).transpose()?;
+            Ok(ActiveRule { rule, pattern })
+        })
+        .collect()
+}
+
+/// Проверка условий правила для операции
+fn rule_matches(active: &ActiveRule, operation: &Operation) -> bool {
+    let rule = &active.rule;
+    
+    active.pattern.as_ref().is_none_or(|re| re.is_match(&operation.description))
+        && rule.account_id.is_none_or(|account_id| account_id == operation.account_id)
+        && rule.amount_gt.is_none_or(|bound| operation.amount > bound)
+        && rule.amount_lt.is_none_or(|bound| operation.amount < bound)
+}
+
+/// Расчёт изменений, которые правила внесут в операцию
+/// 
+/// Заполняются только пустые поля: категорию и контрагента задаёт первое
+/// (по приоритету) подходящее правило, теги добавляют все подходящие правила.
+fn plan_rule_changes(rules: &[ActiveRule], operation: &Operation, existing_tags: &[i64]) -> Vec<RuleChange> {
+    let mut changes: Vec<RuleChange> = Vec::new();
+    let mut category_set = operation.category_id.is_some();
+    let mut payee_set = operation.payee_id.is_some();
+    
+    for active in rules.iter().filter(|active| rule_matches(active, operation)) {
+        let rule = &active.rule;
+        let change = |field: &str, old_value: Option<i64>, new_value: i64| RuleChange {
+            rule_id: rule.id,
+            rule_version: rule.version,
+            operation_id: operation.id,
+            field: field.to_string(),
+            old_value,
+            new_value,
+        };
+        
+        if let (false, Some(category_id)) = (category_set, rule.set_category_id) {
+            changes.push(change("category", operation.category_id, category_id));
+            category_set = true;
+        }
+        
+        if let (false, Some(payee_id)) = (payee_set, rule.set_payee_id) {
+            changes.push(change("payee", operation.payee_id, payee_id));
+            payee_set = true;
+        }
+        
+        if let Some(tag_id) = rule.add_tag_id {
+            let already_tagged = existing_tags.contains(&tag_id)
+                || changes.iter().any(|c| c.field == "tag" && c.new_value == tag_id);
+            if !already_tagged {
+                changes.push(change("tag", None, tag_id));
+            }
+        }
+    }
+    
+    changes
+}
+
+/// Применение изменения правила к операции с записью в version_log
+fn apply_rule_change(conn: &Connection, signer: &VersionSigner, change: &RuleChange) -> Result<(), DbError> {
+    match change.field.as_str() {
+        "category" => {
+            conn.execute(
+                "UPDATE operations SET category_id = ?1 WHERE id = ?2",
+                [change.new_value, change.operation_id],
+            )?;
+        }
+        "payee" => {
+            conn.execute(
+                "UPDATE operations SET payee_id = ?1 WHERE id = ?2",
+                [change.new_value, change.operation_id],
+            )?;
+        }
+        "tag" => {
+            conn.execute(
+                "INSERT OR IGNORE INTO operation_tags (operation_id, tag_id) VALUES (?1, ?2)",
+                [change.operation_id, change.new_value],
+            )?;
+        }
+        _ => {
+            return Err(DbError::ValidationError(format!("Unknown rule change field: {}", change.field)));
+        }
+    }
+    
+    write_signed_version_log(conn, signer, "operation", change.operation_id, "rule_apply", &serialize_entity(change)?)?;
+    refresh_operation_search(conn, change.operation_id)?;
+    
+    Ok(())
+}
+
+/// Проверка определения правила автокатегоризации
+fn validate_rule_input(conn: &Connection, input: &CategorizationRuleInput) -> Result<(), DbError> {
+    if input.name.trim().is_empty() {
+        return Err(DbError::ValidationError("Rule name must not be empty".to_string()));
+    }
+    
+    if input.set_category_id.is_none() && input.set_payee_id.is_none() && input.add_tag_id.is_none() {
+        return Err(DbError::ValidationError("Rule must set a category, payee or tag".to_string()));
+    }
+    
+    if let Some(pattern) = &input.description_pattern {
+        compile_rule_pattern(pattern)?;
+    }
+    
+    if let (Some(gt), Some(lt)) = (input.amount_gt, input.amount_lt) {
+        if gt >= lt {
+            return Err(DbError::ValidationError(format!("Empty amount range: > {} and < {}", gt, lt)));
+        }
+    }
+    
+    if let Some(account_id) = input.account_id {
+        ensure_account_exists(conn, account_id)?;
+    }
+    if let Some(category_id) = input.set_category_id {
+        ensure_row_exists(conn, "categories", "Category", category_id)?;
+    }
+    if let Some(payee_id) = input.set_payee_id {
+        ensure_payee_exists(conn, payee_id)?;
+    }
+    if let Some(tag_id) = input.add_tag_id {
+        ensure_row_exists(conn, "tags", "Tag", tag_id)?;
+    }
+    
+    Ok(())
+}
+
+/// Создание правила автокатегоризации
+/// 
+/// Правило применяется к новым операциям (в том числе импортированным);
+/// к существующим - через `apply_categorization_rules`.
+pub fn create_categorization_rule(
+    path: &str,
+    key: &str,
+    input: CategorizationRuleInput,
+) -> Result<i64, DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    validate_rule_input(&tx, &input)?;
+    
+    let created_at = current_timestamp()?;
+    
+    tx.execute(
+        "INSERT INTO categorization_rules (name, description_pattern, account_id, amount_gt, amount_lt,
+                                           set_category_id, set_payee_id, add_tag_id, priority, enabled,
+                                           version, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, 1, ?11, ?11)",
+        rusqlite::params![
+            input.name.trim(), input.description_pattern, input.account_id, input.amount_gt, input.amount_lt,
+            input.set_category_id, input.set_payee_id, input.add_tag_id, input.priority, input.enabled,
+            created_at
+        ],
+    )?;
+    
+    let rule = load_categorization_rule(&tx, tx.last_insert_rowid())?;
+    
+    write_version_log(&tx, path, key, "categorization_rule", rule.id, "create", &serialize_entity(&rule)?)?;
+    
+    tx.commit()?;
+    
+    Ok(rule.id)
+}
+
+/// Изменение правила автокатегоризации
+/// 
+/// Каждое изменение увеличивает версию правила. Возвращает новую версию.
+pub fn update_categorization_rule(
+    path: &str,
+    key: &str,
+    rule_id: i64,
+    input: CategorizationRuleInput,
+) -> Result<i64, DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    load_categorization_rule(&tx, rule_id)?;
+    validate_rule_input(&tx, &input)?;
+    
+    let updated_at = current_timestamp()?;
+    
+    tx.execute(
+        "UPDATE categorization_rules
+         SET name = ?1, description_pattern = ?2, account_id = ?3, amount_gt = ?4, amount_lt = ?5,
+             set_category_id = ?6, set_payee_id = ?7, add_tag_id = ?8, priority = ?9, enabled = ?10,
+             version = version + 1, updated_at = ?11
+         WHERE id = ?12",
+        rusqlite::params![
+            input.name.trim(), input.description_pattern, input.account_id, input.amount_gt, input.amount_lt,
+            input.set_category_id, input.set_payee_id, input.add_tag_id, input.priority, input.enabled,
+            updated_at, rule_id
+        ],
+    )?;
+    
+    let rule = load_categorization_rule(&tx, rule_id)?;
+    
+    write_version_log(&tx, path, key, "categorization_rule", rule_id, "update", &serialize_entity(&rule)?)?;
+    
+    tx.commit()?;
+    
+    Ok(rule.version)
+}
+
+/// Удаление правила автокатегоризации
+/// 
+/// Изменения, уже сделанные правилом, сохраняются.
+pub fn delete_categorization_rule(path: &str, key: &str, rule_id: i64) -> Result<(), DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let rule = load_categorization_rule(&tx, rule_id)?;
+    
+    tx.execute("DELETE FROM categorization_rules WHERE id = ?1", [rule_id])?;
+    
+    write_version_log(&tx, path, key, "categorization_rule", rule_id, "delete", &serialize_entity(&rule)?)?;
+    
+    tx.commit()?;
+    
+    Ok(())
+}
+
+/// Получение правил автокатегоризации в порядке применения
+pub fn list_categorization_rules(path: &str, key: &str) -> Result<Vec<CategorizationRule>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, name, description_pattern, account_id, amount_gt, amount_lt,
+                set_category_id, set_payee_id, add_tag_id, priority, enabled, version, created_at, updated_at
+         FROM categorization_rules ORDER BY priority DESC, id ASC"
+    )?;
+    
+    let rules = stmt.query_map([], categorization_rule_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(rules)
+}
+
+/// Применение правил к существующим операциям за период
+/// 
+/// Заполняются только пустые категория и контрагент и отсутствующие теги.
+/// Каждое изменение логируется в version_log с ID и версией правила.
+/// 
+/// # Параметры
+/// - `from_ts` - начало периода включительно
+/// - `to_ts` - конец периода, не включая
+/// - `dry_run` - только вернуть предлагаемые изменения, ничего не записывая
+pub fn apply_categorization_rules(
+    path: &str,
+    key: &str,
+    from_ts: i64,
+    to_ts: i64,
+    dry_run: bool,
+) -> Result<Vec<RuleChange>, DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let rules = load_active_rules(&tx)?;
+    
+    let operations = {
+        let mut stmt = tx.prepare(
+            "SELECT id, account_id, amount, description, ts, payee_id, category_id, notes FROM operations
+             WHERE ts >= ?1 AND ts < ?2 ORDER BY ts ASC, id ASC"
+        )?;
+        let rows = stmt.query_map([from_ts, to_ts], operation_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    let mut all_changes = Vec::new();
+    for operation in &operations {
+        let existing_tags = {
+            let mut stmt = tx.prepare("SELECT tag_id FROM operation_tags WHERE operation_id = ?1")?;
+            let rows = stmt.query_map([operation.id], |row| row.get::<_, i64>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            rows
+        };
+        
+        all_changes.extend(plan_rule_changes(&rules, operation, &existing_tags));
+    }
+    
+    if !dry_run {
+        let signer = VersionSigner::load(path, key)?;
+        for change in &all_changes {
+            apply_rule_change(&tx, &signer, change)?;
+        }
+        tx.commit()?;
+    }
+    
+    Ok(all_changes)
+}
+
+// Функции разбивки операций
+
+/// Загрузка строк разбивки операции
+fn load_operation_splits(conn: &Connection, operation_id: i64) -> SqlResult<Vec<SplitLine>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, operation_id, category_id, amount, memo FROM operation_splits
+         WHERE operation_id = ?1 ORDER BY id ASC"
+    )?;
+    
+    let splits = stmt.query_map([operation_id], |row| {
+        Ok(SplitLine {
+            id: row.get(0)?,
+            operation_id: row.get(1)?,
+            category_id: row.get(2)?,
+            amount: row.get(3)?,
+            memo: row.get(4)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(splits)
+}
+
+/// Разбивка операции на строки по категориям
+/// 
+/// Сумма строк должна совпадать с суммой операции, каждая строка - того же
+/// знака, что и операция. Новая разбивка заменяет прежнюю; пустой список
+/// снимает разбивку. Операция вместе со строками логируется в version_log.
+pub fn set_operation_splits(
+    path: &str,
+    key: &str,
+    operation_id: i64,
+    splits: Vec<SplitLineInput>,
+) -> Result<Vec<SplitLine>, DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let splits = replace_operation_splits(&tx, path, key, operation_id, &splits)?;
+    
+    tx.commit()?;
+    
+    Ok(splits)
+}
+
+/// Замена разбивки операции внутри транзакции, см. `set_operation_splits`
+fn replace_operation_splits(
+    conn: &Connection,
+    path: &str,
+    key: &str,
+    operation_id: i64,
+    splits: &[SplitLineInput],
+) -> Result<Vec<SplitLine>, DbError> {
+    let operation = load_operation(conn, operation_id)?;
+    
+    if !splits.is_empty() {
+        for split in splits {
+            if split.amount.abs() < AMOUNT_EPSILON || split.amount.signum() != operation.amount.signum() {
+                return Err(DbError::ValidationError(format!(
+                    "Split amount {} must be non-zero and have the sign of the operation amount {}",
+                    split.amount, operation.amount
+                )));
+            }
+            if let Some(category_id) = split.category_id {
+                ensure_row_exists(conn, "categories", "Category", category_id)?;
+            }
+        }
+        
+        let total: f64 = splits.iter().map(|split| split.amount).sum();
+        if (total - operation.amount).abs() > AMOUNT_EPSILON {
+            return Err(DbError::ValidationError(format!(
+                "Split lines sum to {} but the operation amount is {}",
+                total, operation.amount
+            )));
+        }
+    }
+    
+    conn.execute("DELETE FROM operation_splits WHERE operation_id = ?1", [operation_id])?;
+    
+    for split in splits {
+        conn.execute(
+            "INSERT INTO operation_splits (operation_id, category_id, amount, memo) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![operation_id, split.category_id, split.amount, split.memo],
+        )?;
+    }
+    
+    let split_operation = SplitOperation {
+        operation,
+        splits: load_operation_splits(conn, operation_id)?,
+    };
+    
+    write_version_log(conn, path, key, "operation", operation_id, "update", &serialize_entity(&split_operation)?)?;
+    refresh_operation_search(conn, operation_id)?;
+    
+    Ok(split_operation.splits)
+}
+
+/// Получение строк разбивки операции
+pub fn get_operation_splits(path: &str, key: &str, operation_id: i64) -> Result<Vec<SplitLine>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    Ok(load_operation_splits(&conn, operation_id)?)
+}
+
+/// Расходы по категориям за период
+/// 
+/// Разбитая операция учитывается по своим строкам (каждая - в своей
+/// категории), неразбитая - целиком в категории операции. Учитываются
+/// списания со счетов класса asset, кроме перемещений между своими счетами
+/// (как в `get_cash_flow`); строки без категории собираются в группу
+/// с `category_id = None`.
+/// 
+/// # Параметры
+/// - `from_ts` - начало периода включительно (если не указано - с начала истории)
+/// - `to_ts` - конец периода, не включая (если не указано - до текущего момента)
+pub fn get_spending_by_category(
+    path: &str,
+    key: &str,
+    from_ts: Option<i64>,
+    to_ts: Option<i64>,
+) -> Result<Vec<CategorySpending>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT lines.category_id, c.name, SUM(-lines.amount), COUNT(*)
+         FROM (
+             SELECT o.id, s.category_id, s.amount, o.account_id, o.ts
+             FROM operation_splits s
+             INNER JOIN operations o ON o.id = s.operation_id
+             UNION ALL
+             SELECT o.id, o.category_id, o.amount, o.account_id, o.ts
+             FROM operations o
+             WHERE NOT EXISTS (SELECT 1 FROM operation_splits s WHERE s.operation_id = o.id)
+         ) lines
+         INNER JOIN accounts a ON a.id = lines.account_id
+         LEFT JOIN categories c ON c.id = lines.category_id
+         WHERE lines.amount < 0 AND a.class = 'asset'
+           AND (?1 IS NULL OR lines.ts >= ?1)
+           AND (?2 IS NULL OR lines.ts < ?2)
+           AND lines.id NOT IN (SELECT from_operation_id FROM transfers UNION SELECT to_operation_id FROM transfers)
+           AND lines.id NOT IN (SELECT principal_operation_id FROM loan_payments)
+           AND lines.id NOT IN (SELECT operation_id FROM investment_transactions)
+         GROUP BY lines.category_id
+         ORDER BY SUM(-lines.amount) DESC"
+    )?;
+    
+    let spending = stmt.query_map([from_ts, to_ts], |row| {
+        Ok(CategorySpending {
+            category_id: row.get(0)?,
+            category_name: row.get(1)?,
+            total_spent: row.get(2)?,
+            line_count: row.get(3)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(spending)
+}
+
+// Функции полнотекстового поиска по операциям
+
+/// Обновление строки полнотекстового индекса для операции
+/// 
+/// Индексируются описание, контрагент, категория, теги, заметки
+/// и комментарии строк разбивки. Вызывается всеми функциями, меняющими эти поля.
+fn refresh_operation_search(conn: &Connection, operation_id: i64) -> SqlResult<()> {
+    conn.execute("DELETE FROM operations_fts WHERE rowid = ?1", [operation_id])?;
+    
+    conn.execute(
+        "INSERT INTO operations_fts (rowid, description, payee, category, tags, notes)
+         SELECT o.id, o.description, COALESCE(p.name, ''), COALESCE(c.name, ''),
+                COALESCE((SELECT group_concat(t.name, ' ') FROM operation_tags ot
+                          INNER JOIN tags t ON t.id = ot.tag_id
+                          WHERE ot.operation_id = o.id), ''),
+                TRIM(COALESCE(o.notes, '') || ' ' ||
+                     COALESCE((SELECT group_concat(s.memo, ' ') FROM operation_splits s
+                               WHERE s.operation_id = o.id), ''))
+         FROM operations o
+         LEFT JOIN payees p ON p.id = o.payee_id
+         LEFT JOIN categories c ON c.id = o.category_id
+         WHERE o.id = ?1",
+        [operation_id],
+    )?;
+    
+    Ok(())
+}
+
+/// Перевод пользовательского запроса в синтаксис FTS5
+/// 
+/// - `"оплата сантехнику"` - фраза
+/// - `сантех*` - поиск по префиксу
+/// - остальные слова объединяются через И
+/// 
+/// Служебные символы FTS5 отбрасываются, поэтому запрос не может
+/// оказаться синтаксически некорректным.
+pub fn build_fts_query(input: &str) -> Result<String, DbError> {
+    let words = |text: &str| {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+    
+    let mut terms = Vec::new();
+    for (index, part) in input.split('"').enumerate() {
+        if index % 2 == 1 {
+            let phrase = words(part);
+            if !phrase.is_empty() {
+                terms.push(format!("\"{}\"", phrase.join(" ")));
+            }
+            continue;
+        }
+        
+        for token in part.split_whitespace() {
+            let prefix = token.ends_with('*');
+            let pieces = words(token);
+            for (piece_index, piece) in pieces.iter().enumerate() {
+                if prefix && piece_index + 1 == pieces.len() {
+                    terms.push(format!("\"{}\"*", piece));
+                } else {
+                    terms.push(format!("\"{}\"", piece));
+                }
+            }
+        }
+    }
+    
+    if terms.is_empty() {
+        return Err(DbError::ValidationError("Search query must contain words".to_string()));
+    }
+    
+    Ok(terms.join(" "))
+}
+
+/// Список счетов фильтра в виде JSON-массива для `json_each`
+fn account_ids_json(filters: &OperationFilters) -> Option<String> {
+    filters.account_ids.as_ref().map(|ids| {
+        format!("[{}]", ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(","))
+    })
+}
+
+/// Установка заметки к операции
+/// 
+/// # Параметры
+/// - `notes` - текст заметки; `None` удаляет заметку
+pub fn set_operation_notes(
+    path: &str,
+    key: &str,
+    operation_id: i64,
+    notes: Option<String>,
+) -> Result<(), DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let mut operation = load_operation(&tx, operation_id)?;
+    let notes = notes.map(|text| text.trim().to_string()).filter(|text| !text.is_empty());
+    
+    tx.execute(
+        "UPDATE operations SET notes = ?1 WHERE id = ?2",
+        rusqlite::params![notes, operation_id],
+    )?;
+    
+    operation.notes = notes;
+    write_version_log(&tx, path, key, "operation", operation_id, "update", &serialize_entity(&operation)?)?;
+    refresh_operation_search(&tx, operation_id)?;
+    
+    tx.commit()?;
+    
+    Ok(())
+}
+
+/// Полнотекстовый поиск по операциям всех счетов
+/// 
+/// Поиск идёт по описанию, контрагенту, категории, тегам и заметкам
+/// (см. `build_fts_query`). Результаты упорядочены по релевантности BM25
+/// (совпадения в описании и контрагенте весят больше), при равной
+/// релевантности - от новых к старым.
+/// 
+/// # Параметры
+/// - `query` - поисковый запрос
+/// - `filters` - фильтры по счетам, периоду и сумме
+/// - `limit` - максимальное количество результатов (по умолчанию 50)
+pub fn search_operations(
+    path: &str,
+    key: &str,
+    query: String,
+    filters: OperationFilters,
+    limit: Option<i64>,
+) -> Result<Vec<OperationSearchHit>, DbError> {
+    let fts_query = build_fts_query(&query)?;
+    
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT o.id, o.account_id, o.amount, o.description, o.ts, o.payee_id, o.category_id, o.notes,
+                bm25(operations_fts, 10.0, 5.0, 2.0, 2.0, 1.0) AS score,
+                snippet(operations_fts, -1, '[', ']', '…', 10)
+         FROM operations_fts
+         INNER JOIN operations o ON o.id = operations_fts.rowid
+         WHERE operations_fts MATCH ?1
+           AND (?2 IS NULL OR o.account_id IN (SELECT value FROM json_each(?2)))
+           AND (?3 IS NULL OR o.ts >= ?3)
+           AND (?4 IS NULL OR o.ts < ?4)
+           AND (?5 IS NULL OR o.amount >= ?5)
+           AND (?6 IS NULL OR o.amount <= ?6)
+         ORDER BY score ASC, o.ts DESC, o.id DESC
+         LIMIT ?7"
+    )?;
+    
+    let hits = stmt.query_map(
+        rusqlite::params![
+            fts_query,
+            account_ids_json(&filters),
+            filters.from_ts,
+            filters.to_ts,
+            filters.min_amount,
+            filters.max_amount,
+            limit.unwrap_or(SEARCH_DEFAULT_LIMIT).max(1),
+        ],
+        |row| {
+            Ok(OperationSearchHit {
+                operation: operation_from_row(row)?,
+                rank: -row.get::<_, f64>(8)?,
+                snippet: row.get(9)?,
+            })
+        },
+    )?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(hits)
+}
+
+/// Разбор курсора выборки операций: значение ключа сортировки и ID
+fn parse_operation_cursor(cursor: &str, sort: OperationSort) -> Result<(f64, i64), DbError> {
+    let invalid = || DbError::ValidationError(format!("Invalid cursor: {}", cursor));
+    
+    let mut parts = cursor.splitn(3, ':');
+    let (Some(cursor_sort), Some(value), Some(id)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid());
+    };
+    
+    if cursor_sort != sort.as_str() {
+        return Err(DbError::ValidationError(format!(
+            "Cursor was issued for sort {}, not {}",
+            cursor_sort,
+            sort.as_str()
+        )));
+    }
+    
+    Ok((value.parse().map_err(|_| invalid())?, id.parse().map_err(|_| invalid())?))
+}
+
+/// Постраничная выборка операций с фильтрами и итогами
+/// 
+/// Пагинация по курсору (keyset): следующая страница начинается строго после
+/// последней операции предыдущей по ключу сортировки и ID, поэтому вставка
+/// новых операций не сдвигает страницы. `total_count` и `total_amount`
+/// считаются по всей отфильтрованной выборке, без учёта курсора и лимита.
+pub fn query_operations(path: &str, key: &str, query: OperationQuery) -> Result<OperationPage, DbError> {
+    let text_query = query.text
+        .as_deref()
+        .filter(|text| !text.trim().is_empty())
+        .map(build_fts_query)
+        .transpose()?;
+    
+    let cursor = query.cursor
+        .as_deref()
+        .map(|cursor| parse_operation_cursor(cursor, query.sort))
+        .transpose()?;
+    
+    let limit = query.limit.unwrap_or(OPERATION_PAGE_DEFAULT_LIMIT).clamp(1, OPERATION_PAGE_MAX_LIMIT);
+    
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let filter_sql = "(?1 IS NULL OR o.account_id IN (SELECT value FROM json_each(?1)))
+           AND (?2 IS NULL OR o.ts >= ?2)
+           AND (?3 IS NULL OR o.ts < ?3)
+           AND (?4 IS NULL OR o.amount >= ?4)
+           AND (?5 IS NULL OR o.amount <= ?5)
+           AND (?6 IS NULL OR o.id IN (SELECT rowid FROM operations_fts WHERE operations_fts MATCH ?6))";
+    let account_ids = account_ids_json(&query.filters);
+    
+    let (total_count, total_amount): (i64, f64) = conn.query_row(
+        &format!("SELECT COUNT(*), COALESCE(SUM(o.amount), 0.0) FROM operations o WHERE {}", filter_sql),
+        rusqlite::params![
+            account_ids, query.filters.from_ts, query.filters.to_ts,
+            query.filters.min_amount, query.filters.max_amount, text_query,
+        ],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    
+    let (sort_column, direction, comparison) = match query.sort {
+        OperationSort::TsDesc => ("o.ts", "DESC", "<"),
+        OperationSort::TsAsc => ("o.ts", "ASC", ">"),
+        OperationSort::AmountDesc => ("o.amount", "DESC", "<"),
+        OperationSort::AmountAsc => ("o.amount", "ASC", ">"),
+    };
+    
+    let sql = format!(
+        "SELECT o.id, o.account_id, o.amount, o.description, o.ts, o.payee_id, o.category_id, o.notes
+         FROM operations o
+         WHERE {filter}
+           AND (?7 IS NULL OR {col} {cmp} ?7 OR ({col} = ?7 AND o.id {cmp} ?8))
+         ORDER BY {col} {dir}, o.id {dir}
+         LIMIT ?9",
+        filter = filter_sql,
+        col = sort_column,
+        cmp = comparison,
+        dir = direction,
+    );
+    
+    let mut stmt = conn.prepare(&sql)?;
+    let mut operations = stmt.query_map(
+        rusqlite::params![
+            account_ids, query.filters.from_ts, query.filters.to_ts,
+            query.filters.min_amount, query.filters.max_amount, text_query,
+            cursor.map(|(value, _)| value), cursor.map(|(_, id)| id),
+            limit + 1,
+        ],
+        operation_from_row,
+    )?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    // Лишняя строка означает, что есть следующая страница
+    let next_cursor = if operations.len() as i64 > limit {
+        operations.truncate(limit as usize);
+        operations.last().map(|last| {
+            let value = match query.sort {
+                OperationSort::TsDesc | OperationSort::TsAsc => last.ts.to_string(),
+                OperationSort::AmountDesc | OperationSort::AmountAsc => last.amount.to_string(),
+            };
+            format!("{}:{}:{}", query.sort.as_str(), value, last.id)
+        })
+    } else {
+        None
+    };
+    
+    Ok(OperationPage {
+        operations,
+        next_cursor,
+        total_count,
+        total_amount,
+    })
+}
+
+// Функции вложений
+
+/// Байты в hex (нижний регистр)
+fn hex_encode(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Разбор hex-строки в байты
+fn hex_decode(hex: &str) -> Result<Vec<u8>, DbError> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err(DbError::ValidationError("Invalid hex string".to_string()));
+    }
+    
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| DbError::ValidationError("Invalid hex string".to_string()))
+        })
+        .collect()
+}
+
+/// SHA-256 содержимого в hex
+fn sha256_hex(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    
+    hex_encode(&Sha256::digest(data))
+}
+
+/// Маппинг строки attachments (без содержимого) в структуру
+fn attachment_from_row(row: &rusqlite::Row) -> SqlResult<Attachment> {
+    Ok(Attachment {
+        id: row.get(0)?,
+        owner_type: row.get(1)?,
+        owner_id: row.get(2)?,
+        file_name: row.get(3)?,
+        mime_type: row.get(4)?,
+        size: row.get(5)?,
+        sha256: row.get(6)?,
+        created_at: row.get(7)?,
+    })
+}
+
+/// Загрузка метаданных вложения по ID
+fn load_attachment(conn: &Connection, attachment_id: i64) -> Result<Attachment, DbError> {
+    conn.query_row(
+        "SELECT id, owner_type, owner_id, file_name, mime_type, size, sha256, created_at
+         FROM attachments WHERE id = ?1",
+        [attachment_id],
+        attachment_from_row,
+    ).optional()?
+    .ok_or_else(|| DbError::ValidationError(format!("Attachment {} not found", attachment_id)))
+}
+
+/// Хэш вложения из подписанной записи о его создании
+/// 
+/// Возвращает `None`, если запись отсутствует или подпись не проходит проверку.
+fn signed_attachment_hash(conn: &Connection, attachment_id: i64) -> Result<Option<String>, DbError> {
+    let signed: Option<(String, Vec<u8>, Vec<u8>)> = conn.query_row(
+        "SELECT v.payload, s.signature, s.public_key
+         FROM version_log v
+         INNER JOIN version_signatures s ON s.version_id = v.id
+         WHERE v.entity = 'attachment' AND v.entity_id = ?1 AND v.action = 'create'
+         ORDER BY v.id DESC LIMIT 1",
+        [attachment_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    ).optional()?;
+    
+    let Some((payload, signature, public_key)) = signed else {
+        return Ok(None);
+    };
+    
+    let is_valid = crate::crypto::verify_payload(payload.as_bytes(), &signature, &public_key)
+        .map_err(|e| DbError::InitError(format!("Failed to verify signature: {}", e)))?;
+    if !is_valid {
+        return Ok(None);
+    }
+    
+    Ok(serde_json::from_str::<Attachment>(&payload).ok().map(|attachment| attachment.sha256))
+}
+
+/// Прикрепление файла к операции или счёту
+/// 
+/// Размер файла ограничен `ATTACHMENT_MAX_SIZE`, суммарный размер вложений
+/// одной сущности - `ATTACHMENT_MAX_TOTAL_SIZE`.
+/// 
+/// # Параметры
+/// - `owner_type` - тип сущности (см. `ATTACHMENT_OWNER_TYPES`)
+/// - `owner_id` - ID операции или счёта
+/// - `file_name` - имя файла
+/// - `mime_type` - MIME-тип (image/jpeg, application/pdf, ...)
+/// - `data` - содержимое файла
+#[allow(clippy::too_many_arguments)]
+pub fn add_attachment(
+    path: &str,
+    key: &str,
+    owner_type: String,
+    owner_id: i64,
+    file_name: String,
+    mime_type: String,
+    data: Vec<u8>,
+) -> Result<i64, DbError> {
+    let owner_table = match owner_type.as_str() {
+        "operation" => "operations",
+        "account" => "accounts",
+        _ => return Err(DbError::ValidationError(format!("Unknown attachment owner type: {}", owner_type))),
+    };
+    
+    if file_name.trim().is_empty() {
+        return Err(DbError::ValidationError("File name must not be empty".to_string()));
+    }
+    
+    if data.is_empty() {
+        return Err(DbError::ValidationError("Attachment must not be empty".to_string()));
+    }
+    
+    if data.len() > ATTACHMENT_MAX_SIZE {
+        return Err(DbError::ValidationError(format!(
+            "Attachment is too large: {} bytes (limit {})",
+            data.len(), ATTACHMENT_MAX_SIZE
+        )));
+    }
+    
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    ensure_row_exists(&tx, owner_table, &owner_type, owner_id)?;
+    
+    let used: i64 = tx.query_row(
+        "SELECT COALESCE(SUM(size), 0) FROM attachments WHERE owner_type = ?1 AND owner_id = ?2",
+        rusqlite::params![owner_type, owner_id],
+        |row| row.get(0),
+    )?;
+    if used as usize + data.len() > ATTACHMENT_MAX_TOTAL_SIZE {
+        return Err(DbError::ValidationError(format!(
+            "Attachments of {} {} would exceed {} bytes",
+            owner_type, owner_id, ATTACHMENT_MAX_TOTAL_SIZE
+        )));
+    }
+    
+    let created_at = current_timestamp()?;
+    let sha256 = sha256_hex(&data);
+    
+    tx.execute(
+        "INSERT INTO attachments (owner_type, owner_id, file_name, mime_type, size, sha256, data, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        rusqlite::params![owner_type, owner_id, file_name, mime_type, data.len() as i64, sha256, data, created_at],
+    )?;
+    
+    let attachment = Attachment {
+        id: tx.last_insert_rowid(),
+        owner_type,
+        owner_id,
+        file_name,
+        mime_type,
+        size: data.len() as i64,
+        sha256,
+        created_at,
+    };
+    
+    write_version_log(&tx, path, key, "attachment", attachment.id, "create", &serialize_entity(&attachment)?)?;
+    
+    tx.commit()?;
+    
+    Ok(attachment.id)
+}
+
+/// Получение списка вложений сущности (без содержимого)
+pub fn list_attachments(
+    path: &str,
+    key: &str,
+    owner_type: String,
+    owner_id: i64,
+) -> Result<Vec<Attachment>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, owner_type, owner_id, file_name, mime_type, size, sha256, created_at
+         FROM attachments WHERE owner_type = ?1 AND owner_id = ?2
+         ORDER BY created_at ASC, id ASC"
+    )?;
+    
+    let attachments = stmt.query_map(rusqlite::params![owner_type, owner_id], attachment_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(attachments)
+}
+
+/// Получение вложения с содержимым
+/// 
+/// Хэш содержимого сверяется с SHA-256 из подписанной записи version_log;
+/// подменённый файл даёт `integrity_ok = false`.
+pub fn get_attachment(path: &str, key: &str, attachment_id: i64) -> Result<AttachmentContent, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let attachment = load_attachment(&conn, attachment_id)?;
+    
+    let data: Vec<u8> = conn.query_row(
+        "SELECT data FROM attachments WHERE id = ?1",
+        [attachment_id],
+        |row| row.get(0),
+    )?;
+    
+    let actual_hash = sha256_hex(&data);
+    let integrity_ok = actual_hash == attachment.sha256
+        && signed_attachment_hash(&conn, attachment_id)?.as_deref() == Some(actual_hash.as_str());
+    
+    Ok(AttachmentContent {
+        attachment,
+        data,
+        integrity_ok,
+    })
+}
+
+/// Удаление вложения
+pub fn delete_attachment(path: &str, key: &str, attachment_id: i64) -> Result<(), DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let attachment = load_attachment(&tx, attachment_id)?;
+    
+    tx.execute("DELETE FROM attachments WHERE id = ?1", [attachment_id])?;
+    
+    write_version_log(&tx, path, key, "attachment", attachment_id, "delete", &serialize_entity(&attachment)?)?;
+    
+    tx.commit()?;
+    
+    Ok(())
+}
+
+// Функции целей накоплений
+
+/// Загрузка цели вместе с привязками
+fn load_goal(conn: &Connection, goal_id: i64) -> Result<Goal, DbError> {
+    let (name, target_amount, target_ts, created_at): (String, f64, i64, i64) = conn.query_row(
+        "SELECT name, target_amount, target_ts, created_at FROM goals WHERE id = ?1",
+        [goal_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    ).optional()?
+    .ok_or_else(|| DbError::ValidationError(format!("Goal {} not found", goal_id)))?;
+    
+    let links = {
+        let mut stmt = conn.prepare(
+            "SELECT link_type, link_id FROM goal_links WHERE goal_id = ?1 ORDER BY link_type, link_id"
+        )?;
+        let rows = stmt.query_map([goal_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    let linked = |link_type: &str| {
+        links.iter().filter(|(t, _)| t == link_type).map(|(_, id)| *id).collect::<Vec<_>>()
+    };
+    
+    Ok(Goal {
+        id: goal_id,
+        name,
+        target_amount,
+        target_ts,
+        account_ids: linked("account"),
+        tag_ids: linked("tag"),
+        created_at,
+    })
+}
+
+/// Накопленная по цели сумма на момент ts
+/// 
+/// Для счетов берётся баланс из states, для тегов - сумма помеченных операций.
+fn goal_amount_at(conn: &Connection, goal: &Goal, ts: i64) -> SqlResult<f64> {
+    let mut amount = 0.0;
+    
+    for account_id in &goal.account_ids {
+        amount += get_balance_at(conn, *account_id, ts)?;
+    }
+    
+    for tag_id in &goal.tag_ids {
+        // Операции на привязанных счетах уже учтены в их балансе
+        let tagged: f64 = conn.query_row(
+            "SELECT COALESCE(SUM(o.amount), 0.0) FROM operations o
+             INNER JOIN operation_tags ot ON ot.operation_id = o.id
+             WHERE ot.tag_id = ?1 AND o.ts <= ?2
+               AND o.account_id NOT IN (SELECT link_id FROM goal_links WHERE goal_id = ?3 AND link_type = 'account')",
+            [*tag_id, ts, goal.id],
+            |row| row.get(0),
+        )?;
+        amount += tagged;
+    }
+    
+    Ok(amount)
+}
+
+/// Создание цели накоплений
+/// 
+/// # Параметры
+/// - `target_amount` - целевая сумма
+/// - `target_ts` - дата, к которой сумма должна быть накоплена
+/// - `account_ids` - счета, баланс которых идёт в зачёт цели
+/// - `tag_ids` - теги, помеченные которыми операции идут в зачёт цели
+pub fn create_goal(
+    path: &str,
+    key: &str,
+    name: String,
+    target_amount: f64,
+    target_ts: i64,
+    account_ids: Vec<i64>,
+    tag_ids: Vec<i64>,
+) -> Result<i64, DbError> {
+    if name.trim().is_empty() {
+        return Err(DbError::ValidationError("Goal name must not be empty".to_string()));
+    }
+    
+    if target_amount <= 0.0 {
+        return Err(DbError::ValidationError("Goal target amount must be positive".to_string()));
+    }
+    
+    if account_ids.is_empty() && tag_ids.is_empty() {
+        return Err(DbError::ValidationError("Goal must be linked to an account or a tag".to_string()));
+    }
+    
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    for account_id in &account_ids {
+        ensure_account_exists(&tx, *account_id)?;
+    }
+    for tag_id in &tag_ids {
+        ensure_row_exists(&tx, "tags", "Tag", *tag_id)?;
+    }
+    
+    let created_at = current_timestamp()?;
+    
+    tx.execute(
+        "INSERT INTO goals (name, target_amount, target_ts, created_at) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![name.trim(), target_amount, target_ts, created_at],
+    )?;
+    
+    let goal_id = tx.last_insert_rowid();
+    
+    for account_id in &account_ids {
+        tx.execute(
+            "INSERT OR IGNORE INTO goal_links (goal_id, link_type, link_id) VALUES (?1, 'account', ?2)",
+            [goal_id, *account_id],
+        )?;
+    }
+    for tag_id in &tag_ids {
+        tx.execute(
+            "INSERT OR IGNORE INTO goal_links (goal_id, link_type, link_id) VALUES (?1, 'tag', ?2)",
+            [goal_id, *tag_id],
+        )?;
+    }
+    
+    let goal = load_goal(&tx, goal_id)?;
+    
+    write_version_log(&tx, path, key, "goal", goal_id, "create", &serialize_entity(&goal)?)?;
+    
+    tx.commit()?;
+    
+    Ok(goal_id)
+}
+
+/// Получение списка целей (по дате цели)
+pub fn list_goals(path: &str, key: &str) -> Result<Vec<Goal>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let goal_ids = {
+        let mut stmt = conn.prepare("SELECT id FROM goals ORDER BY target_ts ASC, id ASC")?;
+        let rows = stmt.query_map([], |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    goal_ids.into_iter().map(|goal_id| load_goal(&conn, goal_id)).collect()
+}
+
+/// Удаление цели
+pub fn delete_goal(path: &str, key: &str, goal_id: i64) -> Result<(), DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let goal = load_goal(&tx, goal_id)?;
+    
+    tx.execute("DELETE FROM goal_links WHERE goal_id = ?1", [goal_id])?;
+    tx.execute("DELETE FROM goals WHERE id = ?1", [goal_id])?;
+    
+    write_version_log(&tx, path, key, "goal", goal_id, "delete", &serialize_entity(&goal)?)?;
+    
+    tx.commit()?;
+    
+    Ok(())
+}
+
+/// Прогресс по цели накоплений
+/// 
+/// Текущая сумма берётся из states (и помеченных операций), необходимый
+/// ежемесячный взнос - остаток, делённый на число месяцев до даты цели.
+/// Статус определяется по темпу пополнения за последние
+/// `GOAL_VELOCITY_WINDOW_DAYS` дней:
+/// - `reached` - цель уже достигнута
+/// - `overdue` - дата цели прошла, сумма не накоплена
+/// - `on_track` - при текущем темпе цель будет достигнута в срок
+/// - `behind` - текущего темпа недостаточно
+pub fn get_goal_progress(path: &str, key: &str, goal_id: i64) -> Result<GoalProgress, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let goal = load_goal(&conn, goal_id)?;
+    let now = current_timestamp()?;
+    
+    let current_amount = goal_amount_at(&conn, &goal, now)?;
+    let window_start = now - GOAL_VELOCITY_WINDOW_DAYS * SECONDS_PER_DAY;
+    let window_months = GOAL_VELOCITY_WINDOW_DAYS as f64 / DAYS_PER_MONTH;
+    let recent_monthly_contribution =
+        round_cents((current_amount - goal_amount_at(&conn, &goal, window_start)?) / window_months);
+    
+    let remaining_amount = round_cents((goal.target_amount - current_amount).max(0.0));
+    let months_remaining = ((goal.target_ts - now) as f64 / SECONDS_PER_DAY as f64 / DAYS_PER_MONTH).max(0.0);
+    
+    // Меньше месяца до срока - остаток нужно внести одним взносом
+    let required_monthly_contribution = round_cents(remaining_amount / months_remaining.max(1.0));
+    let projected_amount = round_cents(current_amount + recent_monthly_contribution.max(0.0) * months_remaining);
+    
+    let status = if remaining_amount <= AMOUNT_EPSILON {
+        "reached"
+    } else if now > goal.target_ts {
+        "overdue"
+    } else if projected_amount + AMOUNT_EPSILON >= goal.target_amount {
+        "on_track"
+    } else {
+        "behind"
+    };
+    
+    Ok(GoalProgress {
+        goal_id,
+        current_amount: round_cents(current_amount),
+        target_amount: goal.target_amount,
+        remaining_amount,
+        months_remaining,
+        required_monthly_contribution,
+        recent_monthly_contribution,
+        projected_amount,
+        status: status.to_string(),
+    })
+}
+
+// Функции регулярных платежей и прогноза балансов
+
+fn recurring_item_from_row(row: &rusqlite::Row) -> SqlResult<RecurringItem> {
+    Ok(RecurringItem {
+        id: row.get(0)?,
+        account_id: row.get(1)?,
+        amount: row.get(2)?,
+        description: row.get(3)?,
+        payee_id: row.get(4)?,
+        interval: row.get(5)?,
+        next_ts: row.get(6)?,
+        end_ts: row.get(7)?,
+        created_at: row.get(8)?,
+    })
+}
+
+fn load_recurring_items(conn: &Connection) -> SqlResult<Vec<RecurringItem>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, account_id, amount, description, payee_id, interval, next_ts, end_ts, created_at
+         FROM recurring_items ORDER BY next_ts ASC, id ASC"
+    )?;
+    
+    let items = stmt.query_map([], recurring_item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(items)
+}
+
+/// Сдвиг момента на `steps` шагов интервала по локальному календарю
+fn shift_by_interval(ts: i64, interval: HistoryInterval, steps: i64) -> Result<i64, DbError> {
+    use chrono::TimeZone;
+    
+    let days = match interval {
+        HistoryInterval::Day => steps,
+        HistoryInterval::Week => 7 * steps,
+        HistoryInterval::Month => return add_months(ts, steps),
+        HistoryInterval::Quarter => return add_months(ts, 3 * steps),
+    };
+    
+    let invalid = || DbError::ValidationError(format!("Cannot shift {} by {} days", ts, days));
+    
+    let base = chrono::Local.timestamp_opt(ts, 0).earliest().ok_or_else(invalid)?;
+    let shifted = base
+        .naive_local()
+        .checked_add_signed(chrono::Duration::days(days))
+        .ok_or_else(invalid)?;
+    
+    chrono::Local
+        .from_local_datetime(&shifted)
+        .earliest()
+        .map(|dt| dt.timestamp())
+        .ok_or_else(invalid)
+}
+
+/// Повторения регулярного элемента в интервале (after, until]
+fn recurring_occurrences(item: &RecurringItem, after: i64, until: i64) -> Result<Vec<i64>, DbError> {
+    let until = item.end_ts.map_or(until, |end_ts| end_ts.min(until));
+    
+    let mut occurrences = Vec::new();
+    for step in 0.. {
+        let ts = shift_by_interval(item.next_ts, item.interval, step)?;
+        if ts > until {
+            break;
+        }
+        if ts > after {
+            occurrences.push(ts);
+        }
+    }
+    
+    Ok(occurrences)
+}
+
+/// Создание регулярного платежа или поступления
+pub fn create_recurring_item(path: &str, key: &str, input: RecurringItemInput) -> Result<i64, DbError> {
+    if input.amount.abs() <= AMOUNT_EPSILON {
+        return Err(DbError::ValidationError("Recurring amount must not be zero".to_string()));
+    }
+    
+    if input.end_ts.is_some_and(|end_ts| end_ts < input.next_ts) {
+        return Err(DbError::ValidationError("Recurring end must not be before the next occurrence".to_string()));
+    }
+    
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    ensure_account_exists(&tx, input.account_id)?;
+    if let Some(payee_id) = input.payee_id {
+        ensure_payee_exists(&tx, payee_id)?;
+    }
+    
+    tx.execute(
+        "INSERT INTO recurring_items (account_id, amount, description, payee_id, interval, next_ts, end_ts, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        rusqlite::params![
+            input.account_id, input.amount, input.description.trim(), input.payee_id,
+            input.interval, input.next_ts, input.end_ts, current_timestamp()?,
+        ],
+    )?;
+    
+    let item_id = tx.last_insert_rowid();
+    let item = tx.query_row(
+        "SELECT id, account_id, amount, description, payee_id, interval, next_ts, end_ts, created_at
+         FROM recurring_items WHERE id = ?1",
+        [item_id],
+        recurring_item_from_row,
+    )?;
+    
+    write_version_log(&tx, path, key, "recurring_item", item_id, "create", &serialize_entity(&item)?)?;
+    
+    tx.commit()?;
+    
+    Ok(item_id)
+}
+
+/// Получение списка регулярных элементов (по ближайшему повторению)
+pub fn list_recurring_items(path: &str, key: &str) -> Result<Vec<RecurringItem>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    Ok(load_recurring_items(&conn)?)
+}
+
+/// Удаление регулярного элемента
+pub fn delete_recurring_item(path: &str, key: &str, item_id: i64) -> Result<(), DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let item = tx.query_row(
+        "SELECT id, account_id, amount, description, payee_id, interval, next_ts, end_ts, created_at
+         FROM recurring_items WHERE id = ?1",
+        [item_id],
+        recurring_item_from_row,
+    ).optional()?
+    .ok_or_else(|| DbError::ValidationError(format!("Recurring item {} not found", item_id)))?;
+    
+    tx.execute("DELETE FROM recurring_items WHERE id = ?1", [item_id])?;
+    
+    write_version_log(&tx, path, key, "recurring_item", item_id, "delete", &serialize_entity(&item)?)?;
+    
+    tx.commit()?;
+    
+    Ok(())
+}
+
+/// Известные будущие движения по счетам в (after, until]: регулярные
+/// элементы и плановые платежи по кредитам (со счёта последнего платежа)
+fn scheduled_flows(
+    conn: &Connection,
+    items: &[RecurringItem],
+    after: i64,
+    until: i64,
+) -> Result<std::collections::HashMap<i64, Vec<(i64, f64)>>, DbError> {
+    let mut flows: std::collections::HashMap<i64, Vec<(i64, f64)>> = std::collections::HashMap::new();
+    
+    for item in items {
+        for ts in recurring_occurrences(item, after, until)? {
+            flows.entry(item.account_id).or_default().push((ts, item.amount));
+        }
+    }
+    
+    let loans = {
+        let mut stmt = conn.prepare(
+            "SELECT id, account_id, name, principal, annual_rate, term_months, start_ts, monthly_payment, created_at, funding_operation_id
+             FROM loans ORDER BY id ASC"
+        )?;
+        let rows = stmt.query_map([], loan_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    for loan in loans {
+        let from_account_id: Option<i64> = conn.query_row(
+            "SELECT from_account_id FROM loan_payments WHERE loan_id = ?1 ORDER BY payment_number DESC LIMIT 1",
+            [loan.id],
+            |row| row.get(0),
+        ).optional()?;
+        let Some(from_account_id) = from_account_id else {
+            continue;
+        };
+        
+        let (outstanding, next_payment_number) = loan_outstanding(conn, &loan)?;
+        for row in build_amortization(&loan, outstanding, next_payment_number, 0.0)? {
+            if row.due_ts > until {
+                break;
+            }
+            if row.due_ts > after {
+                flows.entry(from_account_id).or_default().push((row.due_ts, -row.payment));
+            }
+        }
+    }
+    
+    for account_flows in flows.values_mut() {
+        account_flows.sort_by_key(|(ts, _)| *ts);
+    }
+    
+    Ok(flows)
+}
+
+/// Среднее и стандартное отклонение дневного нерегулярного потока счёта
+/// 
+/// Из истории за `FORECAST_LOOKBACK_DAYS` дней исключаются переводы,
+/// сделки с бумагами, платежи по кредитам и повторения регулярных элементов.
+fn fit_daily_trend(
+    conn: &Connection,
+    account_id: i64,
+    items: &[RecurringItem],
+    now: i64,
+) -> Result<(f64, f64), DbError> {
+    let first_ts: Option<i64> = conn.query_row(
+        "SELECT MIN(ts) FROM operations WHERE account_id = ?1",
+        [account_id],
+        |row| row.get(0),
+    )?;
+    let Some(first_ts) = first_ts else {
+        return Ok((0.0, 0.0));
+    };
+    
+    let start = first_ts.max(now - FORECAST_LOOKBACK_DAYS * SECONDS_PER_DAY);
+    let days = ((now - start + SECONDS_PER_DAY - 1) / SECONDS_PER_DAY).max(1);
+    
+    let operations = {
+        let mut stmt = conn.prepare(
+            "SELECT o.ts, o.amount, o.payee_id, o.description FROM operations o
+             WHERE o.account_id = ?1 AND o.ts >= ?2 AND o.ts < ?3
+               AND o.id NOT IN (SELECT from_operation_id FROM transfers UNION SELECT to_operation_id FROM transfers)
+               AND o.id NOT IN (SELECT operation_id FROM investment_transactions)
+               AND o.id NOT IN (SELECT principal_operation_id FROM loan_payments
+                                UNION SELECT interest_operation_id FROM loan_payments WHERE interest_operation_id IS NOT NULL)"
+        )?;
+        let rows = stmt.query_map([account_id, start, now], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, f64>(1)?,
+                row.get::<_, Option<i64>>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    let recurring: Vec<(Option<i64>, String)> = items
+        .iter()
+        .filter(|item| item.account_id == account_id)
+        .map(|item| (item.payee_id, normalize_description(&item.description)))
+        .collect();
+    
+    let mut daily = vec![0.0; days as usize];
+    for (ts, amount, payee_id, description) in operations {
+        let normalized = normalize_description(&description);
+        let is_recurring = recurring.iter().any(|(item_payee_id, item_description)| {
+            (item_payee_id.is_some() && *item_payee_id == payee_id) || *item_description == normalized
+        });
+        if is_recurring {
+            continue;
+        }
+        
+        let day = (((ts - start) / SECONDS_PER_DAY) as usize).min(daily.len() - 1);
+        daily[day] += amount;
+    }
+    
+    // Среднее - по точной длительности окна, разброс - по дневным суммам
+    let elapsed_days = ((now - start) as f64 / SECONDS_PER_DAY as f64).max(1.0);
+    let mean = daily.iter().sum::<f64>() / elapsed_days;
+    let volatility = if daily.len() > 1 {
+        let daily_mean = daily.iter().sum::<f64>() / daily.len() as f64;
+        (daily.iter().map(|x| (x - daily_mean).powi(2)).sum::<f64>() / (daily.len() - 1) as f64).sqrt()
+    } else {
+        0.0
+    };
+    
+    Ok((mean, volatility))
+}
+
+/// Прогноз балансов счетов класса asset на `horizon_days` дней вперёд
+/// 
+/// Ожидаемый баланс = текущий баланс + известные будущие движения
+/// (регулярные элементы и платежи по кредитам) + линейный тренд
+/// нерегулярного потока. Доверительный интервал расширяется как
+/// `FORECAST_BAND_Z * σ * √дней` (случайное блуждание дневных потоков).
+/// 
+/// # Параметры
+/// - `path` - путь к базе данных
+/// - `key` - ключ шифрования
+/// - `horizon_days` - горизонт прогноза в днях (точки - ежедневно)
+pub fn forecast_balances(path: &str, key: &str, horizon_days: i64) -> Result<Vec<AccountForecast>, DbError> {
+    if !(1..=FORECAST_MAX_HORIZON_DAYS).contains(&horizon_days) {
+        return Err(DbError::ValidationError(format!(
+            "Forecast horizon must be between 1 and {} days",
+            FORECAST_MAX_HORIZON_DAYS
+        )));
+    }
+    
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let now = current_timestamp()?;
+    let until = now + horizon_days * SECONDS_PER_DAY;
+    let items = load_recurring_items(&conn)?;
+    let mut scheduled = scheduled_flows(&conn, &items, now, until)?;
+    
+    let account_ids = {
+        let mut stmt = conn.prepare("SELECT id FROM accounts WHERE class = 'asset' ORDER BY id ASC")?;
+        let rows = stmt.query_map([], |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    let mut forecasts = Vec::with_capacity(account_ids.len());
+    for account_id in account_ids {
+        let current_balance = get_balance_at(&conn, account_id, now)?;
+        let (daily_trend, daily_volatility) = fit_daily_trend(&conn, account_id, &items, now)?;
+        let flows = scheduled.remove(&account_id).unwrap_or_default();
+        
+        let next_income_ts = flows.iter().find(|(_, amount)| *amount > 0.0).map(|(ts, _)| *ts);
+        
+        let mut pending = flows.into_iter().peekable();
+        let mut scheduled_total = 0.0;
+        let mut points = Vec::with_capacity(horizon_days as usize);
+        for day in 1..=horizon_days {
+            let ts = now + day * SECONDS_PER_DAY;
+            while let Some((_, amount)) = pending.next_if(|(flow_ts, _)| *flow_ts <= ts) {
+                scheduled_total += amount;
+            }
+            
+            let expected = current_balance + scheduled_total + daily_trend * day as f64;
+            let band = FORECAST_BAND_Z * daily_volatility * (day as f64).sqrt();
+            points.push(ForecastPoint {
+                ts,
+                expected: round_cents(expected),
+                lower: round_cents(expected - band),
+                upper: round_cents(expected + band),
+            });
+        }
+        
+        let first_negative_ts = points.iter().find(|point| point.expected < 0.0).map(|point| point.ts);
+        let negative_before_income = match (first_negative_ts, next_income_ts) {
+            (Some(negative_ts), Some(income_ts)) => negative_ts < income_ts,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        
+        forecasts.push(AccountForecast {
+            account_id,
+            current_balance,
+            daily_trend,
+            daily_volatility,
+            points,
+            first_negative_ts,
+            next_income_ts,
+            negative_before_income,
+        });
+    }
+    
+    Ok(forecasts)
+}
+
+// Функции обнаружения аномалий в расходах
+
+/// Медиана (срез сортируется на месте)
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+/// Робастная z-оценка значения относительно истории (медиана и MAD)
+/// 
+/// Масштаб не меньше 10% медианы, чтобы одинаковые суммы в истории
+/// (подписки) не давали бесконечную оценку на копеечные отличия.
+fn robust_score(value: f64, history: &[f64]) -> f64 {
+    let mut values = history.to_vec();
+    let center = median(&mut values);
+    let mut deviations: Vec<f64> = history.iter().map(|x| (x - center).abs()).collect();
+    let scale = (median(&mut deviations) * 1.4826).max(center.abs() * 0.1).max(AMOUNT_EPSILON);
+    
+    (value - center).abs() / scale
+}
+
+/// Расход по операции: списание со счёта-актива или начисление долга
+/// на счёте-обязательстве. Переводы, сделки с бумагами и погашение
+/// тела кредита не являются расходами и не анализируются.
+fn load_spending_operations(conn: &Connection) -> SqlResult<Vec<(Operation, f64)>> {
+    let mut stmt = conn.prepare(
+        "SELECT o.id, o.account_id, o.amount, o.description, o.ts, o.payee_id, o.category_id, o.notes, a.class
+         FROM operations o
+         INNER JOIN accounts a ON a.id = o.account_id
+         WHERE ((a.class = 'asset' AND o.amount < 0) OR (a.class = 'liability' AND o.amount > 0))
+           AND o.id NOT IN (SELECT from_operation_id FROM transfers UNION SELECT to_operation_id FROM transfers)
+           AND o.id NOT IN (SELECT operation_id FROM investment_transactions)
+           AND o.id NOT IN (SELECT principal_operation_id FROM loan_payments
+                            UNION SELECT loan_operation_id FROM loan_payments)
+         ORDER BY o.ts ASC, o.id ASC"
+    )?;
+    
+    let operations = stmt.query_map([], |row| {
+        let operation = operation_from_row(row)?;
+        let spend = operation.amount.abs();
+        Ok((operation, spend))
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(operations)
+}
+
+/// Поиск аномалий в расходах
+/// 
+/// Виды аномалий:
+/// - `outlier_amount` - сумма далеко за типичным диапазоном контрагента
+///   или категории (робастная z-оценка по предыдущим операциям группы
+///   больше `ANOMALY_SCORE_THRESHOLD`, нужно не меньше `ANOMALY_MIN_HISTORY` операций);
+///   разбитая операция сравнивается с категориями по строкам разбивки
+/// - `duplicate` - такая же сумма у того же контрагента (или с тем же
+///   нормализованным описанием) по тому же счёту в пределах окна; помечается
+///   более поздняя операция
+/// - `new_payee_large` - первая операция контрагента с крупной суммой
+/// 
+/// Аномалии со статусом dismissed не возвращаются без `include_dismissed`.
+/// Порядок - от новых операций к старым.
+pub fn list_anomalies(path: &str, key: &str, options: AnomalyOptions) -> Result<Vec<Anomaly>, DbError> {
+    let window_days = options.duplicate_window_days.unwrap_or(ANOMALY_DUPLICATE_WINDOW_DAYS);
+    if window_days < 0 {
+        return Err(DbError::ValidationError("Duplicate window must not be negative".to_string()));
+    }
+    
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let operations = load_spending_operations(&conn)?;
+    
+    // Строки разбивки: разбитая операция относится к категориям по ним
+    let mut splits: std::collections::HashMap<i64, Vec<(Option<i64>, f64)>> = std::collections::HashMap::new();
+    {
+        let mut stmt = conn.prepare("SELECT operation_id, category_id, amount FROM operation_splits ORDER BY id ASC")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Option<i64>>(1)?, row.get::<_, f64>(2)?)))?;
+        for row in rows {
+            let (operation_id, category_id, amount) = row?;
+            splits.entry(operation_id).or_default().push((category_id, amount.abs()));
+        }
+    }
+    
+    let states: std::collections::HashMap<(i64, String), String> = {
+        let mut stmt = conn.prepare("SELECT operation_id, kind, status FROM anomaly_states")?;
+        let rows = stmt.query_map([], |row| Ok(((row.get(0)?, row.get(1)?), row.get(2)?)))?
+            .collect::<Result<_, _>>()?;
+        rows
+    };
+    
+    let large_amount = match options.large_amount {
+        Some(amount) => Some(amount),
+        None if operations.len() >= ANOMALY_MIN_HISTORY => {
+            let mut amounts: Vec<f64> = operations.iter().map(|(_, spend)| *spend).collect();
+            amounts.sort_by(|a, b| a.total_cmp(b));
+            Some(amounts[((amounts.len() - 1) as f64 * 0.9).round() as usize])
+        }
+        None => None,
+    };
+    
+    let in_period = |operation: &Operation| {
+        options.from_ts.is_none_or(|from| operation.ts >= from) && options.to_ts.is_none_or(|to| operation.ts < to)
+    };
+    
+    // Ключ контрагента: payee_id или нормализованное описание
+    let payee_key = |operation: &Operation| match operation.payee_id {
+        Some(payee_id) => format!("payee:{}", payee_id),
+        None => format!("text:{}", normalize_description(&operation.description)),
+    };
+    
+    let mut found: Vec<(usize, String, f64, Option<i64>, String)> = Vec::new();
+    let mut seen_payees: std::collections::HashSet<String> = std::collections::HashSet::new();
+    let mut payee_history: std::collections::HashMap<i64, Vec<f64>> = std::collections::HashMap::new();
+    let mut category_history: std::collections::HashMap<i64, Vec<f64>> = std::collections::HashMap::new();
+    
+    for (index, (operation, spend)) in operations.iter().enumerate() {
+        let first_for_payee = seen_payees.insert(payee_key(operation));
+        
+        let category_lines: Vec<(i64, f64)> = match splits.get(&operation.id) {
+            Some(lines) => lines.iter().filter_map(|(category_id, amount)| category_id.map(|id| (id, *amount))).collect(),
+            None => operation.category_id.map(|id| (id, *spend)).into_iter().collect(),
+        };
+        
+        if in_period(operation) {
+            // Выброс по контрагенту или категории
+            let payee_group = operation.payee_id
+                .and_then(|id| payee_history.get(&id))
+                .map(|history| ("payee", *spend, history));
+            let category_groups = category_lines
+                .iter()
+                .filter_map(|(id, amount)| category_history.get(id).map(|history| ("category", *amount, history)));
+            let outlier = payee_group
+                .into_iter()
+                .chain(category_groups)
+                .filter(|(_, _, history)| history.len() >= ANOMALY_MIN_HISTORY)
+                .map(|(group, amount, history)| (group, amount, robust_score(amount, history), history.len()))
+                .filter(|(_, _, score, _)| *score > ANOMALY_SCORE_THRESHOLD)
+                .max_by(|a, b| a.2.total_cmp(&b.2));
+            if let Some((group, amount, score, samples)) = outlier {
+                found.push((index, "outlier_amount".to_string(), score, None, format!(
+                    "Amount {:.2} is far outside the typical {} range ({} previous operations)",
+                    amount, group, samples
+                )));
+            }
+            
+            // Дубликат: более ранняя такая же операция в пределах окна
+            let duplicate = operations[..index].iter().rev()
+                .take_while(|(earlier, _)| operation.ts - earlier.ts <= window_days * SECONDS_PER_DAY)
+                .find(|(earlier, _)| {
+                    earlier.account_id == operation.account_id
+                        && (earlier.amount - operation.amount).abs() < AMOUNT_EPSILON
+                        && payee_key(earlier) == payee_key(operation)
+                });
+            if let Some((earlier, _)) = duplicate {
+                let days = (operation.ts - earlier.ts) as f64 / SECONDS_PER_DAY as f64;
+                found.push((index, "duplicate".to_string(), days, Some(earlier.id), format!(
+                    "Same amount {:.2} and payee as operation {} {:.1} days earlier",
+                    spend, earlier.id, days
+                )));
+            }
+            
+            // Крупная сумма у нового контрагента
+            if let Some(threshold) = large_amount.filter(|threshold| *threshold > 0.0) {
+                if first_for_payee && *spend >= threshold {
+                    found.push((index, "new_payee_large".to_string(), spend / threshold, None, format!(
+                        "First operation with this payee is large: {:.2} (threshold {:.2})",
+                        spend, threshold
+                    )));
+                }
+            }
+        }
+        
+        if let Some(payee_id) = operation.payee_id {
+            payee_history.entry(payee_id).or_default().push(*spend);
+        }
+        for (category_id, amount) in category_lines {
+            category_history.entry(category_id).or_default().push(amount);
+        }
+    }
+    
+    let mut anomalies = Vec::new();
+    for (index, kind, score, related_operation_id, reason) in found.into_iter().rev() {
+        let operation = &operations[index].0;
+        let status = states
+            .get(&(operation.id, kind.clone()))
+            .cloned()
+            .unwrap_or_else(|| "open".to_string());
+        if status == "dismissed" && !options.include_dismissed {
+            continue;
+        }
+        
+        anomalies.push(Anomaly {
+            operation: load_operation(&conn, operation.id)?,
+            kind,
+            score,
+            related_operation_id,
+            reason,
+            status,
+        });
+    }
+    
+    Ok(anomalies)
+}
+
+/// Установка статуса аномалии (open, acknowledged, dismissed)
+/// 
+/// Решение хранится в зашифрованной БД и логируется; статус open
+/// удаляет сохранённое решение.
+pub fn set_anomaly_status(
+    path: &str,
+    key: &str,
+    operation_id: i64,
+    kind: String,
+    status: String,
+) -> Result<(), DbError> {
+    if !ANOMALY_KINDS.contains(&kind.as_str()) {
+        return Err(DbError::ValidationError(format!("Unknown anomaly kind: {}", kind)));
+    }
+    
+    if !ANOMALY_STATUSES.contains(&status.as_str()) {
+        return Err(DbError::ValidationError(format!("Unknown anomaly status: {}", status)));
+    }
+    
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    load_operation(&tx, operation_id)?;
+    
+    let existing: Option<i64> = tx.query_row(
+        "SELECT id FROM anomaly_states WHERE operation_id = ?1 AND kind = ?2",
+        rusqlite::params![operation_id, kind],
+        |row| row.get(0),
+    ).optional()?;
+    
+    let updated_at = current_timestamp()?;
+    let (id, action) = match (existing, status.as_str()) {
+        (None, "open") => {
+            tx.commit()?;
+            return Ok(());
+        }
+        (Some(id), "open") => {
+            tx.execute("DELETE FROM anomaly_states WHERE id = ?1", [id])?;
+            (id, "delete")
+        }
+        (Some(id), _) => {
+            tx.execute(
+                "UPDATE anomaly_states SET status = ?1, updated_at = ?2 WHERE id = ?3",
+                rusqlite::params![status, updated_at, id],
+            )?;
+            (id, "update")
+        }
+        (None, _) => {
+            tx.execute(
+                "INSERT INTO anomaly_states (operation_id, kind, status, updated_at) VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![operation_id, kind, status, updated_at],
+            )?;
+            (tx.last_insert_rowid(), "create")
+        }
+    };
+    
+    let state = AnomalyState { id, operation_id, kind, status, updated_at };
+    write_version_log(&tx, path, key, "anomaly_state", id, action, &serialize_entity(&state)?)?;
+    
+    tx.commit()?;
+    
+    Ok(())
+}
+
+// Функции агрегирования
+
+/// Получение текущего баланса аккаунта
+/// 
+/// Возвращает последнее значение баланса из таблицы states
+/// Если записей нет, возвращает 0.0
+/// 
+/// # Параметры
+/// - `path` - путь к базе данных
+/// - `key` - ключ шифрования
+/// - `account_id` - ID аккаунта
+pub fn get_account_balance(path: &str, key: &str, account_id: i64) -> Result<f64, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let balance: Result<f64, _> = conn.query_row(
+        "SELECT balance FROM states WHERE account_id = ?1 ORDER BY ts DESC LIMIT 1",
+        [account_id],
+        |row| row.get(0),
+    );
+    
+    // Если баланса нет, возвращаем 0.0
+    Ok(balance.unwrap_or(0.0))
+}
+
+/// Расчёт активов, обязательств и Net Worth по текущим балансам
+/// 
+/// Баланс счёта-обязательства хранится как положительная сумма долга.
+/// Счета классов equity, income и expense в Net Worth не участвуют.
+fn compute_net_worth(conn: &Connection) -> Result<NetWorth, DbError> {
+    let mut stmt = conn.prepare(
+        "SELECT a.class, COALESCE(SUM(latest_balances.balance), 0.0)
+         FROM accounts a
+         INNER JOIN (
+             SELECT DISTINCT account_id,
+                    (SELECT balance FROM states s2 
+                     WHERE s2.account_id = s1.account_id 
+                     ORDER BY ts DESC LIMIT 1) as balance
+             FROM states s1
+         ) latest_balances ON latest_balances.account_id = a.id
+         GROUP BY a.class"
+    )?;
+    
+    let totals = stmt.query_map([], |row| Ok((row.get::<_, AccountClass>(0)?, row.get::<_, f64>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    let mut assets = 0.0;
+    let mut liabilities = 0.0;
+    for (acc_class, total) in totals {
+        match acc_class {
+            AccountClass::Asset => assets += total,
+            AccountClass::Liability => liabilities += total,
+            _ => {}
+        }
+    }
+    
+    // Рыночная стоимость позиций по ценным бумагам относится к активам
+    assets += load_holdings_at(conn, None, current_timestamp()?)?
+        .iter()
+        .map(|holding| holding.market_value)
+        .sum::<f64>();
+    
+    Ok(NetWorth {
+        assets,
+        liabilities,
+        net_worth: assets - liabilities,
+    })
+}
+
+/// Вычисление общего Net Worth
+/// 
+/// Возвращает разницу между активами и обязательствами
+/// Для каждого аккаунта берётся последняя запись из states,
+/// к активам добавляется рыночная стоимость позиций по ценным бумагам
+/// 
+/// # Параметры
+/// - `path` - путь к базе данных
+/// - `key` - ключ шифрования
+pub fn get_net_worth(path: &str, key: &str) -> Result<f64, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    Ok(compute_net_worth(&conn)?.net_worth)
+}
+
+/// Получение Net Worth с отдельными суммами активов и обязательств
+/// 
+/// # Параметры
+/// - `path` - путь к базе данных
+/// - `key` - ключ шифрования
+pub fn get_net_worth_breakdown(path: &str, key: &str) -> Result<NetWorth, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    compute_net_worth(&conn)
+}
+
+/// Локальная дата момента времени
+fn local_date(ts: i64) -> Result<chrono::NaiveDate, DbError> {
+    use chrono::TimeZone;
+    
+    chrono::Local
+        .timestamp_opt(ts, 0)
+        .earliest()
+        .map(|dt| dt.date_naive())
+        .ok_or_else(|| DbError::ValidationError(format!("Invalid timestamp: {}", ts)))
+}
+
+/// Момент начала локальных суток
+/// 
+/// При переходе на летнее время полночь может отсутствовать - тогда берётся
+/// первый существующий момент этих суток.
+fn local_day_start(date: chrono::NaiveDate) -> Result<i64, DbError> {
+    use chrono::TimeZone;
+    
+    (0..24)
+        .find_map(|hour| {
+            chrono::Local
+                .from_local_datetime(&date.and_hms_opt(hour, 0, 0)?)
+                .earliest()
+        })
+        .map(|dt| dt.timestamp())
+        .ok_or_else(|| DbError::ValidationError(format!("Invalid date: {}", date)))
+}
+
+/// Регулярная сетка границ периодов, покрывающая [from, to)
+/// 
+/// Первая граница - начало периода, содержащего `from` (локальные сутки,
+/// неделя с понедельника, календарный месяц или квартал); последняя - первая граница >= `to`.
+fn history_grid(from: i64, to: i64, interval: HistoryInterval) -> Result<Vec<i64>, DbError> {
+    use chrono::Datelike;
+    
+    if from >= to {
+        return Err(DbError::ValidationError("Period start must be before its end".to_string()));
+    }
+    
+    let start = local_date(from)?;
+    let mut date = match interval {
+        HistoryInterval::Day => start,
+        HistoryInterval::Week => start - chrono::Duration::days(start.weekday().num_days_from_monday() as i64),
+        HistoryInterval::Month => start.with_day(1).unwrap_or(start),
+        HistoryInterval::Quarter => chrono::NaiveDate::from_ymd_opt(start.year(), (start.month0() / 3) * 3 + 1, 1)
+            .unwrap_or(start),
+    };
+    
+    let mut grid = vec![local_day_start(date)?];
+    while *grid.last().unwrap_or(&to) < to {
+        if grid.len() > HISTORY_MAX_BUCKETS {
+            return Err(DbError::ValidationError(format!(
+                "Period is too long for the interval (more than {} points)",
+                HISTORY_MAX_BUCKETS
+            )));
+        }
+        
+        date = match interval {
+            HistoryInterval::Day => date + chrono::Duration::days(1),
+            HistoryInterval::Week => date + chrono::Duration::days(7),
+            HistoryInterval::Month | HistoryInterval::Quarter => {
+                let months = if interval == HistoryInterval::Month { 1 } else { 3 };
+                date.checked_add_months(chrono::Months::new(months))
+                    .ok_or_else(|| DbError::ValidationError(format!("Invalid date: {}", date)))?
+            }
+        };
+        grid.push(local_day_start(date)?);
+    }
+    
+    Ok(grid)
+}
+
+/// История Net Worth на регулярной сетке
+/// 
+/// Для каждого периода берётся последний известный баланс каждого счёта
+/// на конец периода (баланс переносится вперёд, если операций не было),
+/// балансы суммируются по классам asset и liability. К активам добавляется
+/// рыночная стоимость позиций по ценам на конец периода.
+/// 
+/// # Параметры
+/// - `path` - путь к базе данных
+/// - `key` - ключ шифрования
+/// - `from`, `to` - период [from, to)
+/// - `interval` - шаг сетки (day, week, month, quarter)
+pub fn get_net_worth_history(
+    path: &str,
+    key: &str,
+    from: i64,
+    to: i64,
+    interval: HistoryInterval,
+) -> Result<Vec<NetWorthPoint>, DbError> {
+    let grid = history_grid(from, to, interval)?;
+    
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let states = {
+        let mut stmt = conn.prepare(
+            "SELECT s.account_id, a.class, s.balance, s.ts
+             FROM states s
+             INNER JOIN accounts a ON a.id = s.account_id
+             WHERE a.class IN ('asset', 'liability') AND s.ts < ?1
+             ORDER BY s.ts ASC, s.id ASC"
+        )?;
+        let rows = stmt.query_map([to], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, AccountClass>(1)?,
+                row.get::<_, f64>(2)?,
+                row.get::<_, i64>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    let mut latest: std::collections::HashMap<i64, (AccountClass, f64)> = std::collections::HashMap::new();
+    let mut pending = states.into_iter().peekable();
+    let mut points = Vec::with_capacity(grid.len() - 1);
+    
+    for bounds in grid.windows(2) {
+        let as_of = bounds[1].min(to) - 1;
+        while let Some((account_id, acc_class, balance, _)) = pending.next_if(|state| state.3 <= as_of) {
+            latest.insert(account_id, (acc_class, balance));
+        }
+        
+        let mut assets = 0.0;
+        let mut liabilities = 0.0;
+        for (acc_class, balance) in latest.values() {
+            match acc_class {
+                AccountClass::Asset => assets += balance,
+                AccountClass::Liability => liabilities += balance,
+                _ => {}
+            }
+        }
+        
+        assets += load_holdings_at(&conn, None, as_of)?
+            .iter()
+            .map(|holding| holding.market_value)
+            .sum::<f64>();
+        
+        points.push(NetWorthPoint {
+            ts: bounds[0],
+            assets,
+            liabilities,
+            net_worth: assets - liabilities,
+        });
+    }
+    
+    Ok(points)
+}
+
+/// Получение временного ряда балансов для аккаунта
+/// 
+/// Возвращает все записи из таблицы states для указанного аккаунта
+/// с сортировкой по временной метке (ts) в порядке возрастания
+/// 
+/// # Параметры
+/// - `path` - путь к базе данных
+/// - `key` - ключ шифрования
+/// - `account_id` - ID аккаунта
+pub fn get_balance_history(
+    path: &str,
+    key: &str,
+    account_id: i64,
+) -> Result<Vec<State>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, account_id, balance, ts FROM states 
+         WHERE account_id = ?1 
+         ORDER BY ts ASC"
+    )?;
+    
+    let states = stmt.query_map([account_id], |row| {
+        Ok(State {
+            id: row.get(0)?,
+            account_id: row.get(1)?,
+            balance: row.get(2)?,
+            ts: row.get(3)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(states)
+}
+
+/// Ресемплированная история баланса счёта
+/// 
+/// Снимки states раскладываются по периодам регулярной сетки с границами
+/// по локальным датам. Баланс до первого снимка считается нулевым, в периодах
+/// без снимков значение переносится из предыдущего. Среднее взвешивается
+/// по времени действия каждого баланса внутри периода, обрезанного до [from, to).
+/// 
+/// # Параметры
+/// - `path` - путь к базе данных
+/// - `key` - ключ шифрования
+/// - `account_id` - ID аккаунта
+/// - `from`, `to` - период [from, to)
+/// - `interval` - шаг сетки (day, week, month, quarter)
+/// - `aggregation` - агрегация внутри периода (last, ohlc, average)
+pub fn get_balance_history_resampled(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    from: i64,
+    to: i64,
+    interval: HistoryInterval,
+    aggregation: BalanceAggregation,
+) -> Result<Vec<BalanceBucket>, DbError> {
+    let grid = history_grid(from, to, interval)?;
+    
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    ensure_account_exists(&conn, account_id)?;
+    
+    let states = {
+        let mut stmt = conn.prepare(
+            "SELECT balance, ts FROM states
+             WHERE account_id = ?1 AND ts >= ?2 AND ts < ?3
+             ORDER BY ts ASC, id ASC"
+        )?;
+        let rows = stmt.query_map([account_id, grid[0], to], |row| Ok((row.get::<_, f64>(0)?, row.get::<_, i64>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    let mut balance = get_balance_at(&conn, account_id, grid[0] - 1)?;
+    let mut pending = states.into_iter().peekable();
+    let mut buckets = Vec::with_capacity(grid.len() - 1);
+    
+    for bounds in grid.windows(2) {
+        let (start, end) = (bounds[0], bounds[1].min(to));
+        let open = balance;
+        let (mut high, mut low) = (open, open);
+        let mut filled = true;
+        
+        // Время действия баланса внутри [max(start, from), end)
+        let weight_from = start.max(from);
+        let mut weighted_sum = 0.0;
+        let mut segment_start = weight_from;
+        
+        while let Some((next_balance, ts)) = pending.next_if(|state| state.1 < end) {
+            let ts = ts.max(weight_from);
+            weighted_sum += balance * (ts - segment_start) as f64;
+            segment_start = ts;
+            
+            balance = next_balance;
+            high = high.max(balance);
+            low = low.min(balance);
+            filled = false;
+        }
+        weighted_sum += balance * (end - segment_start) as f64;
+        
+        let (value, ohlc) = match aggregation {
+            BalanceAggregation::Last => (balance, None),
+            BalanceAggregation::Ohlc => (balance, Some((open, high, low, balance))),
+            BalanceAggregation::Average => {
+                let duration = end - weight_from;
+                let average = if duration > 0 { weighted_sum / duration as f64 } else { balance };
+                (average, None)
+            }
+        };
+        
+        buckets.push(BalanceBucket {
+            ts: start,
+            value,
+            open: ohlc.map(|(open, _, _, _)| open),
+            high: ohlc.map(|(_, high, _, _)| high),
+            low: ohlc.map(|(_, _, low, _)| low),
+            close: ohlc.map(|(_, _, _, close)| close),
+            filled,
+        });
+    }
+    
+    Ok(buckets)
+}
+
+/// Получение структуры активов (группировка по типам с агрегированием балансов)
+/// 
+/// Возвращает распределение активов по типам аккаунтов класса asset
+/// Для каждого типа вычисляется:
+/// - Общая сумма балансов всех аккаунтов этого типа
+/// - Количество аккаунтов
+/// 
+/// Позиции по ценным бумагам добавляются по классам активов (stock, bond, ...)
+/// по рыночной стоимости
+/// 
+/// # Параметры
+/// - `path` - путь к базе данных
+/// - `key` - ключ шифрования
+pub fn get_asset_allocation(
+    path: &str,
+    key: &str,
+) -> Result<Vec<AssetAllocation>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    compute_asset_allocation(&conn)
+}
+
+/// Структура активов по типам счетов и классам бумаг (по убыванию суммы)
+fn compute_asset_allocation(conn: &Connection) -> Result<Vec<AssetAllocation>, DbError> {
+    // SQL запрос с группировкой по типам и суммированием балансов
+    // Включаем только аккаунты, у которых есть хотя бы одна запись в states
+    let mut stmt = conn.prepare(
+        "SELECT 
+            a.type,
+            SUM(latest_balances.balance) as total_balance,
+            COUNT(a.id) as account_count
+         FROM accounts a
+         INNER JOIN (
+             SELECT DISTINCT account_id,
+                    (SELECT balance FROM states s2 
+                     WHERE s2.account_id = s1.account_id 
+                     ORDER BY ts DESC LIMIT 1) as balance
+             FROM states s1
+         ) latest_balances ON latest_balances.account_id = a.id
+         WHERE a.class = 'asset'
+         GROUP BY a.type
+         ORDER BY total_balance DESC"
+    )?;
+    
+    let mut allocations = stmt.query_map([], |row| {
+        Ok(AssetAllocation {
+            asset_type: row.get(0)?,
+            total_balance: row.get(1)?,
+            account_count: row.get(2)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    // Группируем позиции по ценным бумагам по классам активов
+    let mut by_class: std::collections::BTreeMap<String, (f64, std::collections::HashSet<i64>)> =
+        std::collections::BTreeMap::new();
+    for holding in load_holdings_at(conn, None, current_timestamp()?)? {
+        let entry = by_class.entry(holding.asset_class).or_default();
+        entry.0 += holding.market_value;
+        entry.1.insert(holding.account_id);
+    }
+    
+    for (asset_class, (total_value, accounts)) in by_class {
+        match allocations.iter_mut().find(|a| a.asset_type == asset_class) {
+            Some(allocation) => {
+                allocation.total_balance += total_value;
+                allocation.account_count += accounts.len() as i64;
+            }
+            None => allocations.push(AssetAllocation {
+                asset_type: asset_class,
+                total_balance: total_value,
+                account_count: accounts.len() as i64,
+            }),
+        }
+    }
+    
+    allocations.sort_by(|a, b| b.total_balance.total_cmp(&a.total_balance));
+    
+    Ok(allocations)
+}
+
+// Функции переводов и денежного потока
+
+/// Знак операции перевода на счёте
+/// 
+/// Обязательства хранятся как положительный долг: поступление на счёт
+/// обязательства уменьшает долг, списание с него - увеличивает.
+fn transfer_leg_amount(account: &Account, amount: f64, incoming: bool) -> Result<f64, DbError> {
+    match (account.acc_class, incoming) {
+        (AccountClass::Asset, true) | (AccountClass::Liability, false) => Ok(amount),
+        (AccountClass::Asset, false) | (AccountClass::Liability, true) => Ok(-amount),
+        _ => Err(DbError::ValidationError(format!(
+            "Account {} is not an asset or liability account",
+            account.id
+        ))),
+    }
+}
+
+fn transfer_from_row(row: &rusqlite::Row) -> SqlResult<Transfer> {
+    Ok(Transfer {
+        id: row.get(0)?,
+        from_account_id: row.get(1)?,
+        to_account_id: row.get(2)?,
+        from_operation_id: row.get(3)?,
+        to_operation_id: row.get(4)?,
+        amount: row.get(5)?,
+        description: row.get(6)?,
+        ts: row.get(7)?,
+    })
+}
+
+/// Перевод между собственными счетами
+/// 
+/// Проводит две операции (списание и зачисление) и связывает их записью
+/// в transfers; такие операции не считаются доходом или расходом
+/// в отчёте о денежном потоке.
+/// 
+/// # Параметры
+/// - `from_account_id`, `to_account_id` - счета классов asset или liability
+/// - `amount` - положительная сумма перевода
+/// - `ts` - момент перевода (если не указан - текущий)
+pub fn create_transfer(
+    path: &str,
+    key: &str,
+    from_account_id: i64,
+    to_account_id: i64,
+    amount: f64,
+    description: String,
+    ts: Option<i64>,
+) -> Result<i64, DbError> {
+    if amount <= 0.0 {
+        return Err(DbError::ValidationError("Transfer amount must be positive".to_string()));
+    }
+    
+    if from_account_id == to_account_id {
+        return Err(DbError::ValidationError("Transfer accounts must differ".to_string()));
+    }
+    
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let ts = match ts {
+        Some(ts) => ts,
+        None => current_timestamp()?,
+    };
+    
+    let transfer_id = insert_transfer(
+        &tx, path, key, TransferIds::default(), from_account_id, to_account_id, amount, &description, ts,
+    )?;
+    
+    tx.commit()?;
+    
+    Ok(transfer_id)
+}
+
+/// Идентификаторы перевода и его операций, сохраняемые при импорте
+/// (`None` - новый ID)
+#[derive(Default)]
+struct TransferIds {
+    transfer: Option<i64>,
+    from_operation: Option<i64>,
+    to_operation: Option<i64>,
+}
+
+/// Проведение перевода в рамках транзакции вызывающего
+#[allow(clippy::too_many_arguments)]
+fn insert_transfer(
+    conn: &Connection,
+    path: &str,
+    key: &str,
+    ids: TransferIds,
+    from_account_id: i64,
+    to_account_id: i64,
+    amount: f64,
+    description: &str,
+    ts: i64,
+) -> Result<i64, DbError> {
+    let from_account = load_account(conn, from_account_id)?;
+    let to_account = load_account(conn, to_account_id)?;
+    let from_amount = transfer_leg_amount(&from_account, amount, false)?;
+    let to_amount = transfer_leg_amount(&to_account, amount, true)?;
+    
+    let from_operation_id = insert_operation_with_id(
+        conn, path, key, ids.from_operation, from_account_id, from_amount, description, ts,
+    )?;
+    let to_operation_id = insert_operation_with_id(
+        conn, path, key, ids.to_operation, to_account_id, to_amount, description, ts,
+    )?;
+    
+    conn.execute(
+        "INSERT INTO transfers (id, from_account_id, to_account_id, from_operation_id, to_operation_id,
+                                amount, description, ts, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        rusqlite::params![
+            ids.transfer, from_account_id, to_account_id, from_operation_id, to_operation_id,
+            amount, description, ts, current_timestamp()?,
+        ],
+    )?;
+    
+    let transfer_id = conn.last_insert_rowid();
+    let transfer = conn.query_row(
+        "SELECT id, from_account_id, to_account_id, from_operation_id, to_operation_id, amount, description, ts
+         FROM transfers WHERE id = ?1",
+        [transfer_id],
+        transfer_from_row,
+    )?;
+    
+    write_version_log(conn, path, key, "transfer", transfer_id, "create", &serialize_entity(&transfer)?)?;
+    
+    Ok(transfer_id)
+}
+
+/// Получение переводов за период [from_ts, to_ts) по времени
+pub fn list_transfers(
+    path: &str,
+    key: &str,
+    from_ts: Option<i64>,
+    to_ts: Option<i64>,
+) -> Result<Vec<Transfer>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, from_account_id, to_account_id, from_operation_id, to_operation_id, amount, description, ts
+         FROM transfers
+         WHERE (?1 IS NULL OR ts >= ?1) AND (?2 IS NULL OR ts < ?2)
+         ORDER BY ts ASC, id ASC"
+    )?;
+    
+    let transfers = stmt.query_map([from_ts, to_ts], transfer_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(transfers)
+}
+
+/// Отчёт о денежном потоке: доходы, расходы и сальдо по периодам и группам
+/// 
+/// Учитываются операции по счетам класса asset (разбитые - по строкам разбивки).
+/// Внутренние перемещения не являются доходом или расходом и исключаются:
+/// переводы между своими счетами, получение и погашение тела кредита
+/// и денежные части сделок с бумагами; проценты по кредиту остаются расходом.
+/// 
+/// # Параметры
+/// - `path` - путь к базе данных
+/// - `key` - ключ шифрования
+/// - `from`, `to` - период [from, to)
+/// - `interval` - шаг периодов (day, week, month, quarter)
+/// - `group_by` - разрез (account, category, payee)
+pub fn get_cash_flow(
+    path: &str,
+    key: &str,
+    from: i64,
+    to: i64,
+    interval: HistoryInterval,
+    group_by: CashFlowGroupBy,
+) -> Result<Vec<CashFlow>, DbError> {
+    let grid = history_grid(from, to, interval)?;
+    
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let (group_id, group_name) = match group_by {
+        CashFlowGroupBy::Account => ("a.id", "a.name"),
+        CashFlowGroupBy::Category => ("lines.category_id", "c.name"),
+        CashFlowGroupBy::Payee => ("lines.payee_id", "p.name"),
+    };
+    
+    let sql = format!(
+        "SELECT lines.ts, {group_id}, {group_name}, lines.amount
+         FROM (
+             SELECT o.id, s.category_id, o.payee_id, s.amount, o.account_id, o.ts
+             FROM operation_splits s
+             INNER JOIN operations o ON o.id = s.operation_id
+             UNION ALL
+             SELECT o.id, o.category_id, o.payee_id, o.amount, o.account_id, o.ts
+             FROM operations o
+             WHERE NOT EXISTS (SELECT 1 FROM operation_splits s WHERE s.operation_id = o.id)
+         ) lines
+         INNER JOIN accounts a ON a.id = lines.account_id
+         LEFT JOIN categories c ON c.id = lines.category_id
+         LEFT JOIN payees p ON p.id = lines.payee_id
+         WHERE a.class = 'asset'
+           AND lines.ts >= ?1 AND lines.ts < ?2
+           AND lines.id NOT IN (SELECT from_operation_id FROM transfers UNION SELECT to_operation_id FROM transfers)
+           AND lines.id NOT IN (SELECT principal_operation_id FROM loan_payments)
+           AND lines.id NOT IN (SELECT funding_operation_id FROM loans WHERE funding_operation_id IS NOT NULL)
+           AND lines.id NOT IN (SELECT operation_id FROM investment_transactions)
+         ORDER BY lines.ts ASC",
+        group_id = group_id,
+        group_name = group_name,
+    );
+    
+    let lines = {
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map([from, to], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<i64>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, f64>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    
+    // Ключ: (индекс периода, группа)
+    let mut totals: std::collections::BTreeMap<(usize, Option<i64>), CashFlow> = std::collections::BTreeMap::new();
+    for (ts, group_id, group_name, amount) in lines {
+        let bucket = grid.partition_point(|&boundary| boundary <= ts) - 1;
+        let entry = totals.entry((bucket, group_id)).or_insert_with(|| CashFlow {
+            ts: grid[bucket],
+            group_id,
+            group_name,
+            income: 0.0,
+            expenses: 0.0,
+            net: 0.0,
+            line_count: 0,
+        });
+        
+        if amount >= 0.0 {
+            entry.income += amount;
+        } else {
+            entry.expenses -= amount;
+        }
+        entry.net += amount;
+        entry.line_count += 1;
+    }
+    
+    let mut cash_flow: Vec<CashFlow> = totals.into_values().collect();
+    for entry in &mut cash_flow {
+        entry.income = round_cents(entry.income);
+        entry.expenses = round_cents(entry.expenses);
+        entry.net = round_cents(entry.net);
+    }
+    
+    // Внутри периода - по убыванию оборота
+    cash_flow.sort_by(|a, b| {
+        a.ts.cmp(&b.ts).then_with(|| (b.income + b.expenses).total_cmp(&(a.income + a.expenses)))
+    });
+    
+    Ok(cash_flow)
+}
+
+// Функции целевой структуры активов и ребалансировки
+
+fn load_allocation_targets(conn: &Connection) -> SqlResult<Vec<AllocationTarget>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, asset_type, target_percent, tolerance_percent, updated_at
+         FROM allocation_targets ORDER BY target_percent DESC, asset_type ASC"
+    )?;
+    
+    let targets = stmt.query_map([], |row| {
+        Ok(AllocationTarget {
+            id: row.get(0)?,
+            asset_type: row.get(1)?,
+            target_percent: row.get(2)?,
+            tolerance_percent: row.get(3)?,
+            updated_at: row.get(4)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(targets)
+}
+
+/// Установка целевой доли группы активов
+/// 
+/// Сумма целевых долей всех групп не может превышать 100%.
+/// 
+/// # Параметры
+/// - `asset_type` - тип счёта или класс бумаг (как в `get_asset_allocation`)
+/// - `target_percent` - целевая доля, 0..100
+/// - `tolerance_percent` - допустимое отклонение в процентных пунктах
+pub fn set_allocation_target(
+    path: &str,
+    key: &str,
+    asset_type: String,
+    target_percent: f64,
+    tolerance_percent: f64,
+) -> Result<i64, DbError> {
+    let asset_type = asset_type.trim().to_string();
+    if asset_type.is_empty() {
+        return Err(DbError::ValidationError("Asset type must not be empty".to_string()));
+    }
+    
+    if !(0.0..=100.0).contains(&target_percent) {
+        return Err(DbError::ValidationError("Target percent must be between 0 and 100".to_string()));
+    }
+    
+    if !(0.0..=100.0).contains(&tolerance_percent) {
+        return Err(DbError::ValidationError("Tolerance percent must be between 0 and 100".to_string()));
+    }
+    
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let other_total: f64 = tx.query_row(
+        "SELECT COALESCE(SUM(target_percent), 0.0) FROM allocation_targets WHERE asset_type <> ?1",
+        [&asset_type],
+        |row| row.get(0),
     )?;
     
-    // Создаём индексы
+    if other_total + target_percent > 100.0 + AMOUNT_EPSILON {
+        return Err(DbError::ValidationError(format!(
+            "Target weights would sum to {:.2}% (more than 100%)",
+            other_total + target_percent
+        )));
+    }
+    
+    let existing: Option<i64> = tx.query_row(
+        "SELECT id FROM allocation_targets WHERE asset_type = ?1",
+        [&asset_type],
+        |row| row.get(0),
+    ).optional()?;
+    
+    let updated_at = current_timestamp()?;
+    let (id, action) = match existing {
+        Some(id) => {
+            tx.execute(
+                "UPDATE allocation_targets SET target_percent = ?1, tolerance_percent = ?2, updated_at = ?3 WHERE id = ?4",
+                rusqlite::params![target_percent, tolerance_percent, updated_at, id],
+            )?;
+            (id, "update")
+        }
+        None => {
+            tx.execute(
+                "INSERT INTO allocation_targets (asset_type, target_percent, tolerance_percent, updated_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![asset_type, target_percent, tolerance_percent, updated_at],
+            )?;
+            (tx.last_insert_rowid(), "create")
+        }
+    };
+    
+    let target = AllocationTarget {
+        id,
+        asset_type,
+        target_percent,
+        tolerance_percent,
+        updated_at,
+    };
+    
+    write_version_log(&tx, path, key, "allocation_target", id, action, &serialize_entity(&target)?)?;
+    
+    tx.commit()?;
+    
+    Ok(id)
+}
+
+/// Получение целевых долей (по убыванию доли)
+pub fn list_allocation_targets(path: &str, key: &str) -> Result<Vec<AllocationTarget>, DbError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    Ok(load_allocation_targets(&conn)?)
+}
+
+/// Удаление целевой доли группы активов
+pub fn delete_allocation_target(path: &str, key: &str, asset_type: String) -> Result<(), DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let target = load_allocation_targets(&tx)?
+        .into_iter()
+        .find(|target| target.asset_type == asset_type)
+        .ok_or_else(|| DbError::ValidationError(format!("Allocation target {} not found", asset_type)))?;
+    
+    tx.execute("DELETE FROM allocation_targets WHERE id = ?1", [target.id])?;
+    
+    write_version_log(&tx, path, key, "allocation_target", target.id, "delete", &serialize_entity(&target)?)?;
+    
+    tx.commit()?;
+    
+    Ok(())
+}
+
+/// Группа активов в расчёте ребалансировки: текущая стоимость, границы
+/// полосы и цель в деньгах (от итоговой стоимости портфеля)
+struct RebalanceSlot {
+    asset_type: String,
+    value: f64,
+    projected: f64,
+    target: Option<(f64, f64)>,
+    bounds: (f64, f64, f64),
+}
+
+/// Последовательное пополнение групп до уровней `levels` (индексы в `bounds`):
+/// сначала до нижней границы, затем до цели, затем до верхней границы;
+/// на каждом уровне первыми пополняются группы с наибольшим недобором.
+/// Возвращает нераспределённый остаток.
+fn rebalance_buy(slots: &mut [RebalanceSlot], mut budget: f64, levels: &[usize]) -> f64 {
+    for &level in levels {
+        let mut order: Vec<usize> = (0..slots.len()).filter(|&i| slots[i].target.is_some()).collect();
+        order.sort_by(|&a, &b| {
+            let gap = |slot: &RebalanceSlot| level_value(slot, level) - slot.projected;
+            gap(&slots[b]).total_cmp(&gap(&slots[a]))
+        });
+        
+        for i in order {
+            let room = level_value(&slots[i], level) - slots[i].projected;
+            if budget <= AMOUNT_EPSILON {
+                return 0.0;
+            }
+            if room > 0.0 {
+                let amount = room.min(budget);
+                slots[i].projected += amount;
+                budget -= amount;
+            }
+        }
+    }
+    
+    budget
+}
+
+/// Последовательное сокращение групп до уровней `levels` на сумму `amount`
+/// (первыми сокращаются группы с наибольшим перебором). Возвращает сумму продаж.
+fn rebalance_sell(slots: &mut [RebalanceSlot], amount: f64, levels: &[usize]) -> f64 {
+    let mut remaining = amount;
+    for &level in levels {
+        let mut order: Vec<usize> = (0..slots.len()).filter(|&i| slots[i].target.is_some()).collect();
+        order.sort_by(|&a, &b| {
+            let excess = |slot: &RebalanceSlot| slot.projected - level_value(slot, level);
+            excess(&slots[b]).total_cmp(&excess(&slots[a]))
+        });
+        
+        for i in order {
+            let excess = slots[i].projected - level_value(&slots[i], level);
+            if remaining <= AMOUNT_EPSILON {
+                return amount;
+            }
+            if excess > 0.0 {
+                let sold = excess.min(remaining);
+                slots[i].projected -= sold;
+                remaining -= sold;
+            }
+        }
+    }
+    
+    amount - remaining
+}
+
+/// Значение уровня полосы: 0 - нижняя граница, 1 - цель, 2 - верхняя граница
+fn level_value(slot: &RebalanceSlot, level: usize) -> f64 {
+    match level {
+        0 => slot.bounds.0,
+        1 => slot.bounds.1,
+        _ => slot.bounds.2,
+    }
+}
+
+/// План ребалансировки к целевой структуре активов
+/// 
+/// Минимизирует объём перемещений: группы вне полосы возвращаются только
+/// до ближайшей границы полосы, а не до цели. Высвободившиеся средства
+/// и взнос направляются в группы с наибольшим недобором (до нижней границы,
+/// затем до цели, затем до верхней границы). Если покупок не хватает,
+/// чтобы вернуть недобравшие группы в полосу, сокращаются группы выше цели.
+/// Продаётся не больше, чем могут принять покупки, поэтому каждой продаже
+/// соответствует перевод. В режиме `contributions_only` продажи запрещены
+/// и распределяется только взнос.
+/// 
+/// # Параметры
+/// - `path` - путь к базе данных
+/// - `key` - ключ шифрования
+/// - `contribution` - новый взнос (если не указан - 0)
+/// - `contributions_only` - не продавать, только распределить взнос
+pub fn get_rebalance_plan(
+    path: &str,
+    key: &str,
+    contribution: Option<f64>,
+    contributions_only: bool,
+) -> Result<RebalancePlan, DbError> {
+    let contribution = contribution.unwrap_or(0.0);
+    if contribution < 0.0 {
+        return Err(DbError::ValidationError("Contribution must not be negative".to_string()));
+    }
+    
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let targets = load_allocation_targets(&conn)?;
+    if targets.is_empty() {
+        return Err(DbError::ValidationError("No allocation targets are set".to_string()));
+    }
+    
+    let allocations = compute_asset_allocation(&conn)?;
+    let total_value: f64 = allocations.iter().map(|a| a.total_balance).sum();
+    let final_total = total_value + contribution;
+    if final_total <= AMOUNT_EPSILON {
+        return Err(DbError::ValidationError("Portfolio is empty".to_string()));
+    }
+    
+    let mut slots: Vec<RebalanceSlot> = allocations
+        .iter()
+        .map(|a| (a.asset_type.clone(), a.total_balance))
+        .chain(
+            targets
+                .iter()
+                .filter(|t| !allocations.iter().any(|a| a.asset_type == t.asset_type))
+                .map(|t| (t.asset_type.clone(), 0.0)),
+        )
+        .map(|(asset_type, value)| {
+            let target = targets
+                .iter()
+                .find(|t| t.asset_type == asset_type)
+                .map(|t| (t.target_percent, t.tolerance_percent));
+            let bounds = target.map_or((value, value, value), |(percent, tolerance)| {
+                (
+                    ((percent - tolerance) / 100.0 * final_total).max(0.0),
+                    percent / 100.0 * final_total,
+                    (percent + tolerance) / 100.0 * final_total,
+                )
+            });
+            RebalanceSlot { asset_type, value, projected: value, target, bounds }
+        })
+        .collect();
+    
+    let in_bands = |slots: &[RebalanceSlot], values: &dyn Fn(&RebalanceSlot) -> f64, total: f64| {
+        slots.iter().all(|slot| match slot.target {
+            Some((percent, tolerance)) => (values(slot) / total * 100.0 - percent).abs() <= tolerance + 1e-9,
+            None => true,
+        })
+    };
+    let within_bands_before = total_value > AMOUNT_EPSILON && in_bands(&slots, &|slot| slot.value, total_value);
+    
+    let unallocated = if contributions_only {
+        rebalance_buy(&mut slots, contribution, &[0, 1, 2])
+    } else {
+        // Группы выше полосы - до верхней границы
+        let mut pool = contribution;
+        for slot in slots.iter_mut().filter(|slot| slot.target.is_some()) {
+            if slot.projected > slot.bounds.2 {
+                pool += slot.projected - slot.bounds.2;
+                slot.projected = slot.bounds.2;
+            }
+        }
+        
+        let leftover = rebalance_buy(&mut slots, pool, &[0, 1, 2]);
+        
+        // Выручку, которую некуда направить (цели в сумме меньше 100%),
+        // не продаём: сокращённые группы остаются выше полосы
+        let mut unsold = (leftover - contribution).max(0.0);
+        for slot in slots.iter_mut() {
+            let trimmed = slot.value - slot.projected;
+            if unsold > AMOUNT_EPSILON && trimmed > 0.0 {
+                let restored = trimmed.min(unsold);
+                slot.projected += restored;
+                unsold -= restored;
+            }
+        }
+        
+        // Если группы ниже полосы не пополнены, сокращаем группы выше цели
+        let shortfall: f64 = slots
+            .iter()
+            .filter(|slot| slot.target.is_some())
+            .map(|slot| (slot.bounds.0 - slot.projected).max(0.0))
+            .sum();
+        if shortfall > AMOUNT_EPSILON {
+            let sold = rebalance_sell(&mut slots, shortfall, &[1, 0]);
+            rebalance_buy(&mut slots, sold, &[0]);
+        }
+        
+        leftover.min(contribution)
+    };
+    
+    let lines: Vec<RebalanceLine> = slots
+        .iter()
+        .map(|slot| {
+            let current_percent = if total_value > AMOUNT_EPSILON { slot.value / total_value * 100.0 } else { 0.0 };
+            RebalanceLine {
+                asset_type: slot.asset_type.clone(),
+                current_value: round_cents(slot.value),
+                current_percent,
+                target_percent: slot.target.map(|(percent, _)| percent),
+                tolerance_percent: slot.target.map(|(_, tolerance)| tolerance),
+                drift_percent: slot.target.map(|(percent, _)| current_percent - percent),
+                amount: round_cents(slot.projected - slot.value),
+                projected_value: round_cents(slot.projected),
+                projected_percent: slot.projected / final_total * 100.0,
+            }
+        })
+        .collect();
+    
+    // Сопоставление источников (взнос, продажи) с покупками
+    let mut sources: Vec<(Option<String>, f64)> = Vec::new();
+    if contribution - unallocated > AMOUNT_EPSILON {
+        sources.push((None, contribution - unallocated));
+    }
+    sources.extend(
+        lines.iter()
+            .filter(|line| line.amount < -AMOUNT_EPSILON)
+            .map(|line| (Some(line.asset_type.clone()), -line.amount)),
+    );
+    
+    let mut transfers = Vec::new();
+    let mut source_index = 0;
+    for line in lines.iter().filter(|line| line.amount > AMOUNT_EPSILON) {
+        let mut needed = line.amount;
+        while needed > AMOUNT_EPSILON && source_index < sources.len() {
+            let (source, available) = &mut sources[source_index];
+            let amount = needed.min(*available);
+            transfers.push(RebalanceTransfer {
+                from_asset_type: source.clone(),
+                to_asset_type: line.asset_type.clone(),
+                amount: round_cents(amount),
+            });
+            needed -= amount;
+            *available -= amount;
+            if *available <= AMOUNT_EPSILON {
+                source_index += 1;
+            }
+        }
+    }
+    
+    let within_bands_after = in_bands(&slots, &|slot| slot.projected, final_total);
+    
+    Ok(RebalancePlan {
+        total_value: round_cents(total_value),
+        contribution,
+        contributions_only,
+        lines,
+        transfers,
+        unallocated: round_cents(unallocated),
+        within_bands_before,
+        within_bands_after,
+    })
+}
+
+// Функции импорта выписок
+
+/// Строка выписки в формате, общем для всех источников импорта
+#[derive(Default)]
+struct ParsedImportLine {
+    line_number: usize,
+    ts: i64,
+    amount: f64,
+    description: String,
+    external_id: Option<String>,
+    value_ts: Option<i64>,
+    counterparty: Option<String>,
+    remittance_info: Option<String>,
+    end_to_end_id: Option<String>,
+}
+
+/// Символы 0x80-0xBF кодировки Windows-1251 (0xC0-0xFF - А..я подряд)
+const CP1251_HIGH: [char; 64] = [
+    'Ђ', 'Ѓ', '‚', 'ѓ', '„', '…', '†', '‡', '€', '‰', 'Љ', '‹', 'Њ', 'Ќ', 'Ћ', 'Џ',
+    'ђ', '‘', '’', '“', '”', '•', '–', '—', '\u{98}', '™', 'љ', '›', 'њ', 'ќ', 'ћ', 'џ',
+    '\u{a0}', 'Ў', 'ў', 'Ј', '¤', 'Ґ', '¦', '§', 'Ё', '©', 'Є', '«', '¬', '\u{ad}', '®', 'Ї',
+    '°', '±', 'І', 'і', 'ґ', 'µ', '¶', '·', 'ё', '№', 'є', '»', 'ј', 'Ѕ', 'ѕ', 'ї',
+];
+
+/// Декодирование содержимого файла в строку (BOM UTF-8 отбрасывается)
+fn decode_import_bytes(content: &[u8], encoding: ImportEncoding) -> Result<String, DbError> {
+    match encoding {
+        ImportEncoding::Utf8 => {
+            let content = content.strip_prefix(b"\xef\xbb\xbf").unwrap_or(content);
+            String::from_utf8(content.to_vec()).map_err(|e| DbError::ValidationError(format!(
+                "File is not valid UTF-8 (byte {}); check the encoding",
+                e.utf8_error().valid_up_to()
+            )))
+        }
+        ImportEncoding::Cp1251 => Ok(content
+            .iter()
+            .map(|&byte| match byte {
+                0x00..=0x7f => byte as char,
+                0x80..=0xbf => CP1251_HIGH[(byte - 0x80) as usize],
+                _ => char::from_u32(0x0410 + (byte - 0xc0) as u32).unwrap_or('\u{fffd}'),
+            })
+            .collect()),
+    }
+}
+
+/// Разбор CSV (RFC 4180: кавычки, удвоенные кавычки, переводы строк в полях)
+/// 
+/// Возвращает записи с номером строки, на которой запись начинается.
+/// Пустые строки пропускаются.
+fn parse_csv_records(text: &str, delimiter: char) -> Result<Vec<(usize, Vec<String>)>, DbError> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line_number = 1;
+    let mut record_line = 1;
+    
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => {
+                    if c == '\n' {
+                        line_number += 1;
+                    }
+                    field.push(c);
+                }
+            }
+            continue;
+        }
+        
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            '\r' => {}
+            '\n' => {
+                fields.push(std::mem::take(&mut field));
+                records.push((record_line, std::mem::take(&mut fields)));
+                line_number += 1;
+                record_line = line_number;
+            }
+            c if c == delimiter => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    
+    if in_quotes {
+        return Err(DbError::ValidationError(format!(
+            "Unterminated quoted field starting at line {}",
+            record_line
+        )));
+    }
+    if !field.is_empty() || !fields.is_empty() {
+        fields.push(field);
+        records.push((record_line, fields));
+    }
+    
+    records.retain(|(_, fields)| fields.iter().any(|field| !field.trim().is_empty()));
+    
+    Ok(records)
+}
+
+/// Разбор суммы из выписки
+/// 
+/// Пробелы и апострофы (разделители разрядов) отбрасываются, второй из
+/// символов `.`/`,` считается разделителем разрядов. Отрицательные суммы:
+/// `-12.50`, `(12.50)`, `12.50-`. Пустое значение - `None`.
+fn parse_import_amount(raw: &str, decimal_separator: char) -> Result<Option<f64>, String> {
+    let text: String = raw
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '\'')
+        .map(|c| if c == '−' { '-' } else { c })
+        .collect();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    
+    let (text, parenthesized) = match text.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+        Some(inner) => (inner.to_string(), true),
+        None => (text, false),
+    };
+    let (text, trailing_minus) = match text.strip_suffix('-') {
+        Some(inner) => (inner.to_string(), true),
+        None => (text, false),
+    };
+    
+    let thousands = if decimal_separator == ',' { '.' } else { ',' };
+    let normalized: String = text
+        .chars()
+        .filter(|c| *c != thousands)
+        .map(|c| if c == decimal_separator { '.' } else { c })
+        .collect();
+    
+    let value: f64 = normalized
+        .parse()
+        .ok()
+        .filter(|value: &f64| value.is_finite())
+        .ok_or_else(|| format!("Invalid amount: {}", raw.trim()))?;
+    
+    Ok(Some(if parenthesized || trailing_minus { -value } else { value }))
+}
+
+/// Разбор даты выписки по формату chrono
+/// 
+/// Формат с временем даёт локальный момент, формат только с датой -
+/// начало локальных суток.
+fn parse_import_date(raw: &str, format: &str) -> Result<i64, String> {
+    use chrono::TimeZone;
+    
+    let raw = raw.trim();
+    if let Ok(datetime) = chrono::NaiveDateTime::parse_from_str(raw, format) {
+        return chrono::Local
+            .from_local_datetime(&datetime)
+            .earliest()
+            .map(|dt| dt.timestamp())
+            .ok_or_else(|| format!("Nonexistent local time: {}", raw));
+    }
+    
+    let date = chrono::NaiveDate::parse_from_str(raw, format)
+        .map_err(|_| format!("Invalid date '{}' for format '{}'", raw, format))?;
+    
+    local_day_start(date).map_err(|e| e.to_string())
+}
+
+/// Проверка параметров CSV
+fn validate_csv_options(options: &CsvImportOptions) -> Result<(), DbError> {
+    if ['"', '\n', '\r'].contains(&options.delimiter) {
+        return Err(DbError::ValidationError(format!("Invalid delimiter: {:?}", options.delimiter)));
+    }
+    
+    if options.decimal_separator != '.' && options.decimal_separator != ',' {
+        return Err(DbError::ValidationError(format!(
+            "Decimal separator must be '.' or ',', got {:?}",
+            options.decimal_separator
+        )));
+    }
+    
+    if options.date_format.trim().is_empty() {
+        return Err(DbError::ValidationError("Date format is required".to_string()));
+    }
+    
+    let columns = &options.columns;
+    let has_debit_credit = columns.debit.is_some() || columns.credit.is_some();
+    if columns.amount.is_some() == has_debit_credit {
+        return Err(DbError::ValidationError(
+            "Map either the amount column or the debit/credit columns".to_string(),
+        ));
+    }
+    
+    Ok(())
+}
+
+/// Разбор одной записи CSV по сопоставлению колонок
+fn parse_csv_line(fields: &[String], options: &CsvImportOptions) -> Result<(i64, f64, String), String> {
+    let field = |index: usize| {
+        fields
+            .get(index)
+            .map(|value| value.trim())
+            .ok_or_else(|| format!("Missing column {}", index + 1))
+    };
+    let amount_at = |column: Option<usize>| -> Result<Option<f64>, String> {
+        match column {
+            Some(index) => parse_import_amount(field(index)?, options.decimal_separator),
+            None => Ok(None),
+        }
+    };
+    
+    let columns = &options.columns;
+    let ts = parse_import_date(field(columns.date)?, &options.date_format)?;
+    
+    let amount = match columns.amount {
+        Some(_) => {
+            let amount = amount_at(columns.amount)?.ok_or("Empty amount")?;
+            match options.sign {
+                AmountSign::Signed => amount,
+                AmountSign::Inverted => -amount,
+            }
+        }
+        None => {
+            let debit = amount_at(columns.debit)?;
+            let credit = amount_at(columns.credit)?;
+            if debit.is_none() && credit.is_none() {
+                return Err("Empty amount".to_string());
+            }
+            credit.unwrap_or(0.0).abs() - debit.unwrap_or(0.0).abs()
+        }
+    };
+    
+    let mut parts = Vec::new();
+    for &index in &columns.description {
+        let value = field(index)?;
+        if !value.is_empty() {
+            parts.push(value);
+        }
+    }
+    
+    Ok((ts, amount, parts.join(" ")))
+}
+
+/// Разбор CSV-выписки в строки импорта
+fn parse_csv_statement(
+    content: &[u8],
+    options: &CsvImportOptions,
+) -> Result<(Vec<ParsedImportLine>, Vec<ImportLineError>), DbError> {
+    validate_csv_options(options)?;
+    
+    let text = decode_import_bytes(content, options.encoding)?;
+    
+    // Преамбула может не быть корректным CSV, поэтому отрезается до разбора
+    let mut body = text.as_str();
+    for _ in 0..options.skip_rows {
+        body = body.split_once('\n').map(|(_, rest)| rest).unwrap_or("");
+    }
+    
+    let mut records = parse_csv_records(body, options.delimiter)?.into_iter();
+    if options.has_header {
+        records.next();
+    }
+    
+    let mut lines = Vec::new();
+    let mut errors = Vec::new();
+    for (record_line, fields) in records {
+        let line_number = record_line + options.skip_rows;
+        match parse_csv_line(&fields, options) {
+            Ok((ts, amount, description)) => lines.push(ParsedImportLine {
+                line_number,
+                ts,
+                amount,
+                description,
+                ..Default::default()
+            }),
+            Err(message) => errors.push(ImportLineError { line_number, message }),
+        }
+    }
+    
+    Ok((lines, errors))
+}
+
+/// Построение предпросмотра: хеши строк и поиск уже импортированных
+/// 
+/// Хеш строки с идентификатором банка строится по идентификатору, иначе -
+/// по дате, сумме, описанию и порядковому номеру среди одинаковых строк
+/// файла (две одинаковые покупки за день - две разные операции, а
+/// повторный импорт того же файла даёт те же хеши).
+fn build_import_preview(
+    conn: &Connection,
+    account_id: i64,
+    parsed: Vec<ParsedImportLine>,
+    errors: Vec<ImportLineError>,
+) -> Result<ImportPreview, DbError> {
+    ensure_account_exists(conn, account_id)?;
+    
+    let mut occurrences: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    let mut seen: std::collections::HashSet<String> = std::collections::HashSet::new();
+    let mut lines = Vec::with_capacity(parsed.len());
+    
+    for line in parsed {
+        let identity = match &line.external_id {
+            Some(external_id) => format!("id|{}", external_id),
+            None => {
+                let text = line.description.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+                let base = format!("{}|{:.2}|{}", line.ts, line.amount, text);
+                let occurrence = occurrences.entry(base.clone()).or_insert(0);
+                *occurrence += 1;
+                format!("{}|{}", base, occurrence)
+            }
+        };
+        let import_hash = sha256_hex(format!("{}|{}", account_id, identity).as_bytes());
+        
+        let imported: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM import_hashes WHERE account_id = ?1 AND hash = ?2)",
+            rusqlite::params![account_id, import_hash],
+            |row| row.get(0),
+        )?;
+        let status = if imported || !seen.insert(import_hash.clone()) {
+            ImportLineStatus::Duplicate
+        } else {
+            ImportLineStatus::New
+        };
+        
+        lines.push(ImportLine {
+            line_number: line.line_number,
+            ts: line.ts,
+            amount: line.amount,
+            description: line.description,
+            external_id: line.external_id,
+            value_ts: line.value_ts,
+            counterparty: line.counterparty,
+            remittance_info: line.remittance_info,
+            end_to_end_id: line.end_to_end_id,
+            import_hash,
+            status,
+        });
+    }
+    
+    let new_lines = || lines.iter().filter(|line| line.status == ImportLineStatus::New);
+    let new_count = new_lines().count();
+    let new_amount = round_cents(new_lines().map(|line| line.amount).sum());
+    
+    Ok(ImportPreview {
+        account_id,
+        new_count,
+        duplicate_count: lines.len() - new_count,
+        new_amount,
+        lines,
+        errors,
+    })
+}
+
+/// Запись новых строк предпросмотра в БД в рамках транзакции вызывающего
+/// 
+/// Файл с ошибками разбора не импортируется. Пакет импорта логируется
+/// в version_log (запись подписывается ключом хранилища).
+fn commit_import(
+    conn: &Connection,
+    path: &str,
+    key: &str,
+    source: &str,
+    file_name: &str,
+    content: &[u8],
+    preview: ImportPreview,
+) -> Result<ImportResult, DbError> {
+    if let Some(error) = preview.errors.first() {
+        return Err(DbError::ValidationError(format!(
+            "Line {}: {} ({} invalid lines in total)",
+            error.line_number,
+            error.message,
+            preview.errors.len()
+        )));
+    }
+    
+    let mut batch = ImportBatch {
+        id: 0,
+        account_id: preview.account_id,
+        source: source.to_string(),
+        file_name: file_name.to_string(),
+        file_sha256: sha256_hex(content),
+        line_count: preview.lines.len(),
+        imported_count: preview.new_count,
+        duplicate_count: preview.duplicate_count,
+        imported_at: current_timestamp()?,
+    };
+    
     conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_operations_account_id ON operations(account_id)",
-        [],
+        "INSERT INTO import_batches
+            (account_id, source, file_name, file_sha256, line_count, imported_count, duplicate_count, imported_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        rusqlite::params![
+            batch.account_id,
+            batch.source,
+            batch.file_name,
+            batch.file_sha256,
+            batch.line_count as i64,
+            batch.imported_count as i64,
+            batch.duplicate_count as i64,
+            batch.imported_at,
+        ],
     )?;
+    batch.id = conn.last_insert_rowid();
+    
+    let signer = VersionSigner::load(path, key)?;
+    let rules = load_active_rules(conn)?;
+    
+    // Выписки часто идут от новых строк к старым: вставляем в хронологическом
+    // порядке, а цепочку states пересчитываем один раз после всех строк
+    let mut new_lines: Vec<&ImportLine> = preview.lines.iter()
+        .filter(|line| line.status == ImportLineStatus::New)
+        .collect();
+    new_lines.sort_by_key(|line| (line.ts, line.line_number));
+    
+    let mut inserted = Vec::with_capacity(new_lines.len());
+    let mut deltas = Vec::with_capacity(new_lines.len());
+    for line in new_lines {
+        let operation_id = insert_operation_record(
+            conn, &signer, &rules, None, batch.account_id, line.amount, &line.description, line.ts,
+        )?;
+        conn.execute(
+            "INSERT INTO import_hashes (account_id, hash, operation_id, batch_id) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![batch.account_id, line.import_hash, operation_id, batch.id],
+        )?;
+        inserted.push((line.line_number, operation_id));
+        deltas.push((line.ts, line.amount));
+    }
+    
+    apply_balance_deltas(conn, &signer, batch.account_id, &deltas)?;
+    
+    inserted.sort_unstable();
+    let operation_ids = inserted.into_iter().map(|(_, operation_id)| operation_id).collect();
+    
+    let result = ImportResult { batch, operation_ids, lines: preview.lines };
+    write_signed_version_log(conn, &signer, "import_batch", result.batch.id, "create", &serialize_entity(&result)?)?;
+    
+    Ok(result)
+}
+
+/// Предпросмотр импорта CSV-выписки
+pub fn preview_csv_import(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    content: &[u8],
+    options: &CsvImportOptions,
+) -> Result<ImportPreview, DbError> {
+    let (parsed, errors) = parse_csv_statement(content, options)?;
+    
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    build_import_preview(&conn, account_id, parsed, errors)
+}
+
+/// Импорт CSV-выписки
+/// 
+/// Весь файл записывается в одной транзакции: при ошибке в любой строке
+/// не импортируется ничего. Уже импортированные строки пропускаются.
+pub fn import_csv(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    file_name: String,
+    content: &[u8],
+    options: &CsvImportOptions,
+) -> Result<ImportResult, DbError> {
+    let (parsed, errors) = parse_csv_statement(content, options)?;
+    
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let preview = build_import_preview(&tx, account_id, parsed, errors)?;
+    let result = commit_import(&tx, path, key, "csv", &file_name, content, preview)?;
+    
+    tx.commit()?;
+    
+    Ok(result)
+}
+
+/// Итоговый остаток выписки: (момент, сумма в знаках банка)
+type StatementBalance = (i64, f64);
+
+/// Разобранная выписка с итоговым остатком
+struct ParsedStatement {
+    lines: Vec<ParsedImportLine>,
+    errors: Vec<ImportLineError>,
+    balance: Option<StatementBalance>,
+}
+
+/// Сверка остатка выписки с балансом счёта
+/// 
+/// Для предпросмотра `pending` - новые строки, которые ещё не записаны
+/// и учитываются в балансе, если не позже момента остатка.
+fn check_statement_balance(
+    conn: &Connection,
+    account_id: i64,
+    balance: StatementBalance,
+    pending: &[ImportLine],
+) -> Result<StatementBalanceCheck, DbError> {
+    let (as_of, statement_balance) = balance;
+    
+    let pending_total: f64 = pending
+        .iter()
+        .filter(|line| line.status == ImportLineStatus::New && line.ts <= as_of)
+        .map(|line| line.amount)
+        .sum();
+    let book_balance = round_cents(get_balance_at(conn, account_id, as_of)? + pending_total);
+    let difference = round_cents(statement_balance - book_balance);
+    
+    Ok(StatementBalanceCheck {
+        as_of,
+        statement_balance,
+        book_balance,
+        difference,
+        matches: difference.abs() < AMOUNT_EPSILON,
+    })
+}
+
+/// Перевод сумм выписки в знаки счёта
+/// 
+/// Банк показывает долг по кредитной карте отрицательным, а на счёте-
+/// обязательстве рост долга положителен.
+fn apply_account_sign(
+    conn: &Connection,
+    account_id: i64,
+    lines: &mut [ParsedImportLine],
+    balance: Option<StatementBalance>,
+) -> Result<Option<StatementBalance>, DbError> {
+    if load_account(conn, account_id)?.acc_class != AccountClass::Liability {
+        return Ok(balance);
+    }
+    
+    for line in lines.iter_mut() {
+        line.amount = -line.amount;
+    }
+    
+    Ok(balance.map(|(as_of, amount)| (as_of, -amount)))
+}
+
+/// Элемент OFX/XML: агрегат с дочерними элементами или лист со значением
+struct MarkupElement {
+    name: String,
+    value: Option<String>,
+    children: Vec<MarkupElement>,
+    line_number: usize,
+}
+
+impl MarkupElement {
+    /// Значение дочернего листа
+    fn text(&self, name: &str) -> Option<&str> {
+        self.children
+            .iter()
+            .find(|child| child.name == name)
+            .and_then(|child| child.value.as_deref())
+    }
+    
+    fn child(&self, name: &str) -> Option<&MarkupElement> {
+        self.children.iter().find(|child| child.name == name)
+    }
+    
+    /// Первый элемент с именем на любой глубине
+    fn find_first(&self, name: &str) -> Option<&MarkupElement> {
+        let mut found = Vec::new();
+        self.find_all(name, &mut found);
+        found.into_iter().next()
+    }
+    
+    /// Все элементы с именем на любой глубине (в порядке документа)
+    fn find_all<'a>(&'a self, name: &str, found: &mut Vec<&'a MarkupElement>) {
+        for child in &self.children {
+            if child.name == name {
+                found.push(child);
+            } else {
+                child.find_all(name, found);
+            }
+        }
+    }
+}
+
+/// Раскрытие сущностей XML/SGML в значении
+fn decode_markup_entities(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let decoded = tail.find(';').and_then(|end| {
+            let entity = &tail[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, end))
+        });
+        
+        match decoded {
+            Some((c, end)) => {
+                result.push(c);
+                rest = &tail[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &tail[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    
+    result
+}
+
+/// Декодирование файла OFX/XML по заголовку
+/// 
+/// По умолчанию UTF-8; `CHARSET:1251` (OFX 1.x) и `encoding="windows-1251"`
+/// (XML) - CP1251, остальные однобайтовые кодировки (1252, ISO-8859-1)
+/// читаются как Latin-1.
+fn decode_markup_bytes(content: &[u8]) -> Result<String, DbError> {
+    let head: String = content.iter().take(1024).map(|&byte| byte as char).collect::<String>().to_uppercase();
+    
+    if head.contains("CHARSET:1251") || head.contains("ENCODING=\"WINDOWS-1251\"") {
+        return decode_import_bytes(content, ImportEncoding::Cp1251);
+    }
+    
+    match decode_import_bytes(content, ImportEncoding::Utf8) {
+        Ok(text) => Ok(text),
+        Err(_) if !head.contains("UTF-8") => Ok(content.iter().map(|&byte| byte as char).collect()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Разбор документа OFX 1.x (SGML) или XML в дерево элементов
+/// 
+/// Разбор начинается с корневого элемента `root`. В SGML листья не
+/// закрываются: значение листа - текст до следующего тега. Закрывающий
+/// тег агрегата закрывает и все незакрытые вложенные. Префиксы
+/// пространств имён отбрасываются, атрибуты игнорируются.
+fn parse_markup_document(text: &str, root: &str) -> Result<MarkupElement, DbError> {
+    let opening = format!("<{}", root).to_ascii_uppercase();
+    let start = text
+        .to_ascii_uppercase()
+        .match_indices(&opening)
+        .map(|(index, _)| index)
+        .find(|&index| {
+            text[index + opening.len()..]
+                .starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace())
+        })
+        .ok_or_else(|| DbError::ValidationError(format!("<{}> element not found", root)))?;
+    let body = &text[start..];
+    
+    let element_name = |tag: &str| {
+        let name = tag.split_whitespace().next().unwrap_or("").trim_end_matches('/');
+        name.rsplit(':').next().unwrap_or(name).to_string()
+    };
+    
+    let mut stack = vec![MarkupElement { name: String::new(), value: None, children: Vec::new(), line_number: 0 }];
+    let mut line_number = text[..start].matches('\n').count() + 1;
+    let mut rest = body;
+    
+    while let Some(open) = rest.find('<') {
+        line_number += rest[..open].matches('\n').count();
+        let close = rest[open..]
+            .find('>')
+            .map(|end| open + end)
+            .ok_or_else(|| DbError::ValidationError(format!("Unterminated tag at line {}", line_number)))?;
+        let tag = rest[open + 1..close].trim();
+        rest = &rest[close + 1..];
+        
+        // Инструкции обработки и комментарии
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = element_name(name);
+            let depth = stack
+                .iter()
+                .rposition(|element| element.name == name)
+                .filter(|&depth| depth > 0)
+                .ok_or_else(|| DbError::ValidationError(format!("Unexpected </{}> at line {}", name, line_number)))?;
+            while stack.len() > depth {
+                let element = stack.pop().expect("stack is deeper than depth");
+                stack.last_mut().expect("root is never popped").children.push(element);
+            }
+            continue;
+        }
+        
+        let name = element_name(tag);
+        let value_end = rest.find('<').unwrap_or(rest.len());
+        let value = rest[..value_end].trim();
+        
+        if tag.ends_with('/') {
+            // Пустой элемент XML
+            stack.last_mut().expect("root is never popped").children.push(MarkupElement {
+                name,
+                value: None,
+                children: Vec::new(),
+                line_number,
+            });
+        } else if value.is_empty() {
+            // Агрегат
+            stack.push(MarkupElement { name, value: None, children: Vec::new(), line_number });
+        } else {
+            // Лист; в XML за значением следует закрывающий тег
+            line_number += rest[..value_end].matches('\n').count();
+            rest = &rest[value_end..];
+            if let Some(tail) = rest.strip_prefix("</") {
+                if let Some(end) = tail.find('>').filter(|&end| element_name(&tail[..end]) == name) {
+                    rest = &tail[end + 1..];
+                }
+            }
+            stack.last_mut().expect("root is never popped").children.push(MarkupElement {
+                name,
+                value: Some(decode_markup_entities(value)),
+                children: Vec::new(),
+                line_number,
+            });
+        }
+    }
+    
+    // Незакрытые агрегаты в конце файла
+    while stack.len() > 1 {
+        let element = stack.pop().expect("stack has more than root");
+        stack.last_mut().expect("root is never popped").children.push(element);
+    }
+    
+    Ok(stack.pop().expect("root is never popped"))
+}
+
+/// Начало (`end_of_day = false`) или последняя секунда локальных суток
+/// 
+/// Даты проводок без времени относятся к началу суток, остатки на дату -
+/// к концу суток.
+fn local_date_bound(date: chrono::NaiveDate, end_of_day: bool) -> Result<i64, String> {
+    if end_of_day {
+        date.succ_opt()
+            .ok_or_else(|| format!("Invalid date: {}", date))
+            .and_then(|next_day| local_day_start(next_day).map_err(|e| e.to_string()))
+            .map(|ts| ts - 1)
+    } else {
+        local_day_start(date).map_err(|e| e.to_string())
+    }
+}
+
+/// Разбор даты OFX: `YYYYMMDD[HHMMSS[.XXX]][[+-hh[.mm]:TZ]]`
+/// 
+/// Без часового пояса время считается локальным; дата без времени -
+/// см. `local_date_bound`.
+fn parse_ofx_date(raw: &str, end_of_day: bool) -> Result<i64, String> {
+    use chrono::TimeZone;
+    
+    let invalid = || format!("Invalid OFX date: {}", raw);
+    let (stamp, zone) = match raw.split_once('[') {
+        Some((stamp, zone)) => (stamp.trim(), Some(zone.trim_end_matches(']'))),
+        None => (raw.trim(), None),
+    };
+    let digits = stamp.split('.').next().unwrap_or("");
+    if digits.len() < 8 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    
+    let date = chrono::NaiveDate::parse_from_str(&digits[..8], "%Y%m%d").map_err(|_| invalid())?;
+    if digits.len() == 8 {
+        return local_date_bound(date, end_of_day);
+    }
+    
+    let time = chrono::NaiveTime::parse_from_str(&format!("{:0<6}", &digits[8..digits.len().min(14)]), "%H%M%S")
+        .map_err(|_| invalid())?;
+    let datetime = date.and_time(time);
+    
+    match zone.and_then(|zone| zone.split(':').next()).filter(|offset| !offset.is_empty()) {
+        Some(offset) => {
+            let hours: f64 = offset.parse().map_err(|_| invalid())?;
+            let offset = chrono::FixedOffset::east_opt((hours * 3600.0).round() as i32).ok_or_else(invalid)?;
+            offset
+                .from_local_datetime(&datetime)
+                .single()
+                .map(|dt| dt.timestamp())
+                .ok_or_else(invalid)
+        }
+        None => chrono::Local
+            .from_local_datetime(&datetime)
+            .earliest()
+            .map(|dt| dt.timestamp())
+            .ok_or_else(invalid),
+    }
+}
+
+/// Разбор суммы OFX (десятичный разделитель - точка, у части банков - запятая)
+fn parse_ofx_amount(raw: &str) -> Result<f64, String> {
+    let separator = if raw.contains(',') && !raw.contains('.') { ',' } else { '.' };
+    parse_import_amount(raw, separator)?.ok_or_else(|| "Empty amount".to_string())
+}
+
+/// Разбор транзакции STMTTRN (описание - NAME и MEMO через пробел)
+fn parse_ofx_transaction(transaction: &MarkupElement) -> Result<ParsedImportLine, String> {
+    let posted = transaction.text("DTPOSTED").ok_or("Missing DTPOSTED")?;
+    let amount = transaction.text("TRNAMT").ok_or("Missing TRNAMT")?;
+    let fitid = transaction.text("FITID").ok_or("Missing FITID")?;
+    
+    let mut parts: Vec<&str> = Vec::new();
+    for field in ["NAME", "MEMO"] {
+        if let Some(value) = transaction.text(field) {
+            if !parts.contains(&value) {
+                parts.push(value);
+            }
+        }
+    }
+    if parts.is_empty() {
+        parts.extend(transaction.text("TRNTYPE"));
+    }
+    
+    Ok(ParsedImportLine {
+        line_number: transaction.line_number,
+        ts: parse_ofx_date(posted, false)?,
+        amount: parse_ofx_amount(amount)?,
+        description: parts.join(" "),
+        external_id: Some(fitid.to_string()),
+        ..Default::default()
+    })
+}
+
+/// Разбор выписки OFX: транзакции STMTTRN и остаток LEDGERBAL
+/// 
+/// Файл должен содержать ровно одну выписку (банковскую STMTRS или
+/// по карте CCSTMTRS).
+fn parse_ofx_statement(content: &[u8]) -> Result<ParsedStatement, DbError> {
+    let document = parse_markup_document(&decode_markup_bytes(content)?, "OFX")
+        .map_err(|e| DbError::ValidationError(format!("Not an OFX file: {}", e)))?;
+    
+    let mut statements = Vec::new();
+    document.find_all("STMTRS", &mut statements);
+    document.find_all("CCSTMTRS", &mut statements);
+    let statement = match statements.as_slice() {
+        [statement] => *statement,
+        [] => return Err(DbError::ValidationError("OFX file contains no statement".to_string())),
+        _ => {
+            return Err(DbError::ValidationError(format!(
+                "OFX file contains {} statements; import them separately",
+                statements.len()
+            )))
+        }
+    };
+    
+    let mut transactions = Vec::new();
+    statement.find_all("STMTTRN", &mut transactions);
+    
+    let mut lines = Vec::new();
+    let mut errors = Vec::new();
+    for transaction in transactions {
+        match parse_ofx_transaction(transaction) {
+            Ok(line) => lines.push(line),
+            Err(message) => errors.push(ImportLineError { line_number: transaction.line_number, message }),
+        }
+    }
+    
+    let balance = match statement.child("LEDGERBAL") {
+        Some(ledger) => {
+            let invalid = |message: String| {
+                DbError::ValidationError(format!("LEDGERBAL at line {}: {}", ledger.line_number, message))
+            };
+            let amount = parse_ofx_amount(ledger.text("BALAMT").ok_or_else(|| invalid("missing BALAMT".to_string()))?)
+                .map_err(invalid)?;
+            let as_of = parse_ofx_date(ledger.text("DTASOF").ok_or_else(|| invalid("missing DTASOF".to_string()))?, true)
+                .map_err(invalid)?;
+            Some((as_of, amount))
+        }
+        None => None,
+    };
+    
+    Ok(ParsedStatement { lines, errors, balance })
+}
+
+/// Предпросмотр импорта выписки с предварительной сверкой остатка
+fn preview_statement(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    statement: ParsedStatement,
+) -> Result<StatementImportPreview, DbError> {
+    let ParsedStatement { mut lines, errors, balance } = statement;
     
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_operations_ts ON operations(ts)",
-        [],
-    )?;
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
     
-    Ok(())
+    let balance = apply_account_sign(&conn, account_id, &mut lines, balance)?;
+    let preview = build_import_preview(&conn, account_id, lines, errors)?;
+    let balance_check = balance
+        .map(|balance| check_statement_balance(&conn, account_id, balance, &preview.lines))
+        .transpose()?;
+    
+    Ok(StatementImportPreview { preview, balance_check })
 }
 
-/// Миграция v4: создание таблицы states
-fn migration_v4_states(conn: &Connection) -> SqlResult<()> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS states (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            account_id INTEGER NOT NULL,
-            balance REAL NOT NULL,
-            ts INTEGER NOT NULL,
-            FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
-        )",
-        [],
-    )?;
+/// Импорт выписки с последующей сверкой остатка с балансом в states
+/// 
+/// Расхождение возвращается в `balance_check` и не отменяет импорт.
+fn import_statement(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    source: &str,
+    file_name: &str,
+    content: &[u8],
+    statement: ParsedStatement,
+) -> Result<StatementImportResult, DbError> {
+    let ParsedStatement { mut lines, errors, balance } = statement;
     
-    // Создаём индексы
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_states_account_id ON states(account_id)",
-        [],
-    )?;
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
     
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_states_ts ON states(ts)",
-        [],
-    )?;
+    let tx = conn.transaction()?;
     
-    // Уникальный индекс для предотвращения дублирования снимков
-    conn.execute(
-        "CREATE UNIQUE INDEX IF NOT EXISTS idx_states_account_ts ON states(account_id, ts)",
-        [],
-    )?;
+    let balance = apply_account_sign(&tx, account_id, &mut lines, balance)?;
+    let preview = build_import_preview(&tx, account_id, lines, errors)?;
+    let result = commit_import(&tx, path, key, source, file_name, content, preview)?;
+    let balance_check = balance
+        .map(|balance| check_statement_balance(&tx, account_id, balance, &[]))
+        .transpose()?;
     
-    Ok(())
+    tx.commit()?;
+    
+    Ok(StatementImportResult { result, balance_check })
 }
 
-/// Миграция v5: создание таблицы version_log для аудита изменений
-fn migration_v5_version_log(conn: &Connection) -> SqlResult<()> {
-    // Создаём таблицу version_log
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS version_log (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            entity TEXT NOT NULL,
-            entity_id INTEGER NOT NULL,
-            action TEXT NOT NULL,
-            payload TEXT NOT NULL,
-            ts INTEGER NOT NULL
-        )",
-        [],
-    )?;
+/// Предпросмотр импорта OFX
+pub fn preview_ofx_import(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    content: &[u8],
+) -> Result<StatementImportPreview, DbError> {
+    preview_statement(path, key, account_id, parse_ofx_statement(content)?)
+}
+
+/// Импорт выписки OFX 1.x/2.x
+/// 
+/// Повторный импорт идемпотентен: строки сопоставляются по FITID.
+/// Остаток LEDGERBAL сверяется с балансом счёта после записи.
+pub fn import_ofx(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    file_name: String,
+    content: &[u8],
+) -> Result<StatementImportResult, DbError> {
+    import_statement(path, key, account_id, "ofx", &file_name, content, parse_ofx_statement(content)?)
+}
+
+/// Описание операции из контрагента и назначения платежа
+fn statement_description(parts: &[Option<&str>]) -> String {
+    parts
+        .iter()
+        .flatten()
+        .map(|part| part.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Разбор даты ISO 20022: `2024-01-05` или `2024-01-05T10:00:00[.fff][Z|+01:00]`
+/// 
+/// Время без часового пояса считается локальным.
+fn parse_iso_date(raw: &str, end_of_day: bool) -> Result<i64, String> {
+    use chrono::TimeZone;
     
-    // Создаём индексы для быстрого поиска
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_version_log_entity ON version_log(entity, entity_id)",
-        [],
-    )?;
+    let raw = raw.trim();
+    if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(raw) {
+        return Ok(datetime.timestamp());
+    }
+    if let Ok(datetime) = chrono::NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M:%S%.f") {
+        return chrono::Local
+            .from_local_datetime(&datetime)
+            .earliest()
+            .map(|dt| dt.timestamp())
+            .ok_or_else(|| format!("Nonexistent local time: {}", raw));
+    }
     
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_version_log_ts ON version_log(ts)",
-        [],
-    )?;
+    let date = chrono::NaiveDate::parse_from_str(raw, "%Y-%m-%d").map_err(|_| format!("Invalid date: {}", raw))?;
+    local_date_bound(date, end_of_day)
+}
+
+/// Дата camt из элемента-обёртки (`<Dt>` или `<DtTm>`)
+fn camt_date(wrapper: &MarkupElement, end_of_day: bool) -> Result<i64, String> {
+    let raw = wrapper
+        .text("Dt")
+        .or_else(|| wrapper.text("DtTm"))
+        .ok_or_else(|| format!("Missing date in <{}>", wrapper.name))?;
+    parse_iso_date(raw, end_of_day)
+}
+
+/// Сумма camt со знаком по CdtDbtInd (CRDT - поступление, DBIT - списание)
+fn camt_signed_amount(element: &MarkupElement, amount: &str) -> Result<f64, String> {
+    let amount = parse_import_amount(amount, '.')?.ok_or("Empty amount")?;
+    match element.text("CdtDbtInd") {
+        Some("CRDT") => Ok(amount),
+        Some("DBIT") => Ok(-amount),
+        Some(other) => Err(format!("Invalid CdtDbtInd: {}", other)),
+        None => Err("Missing CdtDbtInd".to_string()),
+    }
+}
+
+/// Строка выписки из записи Ntry camt и (необязательно) её TxDtls
+fn camt_line(
+    entry: &MarkupElement,
+    details: Option<&MarkupElement>,
+    amount: f64,
+    external_id: Option<String>,
+) -> Result<ParsedImportLine, String> {
+    let booking = entry.child("BookgDt").ok_or("Missing BookgDt")?;
+    let value_ts = entry.child("ValDt").map(|value| camt_date(value, false)).transpose()?;
     
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_version_log_action ON version_log(action)",
-        [],
-    )?;
+    let text_in = |name: &str| details.and_then(|details| details.find_first(name)).and_then(|e| e.value.as_deref());
     
-    Ok(())
+    let end_to_end_id = text_in("EndToEndId").filter(|id| *id != "NOTPROVIDED");
+    
+    // Контрагент - получатель для списаний и плательщик для поступлений
+    let party = if amount < 0.0 { "Cdtr" } else { "Dbtr" };
+    let counterparty = details
+        .and_then(|details| details.child("RltdPties"))
+        .and_then(|parties| parties.child(party))
+        .and_then(|party| party.find_first("Nm"))
+        .and_then(|name| name.value.as_deref());
+    
+    let remittance_info = details.and_then(|details| details.child("RmtInf")).and_then(|info| {
+        let mut unstructured = Vec::new();
+        info.find_all("Ustrd", &mut unstructured);
+        let text = unstructured.iter().filter_map(|e| e.value.as_deref()).collect::<Vec<_>>().join(" ");
+        if text.is_empty() {
+            info.find_first("Ref").and_then(|e| e.value.clone())
+        } else {
+            Some(text)
+        }
+    });
+    
+    let fallback = text_in("AddtlTxInf").or_else(|| entry.text("AddtlNtryInf"));
+    let reversal = (entry.text("RvslInd") == Some("true")).then_some("Reversal:");
+    let description = match remittance_info.as_deref() {
+        Some(remittance) => statement_description(&[reversal, counterparty, Some(remittance)]),
+        None => statement_description(&[reversal, counterparty, fallback]),
+    };
+    
+    Ok(ParsedImportLine {
+        line_number: entry.line_number,
+        ts: camt_date(booking, false)?,
+        amount,
+        description,
+        external_id,
+        value_ts,
+        counterparty: counterparty.map(str::to_string),
+        remittance_info,
+        end_to_end_id: end_to_end_id.map(str::to_string),
+    })
 }
 
-/// Миграция M6: Таблица keystore для хранения криптографических ключей
-fn migration_v6_keystore(conn: &Connection) -> SqlResult<()> {
-    // Создаём таблицу keystore для хранения ключей
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS keystore (
-            key TEXT PRIMARY KEY,
-            value BLOB NOT NULL
-        )",
-        [],
-    )?;
-    
-    // Индекс уже есть благодаря PRIMARY KEY
-    Ok(())
+/// Сумма отдельной транзакции пакетной записи (TxDtls/Amt или AmtDtls)
+fn camt_detail_amount(details: &MarkupElement) -> Option<&str> {
+    details
+        .text("Amt")
+        .or_else(|| details.child("AmtDtls").and_then(|amounts| amounts.find_first("Amt")).and_then(|e| e.value.as_deref()))
 }
 
-/// Миграция M7: Таблица version_signatures для подписей версий
-fn migration_v7_version_signatures(conn: &Connection) -> SqlResult<()> {
-    // Создаём таблицу version_signatures
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS version_signatures (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            version_id INTEGER NOT NULL,
-            signature BLOB NOT NULL,
-            public_key BLOB NOT NULL,
-            ts INTEGER NOT NULL,
-            FOREIGN KEY (version_id) REFERENCES version_log(id) ON DELETE CASCADE
-        )",
-        [],
-    )?;
+/// Разбор записи Ntry camt.053
+/// 
+/// Учитываются только проведённые записи (статус BOOK). Направление сторно
+/// (RvslInd) задаёт CdtDbtInd, как и у обычной записи. Пакетная запись
+/// с несколькими TxDtls, у каждой из которых есть своя сумма, раскладывается
+/// на отдельные строки.
+fn parse_camt_entry(entry: &MarkupElement) -> Result<Vec<ParsedImportLine>, String> {
+    let status = entry.child("Sts").and_then(|status| status.value.as_deref().or_else(|| status.text("Cd")));
+    if status.is_some_and(|status| status != "BOOK") {
+        return Ok(Vec::new());
+    }
     
-    // Создаём индексы для быстрого поиска
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_version_signatures_version_id ON version_signatures(version_id)",
-        [],
-    )?;
+    let amount = camt_signed_amount(entry, entry.text("Amt").ok_or("Missing Amt")?)?;
+    let entry_ref = entry
+        .text("AcctSvcrRef")
+        .or_else(|| entry.text("NtryRef"))
+        .filter(|reference| *reference != "NONREF");
     
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_version_signatures_ts ON version_signatures(ts)",
-        [],
-    )?;
+    let mut details = Vec::new();
+    entry.find_all("TxDtls", &mut details);
     
-    Ok(())
+    if details.len() > 1 && details.iter().all(|details| camt_detail_amount(details).is_some()) {
+        return details
+            .iter()
+            .enumerate()
+            .map(|(index, details)| {
+                let raw = camt_detail_amount(details).unwrap_or_default();
+                let amount = match details.text("CdtDbtInd") {
+                    Some(_) => camt_signed_amount(details, raw)?,
+                    None => (parse_import_amount(raw, '.')?.ok_or("Empty amount")?).copysign(amount),
+                };
+                let external_id = details
+                    .child("Refs")
+                    .and_then(|refs| refs.text("AcctSvcrRef"))
+                    .map(str::to_string)
+                    .or_else(|| entry_ref.map(|reference| format!("{}/{}", reference, index + 1)));
+                camt_line(entry, Some(details), amount, external_id)
+            })
+            .collect();
+    }
+    
+    let details = details.first().copied();
+    let external_id = details
+        .and_then(|details| details.child("Refs"))
+        .and_then(|refs| refs.text("AcctSvcrRef"))
+        .or(entry_ref)
+        .map(str::to_string);
+    
+    Ok(vec![camt_line(entry, details, amount, external_id)?])
 }
 
-/// Получение версии БД
-pub fn get_db_version(path: &str, key: &str) -> Result<String, DbError> {
-    let conn = Connection::open(path)?;
-    conn.pragma_update(None, "key", key)?;
+/// Разбор выписки camt.053 (ISO 20022 BkToCstmrStmt)
+/// 
+/// Файл должен содержать ровно одну выписку Stmt. Остаток - последний
+/// закрывающий проведённый баланс CLBD.
+fn parse_camt053_statement(content: &[u8]) -> Result<ParsedStatement, DbError> {
+    let document = parse_markup_document(&decode_markup_bytes(content)?, "Document")
+        .map_err(|e| DbError::ValidationError(format!("Not a camt.053 file: {}", e)))?;
     
-    let version: String = conn.query_row(
-        "SELECT version FROM meta LIMIT 1",
-        [],
-        |row| row.get(0),
-    )?;
+    let mut statements = Vec::new();
+    document.find_all("Stmt", &mut statements);
+    let statement = match statements.as_slice() {
+        [statement] => *statement,
+        [] => return Err(DbError::ValidationError("camt.053 file contains no statement".to_string())),
+        _ => {
+            return Err(DbError::ValidationError(format!(
+                "camt.053 file contains {} statements; import them separately",
+                statements.len()
+            )))
+        }
+    };
     
-    Ok(version)
+    let mut lines = Vec::new();
+    let mut errors = Vec::new();
+    for entry in statement.children.iter().filter(|child| child.name == "Ntry") {
+        match parse_camt_entry(entry) {
+            Ok(entry_lines) => lines.extend(entry_lines),
+            Err(message) => errors.push(ImportLineError { line_number: entry.line_number, message }),
+        }
+    }
+    
+    let closing = statement
+        .children
+        .iter()
+        .filter(|child| child.name == "Bal")
+        .rfind(|balance| balance.find_first("Cd").and_then(|code| code.value.as_deref()) == Some("CLBD"));
+    let balance = match closing {
+        Some(closing) => {
+            let invalid = |message: String| {
+                DbError::ValidationError(format!("Closing balance at line {}: {}", closing.line_number, message))
+            };
+            let amount = camt_signed_amount(closing, closing.text("Amt").ok_or_else(|| invalid("missing Amt".to_string()))?)
+                .map_err(invalid)?;
+            let as_of = camt_date(closing.child("Dt").ok_or_else(|| invalid("missing Dt".to_string()))?, true)
+                .map_err(invalid)?;
+            Some((as_of, amount))
+        }
+        None => None,
+    };
+    
+    Ok(ParsedStatement { lines, errors, balance })
 }
 
-/// Обновление версии БД
-pub fn update_db_version(path: &str, key: &str, new_version: &str) -> Result<(), DbError> {
-    let conn = Connection::open(path)?;
-    conn.pragma_update(None, "key", key)?;
+/// Поле MT940: номер строки, тег (`61`, `86`, `62F`, ...) и строки значения
+struct Mt940Field {
+    line_number: usize,
+    tag: String,
+    lines: Vec<String>,
+}
+
+/// Разбиение сообщения MT940 на поля `:NN[a]:`
+/// 
+/// Заголовки блоков SWIFT (`{1:...}`) и разделители сообщений (`-`, `-}`)
+/// пропускаются, поэтому файл может содержать несколько сообщений подряд.
+fn split_mt940_fields(text: &str) -> Vec<Mt940Field> {
+    let tag_pattern = regex::Regex::new(r"^:(\d{2}[A-Z]?):(.*)$").expect("valid regex");
     
-    conn.execute(
-        "UPDATE meta SET version = ?1",
-        [new_version],
-    )?;
+    let mut fields: Vec<Mt940Field> = Vec::new();
+    for (index, raw) in text.lines().enumerate() {
+        let line = raw.trim_end_matches('\r');
+        let line = match line.rfind("{4:") {
+            Some(start) => &line[start + 3..],
+            None => line,
+        };
+        
+        if let Some(captures) = tag_pattern.captures(line) {
+            fields.push(Mt940Field {
+                line_number: index + 1,
+                tag: captures[1].to_string(),
+                lines: vec![captures[2].to_string()],
+            });
+        } else if line.starts_with('-') || line.starts_with('{') {
+            // Конец сообщения: продолжения к последнему полю больше не относятся
+            fields.push(Mt940Field { line_number: index + 1, tag: String::new(), lines: Vec::new() });
+        } else if let Some(field) = fields.last_mut().filter(|field| !field.tag.is_empty()) {
+            field.lines.push(line.to_string());
+        }
+    }
     
-    Ok(())
+    fields.retain(|field| !field.tag.is_empty());
+    fields
 }
 
-// Функции для version_log (аудит изменений)
+/// Дата `YYMMDD` MT940 (годы 80-99 относятся к XX веку)
+fn parse_mt940_date(raw: &str) -> Result<chrono::NaiveDate, String> {
+    let invalid = || format!("Invalid MT940 date: {}", raw);
+    let year: i32 = raw.get(..2).and_then(|year| year.parse().ok()).ok_or_else(invalid)?;
+    let century = if year >= 80 { 1900 } else { 2000 };
+    chrono::NaiveDate::parse_from_str(&format!("{}{}", century + year, raw.get(2..6).ok_or_else(invalid)?), "%Y%m%d")
+        .map_err(|_| invalid())
+}
 
-/// Запись изменения в version_log
+/// Разбор остатка `:60F:`/`:62F:`: `C|D YYMMDD CCY сумма`
+fn parse_mt940_balance(raw: &str) -> Result<StatementBalance, String> {
+    let raw = raw.trim();
+    let sign = match raw.get(..1) {
+        Some("C") => 1.0,
+        Some("D") => -1.0,
+        _ => return Err(format!("Invalid balance: {}", raw)),
+    };
+    let date = parse_mt940_date(raw.get(1..7).ok_or_else(|| format!("Invalid balance: {}", raw))?)?;
+    let amount = parse_import_amount(raw.get(10..).unwrap_or(""), ',')?.ok_or("Empty balance amount")?;
+    
+    Ok((local_date_bound(date, true)?, sign * amount))
+}
+
+/// Информация к платежу `:86:`: (контрагент, назначение, end-to-end ID)
 /// 
-/// Функция должна вызываться внутри транзакций для обеспечения атомарности
+/// Поддерживаются структура с подполями `?NN` (немецкий формат,
+/// назначение с ключами SEPA `EREF+`, `SVWZ+`), структура `/EREF/.../REMI/...`
+/// и свободный текст (целиком - назначение платежа).
+fn parse_mt940_information(lines: &[String]) -> (Option<String>, Option<String>, Option<String>) {
+    let joined: String = lines.concat();
+    let subfield_pattern = regex::Regex::new(r"\?(\d{2})").expect("valid regex");
+    let slash_pattern = regex::Regex::new(r"/([A-Z]{4})/").expect("valid regex");
+    let non_empty = |value: String| Some(value.trim().to_string()).filter(|value| !value.is_empty());
+    
+    if subfield_pattern.is_match(&joined) {
+        let mut remittance = String::new();
+        let mut name = String::new();
+        let markers: Vec<_> = subfield_pattern.captures_iter(&joined).map(|c| c.get(0).expect("whole match")).collect();
+        for (index, marker) in markers.iter().enumerate() {
+            let end = markers.get(index + 1).map(|next| next.start()).unwrap_or(joined.len());
+            let value = &joined[marker.end()..end];
+            match &marker.as_str()[1..] {
+                "20" | "21" | "22" | "23" | "24" | "25" | "26" | "27" | "28" | "29" | "60" | "61" | "62" | "63" => {
+                    remittance.push_str(value)
+                }
+                "32" | "33" => name.push_str(value),
+                _ => {}
+            }
+        }
+        
+        // Ключи SEPA внутри назначения
+        let keyword_pattern = regex::Regex::new(r"(EREF|KREF|MREF|CRED|DEBT|SVWZ|ABWA|ABWE)\+").expect("valid regex");
+        let keywords: Vec<_> = keyword_pattern.captures_iter(&remittance).map(|c| c.get(0).expect("whole match")).collect();
+        if keywords.is_empty() {
+            return (non_empty(name), non_empty(remittance), None);
+        }
+        
+        let mut end_to_end_id = None;
+        let mut purpose = None;
+        for (index, keyword) in keywords.iter().enumerate() {
+            let end = keywords.get(index + 1).map(|next| next.start()).unwrap_or(remittance.len());
+            let value = remittance[keyword.end()..end].to_string();
+            match keyword.as_str() {
+                "EREF+" => end_to_end_id = non_empty(value).filter(|id| id != "NOTPROVIDED"),
+                "SVWZ+" => purpose = non_empty(value),
+                _ => {}
+            }
+        }
+        return (non_empty(name), purpose, end_to_end_id);
+    }
+    
+    if joined.starts_with('/') && slash_pattern.is_match(&joined) {
+        let markers: Vec<_> = slash_pattern.captures_iter(&joined).collect();
+        let mut values = std::collections::HashMap::new();
+        for (index, marker) in markers.iter().enumerate() {
+            let whole = marker.get(0).expect("whole match");
+            let end = markers.get(index + 1).map(|next| next.get(0).expect("whole match").start()).unwrap_or(joined.len());
+            values.entry(marker[1].to_string()).or_insert_with(|| joined[whole.end()..end].trim_end_matches('/').to_string());
+        }
+        
+        let name = values.get("NAME").cloned().or_else(|| {
+            values.get("CNTP").and_then(|counterparty| counterparty.split('/').nth(2).map(str::to_string))
+        });
+        let remittance = values
+            .get("REMI")
+            .map(|remittance| remittance.strip_prefix("USTD//").unwrap_or(remittance).to_string());
+        let end_to_end_id = values.get("EREF").cloned().filter(|id| id != "NOTPROVIDED");
+        return (name.and_then(non_empty), remittance.and_then(non_empty), end_to_end_id.and_then(non_empty));
+    }
+    
+    (None, non_empty(lines.join(" ")), None)
+}
+
+/// Разбор строки `:61:` MT940
 /// 
-/// # Параметры
-/// - `conn` - ссылка на транзакцию или соединение
-/// - `entity` - тип сущности (account, operation, state)
-/// - `entity_id` - ID сущности
-/// - `action` - тип действия (create, update, delete)
-/// - `payload_json` - JSON-снимок состояния сущности
-fn write_version_log(
-    conn: &Connection,
-    path: &str,
-    db_key: &str,
-    entity: &str,
-    entity_id: i64,
-    action: &str,
-    payload_json: &str,
-) -> Result<(), DbError> {
-    // Получаем текущий timestamp в секундах
-    let ts = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .map_err(|e| DbError::InitError(format!("Failed to get timestamp: {}", e)))?
-        .as_secs() as i64;
+/// `YYMMDD[MMDD]` (валютирование и проводка), `C|D|RC|RD`, необязательный
+/// код средств, сумма, тип операции, референс клиента и `//референс банка`.
+fn parse_mt940_transaction(field: &Mt940Field, information: Option<&Mt940Field>) -> Result<ParsedImportLine, String> {
+    let pattern = regex::Regex::new(r"^(\d{6})(\d{4})?(R?[CD])([A-Z])?(\d+,\d*)([A-Z][A-Z0-9]{3})([^/]*)(?://(.*))?$")
+        .expect("valid regex");
+    let first = field.lines.first().map(|line| line.trim()).unwrap_or("");
+    let captures = pattern.captures(first).ok_or_else(|| format!("Invalid :61: line: {}", first))?;
     
-    // Записываем в version_log
-    conn.execute(
-        "INSERT INTO version_log (entity, entity_id, action, payload, ts) VALUES (?1, ?2, ?3, ?4, ?5)",
-        [
-            entity,
-            &entity_id.to_string(),
-            action,
-            payload_json,
-            &ts.to_string(),
-        ],
-    )?;
+    let value_date = parse_mt940_date(&captures[1])?;
+    let booking_date = match captures.get(2) {
+        Some(booking) => {
+            use chrono::Datelike;
+            
+            let month: u32 = booking.as_str()[..2].parse().map_err(|_| "Invalid booking date")?;
+            let day: u32 = booking.as_str()[2..].parse().map_err(|_| "Invalid booking date")?;
+            // Проводка может попасть на соседний год (валютирование 31.12, проводка 02.01)
+            let year = match (value_date.month(), month) {
+                (12, 1) => value_date.year() + 1,
+                (1, 12) => value_date.year() - 1,
+                _ => value_date.year(),
+            };
+            chrono::NaiveDate::from_ymd_opt(year, month, day).ok_or("Invalid booking date")?
+        }
+        None => value_date,
+    };
     
-    // Получаем id вставленной записи
-    let version_id = conn.last_insert_rowid();
+    let amount = parse_import_amount(&captures[5], ',')?.ok_or("Empty amount")?;
+    let amount = match &captures[3] {
+        "C" | "RD" => amount,
+        _ => -amount,
+    };
     
-    // Загружаем приватный и публичный ключи из keystore
-    let private_key = load_key_from_keystore(path, db_key, "ed25519_private")?
-        .ok_or_else(|| DbError::InitError("Ed25519 private key not found in keystore".to_string()))?;
+    let customer_reference = captures[7].trim();
+    let customer_reference = Some(customer_reference).filter(|reference| !reference.is_empty() && !reference.eq_ignore_ascii_case("NONREF"));
+    let bank_reference = captures
+        .get(8)
+        .map(|reference| reference.as_str().trim())
+        .filter(|reference| !reference.is_empty() && !reference.eq_ignore_ascii_case("NONREF"));
     
-    let public_key = load_key_from_keystore(path, db_key, "ed25519_public")?
-        .ok_or_else(|| DbError::InitError("Ed25519 public key not found in keystore".to_string()))?;
+    let (counterparty, remittance_info, end_to_end_id) = information
+        .map(|information| parse_mt940_information(&information.lines))
+        .unwrap_or((None, None, None));
+    let supplementary = field.lines.get(1).map(|line| line.trim());
+    let description = statement_description(&[
+        counterparty.as_deref(),
+        remittance_info.as_deref().or(supplementary).or(Some(&captures[6])),
+    ]);
     
-    // Сериализуем payload в bytes
-    let payload_bytes = payload_json.as_bytes();
+    Ok(ParsedImportLine {
+        line_number: field.line_number,
+        ts: local_date_bound(booking_date, false)?,
+        amount,
+        description,
+        external_id: bank_reference.map(str::to_string),
+        value_ts: Some(local_date_bound(value_date, false)?),
+        counterparty,
+        remittance_info,
+        end_to_end_id: end_to_end_id.or_else(|| customer_reference.map(str::to_string)),
+    })
+}
+
+/// Разбор выписки SWIFT MT940
+/// 
+/// Файл может содержать несколько сообщений по одному счёту (`:25:`);
+/// остаток - последний `:62F:` (или промежуточный `:62M:`). Кодировка -
+/// UTF-8, иначе CP1251 (выгрузки российских банков). Референс банка,
+/// повторяющийся в файле, не уникален - такие строки идут по хешу содержимого.
+fn parse_mt940_statement(content: &[u8]) -> Result<ParsedStatement, DbError> {
+    let text = decode_import_bytes(content, ImportEncoding::Utf8)
+        .or_else(|_| decode_import_bytes(content, ImportEncoding::Cp1251))?;
+    let fields = split_mt940_fields(&text);
+    
+    if !fields.iter().any(|field| field.tag == "20") {
+        return Err(DbError::ValidationError("Not an MT940 file: :20: field not found".to_string()));
+    }
+    
+    let accounts: std::collections::HashSet<&str> = fields
+        .iter()
+        .filter(|field| field.tag == "25")
+        .map(|field| field.lines[0].trim())
+        .collect();
+    if accounts.len() > 1 {
+        return Err(DbError::ValidationError(format!(
+            "MT940 file contains statements for {} accounts; import them separately",
+            accounts.len()
+        )));
+    }
     
-    // Подписываем payload
-    let signature = crate::crypto::sign_payload(payload_bytes, &private_key)
-        .map_err(|e| DbError::InitError(format!("Failed to sign payload: {}", e)))?;
+    let mut lines = Vec::new();
+    let mut errors = Vec::new();
+    let mut balance = None;
+    for (index, field) in fields.iter().enumerate() {
+        match field.tag.as_str() {
+            "61" => {
+                let information = fields.get(index + 1).filter(|next| next.tag == "86");
+                match parse_mt940_transaction(field, information) {
+                    Ok(line) => lines.push(line),
+                    Err(message) => errors.push(ImportLineError { line_number: field.line_number, message }),
+                }
+            }
+            "62F" | "62M" => {
+                let parsed = parse_mt940_balance(&field.lines[0]).map_err(|message| {
+                    DbError::ValidationError(format!("Closing balance at line {}: {}", field.line_number, message))
+                })?;
+                balance = Some(parsed);
+            }
+            _ => {}
+        }
+    }
     
-    // Записываем подпись в version_signatures
-    conn.execute(
-        "INSERT INTO version_signatures (version_id, signature, public_key, ts) VALUES (?1, ?2, ?3, ?4)",
-        rusqlite::params![version_id, &signature, &public_key, ts],
-    )?;
+    let mut references: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    for reference in lines.iter().filter_map(|line| line.external_id.clone()) {
+        *references.entry(reference).or_insert(0) += 1;
+    }
+    for line in &mut lines {
+        if line.external_id.as_ref().is_some_and(|reference| references[reference] > 1) {
+            line.external_id = None;
+        }
+    }
     
-    Ok(())
+    Ok(ParsedStatement { lines, errors, balance })
 }
 
-/// Верификация подписи записи version_log
-///
-/// # Arguments
-/// - `path` - путь к базе данных
-/// - `key` - ключ шифрования
-/// - `version_id` - ID записи в version_log
-///
-/// # Returns
-/// - `Ok(true)` - подпись валидна
-/// - `Ok(false)` - подпись невалидна
-/// - `Err` - ошибка при выполнении (запись не найдена, отсутствует подпись и т.д.)
-pub fn verify_version_signature(path: &str, key: &str, version_id: i64) -> Result<bool, DbError> {
+/// Предпросмотр импорта выписки camt.053
+pub fn preview_camt053_import(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    content: &[u8],
+) -> Result<StatementImportPreview, DbError> {
+    preview_statement(path, key, account_id, parse_camt053_statement(content)?)
+}
+
+/// Импорт выписки camt.053 со сверкой закрывающего остатка
+/// 
+/// Строки сопоставляются по референсу банка (AcctSvcrRef), при его
+/// отсутствии - по содержимому.
+pub fn import_camt053(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    file_name: String,
+    content: &[u8],
+) -> Result<StatementImportResult, DbError> {
+    import_statement(path, key, account_id, "camt053", &file_name, content, parse_camt053_statement(content)?)
+}
+
+/// Предпросмотр импорта выписки MT940
+pub fn preview_mt940_import(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    content: &[u8],
+) -> Result<StatementImportPreview, DbError> {
+    preview_statement(path, key, account_id, parse_mt940_statement(content)?)
+}
+
+/// Импорт выписки MT940 со сверкой остатка `:62F:`
+/// 
+/// Строки сопоставляются по референсу банка (`//` в `:61:`), при его
+/// отсутствии - по содержимому.
+pub fn import_mt940(
+    path: &str,
+    key: &str,
+    account_id: i64,
+    file_name: String,
+    content: &[u8],
+) -> Result<StatementImportResult, DbError> {
+    import_statement(path, key, account_id, "mt940", &file_name, content, parse_mt940_statement(content)?)
+}
+
+/// Список пакетов импорта (новые первыми)
+pub fn list_import_batches(path: &str, key: &str, account_id: Option<i64>) -> Result<Vec<ImportBatch>, DbError> {
     let conn = Connection::open(path)?;
     conn.pragma_update(None, "key", key)?;
     
-    // Извлекаем payload из version_log
-    let payload: String = conn.query_row(
-        "SELECT payload FROM version_log WHERE id = ?1",
-        [version_id],
-        |row| row.get(0),
-    ).map_err(|e| DbError::InitError(format!("Failed to get version_log payload: {}", e)))?;
-    
-    // Извлекаем signature и public_key из version_signatures
-    let (signature, public_key): (Vec<u8>, Vec<u8>) = conn.query_row(
-        "SELECT signature, public_key FROM version_signatures WHERE version_id = ?1",
-        [version_id],
-        |row| Ok((row.get(0)?, row.get(1)?)),
-    ).map_err(|e| DbError::InitError(format!("Failed to get signature: {}", e)))?;
+    let mut stmt = conn.prepare(
+        "SELECT id, account_id, source, file_name, file_sha256, line_count, imported_count, duplicate_count, imported_at
+         FROM import_batches
+         WHERE ?1 IS NULL OR account_id = ?1
+         ORDER BY imported_at DESC, id DESC"
+    )?;
     
-    // Верифицируем подпись
-    let payload_bytes = payload.as_bytes();
-    let is_valid = crate::crypto::verify_payload(payload_bytes, &signature, &public_key)
-        .map_err(|e| DbError::InitError(format!("Failed to verify signature: {}", e)))?;
+    let batches = stmt.query_map([account_id], |row| {
+        Ok(ImportBatch {
+            id: row.get(0)?,
+            account_id: row.get(1)?,
+            source: row.get(2)?,
+            file_name: row.get(3)?,
+            file_sha256: row.get(4)?,
+            line_count: row.get::<_, i64>(5)? as usize,
+            imported_count: row.get::<_, i64>(6)? as usize,
+            duplicate_count: row.get::<_, i64>(7)? as usize,
+            imported_at: row.get(8)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
     
-    Ok(is_valid)
+    Ok(batches)
 }
 
-// Функции работы со счетами
-
-/// Создание нового счёта
-pub fn create_account(path: &str, key: &str, name: String, acc_type: String) -> Result<i64, DbError> {
-    let conn = Connection::open(path)?;
+/// Сохранение профиля импорта (создание или обновление по имени)
+pub fn save_import_profile(
+    path: &str,
+    key: &str,
+    name: String,
+    options: CsvImportOptions,
+) -> Result<i64, DbError> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(DbError::ValidationError("Profile name is required".to_string()));
+    }
+    
+    validate_csv_options(&options)?;
+    
+    let mut conn = Connection::open(path)?;
     conn.pragma_update(None, "key", key)?;
     
-    // Получаем текущий timestamp в секундах
-    let created_at = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .map_err(|e| DbError::InitError(format!("Failed to get timestamp: {}", e)))?
-        .as_secs() as i64;
+    let tx = conn.transaction()?;
     
-    conn.execute(
-        "INSERT INTO accounts (name, type, created_at) VALUES (?1, ?2, ?3)",
-        [&name, &acc_type, &created_at.to_string()],
-    )?;
+    let options_json = serialize_entity(&options)?;
+    let updated_at = current_timestamp()?;
     
-    let account_id = conn.last_insert_rowid();
+    let existing: Option<(i64, i64)> = tx.query_row(
+        "SELECT id, created_at FROM import_profiles WHERE name = ?1",
+        [&name],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional()?;
     
-    // Создаём объект Account для логирования
-    let account = Account {
-        id: account_id,
-        name: name.clone(),
-        acc_type: acc_type.clone(),
-        created_at,
+    let (id, created_at, action) = match existing {
+        Some((id, created_at)) => {
+            tx.execute(
+                "UPDATE import_profiles SET name = ?1, options = ?2, updated_at = ?3 WHERE id = ?4",
+                rusqlite::params![name, options_json, updated_at, id],
+            )?;
+            (id, created_at, "update")
+        }
+        None => {
+            tx.execute(
+                "INSERT INTO import_profiles (name, options, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)",
+                rusqlite::params![name, options_json, updated_at],
+            )?;
+            (tx.last_insert_rowid(), updated_at, "create")
+        }
     };
     
-    // Сериализуем аккаунт в JSON
-    let payload_json = serialize_entity(&account)?;
+    let profile = ImportProfile { id, name, options, created_at, updated_at };
+    write_version_log(&tx, path, key, "import_profile", id, action, &serialize_entity(&profile)?)?;
     
-    // Логируем создание аккаунта
-    write_version_log(&conn, path, key, "account", account_id, "create", &payload_json)?;
+    tx.commit()?;
     
-    Ok(account_id)
+    Ok(id)
 }
 
-/// Получение списка всех счетов
-pub fn list_accounts(path: &str, key: &str) -> Result<Vec<Account>, DbError> {
+/// Маппинг строки import_profiles в структуру (параметры хранятся в JSON)
+fn import_profile_from_row(row: &rusqlite::Row) -> SqlResult<ImportProfile> {
+    let options: String = row.get(2)?;
+    let options = serde_json::from_str(&options).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+    
+    Ok(ImportProfile {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        options,
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+    })
+}
+
+/// Получение списка профилей импорта
+pub fn list_import_profiles(path: &str, key: &str) -> Result<Vec<ImportProfile>, DbError> {
     let conn = Connection::open(path)?;
     conn.pragma_update(None, "key", key)?;
     
     let mut stmt = conn.prepare(
-        "SELECT id, name, type, created_at FROM accounts ORDER BY created_at DESC"
+        "SELECT id, name, options, created_at, updated_at FROM import_profiles ORDER BY name COLLATE NOCASE ASC"
     )?;
     
-    let accounts = stmt.query_map([], |row| {
-        Ok(Account {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            acc_type: row.get(2)?,
-            created_at: row.get(3)?,
-        })
-    })?
-    .collect::<Result<Vec<_>, _>>()?;
+    let profiles = stmt.query_map([], import_profile_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
     
-    Ok(accounts)
+    Ok(profiles)
 }
 
-// Функции работы с операциями
+/// Удаление профиля импорта
+pub fn delete_import_profile(path: &str, key: &str, profile_id: i64) -> Result<(), DbError> {
+    let mut conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+    
+    let tx = conn.transaction()?;
+    
+    let profile = tx.query_row(
+        "SELECT id, name, options, created_at, updated_at FROM import_profiles WHERE id = ?1",
+        [profile_id],
+        import_profile_from_row,
+    ).optional()?
+    .ok_or_else(|| DbError::ValidationError(format!("Import profile {} not found", profile_id)))?;
+    
+    tx.execute("DELETE FROM import_profiles WHERE id = ?1", [profile_id])?;
+    
+    write_version_log(&tx, path, key, "import_profile", profile_id, "delete", &serialize_entity(&profile)?)?;
+    
+    tx.commit()?;
+    
+    Ok(())
+}
 
-/// Получение текущего баланса счёта
-fn get_current_balance(conn: &Connection, account_id: i64) -> SqlResult<f64> {
-    let balance: Result<f64, _> = conn.query_row(
-        "SELECT balance FROM states WHERE account_id = ?1 ORDER BY ts DESC LIMIT 1",
-        [account_id],
-        |row| row.get(0),
-    );
+// Экспорт и импорт текстового учёта (beancount, ledger/hledger)
+
+/// Счёт собственного капитала для начальных остатков
+const OPENING_BALANCES_ACCOUNT: &str = "Equity:Opening-Balances";
+
+/// Компонент счёта-корреспондента операций без категории (`Income:` / `Expenses:`)
+const UNCATEGORIZED_ACCOUNT: &str = "Uncategorized";
+
+/// Знак сальдо счёта в двойной записи
+///
+/// Обязательства, капитал и доходы хранятся положительными суммами,
+/// а в текстовом учёте имеют кредитовое (отрицательное) сальдо.
+fn ledger_sign(acc_class: AccountClass) -> f64 {
+    match acc_class {
+        AccountClass::Asset | AccountClass::Expense => 1.0,
+        AccountClass::Liability | AccountClass::Equity | AccountClass::Income => -1.0,
+    }
+}
+
+/// Корневой счёт текстового учёта для класса счёта
+fn ledger_root(acc_class: AccountClass) -> &'static str {
+    match acc_class {
+        AccountClass::Asset => "Assets",
+        AccountClass::Liability => "Liabilities",
+        AccountClass::Equity => "Equity",
+        AccountClass::Income => "Income",
+        AccountClass::Expense => "Expenses",
+    }
+}
+
+/// Класс счёта по корневому счёту текстового учёта
+fn class_from_ledger_root(root: &str) -> Option<AccountClass> {
+    match root {
+        "Assets" => Some(AccountClass::Asset),
+        "Liabilities" => Some(AccountClass::Liability),
+        "Equity" => Some(AccountClass::Equity),
+        "Income" => Some(AccountClass::Income),
+        "Expenses" => Some(AccountClass::Expense),
+        _ => None,
+    }
+}
+
+/// Уникальное имя счёта текстового учёта `Root:Component`
+///
+/// Компонент, как требует beancount, начинается с заглавной буквы или цифры
+/// и состоит из букв, цифр и дефисов. При совпадении имён добавляется `-<id>`.
+fn ledger_account_name(
+    used: &mut std::collections::HashSet<String>,
+    root: &str,
+    name: &str,
+    id: i64,
+) -> String {
+    let mut component = String::new();
+    for c in name.chars() {
+        if c.is_alphanumeric() {
+            component.push(c);
+        } else if !component.is_empty() && !component.ends_with('-') {
+            component.push('-');
+        }
+    }
     
-    // Если баланса нет, начинаем с 0
-    Ok(balance.unwrap_or(0.0))
+    let component = component.trim_end_matches('-');
+    let mut chars = component.chars();
+    let component = match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => id.to_string(),
+    };
+    
+    let account = format!("{}:{}", root, component);
+    if used.insert(account.clone()) {
+        return account;
+    }
+    let account = format!("{}-{}", account, id);
+    used.insert(account.clone());
+    account
 }
 
-/// Добавление операции с автоматическим обновлением баланса
-pub fn add_operation(
-    path: &str,
-    key: &str,
-    account_id: i64,
+/// Значение метаданных записи текстового учёта
+enum PlainTextMeta {
+    Int(i64),
+    Text(String),
+}
+
+/// Метаданные записи в порядке вывода
+type PlainTextMetaList = Vec<(&'static str, PlainTextMeta)>;
+
+/// Запись экспорта: текст директивы и ключ сортировки
+struct PlainTextBlock {
+    date: chrono::NaiveDate,
+    /// Порядок директив в пределах суток: open, balance, транзакции, close
+    order: u8,
+    ts: i64,
+    text: String,
+}
+
+/// Проводка экспортируемой транзакции (сумма в знаках двойной записи)
+struct PlainTextPostingOut {
+    account: String,
     amount: f64,
-    description: String,
-) -> Result<i64, DbError> {
-    let mut conn = Connection::open(path)?;
-    conn.pragma_update(None, "key", key)?;
+    assertion: Option<f64>,
+    meta: PlainTextMetaList,
+}
+
+/// Текст в одну строку (переводы строк и `;` ломают синтаксис ledger)
+fn plain_text_line(value: &str, format: PlainTextFormat) -> String {
+    let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    match format {
+        PlainTextFormat::Beancount => value,
+        PlainTextFormat::Ledger => value.replace(';', ","),
+    }
+}
+
+/// Строка beancount в кавычках
+fn beancount_string(value: &str) -> String {
+    format!("\"{}\"", plain_text_line(value, PlainTextFormat::Beancount).replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Сумма с валютой; `-0.00` не выводится
+fn plain_text_amount(amount: f64, currency: &str) -> String {
+    format!("{:.2} {}", round_cents(amount) + 0.0, currency)
+}
+
+/// Строки метаданных: `key: value` в beancount, `; key: value` в ledger
+fn render_plain_meta(out: &mut String, format: PlainTextFormat, indent: &str, meta: &[(&str, PlainTextMeta)]) {
+    for (key, value) in meta {
+        let value = match (format, value) {
+            (_, PlainTextMeta::Int(value)) => value.to_string(),
+            (PlainTextFormat::Beancount, PlainTextMeta::Text(value)) => beancount_string(value),
+            (PlainTextFormat::Ledger, PlainTextMeta::Text(value)) => plain_text_line(value, format),
+        };
+        match format {
+            PlainTextFormat::Beancount => out.push_str(&format!("{}{}: {}\n", indent, key, value)),
+            PlainTextFormat::Ledger => out.push_str(&format!("{}; {}: {}\n", indent, key, value)),
+        }
+    }
+}
+
+/// Текст транзакции
+fn render_plain_transaction(
+    format: PlainTextFormat,
+    currency: &str,
+    date: chrono::NaiveDate,
+    description: &str,
+    meta: &[(&str, PlainTextMeta)],
+    postings: &[PlainTextPostingOut],
+) -> String {
+    let (mut out, indent, posting_meta_indent) = match format {
+        PlainTextFormat::Beancount => (format!("{} * {}\n", date, beancount_string(description)), "  ", "    "),
+        PlainTextFormat::Ledger => (format!("{} * {}\n", date, plain_text_line(description, format)), "    ", "    "),
+    };
+    render_plain_meta(&mut out, format, indent, meta);
     
-    // Начинаем транзакцию
-    let tx = conn.transaction()?;
+    for posting in postings {
+        out.push_str(&format!("{}{}  {}", indent, posting.account, plain_text_amount(posting.amount, currency)));
+        if let Some(assertion) = posting.assertion {
+            out.push_str(&format!(" = {}", plain_text_amount(assertion, currency)));
+        }
+        out.push('\n');
+        render_plain_meta(&mut out, format, posting_meta_indent, &posting.meta);
+    }
     
-    // Получаем текущий timestamp
-    let ts = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .map_err(|e| DbError::InitError(format!("Failed to get timestamp: {}", e)))?
-        .as_secs() as i64;
+    out
+}
+
+/// Учёт первого использования счёта (дата open не может быть позже)
+fn note_account_use(first_use: &mut std::collections::HashMap<String, i64>, account: &str, ts: i64) {
+    let entry = first_use.entry(account.to_string()).or_insert(ts);
+    *entry = (*entry).min(ts);
+}
+
+/// Экспорт учёта в beancount или ledger/hledger
+///
+/// Выгружаются счета, начальные остатки, операции, переводы и сверки баланса.
+/// Операция становится транзакцией из проводки по счёту и проводки по категории
+/// (`Income:<категория>` для поступлений, `Expenses:<категория>` для списаний);
+/// у разбитой операции - по проводке на каждую строку разбивки. Перевод -
+/// одна транзакция по двум счетам. Идентификаторы записей и точные
+/// моменты времени сохраняются в метаданных `fam_*`, поэтому `import_plain_text`
+/// восстанавливает их при обратном импорте.
+///
+/// Сверка на момент `ts` в beancount выгружается директивой `balance`
+/// на следующие сутки (beancount проверяет остаток на начало дня) с суммой,
+/// учитывающей движения до конца суток сверки; в ledger - утверждением
+/// `= сумма` в отдельной транзакции.
+///
+/// # Параметры
+/// - `format` - синтаксис (beancount или ledger)
+/// - `currency` - код валюты сумм (`RUB`, `EUR`, ...)
+pub fn export_plain_text(
+    path: &str,
+    key: &str,
+    format: PlainTextFormat,
+    currency: String,
+) -> Result<String, DbError> {
+    let currency_pattern = regex::Regex::new(r"^[A-Z](?:[A-Z0-9'._-]{0,22}[A-Z0-9])?$")
+        .map_err(|e| DbError::InitError(format!("Invalid currency pattern: {}", e)))?;
+    if !currency_pattern.is_match(&currency) {
+        return Err(DbError::ValidationError(format!("Invalid currency code: {}", currency)));
+    }
     
-    // Вставляем операцию
-    tx.execute(
-        "INSERT INTO operations (account_id, amount, description, ts) VALUES (?1, ?2, ?3, ?4)",
-        [&account_id.to_string(), &amount.to_string(), &description, &ts.to_string()],
-    )?;
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
     
-    let operation_id = tx.last_insert_rowid();
+    let accounts = {
+        let mut stmt = conn.prepare("SELECT id, name, class, type, created_at, closed_at FROM accounts ORDER BY id")?;
+        let rows = stmt.query_map([], account_from_row)?.collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    let categories: std::collections::HashMap<i64, String> = {
+        let mut stmt = conn.prepare("SELECT id, name FROM categories")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        rows
+    };
+    let operations = {
+        let mut stmt = conn.prepare(
+            "SELECT id, account_id, amount, description, ts, payee_id, category_id, notes
+             FROM operations ORDER BY ts ASC, id ASC"
+        )?;
+        let rows = stmt.query_map([], operation_from_row)?.collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    let transfers = {
+        let mut stmt = conn.prepare(
+            "SELECT id, from_account_id, to_account_id, from_operation_id, to_operation_id, amount, description, ts
+             FROM transfers ORDER BY ts ASC, id ASC"
+        )?;
+        let rows = stmt.query_map([], transfer_from_row)?.collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    let openings = {
+        let mut stmt = conn.prepare("SELECT id, account_id, balance, ts FROM opening_balances ORDER BY ts ASC, id ASC")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(OpeningBalance {
+                id: row.get(0)?,
+                account_id: row.get(1)?,
+                balance: row.get(2)?,
+                ts: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    let assertions = {
+        let mut stmt = conn.prepare(
+            "SELECT id, account_id, expected_balance, ts, created_at FROM balance_assertions ORDER BY ts ASC, id ASC"
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(BalanceAssertion {
+                id: row.get(0)?,
+                account_id: row.get(1)?,
+                expected_balance: row.get(2)?,
+                ts: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
     
-    // Создаём объект Operation для логирования
-    let operation = Operation {
-        id: operation_id,
-        account_id,
-        amount,
-        description: description.clone(),
-        ts,
+    // Имена счетов: служебные резервируются первыми
+    let uncategorized = |income: bool| {
+        format!("{}:{}", if income { "Income" } else { "Expenses" }, UNCATEGORIZED_ACCOUNT)
+    };
+    let mut used: std::collections::HashSet<String> =
+        [OPENING_BALANCES_ACCOUNT.to_string(), uncategorized(true), uncategorized(false)].into_iter().collect();
+    let account_names: std::collections::HashMap<i64, (String, &Account)> = accounts
+        .iter()
+        .map(|account| {
+            let name = ledger_account_name(&mut used, ledger_root(account.acc_class), &account.name, account.id);
+            (account.id, (name, account))
+        })
+        .collect();
+    let account_of = |account_id: i64| {
+        account_names
+            .get(&account_id)
+            .ok_or_else(|| DbError::ValidationError(format!("Account {} not found", account_id)))
     };
     
-    // Сериализуем операцию в JSON
-    let operation_json = serialize_entity(&operation)?;
+    let mut category_names: std::collections::HashMap<(i64, bool), String> = std::collections::HashMap::new();
+    let mut first_use: std::collections::HashMap<String, i64> = std::collections::HashMap::new();
+    let mut blocks = Vec::new();
     
-    // Логируем создание операции
-    write_version_log(&tx, path, key, "operation", operation_id, "create", &operation_json)?;
+    for opening in &openings {
+        let (name, account) = account_of(opening.account_id)?;
+        let amount = opening.balance * ledger_sign(account.acc_class);
+        note_account_use(&mut first_use, name, opening.ts);
+        note_account_use(&mut first_use, OPENING_BALANCES_ACCOUNT, opening.ts);
+        
+        let postings = [
+            PlainTextPostingOut { account: name.clone(), amount, assertion: None, meta: Vec::new() },
+            PlainTextPostingOut { account: OPENING_BALANCES_ACCOUNT.to_string(), amount: -amount, assertion: None, meta: Vec::new() },
+        ];
+        let meta = [
+            ("fam_opening_balance_id", PlainTextMeta::Int(opening.id)),
+            ("fam_ts", PlainTextMeta::Int(opening.ts)),
+        ];
+        let date = local_date(opening.ts)?;
+        blocks.push(PlainTextBlock {
+            date,
+            order: 2,
+            ts: opening.ts,
+            text: render_plain_transaction(format, &currency, date, "Opening balance", &meta, &postings),
+        });
+    }
     
-    // Получаем текущий баланс
-    let current_balance = get_current_balance(&tx, account_id)?;
+    let operations_by_id: std::collections::HashMap<i64, &Operation> =
+        operations.iter().map(|operation| (operation.id, operation)).collect();
+    let mut transfer_operations = std::collections::HashSet::new();
     
-    // Рассчитываем новый баланс
-    let new_balance = current_balance + amount;
+    for transfer in &transfers {
+        let mut postings = Vec::with_capacity(2);
+        for operation_id in [transfer.from_operation_id, transfer.to_operation_id] {
+            let operation = operations_by_id
+                .get(&operation_id)
+                .ok_or_else(|| DbError::ValidationError(format!("Operation {} not found", operation_id)))?;
+            let (name, account) = account_of(operation.account_id)?;
+            note_account_use(&mut first_use, name, transfer.ts);
+            transfer_operations.insert(operation_id);
+            postings.push(PlainTextPostingOut {
+                account: name.clone(),
+                amount: operation.amount * ledger_sign(account.acc_class),
+                assertion: None,
+                meta: vec![("fam_operation_id", PlainTextMeta::Int(operation_id))],
+            });
+        }
+        
+        let meta = [
+            ("fam_transfer_id", PlainTextMeta::Int(transfer.id)),
+            ("fam_ts", PlainTextMeta::Int(transfer.ts)),
+        ];
+        let date = local_date(transfer.ts)?;
+        blocks.push(PlainTextBlock {
+            date,
+            order: 2,
+            ts: transfer.ts,
+            text: render_plain_transaction(format, &currency, date, &transfer.description, &meta, &postings),
+        });
+    }
     
-    // Создаём новую запись баланса в states
-    tx.execute(
-        "INSERT INTO states (account_id, balance, ts) VALUES (?1, ?2, ?3)",
-        [&account_id.to_string(), &new_balance.to_string(), &ts.to_string()],
-    )?;
+    for operation in operations.iter().filter(|operation| !transfer_operations.contains(&operation.id)) {
+        let (name, account) = account_of(operation.account_id)?;
+        let sign = ledger_sign(account.acc_class);
+        let amount = operation.amount * sign;
+        let income = amount > 0.0;
+        note_account_use(&mut first_use, name, operation.ts);
+        
+        // Разбитая операция - по проводке на строку разбивки, иначе одна по категории
+        let splits = load_operation_splits(&conn, operation.id)?;
+        let lines: Vec<(Option<i64>, f64, &str)> = if splits.is_empty() {
+            vec![(operation.category_id, amount, "")]
+        } else {
+            splits.iter().map(|split| (split.category_id, split.amount * sign, split.memo.as_str())).collect()
+        };
+        
+        let mut postings = vec![PlainTextPostingOut {
+            account: name.clone(),
+            amount,
+            assertion: None,
+            meta: vec![("fam_operation_id", PlainTextMeta::Int(operation.id))],
+        }];
+        for (category_id, line_amount, memo) in lines {
+            let category_name = match category_id.and_then(|id| categories.get(&id).map(|name| (id, name))) {
+                Some((category_id, category)) => category_names
+                    .entry((category_id, income))
+                    .or_insert_with(|| {
+                        let root = if income { "Income" } else { "Expenses" };
+                        ledger_account_name(&mut used, root, category, category_id)
+                    })
+                    .clone(),
+                None => uncategorized(income),
+            };
+            note_account_use(&mut first_use, &category_name, operation.ts);
+            
+            let meta = if memo.is_empty() {
+                Vec::new()
+            } else {
+                vec![("fam_memo", PlainTextMeta::Text(memo.to_string()))]
+            };
+            postings.push(PlainTextPostingOut { account: category_name, amount: -line_amount, assertion: None, meta });
+        }
+        let meta = [("fam_ts", PlainTextMeta::Int(operation.ts))];
+        let date = local_date(operation.ts)?;
+        blocks.push(PlainTextBlock {
+            date,
+            order: 2,
+            ts: operation.ts,
+            text: render_plain_transaction(format, &currency, date, &operation.description, &meta, &postings),
+        });
+    }
     
-    let state_id = tx.last_insert_rowid();
+    for assertion in &assertions {
+        let (name, account) = account_of(assertion.account_id)?;
+        note_account_use(&mut first_use, name, assertion.ts);
+        
+        let meta = [
+            ("fam_balance_assertion_id", PlainTextMeta::Int(assertion.id)),
+            ("fam_ts", PlainTextMeta::Int(assertion.ts)),
+        ];
+        let date = local_date(assertion.ts)?;
+        let block = match format {
+            PlainTextFormat::Beancount => {
+                // beancount проверяет остаток на начало следующих суток: добавляем
+                // движения от момента сверки до конца её суток
+                let day_end = local_date_bound(date, true).map_err(DbError::ValidationError)?;
+                let movement = get_balance_at(&conn, assertion.account_id, day_end)?
+                    - get_balance_at(&conn, assertion.account_id, assertion.ts)?;
+                let amount = (assertion.expected_balance + movement) * ledger_sign(account.acc_class);
+                let date = date
+                    .succ_opt()
+                    .ok_or_else(|| DbError::ValidationError(format!("Invalid date: {}", date)))?;
+                let mut text = format!("{} balance {}  {}\n", date, name, plain_text_amount(amount, &currency));
+                render_plain_meta(&mut text, format, "  ", &meta);
+                PlainTextBlock { date, order: 1, ts: assertion.ts, text }
+            }
+            PlainTextFormat::Ledger => {
+                let postings = [PlainTextPostingOut {
+                    account: name.clone(),
+                    amount: 0.0,
+                    assertion: Some(assertion.expected_balance * ledger_sign(account.acc_class)),
+                    meta: Vec::new(),
+                }];
+                PlainTextBlock {
+                    date,
+                    order: 2,
+                    ts: assertion.ts,
+                    text: render_plain_transaction(format, &currency, date, "Balance assertion", &meta, &postings),
+                }
+            }
+        };
+        blocks.push(block);
+    }
     
-    // Создаём объект State для логирования
-    let state = State {
-        id: state_id,
-        account_id,
-        balance: new_balance,
-        ts,
-    };
+    // Открытие счетов: beancount - директивы open, ledger - директивы account в начале файла
+    let mut opens: Vec<(i64, String, PlainTextMetaList)> = Vec::new();
+    for account in &accounts {
+        let (name, _) = account_of(account.id)?;
+        let opened_at = [first_use.get(name).copied(), account.closed_at]
+            .into_iter()
+            .flatten()
+            .fold(account.created_at, i64::min);
+        let mut meta = vec![
+            ("fam_account_id", PlainTextMeta::Int(account.id)),
+            ("fam_name", PlainTextMeta::Text(account.name.clone())),
+            ("fam_type", PlainTextMeta::Text(account.acc_type.clone())),
+        ];
+        if let Some(closed_at) = account.closed_at {
+            match format {
+                PlainTextFormat::Beancount => {
+                    let date = local_date(closed_at)?;
+                    let mut text = format!("{} close {}\n", date, name);
+                    render_plain_meta(&mut text, format, "  ", &[("fam_ts", PlainTextMeta::Int(closed_at))]);
+                    blocks.push(PlainTextBlock { date, order: 3, ts: closed_at, text });
+                }
+                PlainTextFormat::Ledger => meta.push(("fam_closed_at", PlainTextMeta::Int(closed_at))),
+            }
+        }
+        opens.push((opened_at, name.clone(), meta));
+    }
+    for ((category_id, _), name) in &category_names {
+        let meta = vec![
+            ("fam_category_id", PlainTextMeta::Int(*category_id)),
+            ("fam_name", PlainTextMeta::Text(categories[category_id].clone())),
+        ];
+        opens.push((first_use[name], name.clone(), meta));
+    }
+    for name in [OPENING_BALANCES_ACCOUNT.to_string(), uncategorized(true), uncategorized(false)] {
+        if let Some(ts) = first_use.get(&name) {
+            opens.push((*ts, name, Vec::new()));
+        }
+    }
+    opens.sort_by(|a, b| a.1.cmp(&b.1));
     
-    // Сериализуем state в JSON
-    let state_json = serialize_entity(&state)?;
+    let mut out = match format {
+        PlainTextFormat::Beancount => format!(";; fam-core export\noption \"operating_currency\" \"{}\"\n\n", currency),
+        PlainTextFormat::Ledger => format!("; fam-core export\ncommodity {}\n\n", currency),
+    };
     
-    // Логируем создание state
-    write_version_log(&tx, path, key, "state", state_id, "create", &state_json)?;
+    for (opened_at, name, meta) in opens {
+        match format {
+            PlainTextFormat::Beancount => {
+                let date = local_date(opened_at)?;
+                let mut text = format!("{} open {} {}\n", date, name, currency);
+                render_plain_meta(&mut text, format, "  ", &meta);
+                blocks.push(PlainTextBlock { date, order: 0, ts: opened_at, text });
+            }
+            PlainTextFormat::Ledger => {
+                out.push_str(&format!("account {}\n", name));
+                render_plain_meta(&mut out, format, "    ", &meta);
+            }
+        }
+    }
+    if format == PlainTextFormat::Ledger {
+        out.push('\n');
+    }
     
-    // Коммитим транзакцию (включая операцию, баланс и оба лога)
-    tx.commit()?;
+    blocks.sort_by_key(|block| (block.date, block.order, block.ts));
+    let body = blocks.into_iter().map(|block| block.text).collect::<Vec<_>>().join("\n");
+    out.push_str(&body);
     
-    Ok(operation_id)
+    Ok(out)
 }
 
-/// Получение списка операций по счёту
-pub fn get_operations(path: &str, key: &str, account_id: i64) -> Result<Vec<Operation>, DbError> {
-    let conn = Connection::open(path)?;
-    conn.pragma_update(None, "key", key)?;
-    
-    let mut stmt = conn.prepare(
-        "SELECT id, account_id, amount, description, ts FROM operations 
-         WHERE account_id = ?1 ORDER BY ts DESC"
-    )?;
-    
-    let operations = stmt.query_map([account_id], |row| {
-        Ok(Operation {
-            id: row.get(0)?,
-            account_id: row.get(1)?,
-            amount: row.get(2)?,
-            description: row.get(3)?,
-            ts: row.get(4)?,
-        })
-    })?
-    .collect::<Result<Vec<_>, _>>()?;
+/// Директива текстового учёта после разбора
+enum PlainTextDirective {
+    Open { account: String },
+    Close { account: String },
+    Balance { account: String, amount: f64 },
+    Transaction { description: String, postings: Vec<PlainTextPosting> },
+}
+
+impl PlainTextDirective {
+    /// Порядок применения в пределах суток (как в beancount)
+    fn order(&self) -> u8 {
+        match self {
+            PlainTextDirective::Open { .. } => 0,
+            PlainTextDirective::Balance { .. } => 1,
+            PlainTextDirective::Transaction { .. } => 2,
+            PlainTextDirective::Close { .. } => 3,
+        }
+    }
+}
+
+/// Запись текстового учёта с датой и метаданными
+///
+/// `date = None` - директива `account` ledger (без даты).
+struct PlainTextEntry {
+    line_number: usize,
+    date: Option<chrono::NaiveDate>,
+    directive: PlainTextDirective,
+    meta: std::collections::HashMap<String, String>,
+}
+
+/// Проводка транзакции; сумма в знаках двойной записи
+struct PlainTextPosting {
+    account: String,
+    /// `None` - сумма опущена и выводится из баланса транзакции
+    amount: Option<f64>,
+    /// Утверждение остатка ledger (`= сумма`)
+    assertion: Option<f64>,
+    meta: std::collections::HashMap<String, String>,
+}
+
+/// Отделение комментария `;` (вне строк в кавычках)
+fn split_plain_comment(line: &str) -> (&str, Option<&str>) {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ';' if !in_string => return (&line[..index], Some(&line[index + 1..])),
+            _ => {}
+        }
+    }
+    (line, None)
+}
+
+/// Первое слово строки и остаток
+fn split_plain_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    match text.find(char::is_whitespace) {
+        Some(index) => (&text[..index], text[index..].trim_start()),
+        None => (text, ""),
+    }
+}
+
+/// Разбор метаданных `key: value`; значение в кавычках раскрывается
+fn parse_plain_meta(text: &str) -> Option<(String, String)> {
+    let (key, value) = text.trim().split_once(':')?;
+    let mut chars = key.chars();
+    if !chars.next()?.is_ascii_alphabetic() || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return None;
+    }
+    if !value.is_empty() && !value.starts_with(char::is_whitespace) {
+        return None;
+    }
     
-    Ok(operations)
+    let value = value.trim();
+    let value = match value.strip_prefix('"').and_then(|value| value.strip_suffix('"')) {
+        Some(quoted) => quoted.replace("\\\"", "\"").replace("\\\\", "\\"),
+        None => value.to_string(),
+    };
+    Some((key.to_string(), value))
 }
 
-// Функции агрегирования
+/// Разбор даты: `2024-01-05`, `2024/01/05` или `2024.01.05`
+fn parse_plain_date(raw: &str) -> Option<chrono::NaiveDate> {
+    ["%Y-%m-%d", "%Y/%m/%d", "%Y.%m.%d"]
+        .iter()
+        .find_map(|format| chrono::NaiveDate::parse_from_str(raw, format).ok())
+}
 
-/// Получение текущего баланса аккаунта
-/// 
-/// Возвращает последнее значение баланса из таблицы states
-/// Если записей нет, возвращает 0.0
+/// Разбор суммы с валютой: `-1,234.56 RUB`, `$-12.00`, `-$12`, `EUR 10`
 /// 
-/// # Параметры
-/// - `path` - путь к базе данных
-/// - `key` - ключ шифрования
-/// - `account_id` - ID аккаунта
-pub fn get_account_balance(path: &str, key: &str, account_id: i64) -> Result<f64, DbError> {
-    let conn = Connection::open(path)?;
-    conn.pragma_update(None, "key", key)?;
+/// Запятая - только разделитель тысяч; десятичная запятая (`1,5`,
+/// `1.234,56`) отклоняется, а не читается как другое число.
+fn parse_plain_amount(raw: &str) -> Result<f64, String> {
+    let number = regex::Regex::new(r"\d[\d,.]*")
+        .map_err(|e| format!("Invalid amount pattern: {}", e))?;
+    let grouping = regex::Regex::new(r"^(?:\d{1,3}(?:,\d{3})+|\d+)(?:\.\d*)?$")
+        .map_err(|e| format!("Invalid amount pattern: {}", e))?;
+    let found = number.find(raw).ok_or_else(|| format!("Invalid amount: {}", raw.trim()))?;
+    if !grouping.is_match(found.as_str()) {
+        return Err(format!("Invalid amount: {}", raw.trim()));
+    }
     
-    let balance: Result<f64, _> = conn.query_row(
-        "SELECT balance FROM states WHERE account_id = ?1 ORDER BY ts DESC LIMIT 1",
-        [account_id],
-        |row| row.get(0),
-    );
+    let value: f64 = found
+        .as_str()
+        .replace(',', "")
+        .parse()
+        .map_err(|_| format!("Invalid amount: {}", raw.trim()))?;
+    if raw[..found.start()].contains('-') {
+        Ok(-value)
+    } else {
+        Ok(value)
+    }
+}
+
+/// Строки в кавычках заголовка транзакции beancount (до тегов и ссылок)
+fn parse_beancount_strings(text: &str) -> Result<Vec<String>, String> {
+    let mut strings = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(body) = rest.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = body.char_indices();
+        let end = loop {
+            match chars.next() {
+                Some((_, '\\')) => {
+                    if let Some((_, escaped)) = chars.next() {
+                        value.push(escaped);
+                    }
+                }
+                Some((index, '"')) => break index,
+                Some((_, c)) => value.push(c),
+                None => return Err("Unterminated string".to_string()),
+            }
+        };
+        strings.push(value);
+        rest = body[end + 1..].trim_start();
+    }
+    Ok(strings)
+}
+
+/// Привязка метаданных к последней проводке транзакции или к самой записи
+fn attach_plain_meta(entry: &mut PlainTextEntry, key: String, value: String) {
+    match &mut entry.directive {
+        PlainTextDirective::Transaction { postings, .. } if !postings.is_empty() => {
+            postings.last_mut().map(|posting| posting.meta.insert(key, value));
+        }
+        _ => {
+            entry.meta.insert(key, value);
+        }
+    }
+}
+
+/// Ошибка разбора с номером строки
+fn plain_text_error(line_number: usize, message: impl std::fmt::Display) -> DbError {
+    DbError::ValidationError(format!("Line {}: {}", line_number, message))
+}
+
+/// Разбор файла beancount
+///
+/// Поддерживаются директивы open, close, balance и транзакции (`*`, `!`, `txn`);
+/// остальные датированные директивы пропускаются с предупреждением.
+fn parse_beancount(text: &str) -> Result<(Vec<PlainTextEntry>, Vec<ImportLineError>), DbError> {
+    let mut entries: Vec<PlainTextEntry> = Vec::new();
+    let mut warnings = Vec::new();
+    // Отступ относится к последней записи, только если она поддерживается
+    let mut in_entry = false;
     
-    // Если баланса нет, возвращаем 0.0
-    Ok(balance.unwrap_or(0.0))
+    for (index, raw_line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let (code, _) = split_plain_comment(raw_line);
+        if code.trim().is_empty() {
+            continue;
+        }
+        
+        if raw_line.starts_with([' ', '\t']) {
+            let Some(entry) = entries.last_mut().filter(|_| in_entry) else {
+                continue;
+            };
+            let code = code.trim();
+            if code.starts_with(|c: char| c.is_ascii_lowercase()) {
+                let (key, value) = parse_plain_meta(code)
+                    .ok_or_else(|| plain_text_error(line_number, format!("Invalid metadata: {}", code)))?;
+                attach_plain_meta(entry, key, value);
+                continue;
+            }
+            
+            let PlainTextDirective::Transaction { postings, .. } = &mut entry.directive else {
+                return Err(plain_text_error(line_number, format!("Unexpected line: {}", code)));
+            };
+            let (first, rest) = split_plain_word(code);
+            let (account, rest) = if first == "*" || first == "!" { split_plain_word(rest) } else { (first, rest) };
+            let amount = rest.split(['{', '@']).next().unwrap_or("").trim();
+            let amount = if amount.is_empty() {
+                None
+            } else {
+                Some(parse_plain_amount(amount).map_err(|e| plain_text_error(line_number, e))?)
+            };
+            postings.push(PlainTextPosting {
+                account: account.to_string(),
+                amount,
+                assertion: None,
+                meta: std::collections::HashMap::new(),
+            });
+            continue;
+        }
+        
+        in_entry = false;
+        let (first, rest) = split_plain_word(code);
+        let Some(date) = parse_plain_date(first) else {
+            // option, plugin, pushtag и заголовки org-mode не влияют на учёт
+            if !matches!(first, "option" | "plugin" | "pushtag" | "poptag") && !first.starts_with('*') {
+                warnings.push(ImportLineError {
+                    line_number,
+                    message: format!("Unsupported directive '{}' skipped", first),
+                });
+            }
+            continue;
+        };
+        
+        let (kind, rest) = split_plain_word(rest);
+        let (account, account_rest) = split_plain_word(rest);
+        let directive = match kind {
+            "open" => PlainTextDirective::Open { account: account.to_string() },
+            "close" => PlainTextDirective::Close { account: account.to_string() },
+            "balance" => {
+                let amount = account_rest.split('~').next().unwrap_or("");
+                let amount = parse_plain_amount(amount).map_err(|e| plain_text_error(line_number, e))?;
+                PlainTextDirective::Balance { account: account.to_string(), amount }
+            }
+            "*" | "!" | "txn" => {
+                let strings = parse_beancount_strings(rest).map_err(|e| plain_text_error(line_number, e))?;
+                let (payee, narration) = match strings.as_slice() {
+                    [payee, narration, ..] => (Some(payee.as_str()), Some(narration.as_str())),
+                    [narration] => (None, Some(narration.as_str())),
+                    [] => (None, None),
+                };
+                PlainTextDirective::Transaction {
+                    description: statement_description(&[payee, narration]),
+                    postings: Vec::new(),
+                }
+            }
+            _ => {
+                warnings.push(ImportLineError {
+                    line_number,
+                    message: format!("Unsupported directive '{}' skipped", kind),
+                });
+                continue;
+            }
+        };
+        
+        entries.push(PlainTextEntry {
+            line_number,
+            date: Some(date),
+            directive,
+            meta: std::collections::HashMap::new(),
+        });
+        in_entry = true;
+    }
+    
+    Ok((entries, warnings))
 }
 
-/// Вычисление общего Net Worth
-/// 
-/// Возвращает сумму всех текущих балансов по всем аккаунтам
-/// Для каждого аккаунта берётся последняя запись из states
-/// 
-/// # Параметры
-/// - `path` - путь к базе данных
-/// - `key` - ключ шифрования
-pub fn get_net_worth(path: &str, key: &str) -> Result<f64, DbError> {
-    let conn = Connection::open(path)?;
-    conn.pragma_update(None, "key", key)?;
+/// Разбор файла ledger/hledger
+///
+/// Поддерживаются директивы `account` и транзакции; утверждения остатка
+/// записываются в проводках (`= сумма`). Метаданные - в комментариях `; key: value`.
+fn parse_ledger(text: &str) -> Result<(Vec<PlainTextEntry>, Vec<ImportLineError>), DbError> {
+    let mut entries: Vec<PlainTextEntry> = Vec::new();
+    let mut warnings = Vec::new();
+    let mut in_entry = false;
     
-    // Получаем последний баланс для каждого аккаунта и суммируем
-    let net_worth: f64 = conn.query_row(
-        "SELECT COALESCE(SUM(balance), 0.0) FROM (
-            SELECT DISTINCT account_id, 
-                   (SELECT balance FROM states s2 
-                    WHERE s2.account_id = s1.account_id 
-                    ORDER BY ts DESC LIMIT 1) as balance
-            FROM states s1
-        )",
-        [],
+    for (index, raw_line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        if raw_line.trim().is_empty() {
+            in_entry = false;
+            continue;
+        }
+        
+        if raw_line.starts_with([' ', '\t']) {
+            let Some(entry) = entries.last_mut().filter(|_| in_entry) else {
+                continue;
+            };
+            let (code, comment) = split_plain_comment(raw_line);
+            let code = code.trim();
+            
+            if !code.is_empty() {
+                // Подкоманды директивы account (note, alias, ...) не влияют на учёт
+                let PlainTextDirective::Transaction { postings, .. } = &mut entry.directive else {
+                    continue;
+                };
+                let code = code.strip_prefix(['*', '!']).unwrap_or(code).trim_start();
+                let split = [code.find("  "), code.find('\t')].into_iter().flatten().min().unwrap_or(code.len());
+                let account = code[..split].trim_matches(['(', ')', '[', ']']);
+                let (amount, assertion) = match code[split..].split_once('=') {
+                    Some((amount, assertion)) => (amount, Some(assertion)),
+                    None => (&code[split..], None),
+                };
+                let amount = amount.split('@').next().unwrap_or("").trim();
+                let amount = if amount.is_empty() {
+                    None
+                } else {
+                    Some(parse_plain_amount(amount).map_err(|e| plain_text_error(line_number, e))?)
+                };
+                let assertion = assertion
+                    .map(|assertion| parse_plain_amount(assertion).map_err(|e| plain_text_error(line_number, e)))
+                    .transpose()?;
+                if amount.is_none() && assertion.is_some() {
+                    return Err(plain_text_error(line_number, "Balance assignments are not supported"));
+                }
+                postings.push(PlainTextPosting {
+                    account: account.to_string(),
+                    amount,
+                    assertion,
+                    meta: std::collections::HashMap::new(),
+                });
+            }
+            if let Some((key, value)) = comment.and_then(parse_plain_meta) {
+                attach_plain_meta(entry, key, value);
+            }
+            continue;
+        }
+        
+        in_entry = false;
+        if raw_line.starts_with([';', '#', '%', '|', '*']) {
+            continue;
+        }
+        
+        let (code, comment) = split_plain_comment(raw_line);
+        let (first, rest) = split_plain_word(code);
+        let (date, directive) = if first == "account" {
+            (None, PlainTextDirective::Open { account: rest.trim().to_string() })
+        } else if let Some(date) = parse_plain_date(first.split('=').next().unwrap_or(first)) {
+            let description = rest.strip_prefix(['*', '!']).unwrap_or(rest).trim_start();
+            let description = match description.strip_prefix('(').and_then(|code| code.split_once(')')) {
+                Some((_, description)) => description,
+                None => description,
+            };
+            let directive = PlainTextDirective::Transaction {
+                description: statement_description(&[Some(description)]),
+                postings: Vec::new(),
+            };
+            (Some(date), directive)
+        } else {
+            if first != "commodity" {
+                warnings.push(ImportLineError {
+                    line_number,
+                    message: format!("Unsupported directive '{}' skipped", first),
+                });
+            }
+            continue;
+        };
+        
+        let mut entry = PlainTextEntry {
+            line_number,
+            date,
+            directive,
+            meta: std::collections::HashMap::new(),
+        };
+        if let Some((key, value)) = comment.and_then(parse_plain_meta) {
+            entry.meta.insert(key, value);
+        }
+        entries.push(entry);
+        in_entry = true;
+    }
+    
+    Ok((entries, warnings))
+}
+
+/// Целочисленное значение метаданных (ID, момент времени)
+fn plain_meta_int(meta: &std::collections::HashMap<String, String>, key: &str) -> Result<Option<i64>, DbError> {
+    meta.get(key)
+        .map(|value| {
+            value.trim().parse::<i64>().map_err(|_| {
+                DbError::ValidationError(format!("Metadata '{}' must be an integer: {}", key, value))
+            })
+        })
+        .transpose()
+}
+
+/// Свободен ли ID в таблице (для сохранения идентификаторов при импорте)
+fn id_available(conn: &Connection, table: &str, id: Option<i64>) -> Result<Option<i64>, DbError> {
+    let Some(id) = id else {
+        return Ok(None);
+    };
+    let taken: bool = conn.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM {} WHERE id = ?1)", table),
+        [id],
         |row| row.get(0),
     )?;
-    
-    Ok(net_worth)
+    Ok(if taken { None } else { Some(id) })
 }
 
-/// Получение временного ряда балансов для аккаунта
-/// 
-/// Возвращает все записи из таблицы states для указанного аккаунта
-/// с сортировкой по временной метке (ts) в порядке возрастания
-/// 
-/// # Параметры
-/// - `path` - путь к базе данных
-/// - `key` - ключ шифрования
-/// - `account_id` - ID аккаунта
-pub fn get_balance_history(
-    path: &str,
-    key: &str,
-    account_id: i64,
-) -> Result<Vec<State>, DbError> {
-    let conn = Connection::open(path)?;
-    conn.pragma_update(None, "key", key)?;
+/// Счёт текстового учёта, сопоставленный записи БД
+#[derive(Clone, Copy)]
+enum PlainTextAccount {
+    Own(i64, AccountClass),
+    Category(Option<i64>),
+    OpeningBalances,
+}
+
+/// Применение разобранных записей в рамках транзакции вызывающего
+struct PlainTextImporter<'a> {
+    conn: &'a Connection,
+    path: &'a str,
+    key: &'a str,
+    accounts: std::collections::HashMap<String, PlainTextAccount>,
+    /// Закрытия из директив `account` ledger применяются после всех транзакций
+    deferred_closes: Vec<(usize, String, i64)>,
+    result: PlainTextImportResult,
+}
+
+impl PlainTextImporter<'_> {
+    /// Сопоставление счёта по директиве open (или при первом использовании)
+    ///
+    /// Assets, Liabilities и Equity - счета, Income и Expenses - категории;
+    /// счёт с `fam_account_id` всегда сопоставляется счёту БД.
+    fn open(&mut self, name: &str, meta: &std::collections::HashMap<String, String>) -> Result<PlainTextAccount, DbError> {
+        if let Some(account) = self.accounts.get(name) {
+            return Ok(*account);
+        }
+        
+        let (root, rest) = name
+            .split_once(':')
+            .ok_or_else(|| DbError::ValidationError(format!("Invalid account name: {}", name)))?;
+        let acc_class = class_from_ledger_root(root)
+            .ok_or_else(|| DbError::ValidationError(format!("Unknown root account: {}", root)))?;
+        let display_name = meta.get("fam_name").cloned().unwrap_or_else(|| rest.to_string());
+        let account_id = plain_meta_int(meta, "fam_account_id")?;
+        let category_id = plain_meta_int(meta, "fam_category_id")?;
+        
+        let account = if name == OPENING_BALANCES_ACCOUNT && account_id.is_none() {
+            PlainTextAccount::OpeningBalances
+        } else if account_id.is_some() || matches!(acc_class, AccountClass::Asset | AccountClass::Liability | AccountClass::Equity) {
+            let existing: Option<i64> = self.conn.query_row(
+                "SELECT id FROM accounts WHERE name = ?1 AND class = ?2 ORDER BY id = ?3 DESC, id ASC LIMIT 1",
+                rusqlite::params![display_name, acc_class, account_id],
+                |row| row.get(0),
+            ).optional()?;
+            let id = match existing {
+                Some(id) => {
+                    if Some(id) == account_id {
+                        self.result.skipped_count += 1;
+                    }
+                    id
+                }
+                None => {
+                    let acc_type = meta.get("fam_type").cloned().unwrap_or_else(|| acc_class.as_str().to_string());
+                    let id = id_available(self.conn, "accounts", account_id)?;
+                    self.result.accounts_created += 1;
+                    insert_account(self.conn, self.path, self.key, id, display_name, acc_class, acc_type)?
+                }
+            };
+            PlainTextAccount::Own(id, acc_class)
+        } else if rest == UNCATEGORIZED_ACCOUNT && category_id.is_none() {
+            PlainTextAccount::Category(None)
+        } else {
+            let existing: Option<i64> = self.conn.query_row(
+                "SELECT id FROM categories WHERE name = ?1",
+                [&display_name],
+                |row| row.get(0),
+            ).optional()?;
+            let id = match existing {
+                Some(id) => id,
+                None => {
+                    self.result.categories_created += 1;
+                    insert_category(self.conn, self.path, self.key, display_name)?
+                }
+            };
+            PlainTextAccount::Category(Some(id))
+        };
+        
+        self.accounts.insert(name.to_string(), account);
+        Ok(account)
+    }
     
-    let mut stmt = conn.prepare(
-        "SELECT id, account_id, balance, ts FROM states 
-         WHERE account_id = ?1 
-         ORDER BY ts ASC"
-    )?;
+    /// Счёт БД, к которому относится запись
+    /// 
+    /// Директива по счёту категории (Income, Expenses) пропускается с
+    /// предупреждением: в БД ни закрытия, ни сверки у категорий нет.
+    fn own_account(&mut self, line_number: usize, directive: &str, name: &str) -> Result<Option<(i64, AccountClass)>, DbError> {
+        let is_category = match self.accounts.get(name) {
+            Some(account) => matches!(account, PlainTextAccount::Category(_)),
+            None => name
+                .split_once(':')
+                .and_then(|(root, _)| class_from_ledger_root(root))
+                .is_some_and(|acc_class| matches!(acc_class, AccountClass::Income | AccountClass::Expense)),
+        };
+        if is_category {
+            self.result.warnings.push(ImportLineError {
+                line_number,
+                message: format!("Directive '{}' on category account {} skipped", directive, name),
+            });
+            return Ok(None);
+        }
+        
+        match self.open(name, &std::collections::HashMap::new())? {
+            PlainTextAccount::Own(account_id, acc_class) => Ok(Some((account_id, acc_class))),
+            _ => Err(DbError::ValidationError(format!("{} is not an asset or liability account", name))),
+        }
+    }
     
-    let states = stmt.query_map([account_id], |row| {
-        Ok(State {
-            id: row.get(0)?,
-            account_id: row.get(1)?,
-            balance: row.get(2)?,
-            ts: row.get(3)?,
-        })
-    })?
-    .collect::<Result<Vec<_>, _>>()?;
+    fn close(&mut self, line_number: usize, name: &str, ts: i64) -> Result<(), DbError> {
+        let Some((account_id, _)) = self.own_account(line_number, "close", name)? else {
+            return Ok(());
+        };
+        if load_account(self.conn, account_id)?.closed_at.is_some() {
+            self.result.skipped_count += 1;
+            return Ok(());
+        }
+        mark_account_closed(self.conn, self.path, self.key, account_id, ts)?;
+        self.result.accounts_closed += 1;
+        Ok(())
+    }
     
-    Ok(states)
+    /// Сверка баланса; совпадающая с существующей пропускается
+    /// 
+    /// `amount` - остаток на момент `checked_at`, сверка сохраняется на момент
+    /// `ts`: движения между ними вычитаются из ожидаемого остатка.
+    fn assert_balance(
+        &mut self,
+        line_number: usize,
+        name: &str,
+        amount: f64,
+        checked_at: i64,
+        ts: i64,
+        id: Option<i64>,
+    ) -> Result<(), DbError> {
+        let Some((account_id, acc_class)) = self.own_account(line_number, "balance", name)? else {
+            return Ok(());
+        };
+        let movement = get_balance_at(self.conn, account_id, checked_at)? - get_balance_at(self.conn, account_id, ts)?;
+        let expected_balance = amount * ledger_sign(acc_class) - movement;
+        
+        let exists: bool = self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM balance_assertions
+                           WHERE account_id = ?1 AND ts = ?2 AND ABS(expected_balance - ?3) <= ?4)",
+            rusqlite::params![account_id, ts, expected_balance, AMOUNT_EPSILON],
+            |row| row.get(0),
+        )?;
+        if exists {
+            self.result.skipped_count += 1;
+            return Ok(());
+        }
+        
+        let id = id_available(self.conn, "balance_assertions", id)?;
+        insert_balance_assertion(self.conn, self.path, self.key, id, account_id, expected_balance, ts)?;
+        self.result.assertions_created += 1;
+        Ok(())
+    }
+    
+    fn apply(&mut self, entry: &PlainTextEntry) -> Result<(), DbError> {
+        let fam_ts = plain_meta_int(&entry.meta, "fam_ts")?;
+        let day_bound = |end_of_day: bool| -> Result<i64, DbError> {
+            let date = entry.date.ok_or_else(|| DbError::ValidationError("Missing date".to_string()))?;
+            local_date_bound(date, end_of_day).map_err(DbError::ValidationError)
+        };
+        
+        match &entry.directive {
+            PlainTextDirective::Open { account } => {
+                self.open(account, &entry.meta)?;
+                if let Some(closed_at) = plain_meta_int(&entry.meta, "fam_closed_at")? {
+                    self.deferred_closes.push((entry.line_number, account.clone(), closed_at));
+                }
+            }
+            PlainTextDirective::Close { account } => {
+                let ts = match fam_ts {
+                    Some(ts) => ts,
+                    None => day_bound(true)?,
+                };
+                self.close(entry.line_number, account, ts)?;
+            }
+            PlainTextDirective::Balance { account, amount } => {
+                // beancount проверяет остаток на начало суток
+                let checked_at = day_bound(false)? - 1;
+                let id = plain_meta_int(&entry.meta, "fam_balance_assertion_id")?;
+                self.assert_balance(entry.line_number, account, *amount, checked_at, fam_ts.unwrap_or(checked_at), id)?;
+            }
+            PlainTextDirective::Transaction { description, postings } => {
+                let ts = match fam_ts {
+                    Some(ts) => ts,
+                    None => day_bound(false)?,
+                };
+                self.apply_transaction(entry, description, postings, ts, day_bound(true)?)?;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Транзакция: начальный остаток, перевод между своими счетами
+    /// или операции по счетам с категорией из второй проводки
+    fn apply_transaction(
+        &mut self,
+        entry: &PlainTextEntry,
+        description: &str,
+        postings: &[PlainTextPosting],
+        ts: i64,
+        end_of_day: i64,
+    ) -> Result<(), DbError> {
+        let known: f64 = postings.iter().filter_map(|posting| posting.amount).sum();
+        let elided = postings.iter().filter(|posting| posting.amount.is_none()).count();
+        if elided > 1 {
+            return Err(DbError::ValidationError("Only one posting may omit the amount".to_string()));
+        }
+        if elided == 0 && known.abs() > AMOUNT_EPSILON {
+            return Err(DbError::ValidationError(format!("Transaction does not balance ({:.2})", known)));
+        }
+        
+        let mut own = Vec::new();
+        let mut others = Vec::new();
+        for posting in postings {
+            let amount = posting.amount.unwrap_or(-known);
+            let account = self.open(&posting.account, &std::collections::HashMap::new())?;
+            
+            if let Some(assertion) = posting.assertion {
+                let assertion_ts = match plain_meta_int(&posting.meta, "fam_ts")?.or(plain_meta_int(&entry.meta, "fam_ts")?) {
+                    Some(ts) => ts,
+                    None => end_of_day,
+                };
+                let id = plain_meta_int(&posting.meta, "fam_balance_assertion_id")?
+                    .or(plain_meta_int(&entry.meta, "fam_balance_assertion_id")?);
+                self.assert_balance(entry.line_number, &posting.account, assertion, assertion_ts, assertion_ts, id)?;
+            }
+            
+            if amount.abs() < AMOUNT_EPSILON {
+                continue;
+            }
+            match account {
+                PlainTextAccount::Own(account_id, acc_class) => own.push((account_id, acc_class, amount, posting)),
+                other => others.push((other, amount, posting)),
+            }
+        }
+        
+        match (own.as_slice(), others.as_slice()) {
+            ([], []) => Ok(()),
+            ([(account_id, acc_class, amount, _)], [(PlainTextAccount::OpeningBalances, _, _)]) => {
+                let balance = amount * ledger_sign(*acc_class);
+                let existing: Option<(f64, i64)> = self.conn.query_row(
+                    "SELECT balance, ts FROM opening_balances WHERE account_id = ?1",
+                    [account_id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                ).optional()?;
+                if matches!(existing, Some((old_balance, old_ts)) if old_ts == ts && (old_balance - balance).abs() <= AMOUNT_EPSILON) {
+                    self.result.skipped_count += 1;
+                    return Ok(());
+                }
+                upsert_opening_balance(self.conn, self.path, self.key, *account_id, balance, ts)?;
+                self.result.opening_balances_set += 1;
+                Ok(())
+            }
+            ([from, to], [])
+                if from.2 * to.2 < 0.0
+                    && [from.1, to.1].iter().all(|class| matches!(class, AccountClass::Asset | AccountClass::Liability)) =>
+            {
+                let (from, to) = if from.2 < 0.0 { (from, to) } else { (to, from) };
+                let amount = to.2.abs();
+                
+                let transfer_id = plain_meta_int(&entry.meta, "fam_transfer_id")?;
+                let exists: bool = self.conn.query_row(
+                    "SELECT EXISTS(SELECT 1 FROM transfers
+                                   WHERE id = ?1 AND from_account_id = ?2 AND to_account_id = ?3 AND ABS(amount - ?4) <= ?5)",
+                    rusqlite::params![transfer_id, from.0, to.0, amount, AMOUNT_EPSILON],
+                    |row| row.get(0),
+                )?;
+                if exists {
+                    self.result.skipped_count += 1;
+                    return Ok(());
+                }
+                
+                let ids = TransferIds {
+                    transfer: id_available(self.conn, "transfers", transfer_id)?,
+                    from_operation: id_available(self.conn, "operations", plain_meta_int(&from.3.meta, "fam_operation_id")?)?,
+                    to_operation: id_available(self.conn, "operations", plain_meta_int(&to.3.meta, "fam_operation_id")?)?,
+                };
+                insert_transfer(self.conn, self.path, self.key, ids, from.0, to.0, amount, description, ts)?;
+                self.result.transfers_created += 1;
+                Ok(())
+            }
+            ([], _) => {
+                self.result.warnings.push(ImportLineError {
+                    line_number: entry.line_number,
+                    message: "Transaction has no postings to own accounts; skipped".to_string(),
+                });
+                Ok(())
+            }
+            (own, others) => {
+                let category_id = match others {
+                    [(PlainTextAccount::Category(category_id), _, _)] => *category_id,
+                    _ => None,
+                };
+                
+                // Одна операция и несколько категорий того же знака - разбивка
+                let splits: Vec<SplitLineInput> = match own {
+                    [(_, acc_class, amount, _)] if others.len() > 1 => others
+                        .iter()
+                        .map(|(account, split_amount, posting)| match account {
+                            PlainTextAccount::Category(category_id) if split_amount * amount < 0.0 => Some(SplitLineInput {
+                                category_id: *category_id,
+                                amount: -split_amount * ledger_sign(*acc_class),
+                                memo: posting.meta.get("fam_memo").cloned().unwrap_or_default(),
+                            }),
+                            _ => None,
+                        })
+                        .collect::<Option<Vec<_>>>()
+                        .unwrap_or_default(),
+                    _ => Vec::new(),
+                };
+                
+                for (account_id, acc_class, amount, posting) in own {
+                    let amount = amount * ledger_sign(*acc_class);
+                    let operation_id = plain_meta_int(&posting.meta, "fam_operation_id")?;
+                    let exists: bool = self.conn.query_row(
+                        "SELECT EXISTS(SELECT 1 FROM operations
+                                       WHERE id = ?1 AND account_id = ?2 AND ts = ?3 AND ABS(amount - ?4) <= ?5)",
+                        rusqlite::params![operation_id, account_id, ts, amount, AMOUNT_EPSILON],
+                        |row| row.get(0),
+                    )?;
+                    if exists {
+                        self.result.skipped_count += 1;
+                        continue;
+                    }
+                    
+                    let id = id_available(self.conn, "operations", operation_id)?;
+                    let operation_id = insert_operation_with_id(
+                        self.conn, self.path, self.key, id, *account_id, amount, description, ts,
+                    )?;
+                    if category_id.is_some() {
+                        assign_operation_category(self.conn, self.path, self.key, operation_id, category_id)?;
+                    }
+                    if !splits.is_empty() {
+                        replace_operation_splits(self.conn, self.path, self.key, operation_id, &splits)?;
+                    }
+                    self.result.operations_created += 1;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
-/// Получение структуры активов (группировка по типам с агрегированием балансов)
-/// 
-/// Возвращает распределение активов по типам аккаунтов
-/// Для каждого типа вычисляется:
-/// - Общая сумма балансов всех аккаунтов этого типа
-/// - Количество аккаунтов
-/// 
-/// # Параметры
-/// - `path` - путь к базе данных
-/// - `key` - ключ шифрования
-pub fn get_asset_allocation(
+/// Ошибка применения записи с номером строки
+fn plain_text_line_error(line_number: usize, error: DbError) -> DbError {
+    match error {
+        DbError::ValidationError(message) => plain_text_error(line_number, message),
+        other => other,
+    }
+}
+
+/// Импорт файла beancount или ledger/hledger
+///
+/// Понимает открытие и закрытие счетов, транзакции и сверки остатков.
+/// Транзакция по своему счёту и `Equity:Opening-Balances` становится начальным
+/// остатком, по двум счетам-активам/обязательствам - переводом, остальные -
+/// операциями по своим счетам; категория берётся из единственной проводки
+/// по `Income:`/`Expenses:`, несколько таких проводок при одном своём счёте
+/// становятся разбивкой операции. Идентификаторы из метаданных `fam_*` (см.
+/// `export_plain_text`) сохраняются, если свободны; записи, уже имеющиеся
+/// в БД под теми же идентификаторами, пропускаются, поэтому повторный импорт
+/// собственного экспорта ничего не меняет.
+///
+/// Файл импортируется в одной транзакции: при ошибке не записывается ничего.
+pub fn import_plain_text(
     path: &str,
     key: &str,
-) -> Result<Vec<AssetAllocation>, DbError> {
-    let conn = Connection::open(path)?;
+    format: PlainTextFormat,
+    content: &[u8],
+) -> Result<PlainTextImportResult, DbError> {
+    let text = decode_import_bytes(content, ImportEncoding::Utf8)?;
+    let (mut entries, warnings) = match format {
+        PlainTextFormat::Beancount => parse_beancount(&text)?,
+        PlainTextFormat::Ledger => parse_ledger(&text)?,
+    };
+    entries.sort_by_key(|entry| {
+        let fam_ts = plain_meta_int(&entry.meta, "fam_ts").ok().flatten();
+        (entry.date, entry.directive.order(), fam_ts)
+    });
+    
+    let mut conn = Connection::open(path)?;
     conn.pragma_update(None, "key", key)?;
     
-    // SQL запрос с группировкой по типам и суммированием балансов
-    // Включаем только аккаунты, у которых есть хотя бы одна запись в states
-    let mut stmt = conn.prepare(
-        "SELECT 
-            a.type,
-            SUM(latest_balances.balance) as total_balance,
-            COUNT(a.id) as account_count
-         FROM accounts a
-         INNER JOIN (
-             SELECT DISTINCT account_id,
-                    (SELECT balance FROM states s2 
-                     WHERE s2.account_id = s1.account_id 
-                     ORDER BY ts DESC LIMIT 1) as balance
-             FROM states s1
-         ) latest_balances ON latest_balances.account_id = a.id
-         GROUP BY a.type
-         ORDER BY total_balance DESC"
-    )?;
+    let tx = conn.transaction()?;
     
-    let allocations = stmt.query_map([], |row| {
-        Ok(AssetAllocation {
-            asset_type: row.get(0)?,
-            total_balance: row.get(1)?,
-            account_count: row.get(2)?,
-        })
-    })?
-    .collect::<Result<Vec<_>, _>>()?;
+    let result = {
+        let mut importer = PlainTextImporter {
+            conn: &tx,
+            path,
+            key,
+            accounts: std::collections::HashMap::new(),
+            deferred_closes: Vec::new(),
+            result: PlainTextImportResult { warnings, ..Default::default() },
+        };
+        for entry in &entries {
+            importer.apply(entry).map_err(|e| plain_text_line_error(entry.line_number, e))?;
+        }
+        for (line_number, account, closed_at) in std::mem::take(&mut importer.deferred_closes) {
+            importer.close(line_number, &account, closed_at).map_err(|e| plain_text_line_error(line_number, e))?;
+        }
+        importer.result
+    };
     
-    Ok(allocations)
+    tx.commit()?;
+    
+    Ok(result)
 }
 
 // Функции для работы с keystore
@@ -103,13 +103,113 @@ pub fn run() {
             // API commands (high-level without path/key)
             api::create_account,
             api::list_accounts,
+            api::set_account_class,
+            api::close_account,
             api::add_operation,
             api::get_operations,
             api::list_versions,
             api::get_account_balance,
             api::get_net_worth,
+            api::get_net_worth_breakdown,
+            api::get_net_worth_history,
             api::get_balance_history,
+            api::get_balance_history_resampled,
             api::get_asset_allocation,
+            api::set_allocation_target,
+            api::list_allocation_targets,
+            api::delete_allocation_target,
+            api::get_rebalance_plan,
+            api::create_transfer,
+            api::list_transfers,
+            api::get_cash_flow,
+            api::create_recurring_item,
+            api::list_recurring_items,
+            api::delete_recurring_item,
+            api::forecast_balances,
+            api::preview_csv_import,
+            api::import_csv,
+            api::preview_ofx_import,
+            api::import_ofx,
+            api::preview_camt053_import,
+            api::import_camt053,
+            api::preview_mt940_import,
+            api::import_mt940,
+            api::export_plain_text,
+            api::import_plain_text,
+            api::list_import_batches,
+            api::save_import_profile,
+            api::list_import_profiles,
+            api::delete_import_profile,
+            api::set_category_tax_kind,
+            api::generate_tax_summary,
+            api::verify_signed_export,
+            api::list_anomalies,
+            api::set_anomaly_status,
+            api::set_opening_balance,
+            api::get_opening_balance,
+            api::add_balance_assertion,
+            api::list_balance_assertions,
+            api::check_balance_assertions,
+            api::start_reconciliation,
+            api::get_reconciliation,
+            api::list_reconciliations,
+            api::auto_match_statement,
+            api::confirm_statement_match,
+            api::create_operation_from_statement_line,
+            api::complete_reconciliation,
+            api::create_security,
+            api::list_securities,
+            api::add_security_price,
+            api::get_price_history,
+            api::add_investment_transaction,
+            api::get_investment_transactions,
+            api::get_holdings,
+            api::sell_security,
+            api::get_tax_lots,
+            api::get_realized_gains,
+            api::get_investment_returns,
+            api::create_loan,
+            api::list_loans,
+            api::record_loan_payment,
+            api::get_loan_payments,
+            api::get_amortization_schedule,
+            api::get_loan_payoff_projection,
+            api::create_payee,
+            api::list_payees,
+            api::add_payee_rule,
+            api::list_payee_rules,
+            api::delete_payee_rule,
+            api::set_operation_payee,
+            api::apply_payee_rules,
+            api::merge_payees,
+            api::get_spending_by_payee,
+            api::create_category,
+            api::list_categories,
+            api::create_tag,
+            api::list_tags,
+            api::get_operation_tags,
+            api::add_operation_tag,
+            api::remove_operation_tag,
+            api::set_operation_category,
+            api::create_categorization_rule,
+            api::update_categorization_rule,
+            api::delete_categorization_rule,
+            api::list_categorization_rules,
+            api::apply_categorization_rules,
+            api::set_operation_splits,
+            api::get_operation_splits,
+            api::get_spending_by_category,
+            api::set_operation_notes,
+            api::search_operations,
+            api::query_operations,
+            api::add_attachment,
+            api::list_attachments,
+            api::get_attachment,
+            api::delete_attachment,
+            api::create_goal,
+            api::list_goals,
+            api::delete_goal,
+            api::get_goal_progress,
             api::verify_entry,
             api::list_signed_versions,
             api::make_request,
@@ -0,0 +1,14 @@
+#![allow(dead_code)]
+
+/// Фиксированный «текущий» момент тестов (2024-06-15 10:00 UTC), чтобы
+/// результаты не зависели от даты запуска.
+pub const T0: i64 = 1718445600;
+
+/// Текущее время для тестов функций, которые сами берут текущий момент
+/// (прогноз остатков, прогресс целей); остальные тесты используют [`T0`].
+pub fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
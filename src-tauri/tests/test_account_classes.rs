@@ -0,0 +1,81 @@
+use fam_core_lib::db;
+use fam_core_lib::db::AccountClass;
+use std::fs;
+
+#[test]
+fn test_infer_account_class_from_type() {
+    assert_eq!(AccountClass::infer_from_type("cash"), AccountClass::Asset);
+    assert_eq!(AccountClass::infer_from_type("card"), AccountClass::Asset);
+    assert_eq!(AccountClass::infer_from_type("Credit_Card"), AccountClass::Liability);
+    assert_eq!(AccountClass::infer_from_type("mortgage"), AccountClass::Liability);
+    assert_eq!(AccountClass::infer_from_type("income"), AccountClass::Income);
+    assert_eq!(AccountClass::infer_from_type("something"), AccountClass::Asset);
+
+    assert!(AccountClass::parse("liability").is_ok());
+    assert!(matches!(AccountClass::parse("debt"), Err(db::DbError::ValidationError(_))));
+}
+
+#[test]
+fn test_net_worth_assets_minus_liabilities() {
+    let db_path = "/tmp/test_account_classes.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let wallet = db::create_account(db_path, key, "Wallet".to_string(), "cash".to_string())
+        .expect("Failed to create wallet");
+    let visa = db::create_account(db_path, key, "Visa".to_string(), "credit_card".to_string())
+        .expect("Failed to create credit card");
+    let debit = db::create_account(db_path, key, "Debit".to_string(), "card".to_string())
+        .expect("Failed to create debit card");
+    let equity = db::create_account_with_class(db_path, key, "Opening equity".to_string(),
+        AccountClass::Equity, "opening".to_string())
+        .expect("Failed to create equity account");
+
+    let accounts = db::list_accounts(db_path, key).expect("Failed to list accounts");
+    let class_of = |id: i64| accounts.iter().find(|a| a.id == id).map(|a| a.acc_class);
+    assert_eq!(class_of(wallet), Some(AccountClass::Asset));
+    assert_eq!(class_of(visa), Some(AccountClass::Liability));
+    assert_eq!(class_of(debit), Some(AccountClass::Asset));
+    assert_eq!(class_of(equity), Some(AccountClass::Equity));
+    println!("✓ Account classes inferred from types");
+
+    db::add_operation(db_path, key, wallet, 1000.0, "Cash".to_string()).expect("Failed to add operation");
+    db::add_operation(db_path, key, visa, 300.0, "Card debt".to_string()).expect("Failed to add operation");
+    db::add_operation(db_path, key, debit, 200.0, "Debit".to_string()).expect("Failed to add operation");
+    db::add_operation(db_path, key, equity, 5000.0, "Equity".to_string()).expect("Failed to add operation");
+
+    // Долг по кредитке уменьшает Net Worth, капитал не учитывается
+    let breakdown = db::get_net_worth_breakdown(db_path, key).expect("Failed to get net worth");
+    assert_eq!(breakdown.assets, 1200.0);
+    assert_eq!(breakdown.liabilities, 300.0);
+    assert_eq!(breakdown.net_worth, 900.0);
+    assert_eq!(db::get_net_worth(db_path, key).expect("Failed to get net worth"), 900.0);
+    println!("✓ Net worth = assets {} - liabilities {}", breakdown.assets, breakdown.liabilities);
+
+    // В структуре активов только счета класса asset
+    let allocation = db::get_asset_allocation(db_path, key).expect("Failed to get allocation");
+    let types: Vec<&str> = allocation.iter().map(|a| a.asset_type.as_str()).collect();
+    assert_eq!(types, vec!["cash", "card"]);
+
+    // Переклассификация счёта логируется и меняет Net Worth
+    db::set_account_class(db_path, key, debit, AccountClass::Liability, "overdraft".to_string())
+        .expect("Failed to set account class");
+    let breakdown = db::get_net_worth_breakdown(db_path, key).expect("Failed to get net worth");
+    assert_eq!(breakdown.assets, 1000.0);
+    assert_eq!(breakdown.liabilities, 500.0);
+
+    let log = db::list_version_log(db_path, key, Some("account".to_string()), Some(debit))
+        .expect("Failed to list version log");
+    assert_eq!(log.len(), 2);
+    assert_eq!(log[0].action, "update");
+    assert!(log[0].payload.contains("\"class\":\"liability\""));
+    assert!(log[0].payload.contains("\"type\":\"overdraft\""));
+
+    println!("\n✅ Account class tests passed!");
+
+    let _ = fs::remove_file(db_path);
+}
@@ -0,0 +1,180 @@
+mod common;
+
+use common::T0;
+use fam_core_lib::db;
+use std::fs;
+
+const DAY: i64 = 86400;
+
+#[test]
+fn test_anomalies() {
+    let db_path = "/tmp/test_anomalies.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let start = T0;
+
+    let checking = db::create_account(db_path, key, "Checking".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let savings = db::create_account(db_path, key, "Savings".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let coffee = db::create_payee(db_path, key, "Coffee House".to_string()).expect("Failed to create payee");
+
+    db::add_operation_at(db_path, key, checking, 3000.0, "Salary".to_string(), start - 50 * DAY)
+        .expect("Failed to add operation");
+
+    // Типичный диапазон контрагента: 4-6 за чашку
+    for (i, amount) in [4.5, 5.0, 5.5, 4.0, 6.0, 5.0, 5.2, 4.8].iter().enumerate() {
+        let id = db::add_operation_at(db_path, key, checking, -amount, "Coffee".to_string(), start - (40 - 4 * i as i64) * DAY)
+            .expect("Failed to add operation");
+        db::set_operation_payee(db_path, key, id, Some(coffee)).expect("Failed to set payee");
+    }
+    let outlier = db::add_operation_at(db_path, key, checking, -60.0, "Coffee".to_string(), start - 5 * DAY)
+        .expect("Failed to add operation");
+    db::set_operation_payee(db_path, key, outlier, Some(coffee)).expect("Failed to set payee");
+
+    // Дубликат по описанию без контрагента
+    let first_charge = db::add_operation_at(db_path, key, checking, -15.0, "NETFLIX.COM".to_string(), start - 3 * DAY)
+        .expect("Failed to add operation");
+    let second_charge = db::add_operation_at(db_path, key, checking, -15.0, "Netflix.com".to_string(), start - 2 * DAY)
+        .expect("Failed to add operation");
+
+    // Крупная сумма у нового контрагента
+    let jeweler = db::add_operation_at(db_path, key, checking, -900.0, "Jeweler".to_string(), start - DAY)
+        .expect("Failed to add operation");
+
+    // Переводы не являются расходами и не считаются дубликатами
+    db::create_transfer(db_path, key, checking, savings, 100.0, "Save".to_string(), Some(start - DAY)).expect("Failed to transfer");
+    db::create_transfer(db_path, key, checking, savings, 100.0, "Save".to_string(), Some(start - DAY + 60)).expect("Failed to transfer");
+
+    let options = db::AnomalyOptions {
+        large_amount: Some(500.0),
+        ..Default::default()
+    };
+    let anomalies = db::list_anomalies(db_path, key, options.clone()).expect("Failed to list anomalies");
+    for anomaly in &anomalies {
+        println!("  {} #{} score {:.2}: {}", anomaly.kind, anomaly.operation.id, anomaly.score, anomaly.reason);
+    }
+    assert_eq!(anomalies.len(), 3);
+
+    assert_eq!(anomalies[0].kind, "new_payee_large");
+    assert_eq!(anomalies[0].operation.id, jeweler);
+    assert!((anomalies[0].score - 1.8).abs() < 1e-9);
+
+    assert_eq!(anomalies[1].kind, "duplicate");
+    assert_eq!(anomalies[1].operation.id, second_charge);
+    assert_eq!(anomalies[1].related_operation_id, Some(first_charge));
+    assert!((anomalies[1].score - 1.0).abs() < 1e-9);
+
+    assert_eq!(anomalies[2].kind, "outlier_amount");
+    assert_eq!(anomalies[2].operation.id, outlier);
+    assert!(anomalies[2].score > 3.5);
+    assert!(anomalies.iter().all(|anomaly| anomaly.status == "open"));
+    println!("✓ Outlier, duplicate and new payee detected");
+
+    // Период проверки ограничивает операции, история сравнения - вся
+    let recent = db::list_anomalies(db_path, key, db::AnomalyOptions {
+        from_ts: Some(start - 6 * DAY),
+        to_ts: Some(start - 4 * DAY),
+        ..options.clone()
+    }).expect("Failed to list anomalies");
+    assert_eq!(recent.len(), 1);
+    assert_eq!(recent[0].operation.id, outlier);
+
+    // Узкое окно дубликатов
+    let narrow = db::list_anomalies(db_path, key, db::AnomalyOptions {
+        duplicate_window_days: Some(0),
+        ..options.clone()
+    }).expect("Failed to list anomalies");
+    assert!(narrow.iter().all(|anomaly| anomaly.kind != "duplicate"));
+    println!("✓ Period and duplicate window respected");
+
+    // Решения пользователя
+    db::set_anomaly_status(db_path, key, jeweler, "new_payee_large".to_string(), "dismissed".to_string())
+        .expect("Failed to dismiss");
+    db::set_anomaly_status(db_path, key, second_charge, "duplicate".to_string(), "acknowledged".to_string())
+        .expect("Failed to acknowledge");
+
+    let anomalies = db::list_anomalies(db_path, key, options.clone()).expect("Failed to list anomalies");
+    assert_eq!(anomalies.len(), 2);
+    assert_eq!(anomalies[0].operation.id, second_charge);
+    assert_eq!(anomalies[0].status, "acknowledged");
+
+    let all = db::list_anomalies(db_path, key, db::AnomalyOptions {
+        include_dismissed: true,
+        ..options.clone()
+    }).expect("Failed to list anomalies");
+    assert_eq!(all.len(), 3);
+    assert_eq!(all[0].status, "dismissed");
+
+    db::set_anomaly_status(db_path, key, second_charge, "duplicate".to_string(), "open".to_string())
+        .expect("Failed to reopen");
+    let anomalies = db::list_anomalies(db_path, key, options).expect("Failed to list anomalies");
+    assert_eq!(anomalies[0].status, "open");
+    println!("✓ Dismiss/acknowledge state persisted");
+
+    let invalid_kind = db::set_anomaly_status(db_path, key, jeweler, "weird".to_string(), "dismissed".to_string());
+    assert!(matches!(invalid_kind, Err(db::DbError::ValidationError(_))));
+    let invalid_status = db::set_anomaly_status(db_path, key, jeweler, "duplicate".to_string(), "ignored".to_string());
+    assert!(matches!(invalid_status, Err(db::DbError::ValidationError(_))));
+    let missing = db::set_anomaly_status(db_path, key, 999_999, "duplicate".to_string(), "dismissed".to_string());
+    assert!(missing.is_err());
+    println!("✓ Invalid input rejected");
+
+    println!("\n✅ Anomaly detection test passed!");
+}
+
+#[test]
+fn test_category_outliers_use_split_lines() {
+    let db_path = "/tmp/test_anomalies_splits.db";
+    let key = "test_key_123";
+
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let checking = db::create_account(db_path, key, "Checking".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let groceries = db::create_category(db_path, key, "Groceries".to_string()).expect("Failed to create category");
+    let furniture = db::create_category(db_path, key, "Furniture".to_string()).expect("Failed to create category");
+
+    // Типичный диапазон категории: около 100
+    for (i, amount) in [95.0, 100.0, 105.0, 98.0, 102.0, 110.0, 90.0, 97.0].iter().enumerate() {
+        let id = db::add_operation_at(db_path, key, checking, -amount, "Market".to_string(), T0 - (40 - 3 * i as i64) * DAY)
+            .expect("Failed to add operation");
+        db::set_operation_category(db_path, key, id, Some(groceries)).expect("Failed to set category");
+    }
+
+    // Крупный чек с обычной частью продуктов - не выброс по категории
+    let mall = db::add_operation_at(db_path, key, checking, -1000.0, "Mall".to_string(), T0 - 5 * DAY)
+        .expect("Failed to add operation");
+    db::set_operation_category(db_path, key, mall, Some(groceries)).expect("Failed to set category");
+    db::set_operation_splits(db_path, key, mall, vec![
+        db::SplitLineInput { category_id: Some(groceries), amount: -99.0, memo: "food".to_string() },
+        db::SplitLineInput { category_id: Some(furniture), amount: -901.0, memo: "sofa".to_string() },
+    ]).expect("Failed to set splits");
+
+    // Разбитый чек без категории с необычно крупной частью продуктов
+    let party = db::add_operation_at(db_path, key, checking, -500.0, "Party supplies".to_string(), T0 - 2 * DAY)
+        .expect("Failed to add operation");
+    db::set_operation_splits(db_path, key, party, vec![
+        db::SplitLineInput { category_id: Some(groceries), amount: -450.0, memo: "food".to_string() },
+        db::SplitLineInput { category_id: None, amount: -50.0, memo: "decor".to_string() },
+    ]).expect("Failed to set splits");
+
+    let anomalies = db::list_anomalies(db_path, key, db::AnomalyOptions {
+        large_amount: Some(10000.0),
+        ..Default::default()
+    }).expect("Failed to list anomalies");
+    assert_eq!(anomalies.len(), 1);
+    assert_eq!(anomalies[0].kind, "outlier_amount");
+    assert_eq!(anomalies[0].operation.id, party);
+    assert!(anomalies[0].reason.contains("Amount 450.00"));
+    println!("✓ Category outliers are checked per split line");
+
+    let _ = fs::remove_file(db_path);
+}
@@ -0,0 +1,84 @@
+use fam_core_lib::db;
+use rusqlite::Connection;
+use std::fs;
+
+#[test]
+fn test_attachments_integrity_and_limits() {
+    let db_path = "/tmp/test_attachments.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let acc = db::create_account(db_path, key, "Card".to_string(), "card".to_string())
+        .expect("Failed to create account");
+    let op = db::add_operation(db_path, key, acc, -42.0, "Coffee".to_string())
+        .expect("Failed to add operation");
+
+    let receipt = b"%PDF-1.4 receipt".to_vec();
+    let attachment_id = db::add_attachment(
+        db_path, key, "operation".to_string(), op, "receipt.pdf".to_string(),
+        "application/pdf".to_string(), receipt.clone(),
+    ).expect("Failed to add attachment");
+
+    let attachments = db::list_attachments(db_path, key, "operation".to_string(), op)
+        .expect("Failed to list attachments");
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0].size, receipt.len() as i64);
+    assert_eq!(attachments[0].sha256.len(), 64);
+
+    let content = db::get_attachment(db_path, key, attachment_id).expect("Failed to get attachment");
+    assert_eq!(content.data, receipt);
+    assert!(content.integrity_ok);
+
+    // SHA-256 попадает в подписанную запись version_log
+    let log = db::list_version_log(db_path, key, Some("attachment".to_string()), Some(attachment_id))
+        .expect("Failed to list version log");
+    assert_eq!(log.len(), 1);
+    assert!(log[0].payload.contains(&attachments[0].sha256));
+    assert!(db::verify_version_signature(db_path, key, log[0].id).expect("Failed to verify"));
+    println!("✓ Attachment stored with signed SHA-256");
+
+    // Подмена содержимого обнаруживается, даже если пересчитать хэш в таблице
+    {
+        let conn = Connection::open(db_path).unwrap();
+        conn.pragma_update(None, "key", key).unwrap();
+        conn.execute(
+            "UPDATE attachments SET data = ?1, sha256 = ?2 WHERE id = ?3",
+            rusqlite::params![b"forged".to_vec(), "0".repeat(64), attachment_id],
+        ).unwrap();
+    }
+    let content = db::get_attachment(db_path, key, attachment_id).expect("Failed to get attachment");
+    assert!(!content.integrity_ok);
+    println!("✓ Swapped receipt detected");
+
+    // Ограничения в слое БД
+    let too_large = vec![0u8; db::ATTACHMENT_MAX_SIZE + 1];
+    let result = db::add_attachment(
+        db_path, key, "account".to_string(), acc, "big.jpg".to_string(), "image/jpeg".to_string(), too_large,
+    );
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    let result = db::add_attachment(
+        db_path, key, "operation".to_string(), 9999, "x.jpg".to_string(), "image/jpeg".to_string(), vec![1],
+    );
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    let result = db::add_attachment(
+        db_path, key, "budget".to_string(), 1, "x.jpg".to_string(), "image/jpeg".to_string(), vec![1],
+    );
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+    println!("✓ Size and owner limits enforced");
+
+    db::delete_attachment(db_path, key, attachment_id).expect("Failed to delete attachment");
+    assert!(db::list_attachments(db_path, key, "operation".to_string(), op).unwrap().is_empty());
+    let log = db::list_version_log(db_path, key, Some("attachment".to_string()), Some(attachment_id))
+        .expect("Failed to list version log");
+    assert_eq!(log[0].action, "delete");
+
+    println!("\n✅ Attachment tests passed!");
+
+    let _ = fs::remove_file(db_path);
+}
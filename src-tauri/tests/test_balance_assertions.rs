@@ -0,0 +1,99 @@
+mod common;
+
+use common::T0;
+use fam_core_lib::db;
+use std::fs;
+
+#[test]
+fn test_opening_balance_and_assertions() {
+    let db_path = "/tmp/test_balance_assertions.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let acc = db::create_account(db_path, key, "Bank".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+
+    let day = 86400;
+    let opening_ts = T0 - 30 * day;
+
+    // Начальный остаток не создаёт операцию
+    db::set_opening_balance(db_path, key, acc, 1000.0, opening_ts)
+        .expect("Failed to set opening balance");
+
+    let balance = db::get_account_balance(db_path, key, acc).expect("Failed to get balance");
+    assert_eq!(balance, 1000.0, "Opening balance should seed the states chain");
+
+    let operations = db::get_operations(db_path, key, acc).expect("Failed to get operations");
+    assert_eq!(operations.len(), 0, "Opening balance must not be stored as an operation");
+    println!("✓ Opening balance seeded without operations");
+
+    db::add_operation_at(db_path, key, acc, -200.0, "Purchase".to_string(), T0)
+        .expect("Failed to add operation");
+
+    let balance = db::get_account_balance(db_path, key, acc).expect("Failed to get balance");
+    assert_eq!(balance, 800.0, "Operation should build on top of the opening balance");
+
+    // Сверки: две корректные и одна ошибочная
+    let a1 = db::add_balance_assertion(db_path, key, acc, 1000.0, T0 - 10 * day)
+        .expect("Failed to add assertion 1");
+    let a2 = db::add_balance_assertion(db_path, key, acc, 800.0, T0 + 3600)
+        .expect("Failed to add assertion 2");
+    let a3 = db::add_balance_assertion(db_path, key, acc, 900.0, T0 + 7200)
+        .expect("Failed to add assertion 3");
+
+    let report = db::check_balance_assertions(db_path, key, Some(acc))
+        .expect("Failed to check assertions");
+    assert_eq!(report.results.len(), 3);
+    assert_eq!(report.passed_count, 2);
+    assert_eq!(report.failed_count, 1);
+
+    let failure = report.first_failure.expect("Third assertion should fail");
+    assert_eq!(failure.assertion_id, a3);
+    assert_eq!(failure.actual_balance, 800.0);
+    assert_eq!(failure.difference, -100.0);
+    println!("✓ First failing assertion flagged: {}", failure.assertion_id);
+
+    // Исправление начального остатка сдвигает всю цепочку states
+    db::set_opening_balance(db_path, key, acc, 1500.0, opening_ts - day)
+        .expect("Failed to update opening balance");
+
+    let opening = db::get_opening_balance(db_path, key, acc)
+        .expect("Failed to get opening balance")
+        .expect("Opening balance should exist");
+    assert_eq!(opening.balance, 1500.0);
+    assert_eq!(opening.ts, opening_ts - day);
+
+    let balance = db::get_account_balance(db_path, key, acc).expect("Failed to get balance");
+    assert_eq!(balance, 1300.0, "Later states should be shifted by the corrected opening balance");
+
+    let report = db::check_balance_assertions(db_path, key, None)
+        .expect("Failed to check assertions");
+    assert_eq!(report.failed_count, 3);
+    assert_eq!(report.first_failure.map(|f| f.assertion_id), Some(a1));
+    assert!(!report.results.iter().any(|r| r.assertion_id == a2 && r.passed));
+    println!("✓ Earliest failing assertion reported after correction");
+
+    // Начальный остаток не может быть позже первой операции
+    let result = db::set_opening_balance(db_path, key, acc, 100.0, T0 + day);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    // Изменения попадают в подписанный version_log
+    let log = db::list_version_log(db_path, key, Some("opening_balance".to_string()), None)
+        .expect("Failed to list version log");
+    assert_eq!(log.len(), 2);
+    for record in &log {
+        assert!(db::verify_version_signature(db_path, key, record.id).expect("Failed to verify"));
+    }
+
+    let log = db::list_version_log(db_path, key, Some("balance_assertion".to_string()), None)
+        .expect("Failed to list version log");
+    assert_eq!(log.len(), 3);
+
+    println!("\n✅ Opening balance and assertion tests passed!");
+
+    let _ = fs::remove_file(db_path);
+}
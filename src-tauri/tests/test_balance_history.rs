@@ -1,3 +1,6 @@
+mod common;
+
+use common::T0;
 use fam_core_lib::db;
 use std::fs;
 
@@ -120,3 +123,67 @@ fn test_balance_history() {
     let _ = fs::remove_file(db_path);
 }
 
+
+#[test]
+fn test_backdated_writes_shift_states() {
+    let db_path = "/tmp/test_backdated_states.db";
+    let key = "test_key_123";
+    let day = 86400;
+    
+    let _ = fs::remove_file(db_path);
+    db::init_db(db_path, key).expect("Failed to init db");
+    
+    let cash = db::create_account(db_path, key, "Cash".to_string(), "cash".to_string())
+        .expect("Failed to create account");
+    let savings = db::create_account(db_path, key, "Savings".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let food = db::create_category(db_path, key, "Food".to_string()).expect("Failed to create category");
+    
+    // Ручные операции: по порядку, задним числом и в момент существующего снимка
+    db::set_opening_balance(db_path, key, cash, 1000.0, T0 - 10 * day).expect("Failed to set opening balance");
+    db::add_operation_at(db_path, key, cash, -100.0, "Shop".to_string(), T0).expect("Failed to add operation");
+    let market = db::add_operation_at(db_path, key, cash, -50.0, "Market".to_string(), T0 + 2 * day)
+        .expect("Failed to add operation");
+    db::add_operation_at(db_path, key, cash, 200.0, "Refund".to_string(), T0 + day).expect("Failed to add operation");
+    db::add_operation_at(db_path, key, cash, -30.0, "Kiosk".to_string(), T0).expect("Failed to add operation");
+    
+    let balances = |account_id: i64| -> Vec<(i64, f64)> {
+        db::get_balance_history(db_path, key, account_id).expect("Failed to get history")
+            .iter().map(|s| ((s.ts - T0) / day, s.balance)).collect()
+    };
+    assert_eq!(balances(cash), vec![(-10, 1000.0), (0, 870.0), (1, 1070.0), (2, 1020.0)]);
+    println!("✓ Manual operations shift later states");
+    
+    // Перевод задним числом сдвигает цепочки обоих счетов
+    db::set_opening_balance(db_path, key, savings, 500.0, T0 - 20 * day).expect("Failed to set opening balance");
+    db::create_transfer(db_path, key, savings, cash, 300.0, "Top up".to_string(), Some(T0 - day))
+        .expect("Failed to create transfer");
+    assert_eq!(balances(cash), vec![(-10, 1000.0), (-1, 1300.0), (0, 1170.0), (1, 1370.0), (2, 1320.0)]);
+    assert_eq!(balances(savings), vec![(-20, 500.0), (-1, 200.0)]);
+    println!("✓ Backdated transfer shifts both accounts");
+    
+    // Замена начального остатка откатывает прежний и применяет новый
+    db::set_opening_balance(db_path, key, cash, 800.0, T0 - 10 * day).expect("Failed to set opening balance");
+    assert_eq!(balances(cash), vec![(-10, 800.0), (-1, 1100.0), (0, 970.0), (1, 1170.0), (2, 1120.0)]);
+    
+    // Разбивка операции не меняет баланс
+    db::set_operation_splits(db_path, key, market, vec![
+        db::SplitLineInput { category_id: Some(food), amount: -30.0, memo: String::new() },
+        db::SplitLineInput { category_id: None, amount: -20.0, memo: String::new() },
+    ]).expect("Failed to set splits");
+    assert_eq!(balances(cash), vec![(-10, 800.0), (-1, 1100.0), (0, 970.0), (1, 1170.0), (2, 1120.0)]);
+    println!("✓ Opening balance update and splits keep the chain consistent");
+    
+    // Последняя запись version_log каждого снимка совпадает с ним и подписана
+    for state in db::get_balance_history(db_path, key, cash).expect("Failed to get history") {
+        let log = db::list_version_log(db_path, key, Some("state".to_string()), Some(state.id))
+            .expect("Failed to list version log");
+        assert!(log[0].payload.contains(&format!("\"balance\":{:?}", state.balance)));
+        for record in &log {
+            assert!(db::verify_version_signature(db_path, key, record.id).expect("Failed to verify"));
+        }
+    }
+    println!("✓ Every state change is logged and signed");
+    
+    let _ = fs::remove_file(db_path);
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::T0;
+use fam_core_lib::db;
+use fam_core_lib::db::{BalanceAggregation, HistoryInterval};
+use std::fs;
+
+#[test]
+fn test_balance_history_resampled() {
+    let db_path = "/tmp/test_balance_resampling.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let day = 86400;
+    let hour = 3600;
+
+    let wallet = db::create_account(db_path, key, "Wallet".to_string(), "cash".to_string())
+        .expect("Failed to create account");
+
+    // Начало локальных суток 120 дней назад - первая граница дневной сетки
+    let start = T0 - 120 * day;
+    let probe = db::get_balance_history_resampled(db_path, key, wallet, start, start + 1,
+        HistoryInterval::Day, BalanceAggregation::Last)
+        .expect("Failed to resample");
+    let day0 = probe[0].ts;
+    assert!(probe[0].filled);
+
+    let at = |k: i64, h: i64| day0 + k * day + h * hour;
+    db::add_operation_at(db_path, key, wallet, 1000.0, "Salary".to_string(), at(1, 12)).expect("Failed to add operation");
+    db::add_operation_at(db_path, key, wallet, -400.0, "Rent".to_string(), at(1, 18)).expect("Failed to add operation");
+    db::add_operation_at(db_path, key, wallet, 200.0, "Refund".to_string(), at(3, 12)).expect("Failed to add operation");
+
+    let resample = |interval, aggregation| {
+        db::get_balance_history_resampled(db_path, key, wallet, day0, at(5, 0), interval, aggregation)
+            .expect("Failed to resample")
+    };
+
+    // Последнее значение с переносом вперёд
+    let last = resample(HistoryInterval::Day, BalanceAggregation::Last);
+    assert_eq!(last.len(), 5);
+    let values: Vec<f64> = last.iter().map(|b| b.value).collect();
+    assert_eq!(values, vec![0.0, 600.0, 600.0, 800.0, 800.0]);
+    let filled: Vec<bool> = last.iter().map(|b| b.filled).collect();
+    assert_eq!(filled, vec![true, false, true, false, true]);
+    assert!(last.iter().all(|b| b.open.is_none()));
+    println!("✓ Last value per day with forward fill: {:?}", values);
+
+    // OHLC
+    let ohlc = resample(HistoryInterval::Day, BalanceAggregation::Ohlc);
+    assert_eq!((ohlc[1].open, ohlc[1].high, ohlc[1].low, ohlc[1].close), (Some(0.0), Some(1000.0), Some(0.0), Some(600.0)));
+    assert_eq!((ohlc[2].open, ohlc[2].high, ohlc[2].low, ohlc[2].close), (Some(600.0), Some(600.0), Some(600.0), Some(600.0)));
+    assert_eq!(ohlc[3].open, Some(600.0));
+    assert_eq!(ohlc[3].close, Some(800.0));
+    println!("✓ OHLC per day");
+
+    // Среднее, взвешенное по времени внутри суток
+    let average = resample(HistoryInterval::Day, BalanceAggregation::Average);
+    let (bucket_start, bucket_end) = (average[1].ts, average[2].ts);
+    let expected = (1000.0 * (at(1, 18) - at(1, 12)) as f64 + 600.0 * (bucket_end - at(1, 18)) as f64)
+        / (bucket_end - bucket_start) as f64;
+    assert!((average[1].value - expected).abs() < 1e-9);
+    assert_eq!(average[2].value, 600.0);
+    println!("✓ Time-weighted average: {:.2}", average[1].value);
+
+    // Месяцы и кварталы начинаются с локальных дат, итоговое значение совпадает
+    let monthly = resample(HistoryInterval::Month, BalanceAggregation::Last);
+    let quarterly = resample(HistoryInterval::Quarter, BalanceAggregation::Last);
+    assert!(monthly[0].ts <= day0 && quarterly[0].ts <= monthly[0].ts);
+    assert!(!monthly.is_empty() && monthly.len() <= 2);
+    assert!(!quarterly.is_empty() && quarterly.len() <= 2);
+    assert_eq!(monthly.last().unwrap().value, 800.0);
+    assert_eq!(quarterly.last().unwrap().value, 800.0);
+
+    let long = db::get_balance_history_resampled(db_path, key, wallet, day0, T0,
+        HistoryInterval::Quarter, BalanceAggregation::Last)
+        .expect("Failed to resample");
+    assert!(long.len() >= 2);
+    assert!(long.windows(2).all(|w| w[1].ts - w[0].ts >= 89 * day && w[1].ts - w[0].ts <= 92 * day + hour));
+    println!("✓ Month and quarter buckets: {} / {}", monthly.len(), long.len());
+
+    // Несуществующий счёт
+    let missing = db::get_balance_history_resampled(db_path, key, 9999, day0, T0,
+        HistoryInterval::Day, BalanceAggregation::Last);
+    assert!(missing.is_err());
+
+    let _ = fs::remove_file(db_path);
+    println!("✅ test_balance_history_resampled passed");
+}
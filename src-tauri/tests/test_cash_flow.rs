@@ -0,0 +1,128 @@
+mod common;
+
+use common::T0;
+use fam_core_lib::db;
+use fam_core_lib::db::{CashFlowGroupBy, HistoryInterval};
+use std::fs;
+
+#[test]
+fn test_cash_flow_excludes_transfers() {
+    let db_path = "/tmp/test_cash_flow.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let day = 86400;
+
+    // Начало локальных суток 30 дней назад - первая граница дневной сетки
+    let start = T0 - 30 * day;
+    let day0 = db::get_net_worth_history(db_path, key, start, start + 1, HistoryInterval::Day)
+        .expect("Failed to get history")[0].ts;
+    let at = |k: i64| day0 + k * day + 12 * 3600;
+
+    let checking = db::create_account(db_path, key, "Checking".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let savings = db::create_account(db_path, key, "Savings".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let visa = db::create_account(db_path, key, "Visa".to_string(), "credit_card".to_string())
+        .expect("Failed to create account");
+    let mortgage = db::create_account(db_path, key, "Mortgage".to_string(), "mortgage".to_string())
+        .expect("Failed to create account");
+
+    let food = db::create_category(db_path, key, "Food".to_string()).expect("Failed to create category");
+    let acme = db::create_payee(db_path, key, "ACME".to_string()).expect("Failed to create payee");
+    db::add_payee_rule(db_path, key, acme, "alias".to_string(), "acme".to_string(), 0)
+        .expect("Failed to add payee rule");
+
+    db::add_operation_at(db_path, key, checking, 3000.0, "ACME salary".to_string(), at(1))
+        .expect("Failed to add operation");
+    let grocery = db::add_operation_at(db_path, key, checking, -500.0, "Grocery".to_string(), at(1))
+        .expect("Failed to add operation");
+    db::set_operation_category(db_path, key, grocery, Some(food)).expect("Failed to set category");
+    db::add_operation_at(db_path, key, visa, 150.0, "Restaurant".to_string(), at(1))
+        .expect("Failed to add operation");
+
+    // Переводы: на накопительный счёт и погашение кредитки
+    let to_savings = db::create_transfer(db_path, key, checking, savings, 1000.0, "To savings".to_string(), Some(at(2)))
+        .expect("Failed to create transfer");
+    db::create_transfer(db_path, key, checking, visa, 100.0, "Card payment".to_string(), Some(at(2)))
+        .expect("Failed to create transfer");
+    db::add_operation_at(db_path, key, savings, 10.0, "Interest".to_string(), at(3))
+        .expect("Failed to add operation");
+
+    assert_eq!(db::get_account_balance(db_path, key, checking).expect("Failed to get balance"), 1400.0);
+    assert_eq!(db::get_account_balance(db_path, key, savings).expect("Failed to get balance"), 1010.0);
+    assert_eq!(db::get_account_balance(db_path, key, visa).expect("Failed to get balance"), 50.0);
+
+    let transfers = db::list_transfers(db_path, key, None, None).expect("Failed to list transfers");
+    assert_eq!(transfers.len(), 2);
+    assert_eq!(transfers[0].id, to_savings);
+    assert_eq!((transfers[0].from_account_id, transfers[0].to_account_id), (checking, savings));
+    println!("✓ Transfers post two linked operations");
+
+    // Выдача и платёж по кредиту: тело - перемещение, проценты - расход
+    let loan = db::create_loan(db_path, key, mortgage, "Home".to_string(), 12000.0, 12.0, 12, at(0), Some(checking))
+        .expect("Failed to create loan");
+    db::record_loan_payment(db_path, key, loan, checking, 0.0, at(3)).expect("Failed to record payment");
+    let interest = db::get_loan_payments(db_path, key, loan).expect("Failed to get payments")[0].interest;
+    assert!(interest > 0.0);
+
+    let by_account = db::get_cash_flow(db_path, key, day0, day0 + 5 * day, HistoryInterval::Day, CashFlowGroupBy::Account)
+        .expect("Failed to get cash flow");
+    let rows: Vec<(i64, Option<i64>, f64, f64, f64)> = by_account.iter()
+        .map(|r| ((r.ts - day0 + 3600) / day, r.group_id, r.income, r.expenses, r.net))
+        .collect();
+    assert_eq!(rows, vec![
+        (1, Some(checking), 3000.0, 500.0, 2500.0),
+        (3, Some(checking), 0.0, interest, -interest),
+        (3, Some(savings), 10.0, 0.0, 10.0),
+    ]);
+    assert_eq!(by_account[0].group_name.as_deref(), Some("Checking"));
+    println!("✓ Cash flow by account excludes transfers, loan disbursement and principal");
+
+    let by_category = db::get_cash_flow(db_path, key, day0, day0 + 2 * day, HistoryInterval::Day, CashFlowGroupBy::Category)
+        .expect("Failed to get cash flow");
+    assert_eq!(by_category.len(), 2);
+    assert_eq!((by_category[0].group_id, by_category[0].income), (None, 3000.0));
+    assert_eq!((by_category[1].group_id, by_category[1].expenses), (Some(food), 500.0));
+    assert_eq!(by_category[1].group_name.as_deref(), Some("Food"));
+
+    let by_payee = db::get_cash_flow(db_path, key, day0, day0 + 5 * day, HistoryInterval::Month, CashFlowGroupBy::Payee)
+        .expect("Failed to get cash flow");
+    let acme_income: f64 = by_payee.iter().filter(|r| r.group_id == Some(acme)).map(|r| r.income).sum();
+    assert_eq!(acme_income, 3000.0);
+    let total_net: f64 = by_payee.iter().map(|r| r.net).sum();
+    assert!((total_net - (2510.0 - interest)).abs() < 0.01);
+    println!("✓ Cash flow by category and payee");
+
+    // Денежные части сделок с бумагами - не доход и не расход
+    let broker = db::create_account(db_path, key, "Broker".to_string(), "brokerage".to_string())
+        .expect("Failed to create account");
+    let aapl = db::create_security(db_path, key, "AAPL".to_string(), None, "Apple".to_string(), "stock".to_string())
+        .expect("Failed to create security");
+    db::add_investment_transaction(db_path, key, broker, aapl, "buy".to_string(), 10.0, 150.0, 5.0, at(4))
+        .expect("Failed to buy");
+    db::add_investment_transaction(db_path, key, broker, aapl, "sell".to_string(), 4.0, 160.0, 1.0, at(4) + 3600)
+        .expect("Failed to sell");
+
+    let trades = db::get_cash_flow(db_path, key, day0 + 4 * day, day0 + 5 * day, HistoryInterval::Day, CashFlowGroupBy::Account)
+        .expect("Failed to get cash flow");
+    assert!(trades.is_empty(), "Trade cash legs must not be reported: {:?}", trades.iter().map(|r| r.net).collect::<Vec<_>>());
+    println!("✓ Cash flow excludes investment trade cash legs");
+
+    // Ошибки перевода
+    assert!(matches!(
+        db::create_transfer(db_path, key, checking, checking, 10.0, "Self".to_string(), None),
+        Err(db::DbError::ValidationError(_))
+    ));
+    assert!(matches!(
+        db::create_transfer(db_path, key, checking, savings, -10.0, "Negative".to_string(), None),
+        Err(db::DbError::ValidationError(_))
+    ));
+
+    let _ = fs::remove_file(db_path);
+    println!("✅ test_cash_flow_excludes_transfers passed");
+}
@@ -0,0 +1,165 @@
+mod common;
+
+use common::T0;
+use fam_core_lib::db;
+use std::fs;
+
+fn rule_input(name: &str) -> db::CategorizationRuleInput {
+    db::CategorizationRuleInput {
+        name: name.to_string(),
+        description_pattern: None,
+        account_id: None,
+        amount_gt: None,
+        amount_lt: None,
+        set_category_id: None,
+        set_payee_id: None,
+        add_tag_id: None,
+        priority: 0,
+        enabled: true,
+    }
+}
+
+#[test]
+fn test_categorization_rules() {
+    let db_path = "/tmp/test_categorization_rules.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let day = 86400;
+    let base_ts = T0 - 30 * day;
+
+    let card = db::create_account(db_path, key, "Card".to_string(), "card".to_string())
+        .expect("Failed to create card");
+    let checking = db::create_account(db_path, key, "Checking".to_string(), "bank".to_string())
+        .expect("Failed to create checking");
+
+    // Операция, добавленная до появления правил
+    let early = db::add_operation_at(db_path, key, card, -100.0, "5KA store".to_string(), base_ts)
+        .expect("Failed to add operation");
+
+    let groceries = db::create_category(db_path, key, "Groceries".to_string()).expect("Failed to create category");
+    let income = db::create_category(db_path, key, "Income".to_string()).expect("Failed to create category");
+    let food = db::create_tag(db_path, key, "food".to_string()).expect("Failed to create tag");
+    let payee = db::create_payee(db_path, key, "Pyaterochka".to_string()).expect("Failed to create payee");
+
+    // Правило без действий недопустимо
+    let result = db::create_categorization_rule(db_path, key, rule_input("Empty"));
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    let grocery_rule = db::create_categorization_rule(db_path, key, db::CategorizationRuleInput {
+        description_pattern: Some("pyaterochka|5ka".to_string()),
+        account_id: Some(card),
+        amount_lt: Some(0.0),
+        set_category_id: Some(groceries),
+        set_payee_id: Some(payee),
+        add_tag_id: Some(food),
+        priority: 10,
+        ..rule_input("Groceries on card")
+    }).expect("Failed to create rule");
+
+    let salary_rule = db::create_categorization_rule(db_path, key, db::CategorizationRuleInput {
+        description_pattern: Some("salary".to_string()),
+        amount_gt: Some(0.0),
+        set_category_id: Some(income),
+        ..rule_input("Salary")
+    }).expect("Failed to create rule");
+
+    // Правила применяются при добавлении операции
+    let op = db::add_operation_at(db_path, key, card, -500.0, "PYATEROCHKA 12".to_string(), base_ts + day)
+        .expect("Failed to add operation");
+    let other_account = db::add_operation_at(db_path, key, checking, -500.0, "PYATEROCHKA 12".to_string(), base_ts + day)
+        .expect("Failed to add operation");
+    let salary = db::add_operation_at(db_path, key, checking, 3000.0, "Salary March".to_string(), base_ts + 2 * day)
+        .expect("Failed to add operation");
+
+    let card_ops = db::get_operations(db_path, key, card).expect("Failed to get operations");
+    let tagged = card_ops.iter().find(|o| o.id == op).unwrap();
+    assert_eq!(tagged.category_id, Some(groceries));
+    assert_eq!(tagged.payee_id, Some(payee));
+    let tags = db::get_operation_tags(db_path, key, op).expect("Failed to get tags");
+    assert_eq!(tags.len(), 1);
+    assert_eq!(tags[0].id, food);
+
+    let checking_ops = db::get_operations(db_path, key, checking).expect("Failed to get operations");
+    assert_eq!(checking_ops.iter().find(|o| o.id == other_account).unwrap().category_id, None);
+    assert_eq!(checking_ops.iter().find(|o| o.id == salary).unwrap().category_id, Some(income));
+
+    let log = db::list_version_log(db_path, key, Some("operation".to_string()), Some(op))
+        .expect("Failed to list version log");
+    let applied: Vec<_> = log.iter().filter(|r| r.action == "rule_apply").collect();
+    assert_eq!(applied.len(), 3);
+    for record in &applied {
+        assert!(record.payload.contains(&format!("\"rule_id\":{}", grocery_rule)));
+        assert!(db::verify_version_signature(db_path, key, record.id).expect("Failed to verify"));
+    }
+    println!("✓ Rules applied on insert and logged with rule id");
+
+    // Предпросмотр задним числом ничего не меняет
+    let preview = db::apply_categorization_rules(db_path, key, base_ts, base_ts + day, true)
+        .expect("Failed to preview rules");
+    assert_eq!(preview.len(), 3);
+    assert!(preview.iter().all(|c| c.operation_id == early && c.rule_id == grocery_rule));
+    let card_ops = db::get_operations(db_path, key, card).expect("Failed to get operations");
+    assert_eq!(card_ops.iter().find(|o| o.id == early).unwrap().category_id, None);
+    println!("✓ Dry run previewed {} changes", preview.len());
+
+    let applied = db::apply_categorization_rules(db_path, key, base_ts, base_ts + day, false)
+        .expect("Failed to apply rules");
+    assert_eq!(applied.len(), 3);
+    let card_ops = db::get_operations(db_path, key, card).expect("Failed to get operations");
+    assert_eq!(card_ops.iter().find(|o| o.id == early).unwrap().category_id, Some(groceries));
+
+    // Повторный прогон не находит изменений
+    let again = db::apply_categorization_rules(db_path, key, base_ts, T0, false)
+        .expect("Failed to apply rules");
+    assert!(again.is_empty());
+    println!("✓ Retroactive application is idempotent");
+
+    // Правила версионируются
+    let version = db::update_categorization_rule(db_path, key, salary_rule, db::CategorizationRuleInput {
+        description_pattern: Some("salary|bonus".to_string()),
+        amount_gt: Some(0.0),
+        set_category_id: Some(income),
+        ..rule_input("Salary and bonus")
+    }).expect("Failed to update rule");
+    assert_eq!(version, 2);
+
+    let rules = db::list_categorization_rules(db_path, key).expect("Failed to list rules");
+    assert_eq!(rules[0].id, grocery_rule);
+    assert_eq!(rules[1].version, 2);
+
+    let log = db::list_version_log(db_path, key, Some("categorization_rule".to_string()), Some(salary_rule))
+        .expect("Failed to list version log");
+    assert_eq!(log.len(), 2);
+    assert_eq!(log[0].action, "update");
+
+    db::delete_categorization_rule(db_path, key, salary_rule).expect("Failed to delete rule");
+    assert_eq!(db::list_categorization_rules(db_path, key).unwrap().len(), 1);
+    println!("✓ Rule updates bump version and are logged");
+
+    // Слияние контрагентов переназначает контрагента в правилах
+    let merged = db::create_payee(db_path, key, "Pyaterochka Group".to_string()).expect("Failed to create payee");
+    db::merge_payees(db_path, key, merged, vec![payee]).expect("Failed to merge payees");
+
+    let rules = db::list_categorization_rules(db_path, key).expect("Failed to list rules");
+    assert_eq!(rules[0].set_payee_id, Some(merged));
+    assert_eq!(rules[0].version, 2);
+
+    let log = db::list_version_log(db_path, key, Some("categorization_rule".to_string()), Some(grocery_rule))
+        .expect("Failed to list version log");
+    assert_eq!(log[0].action, "update");
+
+    let after_merge = db::add_operation_at(db_path, key, card, -80.0, "5KA 7".to_string(), base_ts + 3 * day)
+        .expect("Failed to add operation");
+    let card_ops = db::get_operations(db_path, key, card).expect("Failed to get operations");
+    assert_eq!(card_ops.iter().find(|o| o.id == after_merge).unwrap().payee_id, Some(merged));
+    println!("✓ Payee merge repoints rule actions");
+
+    println!("\n✅ Categorization rule tests passed!");
+
+    let _ = fs::remove_file(db_path);
+}
@@ -0,0 +1,295 @@
+use fam_core_lib::db;
+use fam_core_lib::db::{AmountSign, CsvColumnMapping, CsvImportOptions, ImportEncoding, ImportLineStatus};
+use std::fs;
+
+/// Кодирование строки в Windows-1251 (только ASCII и кириллица)
+fn to_cp1251(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            'ё' => 0xb8,
+            'Ё' => 0xa8,
+            '№' => 0xb9,
+            'А'..='я' => (c as u32 - 0x410 + 0xc0) as u8,
+            _ => c as u8,
+        })
+        .collect()
+}
+
+fn iso_options() -> CsvImportOptions {
+    CsvImportOptions {
+        delimiter: ',',
+        encoding: ImportEncoding::Utf8,
+        date_format: "%Y-%m-%d".to_string(),
+        decimal_separator: '.',
+        has_header: true,
+        skip_rows: 0,
+        columns: CsvColumnMapping {
+            date: 0,
+            description: vec![1],
+            amount: Some(2),
+            debit: None,
+            credit: None,
+        },
+        sign: AmountSign::Signed,
+    }
+}
+
+#[test]
+fn test_csv_import() {
+    let db_path = "/tmp/test_csv_import.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let checking = db::create_account(db_path, key, "Checking".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let rub = db::create_account(db_path, key, "Рубли".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let card = db::create_account(db_path, key, "Card".to_string(), "credit_card".to_string())
+        .expect("Failed to create account");
+
+    // UTF-8 с BOM, кавычки, запятая и перевод строки в описании,
+    // две одинаковые покупки за день
+    let january = "\u{feff}Date,Description,Amount\r\n\
+        2024-01-05,Salary,2500.00\r\n\
+        2024-01-06,\"Coffee, \"\"Central\"\"\",-4.50\r\n\
+        2024-01-06,\"Coffee, \"\"Central\"\"\",-4.50\r\n\
+        2024-01-07,\"Hardware\nstore\",\"-1,200.00\"\r\n";
+    let options = iso_options();
+
+    let preview = db::preview_csv_import(db_path, key, checking, january.as_bytes(), &options)
+        .expect("Failed to preview");
+    assert!(preview.errors.is_empty());
+    assert_eq!(preview.lines.len(), 4);
+    assert_eq!(preview.new_count, 4);
+    assert_eq!(preview.lines[1].description, "Coffee, \"Central\"");
+    assert_eq!(preview.lines[3].description, "Hardware\nstore");
+    assert_eq!(preview.lines[3].line_number, 5);
+    assert!((preview.lines[3].amount + 1200.0).abs() < 1e-9);
+    assert_ne!(preview.lines[1].import_hash, preview.lines[2].import_hash);
+    assert!((preview.new_amount - 1291.0).abs() < 1e-9);
+    assert!(db::get_operations(db_path, key, checking).expect("Failed to get operations").is_empty());
+    println!("✓ Preview parses quotes, BOM and thousands separators without writing");
+
+    let result = db::import_csv(db_path, key, checking, "january.csv".to_string(), january.as_bytes(), &options)
+        .expect("Failed to import");
+    assert_eq!(result.batch.imported_count, 4);
+    assert_eq!(result.batch.source, "csv");
+    assert_eq!(result.operation_ids.len(), 4);
+    assert_eq!(result.batch.file_sha256.len(), 64);
+    let balance = db::get_account_balance(db_path, key, checking).expect("Failed to get balance");
+    assert!((balance - 1291.0).abs() < 1e-9);
+    println!("✓ Import writes operations and balances");
+
+    // Повторный импорт и пересекающийся файл
+    let again = db::import_csv(db_path, key, checking, "january.csv".to_string(), january.as_bytes(), &options)
+        .expect("Failed to import");
+    assert_eq!(again.batch.imported_count, 0);
+    assert_eq!(again.batch.duplicate_count, 4);
+
+    let overlap = "Date,Description,Amount\n\
+        2024-01-06,\"Coffee, \"\"Central\"\"\",-4.50\n\
+        2024-01-06,\"Coffee, \"\"Central\"\"\",-4.50\n\
+        2024-01-07,Hardware   STORE,-1200\n\
+        2024-01-08,Bakery,-3.20\n";
+    let preview = db::preview_csv_import(db_path, key, checking, overlap.as_bytes(), &options)
+        .expect("Failed to preview");
+    let statuses: Vec<ImportLineStatus> = preview.lines.iter().map(|line| line.status).collect();
+    assert_eq!(statuses, vec![
+        ImportLineStatus::Duplicate,
+        ImportLineStatus::Duplicate,
+        ImportLineStatus::Duplicate,
+        ImportLineStatus::New,
+    ]);
+    db::import_csv(db_path, key, checking, "overlap.csv".to_string(), overlap.as_bytes(), &options)
+        .expect("Failed to import");
+    assert_eq!(db::get_operations(db_path, key, checking).expect("Failed to get operations").len(), 5);
+    println!("✓ Import hashes prevent duplicates");
+
+    // Ошибка в строке - файл не импортируется целиком
+    let broken = "Date,Description,Amount\n2024-02-01,Rent,-900\n2024-02-31,Gym,-30\n2024-02-03,Books,abc\n";
+    let preview = db::preview_csv_import(db_path, key, checking, broken.as_bytes(), &options)
+        .expect("Failed to preview");
+    assert_eq!(preview.lines.len(), 1);
+    assert_eq!(preview.errors.len(), 2);
+    assert_eq!(preview.errors[0].line_number, 3);
+    assert_eq!(preview.errors[1].line_number, 4);
+    let result = db::import_csv(db_path, key, checking, "broken.csv".to_string(), broken.as_bytes(), &options);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+    assert_eq!(db::get_operations(db_path, key, checking).expect("Failed to get operations").len(), 5);
+    assert_eq!(db::list_import_batches(db_path, key, Some(checking)).expect("Failed to list batches").len(), 3);
+    println!("✓ Invalid file rejected atomically");
+
+    // CP1251, преамбула, точка с запятой, десятичная запятая, колонки расход/приход
+    let cp1251 = to_cp1251(
+        "Выписка по счёту №40817\n\
+         \n\
+         Дата;Описание;Контрагент;Расход;Приход\n\
+         01.03.2024;Покупка;Пятёрочка;1 234,50;\n\
+         02.03.2024;Зарплата;ООО Ромашка;;50 000,00\n",
+    );
+    let cp1251_options = CsvImportOptions {
+        delimiter: ';',
+        encoding: ImportEncoding::Cp1251,
+        date_format: "%d.%m.%Y".to_string(),
+        decimal_separator: ',',
+        has_header: true,
+        skip_rows: 2,
+        columns: CsvColumnMapping {
+            date: 0,
+            description: vec![1, 2],
+            amount: None,
+            debit: Some(3),
+            credit: Some(4),
+        },
+        sign: AmountSign::Signed,
+    };
+    let result = db::import_csv(db_path, key, rub, "vypiska.csv".to_string(), &cp1251, &cp1251_options)
+        .expect("Failed to import");
+    assert_eq!(result.batch.imported_count, 2);
+    let operations = db::get_operations(db_path, key, rub).expect("Failed to get operations");
+    let purchase = operations.iter().find(|op| op.amount < 0.0).expect("Purchase not imported");
+    assert_eq!(purchase.description, "Покупка Пятёрочка");
+    assert!((purchase.amount + 1234.5).abs() < 1e-9);
+    let balance = db::get_account_balance(db_path, key, rub).expect("Failed to get balance");
+    assert!((balance - 48765.5).abs() < 1e-9);
+
+    // В UTF-8 такой файл не читается
+    let mut wrong_encoding = cp1251_options.clone();
+    wrong_encoding.encoding = ImportEncoding::Utf8;
+    let result = db::preview_csv_import(db_path, key, rub, &cp1251, &wrong_encoding);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+    println!("✓ CP1251 with debit/credit columns imported");
+
+    // Выписка по карте: списания положительные, дата со временем
+    let card_csv = "2024-03-05 14:30:00|Airline|350.00\n2024-03-06 09:00:00|Payment received|-200.00\n";
+    let card_options = CsvImportOptions {
+        delimiter: '|',
+        date_format: "%Y-%m-%d %H:%M:%S".to_string(),
+        has_header: false,
+        sign: AmountSign::Inverted,
+        ..iso_options()
+    };
+    let preview = db::preview_csv_import(db_path, key, card, card_csv.as_bytes(), &card_options)
+        .expect("Failed to preview");
+    assert!((preview.lines[0].amount + 350.0).abs() < 1e-9);
+    assert!((preview.lines[1].amount - 200.0).abs() < 1e-9);
+    assert_eq!(preview.lines[1].ts - preview.lines[0].ts, 18 * 3600 + 30 * 60);
+    println!("✓ Inverted sign convention and timestamps");
+
+    // Профили банков
+    let profile_id = db::save_import_profile(db_path, key, "Sber".to_string(), cp1251_options.clone())
+        .expect("Failed to save profile");
+    db::save_import_profile(db_path, key, "Card".to_string(), card_options).expect("Failed to save profile");
+    let mut updated = cp1251_options.clone();
+    updated.skip_rows = 3;
+    let same_id = db::save_import_profile(db_path, key, "sber".to_string(), updated).expect("Failed to save profile");
+    assert_eq!(same_id, profile_id);
+
+    let profiles = db::list_import_profiles(db_path, key).expect("Failed to list profiles");
+    assert_eq!(profiles.len(), 2);
+    let sber = profiles.iter().find(|profile| profile.id == profile_id).unwrap();
+    assert_eq!(sber.options.skip_rows, 3);
+    assert_eq!(sber.options.encoding, ImportEncoding::Cp1251);
+    assert_eq!(sber.options.delimiter, ';');
+    assert_eq!(sber.options.columns.description, vec![1, 2]);
+
+    let mut ambiguous = iso_options();
+    ambiguous.columns.debit = Some(3);
+    let result = db::save_import_profile(db_path, key, "Bad".to_string(), ambiguous);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    db::delete_import_profile(db_path, key, profile_id).expect("Failed to delete profile");
+    assert_eq!(db::list_import_profiles(db_path, key).expect("Failed to list profiles").len(), 1);
+    assert!(db::delete_import_profile(db_path, key, profile_id).is_err());
+    println!("✓ Import profiles saved, updated and deleted");
+
+    let log = db::list_version_log(db_path, key, Some("import_batch".to_string()), None)
+        .expect("Failed to list version log");
+    assert_eq!(log.len(), 4);
+    assert!(db::verify_version_signature(db_path, key, log[0].id).expect("Failed to verify"));
+    println!("✓ Import batches logged and signed");
+
+    println!("\n✅ CSV import test passed!");
+}
+
+#[test]
+fn test_csv_import_newest_first() {
+    let db_path = "/tmp/test_csv_import_newest_first.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let checking = db::create_account(db_path, key, "Checking".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+
+    // Более поздняя операция: её снимок должен сдвинуться один раз
+    db::add_operation_at(db_path, key, checking, -100.0, "Rent".to_string(), 1709294400)
+        .expect("Failed to add operation");
+
+    // Правила загружаются один раз на импорт и применяются к каждой строке
+    let leisure = db::create_category(db_path, key, "Leisure".to_string()).expect("Failed to create category");
+    db::create_categorization_rule(db_path, key, db::CategorizationRuleInput {
+        name: "Leisure".to_string(),
+        description_pattern: Some("cinema|coffee".to_string()),
+        account_id: None,
+        amount_gt: None,
+        amount_lt: None,
+        set_category_id: Some(leisure),
+        set_payee_id: None,
+        add_tag_id: None,
+        priority: 0,
+        enabled: true,
+    }).expect("Failed to create rule");
+
+    // Выписка от новых строк к старым
+    let statement = "Date,Description,Amount\n\
+        2024-01-20,Groceries,-30.00\n\
+        2024-01-15,Cinema,-20.00\n\
+        2024-01-10,Bonus,500.00\n\
+        2024-01-05,Coffee,-5.00\n\
+        2024-01-01,Salary,1000.00\n";
+    let result = db::import_csv(db_path, key, checking, "newest_first.csv".to_string(), statement.as_bytes(), &iso_options())
+        .expect("Failed to import");
+    assert_eq!(result.batch.imported_count, 5);
+
+    // Идентификаторы операций - в порядке строк файла, вставка - в хронологическом
+    let operations = db::get_operations(db_path, key, checking).expect("Failed to get operations");
+    let description_of = |id: i64| operations.iter().find(|op| op.id == id).unwrap().description.clone();
+    assert_eq!(description_of(result.operation_ids[0]), "Groceries");
+    assert_eq!(description_of(result.operation_ids[4]), "Salary");
+    assert!(result.operation_ids[4] < result.operation_ids[0]);
+    let categorized: Vec<&str> = operations.iter()
+        .filter(|op| op.category_id == Some(leisure))
+        .map(|op| op.description.as_str())
+        .collect();
+    assert_eq!(categorized, vec!["Cinema", "Coffee"]);
+
+    let balances: Vec<f64> = db::get_balance_history(db_path, key, checking)
+        .expect("Failed to get history")
+        .iter()
+        .map(|state| state.balance)
+        .collect();
+    assert_eq!(balances, vec![1000.0, 995.0, 1495.0, 1475.0, 1445.0, 1345.0]);
+    println!("✓ Newest-first statement builds the states chain in date order");
+
+    // Пять новых снимков и один сдвиг более позднего: O(N) записей в журнале
+    let state_log = db::list_version_log(db_path, key, Some("state".to_string()), None)
+        .expect("Failed to list version log");
+    assert_eq!(state_log.len(), 1 + 5 + 1);
+    assert_eq!(state_log.iter().filter(|record| record.action == "update").count(), 1);
+    assert!(db::verify_version_signature(db_path, key, state_log[0].id).expect("Failed to verify"));
+
+    let operation_log = db::list_version_log(db_path, key, Some("operation".to_string()), None)
+        .expect("Failed to list version log");
+    assert_eq!(operation_log.len(), 6 + 2);
+    assert_eq!(operation_log.iter().filter(|record| record.action == "rule_apply").count(), 2);
+
+    let _ = fs::remove_file(db_path);
+}
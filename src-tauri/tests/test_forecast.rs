@@ -0,0 +1,138 @@
+mod common;
+
+use common::now;
+use fam_core_lib::db;
+use fam_core_lib::db::HistoryInterval;
+use std::fs;
+
+const DAY: i64 = 86400;
+
+#[test]
+fn test_forecast_balances() {
+    let db_path = "/tmp/test_forecast.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let start = now();
+
+    let checking = db::create_account(db_path, key, "Checking".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let savings = db::create_account(db_path, key, "Savings".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let mortgage = db::create_account(db_path, key, "Mortgage".to_string(), "mortgage".to_string())
+        .expect("Failed to create account");
+
+    // История: зарплата (регулярная), покупки каждые 3 дня, платёж по кредиту
+    db::add_operation_at(db_path, key, checking, 3000.0, "Salary".to_string(), start - 61 * DAY)
+        .expect("Failed to add operation");
+    for i in 0..20 {
+        db::add_operation_at(db_path, key, checking, -90.0, "Groceries".to_string(), start - (60 - 3 * i) * DAY)
+            .expect("Failed to add operation");
+    }
+    let loan = db::create_loan(db_path, key, mortgage, "Home".to_string(), 12000.0, 12.0, 12, start - 50 * DAY, None)
+        .expect("Failed to create loan");
+    db::record_loan_payment(db_path, key, loan, checking, 0.0, start - 20 * DAY).expect("Failed to record payment");
+    let loan_payment = db::get_amortization_schedule(db_path, key, loan, None).expect("Failed to get schedule")[0].payment;
+    db::add_operation_at(db_path, key, savings, 5000.0, "Deposit".to_string(), start - 10 * DAY)
+        .expect("Failed to add operation");
+
+    // Регулярные элементы: зарплата через 29 дней, аренда через 5
+    let salary = db::create_recurring_item(db_path, key, db::RecurringItemInput {
+        account_id: checking,
+        amount: 3000.0,
+        description: "salary".to_string(),
+        payee_id: None,
+        interval: HistoryInterval::Month,
+        next_ts: start + 29 * DAY,
+        end_ts: None,
+    }).expect("Failed to create recurring item");
+    db::create_recurring_item(db_path, key, db::RecurringItemInput {
+        account_id: checking,
+        amount: -2500.0,
+        description: "Rent".to_string(),
+        payee_id: None,
+        interval: HistoryInterval::Month,
+        next_ts: start + 5 * DAY,
+        end_ts: None,
+    }).expect("Failed to create recurring item");
+
+    let invalid = db::create_recurring_item(db_path, key, db::RecurringItemInput {
+        account_id: checking,
+        amount: 0.0,
+        description: "Nothing".to_string(),
+        payee_id: None,
+        interval: HistoryInterval::Week,
+        next_ts: start,
+        end_ts: None,
+    });
+    assert!(matches!(invalid, Err(db::DbError::ValidationError(_))));
+    assert_eq!(db::list_recurring_items(db_path, key).expect("Failed to list items").len(), 2);
+    println!("✓ Recurring items created");
+
+    let forecasts = db::forecast_balances(db_path, key, 40).expect("Failed to forecast");
+    assert_eq!(forecasts.len(), 2, "Only asset accounts are forecast");
+
+    let forecast = forecasts.iter().find(|f| f.account_id == checking).expect("Checking forecast");
+    let current = 3000.0 - 1800.0 - loan_payment;
+    assert!((forecast.current_balance - current).abs() < 0.01);
+
+    // Тренд: только покупки (зарплата регулярная, кредит - плановый платёж)
+    let trend = -1800.0 / 61.0;
+    assert!((forecast.daily_trend - trend).abs() < 1e-3, "Unexpected trend: {}", forecast.daily_trend);
+    assert!(forecast.daily_volatility > 0.0);
+    assert_eq!(forecast.points.len(), 40);
+
+    let expected_at = |day: usize, scheduled: f64| current + scheduled + forecast.daily_trend * day as f64;
+    assert!((forecast.points[0].expected - expected_at(1, 0.0)).abs() < 0.01);
+    assert!((forecast.points[4].expected - expected_at(5, -2500.0)).abs() < 0.01);
+    assert!((forecast.points[12].expected - expected_at(13, -2500.0 - loan_payment)).abs() < 0.01);
+    assert!((forecast.points[29].expected - expected_at(30, 500.0 - loan_payment)).abs() < 0.01);
+    assert!(forecast.points.iter().all(|p| p.lower < p.expected && p.expected < p.upper));
+    assert!(forecast.points[39].upper - forecast.points[39].lower > forecast.points[0].upper - forecast.points[0].lower);
+    println!("✓ Expected balance combines schedule and trend");
+
+    // Минус после аренды, до зарплаты
+    assert_eq!(forecast.first_negative_ts, Some(forecast.points[4].ts));
+    assert_eq!(forecast.next_income_ts, Some(start + 29 * DAY));
+    assert!(forecast.negative_before_income);
+    println!("✓ Negative balance before payday detected");
+
+    let savings_forecast = forecasts.iter().find(|f| f.account_id == savings).expect("Savings forecast");
+    assert!(savings_forecast.first_negative_ts.is_none());
+    assert!(!savings_forecast.negative_before_income);
+
+    // Без аренды, с подработкой через 3 дня: минус наступает уже после поступления
+    let rent = db::list_recurring_items(db_path, key).expect("Failed to list items")
+        .into_iter()
+        .find(|item| item.id != salary)
+        .expect("Rent item");
+    db::delete_recurring_item(db_path, key, rent.id).expect("Failed to delete item");
+    db::create_recurring_item(db_path, key, db::RecurringItemInput {
+        account_id: checking,
+        amount: 1000.0,
+        description: "Side job".to_string(),
+        payee_id: None,
+        interval: HistoryInterval::Month,
+        next_ts: start + 3 * DAY,
+        end_ts: None,
+    }).expect("Failed to create recurring item");
+
+    let forecasts = db::forecast_balances(db_path, key, 40).expect("Failed to forecast");
+    let forecast = forecasts.iter().find(|f| f.account_id == checking).expect("Checking forecast");
+    assert_eq!(forecast.next_income_ts, Some(start + 3 * DAY));
+    assert!(forecast.first_negative_ts.is_some_and(|ts| ts > start + 3 * DAY));
+    assert!(!forecast.negative_before_income);
+
+    let log = db::list_version_log(db_path, key, Some("recurring_item".to_string()), None)
+        .expect("Failed to list version log");
+    assert_eq!(log.len(), 4);
+
+    assert!(matches!(db::forecast_balances(db_path, key, 0), Err(db::DbError::ValidationError(_))));
+
+    let _ = fs::remove_file(db_path);
+    println!("✅ test_forecast_balances passed");
+}
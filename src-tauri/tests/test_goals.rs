@@ -0,0 +1,92 @@
+mod common;
+
+use common::now;
+use fam_core_lib::db;
+use std::fs;
+
+#[test]
+fn test_goal_progress() {
+    let db_path = "/tmp/test_goals.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let day = 86400;
+    let savings = db::create_account(db_path, key, "Savings".to_string(), "deposit".to_string())
+        .expect("Failed to create account");
+    let checking = db::create_account(db_path, key, "Checking".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+
+    // Пополнения: одно до окна оценки темпа, два внутри окна
+    db::add_operation_at(db_path, key, savings, 10000.0, "Initial".to_string(), now() - 120 * day).unwrap();
+    db::add_operation_at(db_path, key, savings, 30000.0, "Top up".to_string(), now() - 60 * day).unwrap();
+    db::add_operation_at(db_path, key, savings, 30000.0, "Top up".to_string(), now() - 30 * day).unwrap();
+
+    let result = db::create_goal(db_path, key, "Empty".to_string(), 1000.0, now() + day, vec![], vec![]);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    let vacation = db::create_goal(
+        db_path, key, "Vacation fund".to_string(), 300000.0, now() + 365 * day, vec![savings], vec![],
+    ).expect("Failed to create goal");
+
+    let progress = db::get_goal_progress(db_path, key, vacation).expect("Failed to get progress");
+    assert_eq!(progress.current_amount, 70000.0);
+    assert_eq!(progress.remaining_amount, 230000.0);
+    assert!((progress.months_remaining - 12.0).abs() < 0.1);
+    assert!((progress.required_monthly_contribution - 230000.0 / 12.0).abs() < 200.0);
+    assert!(progress.recent_monthly_contribution > 20000.0);
+    assert_eq!(progress.status, "on_track");
+    println!(
+        "✓ Vacation goal on track: need {}/month, saving {}/month",
+        progress.required_monthly_contribution, progress.recent_monthly_contribution
+    );
+
+    let house = db::create_goal(
+        db_path, key, "House".to_string(), 1000000.0, now() + 365 * day, vec![savings], vec![],
+    ).expect("Failed to create goal");
+    assert_eq!(db::get_goal_progress(db_path, key, house).unwrap().status, "behind");
+
+    let missed = db::create_goal(
+        db_path, key, "Missed".to_string(), 100000.0, now() - day, vec![savings], vec![],
+    ).expect("Failed to create goal");
+    let progress = db::get_goal_progress(db_path, key, missed).unwrap();
+    assert_eq!(progress.status, "overdue");
+    assert_eq!(progress.required_monthly_contribution, 30000.0);
+    println!("✓ Behind and overdue goals detected");
+
+    // Цель по тегу считает помеченные операции
+    let tag = db::create_tag(db_path, key, "gift".to_string()).expect("Failed to create tag");
+    let op1 = db::add_operation_at(db_path, key, checking, 3000.0, "Gift".to_string(), now() - 10 * day).unwrap();
+    let op2 = db::add_operation_at(db_path, key, checking, 2500.0, "Gift".to_string(), now() - 5 * day).unwrap();
+    db::add_operation_at(db_path, key, checking, 9000.0, "Salary".to_string(), now() - 4 * day).unwrap();
+    db::add_operation_tag(db_path, key, op1, tag).expect("Failed to tag operation");
+    db::add_operation_tag(db_path, key, op2, tag).expect("Failed to tag operation");
+
+    let gift = db::create_goal(
+        db_path, key, "Gift".to_string(), 5000.0, now() + 30 * day, vec![], vec![tag],
+    ).expect("Failed to create goal");
+    let progress = db::get_goal_progress(db_path, key, gift).unwrap();
+    assert_eq!(progress.current_amount, 5500.0);
+    assert_eq!(progress.status, "reached");
+    assert_eq!(progress.required_monthly_contribution, 0.0);
+    println!("✓ Tag-linked goal reached");
+
+    let goals = db::list_goals(db_path, key).expect("Failed to list goals");
+    assert_eq!(goals.len(), 4);
+    assert_eq!(goals[0].id, missed);
+    assert_eq!(goals.iter().find(|g| g.id == gift).unwrap().tag_ids, vec![tag]);
+
+    db::delete_goal(db_path, key, missed).expect("Failed to delete goal");
+    assert_eq!(db::list_goals(db_path, key).unwrap().len(), 3);
+
+    let log = db::list_version_log(db_path, key, Some("goal".to_string()), Some(missed))
+        .expect("Failed to list version log");
+    assert_eq!(log.len(), 2);
+
+    println!("\n✅ Goal tests passed!");
+
+    let _ = fs::remove_file(db_path);
+}
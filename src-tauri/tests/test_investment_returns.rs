@@ -0,0 +1,134 @@
+use fam_core_lib::db;
+use std::fs;
+
+const DAY: i64 = 86400;
+
+#[test]
+fn test_xirr_solver() {
+    let t0 = 1_199_145_600;
+
+    // Классический пример: ставка 37.34%
+    let flows = [
+        (t0, -10000.0),
+        (t0 + 60 * DAY, 2750.0),
+        (t0 + 303 * DAY, 4250.0),
+        (t0 + 411 * DAY, 3250.0),
+        (t0 + 456 * DAY, 2750.0),
+    ];
+    let rate = db::xirr(&flows).expect("Failed to solve XIRR");
+    assert!((rate - 0.373362535).abs() < 1e-6, "Unexpected XIRR: {}", rate);
+
+    // Ровно год: 10% годовых
+    let rate = db::xirr(&[(t0, -1000.0), (t0 + 365 * DAY, 1100.0)]).expect("Failed to solve XIRR");
+    assert!((rate - 0.1).abs() < 1e-9);
+
+    // Полная потеря и многократный рост не ломают решатель
+    let rate = db::xirr(&[(t0, -1000.0), (t0 + 365 * DAY, 0.01)]).expect("Failed to solve XIRR");
+    assert!(rate > -1.0 && rate < -0.99);
+    let rate = db::xirr(&[(t0, -1.0), (t0 + 73 * DAY, 3.0)]).expect("Failed to solve XIRR");
+    assert!((rate - 242.0).abs() < 1e-6);
+
+    // Потоки за десятилетия: у ставки около -100% NPV переполняется
+    let rate = db::xirr(&[(t0, -1000.0), (t0 + 12 * 365 * DAY, 2000.0)]).expect("Failed to solve XIRR");
+    assert!((rate - (2.0_f64.powf(1.0 / 12.0) - 1.0)).abs() < 1e-9);
+    let rate = db::xirr(&[
+        (t0, -1000.0),
+        (t0 + 36 * 365 * DAY, -1000.0),
+        (t0 + 45 * 365 * DAY, 20000.0),
+    ]).expect("Failed to solve XIRR");
+    assert!((rate - 0.066617195).abs() < 1e-6, "Unexpected XIRR: {}", rate);
+
+    // Потоки одного знака - доходность не определена
+    assert!(matches!(db::xirr(&[(t0, -1000.0), (t0 + DAY, -500.0)]), Err(db::DbError::ValidationError(_))));
+    assert!(matches!(db::xirr(&[]), Err(db::DbError::ValidationError(_))));
+    println!("✓ XIRR solver");
+}
+
+#[test]
+fn test_investment_returns() {
+    let db_path = "/tmp/test_investment_returns.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let base = 1_700_000_000;
+    let to = base + 365 * DAY;
+
+    let broker = db::create_account(db_path, key, "Broker".to_string(), "brokerage".to_string())
+        .expect("Failed to create account");
+    let ira = db::create_account(db_path, key, "IRA".to_string(), "brokerage".to_string())
+        .expect("Failed to create account");
+    let stock = db::create_security(db_path, key, "VTI".to_string(), None, "Total Market".to_string(), "etf".to_string())
+        .expect("Failed to create security");
+    let bond = db::create_security(db_path, key, "BND".to_string(), None, "Bonds".to_string(), "bond".to_string())
+        .expect("Failed to create security");
+
+    // Broker: взнос 10000, покупка, рост 10%, взнос 11000 остаётся деньгами, цена падает на 10%
+    db::add_operation_at(db_path, key, broker, 10000.0, "Deposit".to_string(), base + DAY)
+        .expect("Failed to add operation");
+    db::add_security_price(db_path, key, stock, 100.0, base + 2 * DAY).expect("Failed to add price");
+    db::add_investment_transaction(db_path, key, broker, stock, "buy".to_string(), 100.0, 100.0, 0.0, base + 2 * DAY)
+        .expect("Failed to buy");
+    db::add_security_price(db_path, key, stock, 110.0, base + 100 * DAY).expect("Failed to add price");
+    db::add_operation_at(db_path, key, broker, 11000.0, "Deposit".to_string(), base + 100 * DAY)
+        .expect("Failed to add operation");
+    db::add_security_price(db_path, key, stock, 99.0, base + 300 * DAY).expect("Failed to add price");
+
+    // IRA: взнос 1000, облигации по неизменной цене
+    db::add_operation_at(db_path, key, ira, 1000.0, "Contribution".to_string(), base + DAY)
+        .expect("Failed to add operation");
+    db::add_security_price(db_path, key, bond, 50.0, base + DAY).expect("Failed to add price");
+    db::add_investment_transaction(db_path, key, ira, bond, "buy".to_string(), 20.0, 50.0, 0.0, base + DAY)
+        .expect("Failed to buy");
+
+    let returns = db::get_investment_returns(db_path, key, base, to).expect("Failed to get returns");
+    assert_eq!(returns.accounts.len(), 2);
+
+    let broker_return = &returns.accounts[0];
+    assert_eq!(broker_return.account_id, Some(broker));
+    assert_eq!(broker_return.start_value, 0.0);
+    assert!((broker_return.end_value - 20900.0).abs() < 1e-6);
+    assert!((broker_return.net_contributions - 21000.0).abs() < 1e-6);
+    assert!((broker_return.gain + 100.0).abs() < 1e-6);
+
+    // TWR = 1.1 * (20900 / 22000) - 1 не зависит от момента взносов
+    let twr = broker_return.twr.expect("TWR must be defined");
+    assert!((twr - (1.1 * 0.95 - 1.0)).abs() < 1e-9, "Unexpected TWR: {}", twr);
+    assert!((broker_return.twr_annualized.expect("Annualized TWR") - twr).abs() < 1e-9);
+
+    // XIRR совпадает с решением по тем же потокам
+    let expected_xirr = db::xirr(&[(base + DAY, -10000.0), (base + 100 * DAY, -11000.0), (to, 20900.0)])
+        .expect("Failed to solve XIRR");
+    assert!((broker_return.xirr.expect("XIRR must be defined") - expected_xirr).abs() < 1e-9);
+    assert!(broker_return.xirr.unwrap() < 0.0);
+    println!("✓ Broker TWR {:.4}, XIRR {:.4}", twr, broker_return.xirr.unwrap());
+
+    let ira_return = &returns.accounts[1];
+    assert!(ira_return.twr.unwrap().abs() < 1e-9);
+    assert!(ira_return.xirr.unwrap().abs() < 1e-9);
+
+    let portfolio = &returns.portfolio;
+    assert_eq!(portfolio.account_id, None);
+    assert!((portfolio.end_value - 21900.0).abs() < 1e-6);
+    assert!((portfolio.net_contributions - 22000.0).abs() < 1e-6);
+    assert!(portfolio.twr.is_some() && portfolio.xirr.is_some());
+    println!("✓ Portfolio return: TWR {:.4}", portfolio.twr.unwrap());
+
+    // Период до первых вложений: доходность не определена, причины описаны
+    let empty = db::get_investment_returns(db_path, key, base - 10 * DAY, base).expect("Failed to get returns");
+    assert!(empty.accounts.is_empty());
+    assert!(empty.portfolio.xirr.is_none() && empty.portfolio.xirr_error.is_some());
+    assert!(empty.portfolio.twr.is_none() && empty.portfolio.twr_error.is_some());
+
+    // Короткий период не пересчитывается в годовой TWR
+    let short = db::get_investment_returns(db_path, key, base + 50 * DAY, base + 150 * DAY).expect("Failed to get returns");
+    assert!(short.accounts[0].twr.is_some() && short.accounts[0].twr_annualized.is_none());
+
+    assert!(matches!(db::get_investment_returns(db_path, key, to, base), Err(db::DbError::ValidationError(_))));
+
+    let _ = fs::remove_file(db_path);
+    println!("✅ test_investment_returns passed");
+}
@@ -0,0 +1,98 @@
+use fam_core_lib::db;
+use std::fs;
+
+const DAY: i64 = 86400;
+
+#[test]
+fn test_investment_holdings_and_market_value() {
+    let db_path = "/tmp/test_investments.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let broker = db::create_account(db_path, key, "Broker".to_string(), "brokerage".to_string())
+        .expect("Failed to create account");
+
+    let base = 1_700_000_000;
+    db::set_opening_balance(db_path, key, broker, 10000.0, base)
+        .expect("Failed to set opening balance");
+
+    let aapl = db::create_security(db_path, key, "AAPL".to_string(), Some("US0378331005".to_string()),
+        "Apple Inc.".to_string(), "stock".to_string())
+        .expect("Failed to create security");
+    let bnd = db::create_security(db_path, key, "BND".to_string(), None,
+        "Total Bond Market ETF".to_string(), "bond".to_string())
+        .expect("Failed to create security");
+
+    let result = db::create_security(db_path, key, "XXX".to_string(), None, "Unknown".to_string(), "art".to_string());
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+    assert_eq!(db::list_securities(db_path, key).expect("Failed to list securities").len(), 2);
+    println!("✓ Securities registered");
+
+    // Сделки: покупки, продажа и дивиденд
+    db::add_investment_transaction(db_path, key, broker, aapl, "buy".to_string(), 10.0, 150.0, 5.0, base + DAY)
+        .expect("Failed to buy AAPL");
+    db::add_investment_transaction(db_path, key, broker, bnd, "buy".to_string(), 20.0, 50.0, 0.0, base + 2 * DAY)
+        .expect("Failed to buy BND");
+    db::add_security_price(db_path, key, aapl, 170.0, base + 3 * DAY)
+        .expect("Failed to add price");
+    db::add_investment_transaction(db_path, key, broker, aapl, "sell".to_string(), 4.0, 160.0, 1.0, base + 4 * DAY)
+        .expect("Failed to sell AAPL");
+    db::add_investment_transaction(db_path, key, broker, bnd, "dividend".to_string(), 20.0, 0.5, 0.0, base + 5 * DAY)
+        .expect("Failed to add dividend");
+    db::add_security_price(db_path, key, aapl, 180.0, base + 6 * DAY)
+        .expect("Failed to add price");
+
+    // Нельзя продать больше, чем есть на счёте
+    let result = db::add_investment_transaction(db_path, key, broker, aapl, "sell".to_string(), 100.0, 180.0, 0.0, base + 7 * DAY);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    let transactions = db::get_investment_transactions(db_path, key, broker)
+        .expect("Failed to get transactions");
+    assert_eq!(transactions.len(), 4);
+    assert_eq!(db::get_operations(db_path, key, broker).expect("Failed to get operations").len(), 4);
+
+    // Денежный остаток: 10000 - 1505 - 1000 + 639 + 10
+    let cash = db::get_account_balance(db_path, key, broker).expect("Failed to get balance");
+    assert!((cash - 8144.0).abs() < 1e-9, "Unexpected cash balance: {}", cash);
+    println!("✓ Cash leg of trades posted: {:.2}", cash);
+
+    let holdings = db::get_holdings(db_path, key, Some(broker)).expect("Failed to get holdings");
+    assert_eq!(holdings.len(), 2);
+
+    let aapl_holding = holdings.iter().find(|h| h.security_id == aapl).expect("AAPL holding");
+    assert_eq!(aapl_holding.quantity, 6.0);
+    assert_eq!(aapl_holding.price, Some(180.0));
+    assert_eq!(aapl_holding.market_value, 1080.0);
+
+    // Без котировок используется цена последней сделки
+    let bnd_holding = holdings.iter().find(|h| h.security_id == bnd).expect("BND holding");
+    assert_eq!(bnd_holding.quantity, 20.0);
+    assert_eq!(bnd_holding.market_value, 1000.0);
+    println!("✓ Holdings valued at market prices");
+
+    assert_eq!(db::get_price_history(db_path, key, aapl).expect("Failed to get prices").len(), 2);
+
+    let net_worth = db::get_net_worth(db_path, key).expect("Failed to get net worth");
+    assert!((net_worth - 10224.0).abs() < 1e-9, "Unexpected net worth: {}", net_worth);
+
+    let allocation = db::get_asset_allocation(db_path, key).expect("Failed to get allocation");
+    let by_type = |t: &str| allocation.iter().find(|a| a.asset_type == t).map(|a| a.total_balance);
+    assert_eq!(allocation.len(), 3);
+    assert_eq!(allocation[0].asset_type, "brokerage");
+    assert!((by_type("brokerage").unwrap() - 8144.0).abs() < 1e-9);
+    assert_eq!(by_type("stock"), Some(1080.0));
+    assert_eq!(by_type("bond"), Some(1000.0));
+    println!("✓ Net worth {:.2} and allocation include holdings", net_worth);
+
+    let log = db::list_version_log(db_path, key, Some("investment_transaction".to_string()), None)
+        .expect("Failed to list version log");
+    assert_eq!(log.len(), 4);
+
+    println!("\n✅ Investment holdings tests passed!");
+
+    let _ = fs::remove_file(db_path);
+}
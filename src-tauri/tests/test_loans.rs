@@ -0,0 +1,121 @@
+mod common;
+
+use common::T0;
+use fam_core_lib::db;
+use std::fs;
+
+fn assert_amount(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 0.005, "expected {}, got {}", expected, actual);
+}
+
+#[test]
+fn test_loan_amortization_and_payments() {
+    let db_path = "/tmp/test_loans.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let day = 86400;
+    let start_ts = T0 - 90 * day;
+
+    let checking = db::create_account(db_path, key, "Checking".to_string(), "bank".to_string())
+        .expect("Failed to create checking account");
+    let loan_account = db::create_account(db_path, key, "Car loan".to_string(), "loan".to_string())
+        .expect("Failed to create loan account");
+    db::add_operation_at(db_path, key, checking, 20000.0, "Salary".to_string(), start_ts - day)
+        .expect("Failed to add operation");
+
+    // Кредит можно вести только на счёте-обязательстве
+    let result = db::create_loan(db_path, key, checking, "Bad".to_string(), 1000.0, 10.0, 12, start_ts, None);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    // Срок вне диапазона не доходит до расчёта платежа
+    for term in [0, 1201, i64::from(i32::MAX) + 13] {
+        let result = db::create_loan(db_path, key, loan_account, "Bad".to_string(), 1000.0, 10.0, term, start_ts, None);
+        assert!(matches!(result, Err(db::DbError::ValidationError(_))), "term {} must be rejected", term);
+    }
+    assert!(db::list_loans(db_path, key).expect("Failed to list loans").is_empty());
+
+    // Сумму кредита можно зачислить только на счёт-актив
+    let result = db::create_loan(db_path, key, loan_account, "Bad".to_string(), 1000.0, 10.0, 12, start_ts, Some(loan_account));
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    let net_worth_before = db::get_net_worth_breakdown(db_path, key).expect("Failed to get net worth").net_worth;
+    let loan_id = db::create_loan(db_path, key, loan_account, "Car loan".to_string(), 12000.0, 12.0, 12, start_ts, Some(checking))
+        .expect("Failed to create loan");
+
+    let loans = db::list_loans(db_path, key).expect("Failed to list loans");
+    assert_eq!(loans.len(), 1);
+    assert_amount(loans[0].monthly_payment, 1066.19);
+    assert_amount(db::get_account_balance(db_path, key, loan_account).unwrap(), 12000.0);
+    assert_amount(db::get_account_balance(db_path, key, checking).unwrap(), 32000.0);
+    assert!(loans[0].funding_operation_id.is_some());
+    let breakdown = db::get_net_worth_breakdown(db_path, key).expect("Failed to get net worth");
+    assert_amount(breakdown.net_worth, net_worth_before);
+    println!("✓ Loan created, monthly payment {}", loans[0].monthly_payment);
+
+    // График погашения: 12 платежей, тело полностью погашается
+    let schedule = db::get_amortization_schedule(db_path, key, loan_id, None)
+        .expect("Failed to get schedule");
+    assert_eq!(schedule.len(), 12);
+    assert_amount(schedule[0].interest, 120.0);
+    assert_amount(schedule[0].principal, 946.19);
+    assert_amount(schedule.iter().map(|row| row.principal).sum::<f64>(), 12000.0);
+    assert_amount(schedule.last().unwrap().remaining_balance, 0.0);
+    assert!(schedule.windows(2).all(|pair| pair[0].due_ts < pair[1].due_ts));
+    println!("✓ Amortization schedule has {} payments", schedule.len());
+
+    // Платёж проводится отдельными операциями для тела и процентов
+    let payment_id = db::record_loan_payment(db_path, key, loan_id, checking, 0.0, start_ts + 30 * day)
+        .expect("Failed to record payment");
+    assert_amount(db::get_account_balance(db_path, key, loan_account).unwrap(), 11053.81);
+    assert_amount(db::get_account_balance(db_path, key, checking).unwrap(), 32000.0 - 1066.19);
+
+    let operations = db::get_operations(db_path, key, checking).expect("Failed to get operations");
+    let amounts: Vec<f64> = operations.iter().map(|op| op.amount).collect();
+    assert!(amounts.iter().any(|a| (a + 946.19).abs() < 0.005));
+    assert!(amounts.iter().any(|a| (a + 120.0).abs() < 0.005));
+
+    let log = db::list_version_log(db_path, key, Some("loan_payment".to_string()), Some(payment_id))
+        .expect("Failed to list version log");
+    assert_eq!(log.len(), 1);
+    assert!(db::verify_version_signature(db_path, key, log[0].id).expect("Failed to verify"));
+    println!("✓ Principal and interest posted as separate signed operations");
+
+    // Досрочное погашение уменьшает тело сверх планового платежа
+    db::record_loan_payment(db_path, key, loan_id, checking, 1000.0, start_ts + 60 * day)
+        .expect("Failed to record payment");
+    let payments = db::get_loan_payments(db_path, key, loan_id).expect("Failed to get payments");
+    assert_eq!(payments.len(), 2);
+    assert_eq!(payments[1].payment_number, 2);
+    assert_amount(payments[1].interest, 110.54);
+    assert_amount(payments[1].principal, 955.65);
+    assert_amount(payments[1].extra_principal, 1000.0);
+    assert_amount(db::get_account_balance(db_path, key, loan_account).unwrap(), 9098.16);
+
+    // Прогноз: доплата сокращает срок и сумму процентов
+    let projection = db::get_loan_payoff_projection(db_path, key, loan_id, 500.0)
+        .expect("Failed to get projection");
+    assert_amount(projection.outstanding_principal, 9098.16);
+    assert_eq!(projection.schedule.first().map(|row| row.payment_number), Some(3));
+    assert!(projection.baseline_payments <= 10);
+    assert!(projection.months_saved > 0);
+    assert!(projection.interest_saved > 0.0);
+    assert!(projection.projected_payoff_ts < projection.baseline_payoff_ts);
+    assert_amount(projection.schedule.last().unwrap().remaining_balance, 0.0);
+    println!(
+        "✓ Extra 500/month saves {} months and {} interest",
+        projection.months_saved, projection.interest_saved
+    );
+
+    // Долг по кредиту уменьшает Net Worth
+    let breakdown = db::get_net_worth_breakdown(db_path, key).expect("Failed to get net worth");
+    assert_amount(breakdown.liabilities, 9098.16);
+
+    println!("\n✅ Loan tests passed!");
+
+    let _ = fs::remove_file(db_path);
+}
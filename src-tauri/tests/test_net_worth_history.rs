@@ -0,0 +1,77 @@
+mod common;
+
+use common::T0;
+use fam_core_lib::db;
+use fam_core_lib::db::HistoryInterval;
+use std::fs;
+
+#[test]
+fn test_net_worth_history() {
+    let db_path = "/tmp/test_net_worth_history.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let day = 86400;
+
+    // Начало локальных суток 60 дней назад - первая точка дневной сетки
+    let start = T0 - 60 * day;
+    let empty = db::get_net_worth_history(db_path, key, start, start + 1, HistoryInterval::Day)
+        .expect("Failed to get history");
+    assert_eq!(empty.len(), 1);
+    assert_eq!(empty[0].net_worth, 0.0);
+    let day0 = empty[0].ts;
+
+    let wallet = db::create_account(db_path, key, "Wallet".to_string(), "cash".to_string())
+        .expect("Failed to create account");
+    let bank = db::create_account(db_path, key, "Bank".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let visa = db::create_account(db_path, key, "Visa".to_string(), "credit_card".to_string())
+        .expect("Failed to create account");
+
+    // Операции в середине дня, чтобы переходы на летнее время не меняли сутки
+    let at = |k: i64| day0 + k * day + 12 * 3600;
+    db::add_operation_at(db_path, key, wallet, 1000.0, "Cash".to_string(), at(1)).expect("Failed to add operation");
+    db::add_operation_at(db_path, key, bank, 5000.0, "Salary".to_string(), at(2)).expect("Failed to add operation");
+    db::add_operation_at(db_path, key, visa, 300.0, "Card debt".to_string(), at(4)).expect("Failed to add operation");
+    db::add_operation_at(db_path, key, wallet, -200.0, "Lunch".to_string(), at(4) + 3600).expect("Failed to add operation");
+    db::add_operation_at(db_path, key, bank, 1000.0, "Bonus".to_string(), at(20)).expect("Failed to add operation");
+
+    let history = db::get_net_worth_history(db_path, key, day0 + 3600, day0 + 7 * day, HistoryInterval::Day)
+        .expect("Failed to get history");
+    assert_eq!(history.len(), 7);
+    assert_eq!(history[0].ts, day0);
+    assert!(history.windows(2).all(|w| (w[1].ts - w[0].ts - day).abs() <= 3600));
+
+    let net: Vec<f64> = history.iter().map(|p| p.net_worth).collect();
+    assert_eq!(net, vec![0.0, 1000.0, 6000.0, 6000.0, 5500.0, 5500.0, 5500.0]);
+    assert_eq!(history[4].assets, 5800.0);
+    assert_eq!(history[4].liabilities, 300.0);
+    println!("✓ Daily grid carries balances forward: {:?}", net);
+
+    // Недельная сетка начинается с понедельника
+    let weekly = db::get_net_worth_history(db_path, key, day0, day0 + 28 * day, HistoryInterval::Week)
+        .expect("Failed to get history");
+    assert!(weekly.len() == 4 || weekly.len() == 5);
+    assert!(weekly[0].ts <= day0 && day0 - weekly[0].ts < 7 * day);
+    assert!(weekly.windows(2).all(|w| (w[1].ts - w[0].ts - 7 * day).abs() <= 3600));
+    assert_eq!(weekly.last().unwrap().net_worth, 6500.0);
+    println!("✓ Weekly grid: {} points", weekly.len());
+
+    // Месячная сетка: последний период содержит текущий баланс
+    let monthly = db::get_net_worth_history(db_path, key, day0, T0, HistoryInterval::Month)
+        .expect("Failed to get history");
+    assert!(monthly.len() >= 2 && monthly.len() <= 4);
+    assert_eq!(monthly.last().unwrap().net_worth, db::get_net_worth(db_path, key).expect("Failed to get net worth"));
+    println!("✓ Monthly grid: {} points", monthly.len());
+
+    // Пустой период отклоняется
+    let invalid = db::get_net_worth_history(db_path, key, day0, day0, HistoryInterval::Day);
+    assert!(matches!(invalid, Err(db::DbError::ValidationError(_))));
+
+    let _ = fs::remove_file(db_path);
+    println!("✅ test_net_worth_history passed");
+}
@@ -0,0 +1,210 @@
+use fam_core_lib::db;
+use fam_core_lib::db::ImportLineStatus;
+use std::fs;
+
+const SGML_STATEMENT: &str = "OFXHEADER:100
+DATA:OFXSGML
+VERSION:102
+SECURITY:NONE
+ENCODING:USASCII
+CHARSET:1252
+COMPRESSION:NONE
+OLDFILEUID:NONE
+NEWFILEUID:NONE
+
+<OFX>
+<SIGNONMSGSRSV1>
+<SONRS>
+<STATUS><CODE>0<SEVERITY>INFO</STATUS>
+<DTSERVER>20240201120000
+<LANGUAGE>ENG
+</SONRS>
+</SIGNONMSGSRSV1>
+<BANKMSGSRSV1>
+<STMTTRNRS>
+<TRNUID>1
+<STMTRS>
+<CURDEF>USD
+<BANKACCTFROM>
+<BANKID>121000248
+<ACCTID>1234567890
+<ACCTTYPE>CHECKING
+</BANKACCTFROM>
+<BANKTRANLIST>
+<DTSTART>20240101
+<DTEND>20240131
+<STMTTRN>
+<TRNTYPE>CREDIT
+<DTPOSTED>20240105
+<TRNAMT>2500.00
+<FITID>FIT-001
+<NAME>ACME PAYROLL
+<MEMO>January salary
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>20240115120000.000[-5:EST]
+<TRNAMT>-42.10
+<FITID>FIT-002
+<NAME>Smith &amp; Sons
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>20240120
+<TRNAMT>-42.10
+<FITID>FIT-003
+<NAME>Smith &amp; Sons
+</STMTTRN>
+</BANKTRANLIST>
+<LEDGERBAL>
+<BALAMT>2415.80
+<DTASOF>20240131
+</LEDGERBAL>
+</STMTRS>
+</STMTTRNRS>
+</BANKMSGSRSV1>
+</OFX>
+";
+
+const XML_CARD_STATEMENT: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<?OFX OFXHEADER="200" VERSION="220" SECURITY="NONE" OLDFILEUID="NONE" NEWFILEUID="NONE"?>
+<OFX>
+  <CREDITCARDMSGSRSV1>
+    <CCSTMTTRNRS>
+      <TRNUID>1</TRNUID>
+      <STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>
+      <CCSTMTRS>
+        <CURDEF>EUR</CURDEF>
+        <CCACCTFROM><ACCTID>4111111111111111</ACCTID></CCACCTFROM>
+        <BANKTRANLIST>
+          <DTSTART>20240201</DTSTART>
+          <DTEND>20240229</DTEND>
+          <STMTTRN>
+            <TRNTYPE>DEBIT</TRNTYPE>
+            <DTPOSTED>20240203</DTPOSTED>
+            <TRNAMT>-200.00</TRNAMT>
+            <FITID>CC-1</FITID>
+            <NAME>Café Zürich</NAME>
+          </STMTTRN>
+          <STMTTRN>
+            <TRNTYPE>PAYMENT</TRNTYPE>
+            <DTPOSTED>20240210</DTPOSTED>
+            <TRNAMT>50,00</TRNAMT>
+            <FITID>CC-2</FITID>
+          </STMTTRN>
+        </BANKTRANLIST>
+        <LEDGERBAL>
+          <BALAMT>-150.00</BALAMT>
+          <DTASOF>20240229235959</DTASOF>
+        </LEDGERBAL>
+      </CCSTMTRS>
+    </CCSTMTTRNRS>
+  </CREDITCARDMSGSRSV1>
+</OFX>
+"#;
+
+#[test]
+fn test_ofx_import() {
+    let db_path = "/tmp/test_ofx_import.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let checking = db::create_account(db_path, key, "Checking".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let card = db::create_account(db_path, key, "Card".to_string(), "credit_card".to_string())
+        .expect("Failed to create account");
+
+    // OFX 1.x (SGML): листья без закрывающих тегов
+    let preview = db::preview_ofx_import(db_path, key, checking, SGML_STATEMENT.as_bytes())
+        .expect("Failed to preview");
+    let lines = &preview.preview.lines;
+    assert_eq!(lines.len(), 3);
+    assert!(preview.preview.errors.is_empty());
+    assert_eq!(lines[0].description, "ACME PAYROLL January salary");
+    assert_eq!(lines[0].external_id.as_deref(), Some("FIT-001"));
+    assert_eq!(lines[1].description, "Smith & Sons");
+    assert_eq!(lines[1].ts, 1705338000);
+    assert!((lines[1].amount + 42.10).abs() < 1e-9);
+    // Одинаковые строки с разными FITID - разные операции
+    assert_eq!(lines[2].status, ImportLineStatus::New);
+
+    let check = preview.balance_check.as_ref().expect("LEDGERBAL not parsed");
+    assert!(check.matches);
+    assert!((check.book_balance - 2415.80).abs() < 1e-9);
+    assert!(db::get_operations(db_path, key, checking).expect("Failed to get operations").is_empty());
+    println!("✓ SGML preview with projected LEDGERBAL check");
+
+    let imported = db::import_ofx(db_path, key, checking, "jan.ofx".to_string(), SGML_STATEMENT.as_bytes())
+        .expect("Failed to import");
+    assert_eq!(imported.result.batch.source, "ofx");
+    assert_eq!(imported.result.batch.imported_count, 3);
+    let check = imported.balance_check.expect("LEDGERBAL not checked");
+    assert!(check.matches);
+    assert!((check.statement_balance - 2415.80).abs() < 1e-9);
+    println!("✓ SGML statement imported, LEDGERBAL matches states");
+
+    // Повторный импорт по FITID идемпотентен, даже если банк поправил описание
+    let reissued = SGML_STATEMENT.replace("<NAME>ACME PAYROLL", "<NAME>ACME CORP PAYROLL");
+    let again = db::import_ofx(db_path, key, checking, "jan-2.ofx".to_string(), reissued.as_bytes())
+        .expect("Failed to import");
+    assert_eq!(again.result.batch.imported_count, 0);
+    assert_eq!(again.result.batch.duplicate_count, 3);
+    assert!(again.balance_check.unwrap().matches);
+    assert_eq!(db::get_operations(db_path, key, checking).expect("Failed to get operations").len(), 3);
+    println!("✓ Re-import is idempotent by FITID");
+
+    // Расхождение остатка сообщается, но импорт выполняется
+    let mismatched = SGML_STATEMENT
+        .replace("FIT-00", "FEB-00")
+        .replace("<DTASOF>20240131", "<DTASOF>20240229")
+        .replace("<BALAMT>2415.80", "<BALAMT>4800.00");
+    let imported = db::import_ofx(db_path, key, checking, "feb.ofx".to_string(), mismatched.as_bytes())
+        .expect("Failed to import");
+    let check = imported.balance_check.unwrap();
+    assert!(!check.matches);
+    assert!((check.book_balance - 4831.60).abs() < 1e-9);
+    assert!((check.difference + 31.60).abs() < 1e-9);
+    assert_eq!(imported.result.batch.imported_count, 3);
+    println!("✓ LEDGERBAL mismatch reported");
+
+    // OFX 2.x (XML) по кредитной карте: знаки переводятся в знаки обязательства
+    let imported = db::import_ofx(db_path, key, card, "card.ofx".to_string(), XML_CARD_STATEMENT.as_bytes())
+        .expect("Failed to import");
+    assert_eq!(imported.result.batch.imported_count, 2);
+    let check = imported.balance_check.unwrap();
+    assert!(check.matches);
+    assert!((check.statement_balance - 150.0).abs() < 1e-9);
+    let operations = db::get_operations(db_path, key, card).expect("Failed to get operations");
+    let charge = operations.iter().find(|op| op.amount > 0.0).expect("Charge not imported");
+    assert_eq!(charge.description, "Café Zürich");
+    assert!((charge.amount - 200.0).abs() < 1e-9);
+    let payment = operations.iter().find(|op| op.amount < 0.0).expect("Payment not imported");
+    assert_eq!(payment.description, "PAYMENT");
+    assert!((payment.amount + 50.0).abs() < 1e-9);
+    println!("✓ XML credit card statement imported");
+
+    // Ошибки
+    let missing_fitid = SGML_STATEMENT.replace("<FITID>FIT-002\n", "");
+    let preview = db::preview_ofx_import(db_path, key, checking, missing_fitid.as_bytes()).expect("Failed to preview");
+    assert_eq!(preview.preview.errors.len(), 1);
+    assert!(preview.preview.errors[0].message.contains("FITID"));
+    let result = db::import_ofx(db_path, key, checking, "bad.ofx".to_string(), missing_fitid.as_bytes());
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    let not_ofx = db::preview_ofx_import(db_path, key, checking, b"Date,Amount\n2024-01-01,5\n");
+    assert!(matches!(not_ofx, Err(db::DbError::ValidationError(_))));
+
+    let two_statements = SGML_STATEMENT.replace(
+        "</STMTTRNRS>",
+        "</STMTTRNRS><STMTTRNRS><STMTRS><BANKTRANLIST></BANKTRANLIST></STMTRS></STMTTRNRS>",
+    );
+    let result = db::preview_ofx_import(db_path, key, checking, two_statements.as_bytes());
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+    println!("✓ Invalid files rejected");
+
+    println!("\n✅ OFX import test passed!");
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use common::T0;
+use fam_core_lib::db;
+use std::fs;
+
+#[test]
+fn test_query_operations_pagination() {
+    let db_path = "/tmp/test_operation_query.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let day = 86400;
+    let base = T0 - 100 * day;
+
+    let card = db::create_account(db_path, key, "Card".to_string(), "card".to_string())
+        .expect("Failed to create account");
+    let checking = db::create_account(db_path, key, "Checking".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let cash = db::create_account(db_path, key, "Cash".to_string(), "cash".to_string())
+        .expect("Failed to create account");
+
+    // 10 операций по карте, две пары с одинаковой датой и суммой
+    let mut card_ops = Vec::new();
+    for i in 0..10 {
+        let ts = base + (i / 2) * day;
+        let amount = -100.0 * ((i % 5) + 1) as f64;
+        let id = db::add_operation_at(db_path, key, card, amount, format!("Grocery {}", i), ts)
+            .expect("Failed to add operation");
+        card_ops.push(id);
+    }
+    db::add_operation_at(db_path, key, checking, 5000.0, "Salary".to_string(), base + 3 * day)
+        .expect("Failed to add operation");
+    db::add_operation_at(db_path, key, cash, -50.0, "Grocery kiosk".to_string(), base + 4 * day)
+        .expect("Failed to add operation");
+
+    let query = |query: db::OperationQuery| db::query_operations(db_path, key, query).expect("Failed to query");
+
+    // Обход всех страниц по курсору: без пропусков и повторов
+    let mut seen = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = query(db::OperationQuery {
+            filters: db::OperationFilters { account_ids: Some(vec![card]), ..Default::default() },
+            cursor,
+            limit: Some(3),
+            ..Default::default()
+        });
+        assert_eq!(page.total_count, 10);
+        assert!((page.total_amount + 3000.0).abs() < 0.01);
+        assert!(page.operations.len() <= 3);
+        seen.extend(page.operations.iter().map(|op| (op.ts, op.id)));
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(seen.len(), 10);
+    let mut expected = seen.clone();
+    expected.sort_by(|a, b| b.cmp(a));
+    assert_eq!(seen, expected);
+    println!("✓ Keyset pagination by ts desc covers all rows exactly once");
+
+    // Сортировка по сумме с равными ключами
+    let mut amounts = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = query(db::OperationQuery {
+            filters: db::OperationFilters { account_ids: Some(vec![card]), ..Default::default() },
+            sort: db::OperationSort::AmountAsc,
+            cursor,
+            limit: Some(4),
+            ..Default::default()
+        });
+        amounts.extend(page.operations.iter().map(|op| (op.amount, op.id)));
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(amounts.len(), 10);
+    assert!(amounts.windows(2).all(|w| w[0].0 < w[1].0 || (w[0].0 == w[1].0 && w[0].1 < w[1].1)));
+    println!("✓ Keyset pagination by amount asc handles ties");
+
+    // Несколько счетов, текст, диапазон сумм и дат
+    let page = query(db::OperationQuery {
+        filters: db::OperationFilters {
+            account_ids: Some(vec![card, cash]),
+            from_ts: Some(base + 2 * day),
+            to_ts: None,
+            min_amount: Some(-300.0),
+            max_amount: Some(-50.0),
+        },
+        text: Some("grocery".to_string()),
+        ..Default::default()
+    });
+    // Карта: i = 4..9, суммы -500,-100,-200,-300,-400,-500 → подходят -100,-200,-300; плюс киоск -50
+    assert_eq!(page.total_count, 4);
+    assert!((page.total_amount + 650.0).abs() < 0.01);
+    assert!(page.next_cursor.is_none());
+    assert!(page.operations.iter().all(|op| op.account_id != checking));
+    println!("✓ Filters and totals: {} results, sum {}", page.total_count, page.total_amount);
+
+    // Курсор другой сортировки и мусорный курсор отклоняются
+    let first = query(db::OperationQuery { limit: Some(1), ..Default::default() });
+    let foreign = db::query_operations(db_path, key, db::OperationQuery {
+        sort: db::OperationSort::AmountDesc,
+        cursor: first.next_cursor.clone(),
+        ..Default::default()
+    });
+    assert!(matches!(foreign, Err(db::DbError::ValidationError(_))));
+    let garbage = db::query_operations(db_path, key, db::OperationQuery {
+        cursor: Some("ts_desc:abc".to_string()),
+        ..Default::default()
+    });
+    assert!(matches!(garbage, Err(db::DbError::ValidationError(_))));
+    println!("✓ Invalid cursors rejected");
+
+    let _ = fs::remove_file(db_path);
+    println!("✅ test_query_operations_pagination passed");
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::T0;
+use fam_core_lib::db;
+use std::fs;
+
+#[test]
+fn test_build_fts_query() {
+    assert_eq!(db::build_fts_query("plumber").unwrap(), "\"plumber\"");
+    assert_eq!(db::build_fts_query("plumb* spring").unwrap(), "\"plumb\"* \"spring\"");
+    assert_eq!(db::build_fts_query("\"plumber ivanov\" repair").unwrap(), "\"plumber ivanov\" \"repair\"");
+    assert_eq!(db::build_fts_query("AND (NEAR").unwrap(), "\"AND\" \"NEAR\"");
+    assert!(matches!(db::build_fts_query(" * \"\" "), Err(db::DbError::ValidationError(_))));
+}
+
+#[test]
+fn test_search_operations() {
+    let db_path = "/tmp/test_operation_search.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let day = 86400;
+    let spring = T0 - 200 * day;
+
+    let card = db::create_account(db_path, key, "Card".to_string(), "card".to_string())
+        .expect("Failed to create account");
+    let checking = db::create_account(db_path, key, "Checking".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+
+    let aqua = db::create_payee(db_path, key, "Aqua Service".to_string()).expect("Failed to create payee");
+    db::add_payee_rule(db_path, key, aqua, "alias".to_string(), "plumber".to_string(), 0)
+        .expect("Failed to add payee rule");
+
+    let plumber = db::add_operation_at(db_path, key, checking, -5000.0, "Plumber Ivanov repair".to_string(), spring)
+        .expect("Failed to add operation");
+    let supplies = db::add_operation_at(db_path, key, card, -800.0, "Plumbing supplies store".to_string(), spring + day)
+        .expect("Failed to add operation");
+    let coffee = db::add_operation_at(db_path, key, card, -150.0, "Coffee".to_string(), T0 - day)
+        .expect("Failed to add operation");
+    let cyrillic = db::add_operation_at(db_path, key, card, -3000.0, "Сантехник Петров".to_string(), T0 - 2 * day)
+        .expect("Failed to add operation");
+
+    let ids = |hits: &[db::OperationSearchHit]| hits.iter().map(|h| h.operation.id).collect::<Vec<_>>();
+    let search = |query: &str, filters: db::OperationFilters| {
+        db::search_operations(db_path, key, query.to_string(), filters, None).expect("Failed to search")
+    };
+
+    // Префиксный поиск и фразы
+    let hits = search("plumb*", Default::default());
+    assert_eq!(hits.len(), 2);
+    assert!(hits[0].snippet.contains('['));
+
+    assert_eq!(ids(&search("\"plumber ivanov\"", Default::default())), vec![plumber]);
+    assert_eq!(ids(&search("ivanov plumber", Default::default())), vec![plumber]);
+    assert!(search("\"ivanov plumber\"", Default::default()).is_empty());
+    assert_eq!(ids(&search("сантехник", Default::default())), vec![cyrillic]);
+    println!("✓ Phrase, prefix and Cyrillic queries work");
+
+    // Контрагент, теги и заметки попадают в индекс
+    assert_eq!(ids(&search("aqua", Default::default())), vec![plumber]);
+
+    let tag = db::create_tag(db_path, key, "renovation".to_string()).expect("Failed to create tag");
+    db::add_operation_tag(db_path, key, supplies, tag).expect("Failed to tag operation");
+    assert_eq!(ids(&search("renovation", Default::default())), vec![supplies]);
+
+    db::set_operation_notes(db_path, key, coffee, Some("met the plumber".to_string()))
+        .expect("Failed to set notes");
+    let hits = search("plumber", Default::default());
+    assert_eq!(ids(&hits), vec![plumber, coffee], "Description match must rank above notes match");
+    assert!(hits[0].rank > hits[1].rank);
+
+    db::remove_operation_tag(db_path, key, supplies, tag).expect("Failed to untag operation");
+    assert!(search("renovation", Default::default()).is_empty());
+    println!("✓ Index kept in sync by write paths");
+
+    // Фильтры по счетам, периоду и сумме
+    let by_account = search("plumb*", db::OperationFilters {
+        account_ids: Some(vec![card]),
+        ..Default::default()
+    });
+    assert_eq!(ids(&by_account), vec![supplies, coffee]);
+
+    let by_amount = search("plumb*", db::OperationFilters {
+        min_amount: Some(-6000.0),
+        max_amount: Some(-1000.0),
+        ..Default::default()
+    });
+    assert_eq!(ids(&by_amount), vec![plumber]);
+
+    let by_date = search("plumber", db::OperationFilters {
+        from_ts: Some(T0 - 30 * day),
+        to_ts: Some(T0),
+        ..Default::default()
+    });
+    assert_eq!(ids(&by_date), vec![coffee]);
+    println!("✓ Account, amount and date filters applied");
+
+    let limited = db::search_operations(db_path, key, "plumb*".to_string(), Default::default(), Some(1))
+        .expect("Failed to search");
+    assert_eq!(limited.len(), 1);
+
+    println!("\n✅ Operation search tests passed!");
+
+    let _ = fs::remove_file(db_path);
+}
@@ -0,0 +1,99 @@
+use fam_core_lib::db;
+use std::fs;
+
+fn split(category_id: Option<i64>, amount: f64, memo: &str) -> db::SplitLineInput {
+    db::SplitLineInput {
+        category_id,
+        amount,
+        memo: memo.to_string(),
+    }
+}
+
+#[test]
+fn test_operation_splits_and_category_report() {
+    let db_path = "/tmp/test_operation_splits.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let acc = db::create_account(db_path, key, "Card".to_string(), "card".to_string())
+        .expect("Failed to create account");
+    let groceries = db::create_category(db_path, key, "Groceries".to_string()).expect("Failed to create category");
+    let household = db::create_category(db_path, key, "Household".to_string()).expect("Failed to create category");
+    let kids = db::create_category(db_path, key, "Kids".to_string()).expect("Failed to create category");
+
+    let receipt = db::add_operation(db_path, key, acc, -1000.0, "Supermarket".to_string())
+        .expect("Failed to add operation");
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let coffee = db::add_operation(db_path, key, acc, -150.0, "Coffee".to_string())
+        .expect("Failed to add operation");
+    db::set_operation_category(db_path, key, coffee, Some(groceries)).expect("Failed to set category");
+
+    // Сумма строк должна совпадать с суммой операции
+    let result = db::set_operation_splits(db_path, key, receipt, vec![
+        split(Some(groceries), -600.0, "food"),
+        split(Some(household), -300.0, "detergent"),
+    ]);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    // Знак строк должен совпадать со знаком операции
+    let result = db::set_operation_splits(db_path, key, receipt, vec![
+        split(Some(groceries), -1200.0, "food"),
+        split(Some(household), 200.0, "refund"),
+    ]);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    let lines = db::set_operation_splits(db_path, key, receipt, vec![
+        split(Some(groceries), -600.0, "food"),
+        split(Some(household), -250.0, "detergent"),
+        split(Some(kids), -150.0, "toys"),
+    ]).expect("Failed to set splits");
+    assert_eq!(lines.len(), 3);
+    assert_eq!(db::get_operation_splits(db_path, key, receipt).unwrap().len(), 3);
+    println!("✓ Split lines validated and stored");
+
+    // Строки разбивки входят в подписанный payload операции
+    let log = db::list_version_log(db_path, key, Some("operation".to_string()), Some(receipt))
+        .expect("Failed to list version log");
+    assert_eq!(log[0].action, "update");
+    assert!(log[0].payload.contains("\"splits\":["));
+    assert!(log[0].payload.contains("\"memo\":\"toys\""));
+    assert!(db::verify_version_signature(db_path, key, log[0].id).expect("Failed to verify"));
+    println!("✓ Splits stored in signed payload");
+
+    // Перевод на свой счёт и покупка бумаг - не расходы
+    let savings = db::create_account(db_path, key, "Savings".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    db::create_transfer(db_path, key, acc, savings, 500.0, "To savings".to_string(), None)
+        .expect("Failed to create transfer");
+    let broker = db::create_account(db_path, key, "Broker".to_string(), "brokerage".to_string())
+        .expect("Failed to create account");
+    let security = db::create_security(db_path, key, "VTI".to_string(), None, "Total Market".to_string(), "etf".to_string())
+        .expect("Failed to create security");
+    db::add_investment_transaction(db_path, key, broker, security, "buy".to_string(), 2.0, 200.0, 1.0, 1700000000)
+        .expect("Failed to buy");
+
+    // Отчёт по категориям учитывает каждую строку отдельно
+    let report = db::get_spending_by_category(db_path, key, None, None)
+        .expect("Failed to get spending by category");
+    let total_of = |id: i64| report.iter().find(|r| r.category_id == Some(id)).map(|r| r.total_spent);
+    assert_eq!(total_of(groceries), Some(750.0));
+    assert_eq!(total_of(household), Some(250.0));
+    assert_eq!(total_of(kids), Some(150.0));
+    assert!(report.iter().all(|r| r.category_id.is_some()));
+    println!("✓ Category report counts each split line");
+
+    // Пустой список снимает разбивку
+    db::set_operation_splits(db_path, key, receipt, vec![]).expect("Failed to clear splits");
+    let report = db::get_spending_by_category(db_path, key, None, None)
+        .expect("Failed to get spending by category");
+    let uncategorized = report.iter().find(|r| r.category_id.is_none()).expect("Uncategorized group");
+    assert_eq!(uncategorized.total_spent, 1000.0);
+
+    println!("\n✅ Operation split tests passed!");
+
+    let _ = fs::remove_file(db_path);
+}
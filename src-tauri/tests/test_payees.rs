@@ -0,0 +1,145 @@
+mod common;
+
+use common::T0;
+use fam_core_lib::db;
+use std::fs;
+
+#[test]
+fn test_normalize_description() {
+    assert_eq!(db::normalize_description("PYATEROCHKA 1234 MOSCOW"), "pyaterochka moscow");
+    assert_eq!(db::normalize_description("Pyaterochka #88"), "pyaterochka");
+    assert_eq!(db::normalize_description("  Кофе*Хаус  "), "кофе хаус");
+}
+
+#[test]
+fn test_payee_normalization_and_merge() {
+    let db_path = "/tmp/test_payees.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let day = 86400;
+    let base_ts = T0 - 30 * day;
+
+    let acc = db::create_account(db_path, key, "Checking".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    db::add_operation_at(db_path, key, acc, 10000.0, "Salary".to_string(), base_ts)
+        .expect("Failed to add operation");
+
+    // Операция до появления правила остаётся без контрагента
+    let early = db::add_operation_at(db_path, key, acc, -50.0, "PIATEROCHKA SPB".to_string(), base_ts + day)
+        .expect("Failed to add operation");
+
+    let pyaterochka = db::create_payee(db_path, key, "Pyaterochka".to_string())
+        .expect("Failed to create payee");
+    let perekrestok = db::create_payee(db_path, key, "Perekrestok".to_string())
+        .expect("Failed to create payee");
+    assert!(matches!(
+        db::create_payee(db_path, key, "pyaterochka".to_string()),
+        Err(db::DbError::ValidationError(_))
+    ));
+
+    db::add_payee_rule(db_path, key, perekrestok, "regex".to_string(), r"^PRKR\b".to_string(), 10)
+        .expect("Failed to add regex rule");
+    assert!(matches!(
+        db::add_payee_rule(db_path, key, perekrestok, "regex".to_string(), "(".to_string(), 0),
+        Err(db::DbError::ValidationError(_))
+    ));
+
+    // Новые операции сопоставляются с контрагентом при вставке
+    let op1 = db::add_operation_at(db_path, key, acc, -500.0, "PYATEROCHKA 1234 MOSCOW".to_string(), base_ts + 2 * day)
+        .expect("Failed to add operation");
+    let op2 = db::add_operation_at(db_path, key, acc, -300.0, "Pyaterochka #88".to_string(), base_ts + 3 * day)
+        .expect("Failed to add operation");
+    let op3 = db::add_operation_at(db_path, key, acc, -200.0, "prkr 77 spb".to_string(), base_ts + 4 * day)
+        .expect("Failed to add operation");
+    let op4 = db::add_operation_at(db_path, key, acc, -100.0, "Unknown shop".to_string(), base_ts + 5 * day)
+        .expect("Failed to add operation");
+
+    let operations = db::get_operations(db_path, key, acc).expect("Failed to get operations");
+    let payee_of = |id: i64| operations.iter().find(|op| op.id == id).and_then(|op| op.payee_id);
+    assert_eq!(payee_of(op1), Some(pyaterochka));
+    assert_eq!(payee_of(op2), Some(pyaterochka));
+    assert_eq!(payee_of(op3), Some(perekrestok));
+    assert_eq!(payee_of(op4), None);
+    assert_eq!(payee_of(early), None);
+    println!("✓ Descriptions normalized to payees on insert");
+
+    // Новый псевдоним применяется задним числом
+    db::add_payee_rule(db_path, key, pyaterochka, "alias".to_string(), "Piaterochka".to_string(), 0)
+        .expect("Failed to add alias rule");
+    let changed = db::apply_payee_rules(db_path, key, false).expect("Failed to apply rules");
+    assert_eq!(changed, 1);
+    println!("✓ Rules re-applied to {} existing operation", changed);
+
+    // Слияние дублей
+    let duplicate = db::create_payee(db_path, key, "Pyaterochka Express".to_string())
+        .expect("Failed to create payee");
+    db::set_operation_payee(db_path, key, op4, Some(duplicate)).expect("Failed to set payee");
+    let subscription = db::create_recurring_item(db_path, key, db::RecurringItemInput {
+        account_id: acc,
+        amount: -300.0,
+        description: "Weekly groceries".to_string(),
+        payee_id: Some(duplicate),
+        interval: db::HistoryInterval::Week,
+        next_ts: base_ts + 31 * day,
+        end_ts: None,
+    }).expect("Failed to create recurring item");
+
+    let merges = db::merge_payees(db_path, key, pyaterochka, vec![duplicate])
+        .expect("Failed to merge payees");
+    assert_eq!(merges.len(), 1);
+    assert_eq!(merges[0].operations_moved, 1);
+
+    let payees = db::list_payees(db_path, key).expect("Failed to list payees");
+    assert_eq!(payees.len(), 2);
+    assert!(!payees.iter().any(|p| p.id == duplicate));
+
+    let items = db::list_recurring_items(db_path, key).expect("Failed to list recurring items");
+    assert_eq!(items.iter().find(|item| item.id == subscription).unwrap().payee_id, Some(pyaterochka));
+
+    let rules = db::list_payee_rules(db_path, key, Some(pyaterochka)).expect("Failed to list rules");
+    assert!(rules.iter().any(|r| r.kind == "alias" && r.pattern == "Pyaterochka Express"));
+
+    let log = db::list_version_log(db_path, key, Some("payee".to_string()), Some(duplicate))
+        .expect("Failed to list version log");
+    assert_eq!(log[0].action, "merge");
+    assert!(db::verify_version_signature(db_path, key, log[0].id).expect("Failed to verify"));
+    println!("✓ Payees merged with signed log entry");
+
+    // Перевод на свой счёт и покупка бумаг - не расходы
+    let savings = db::create_account(db_path, key, "Savings".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    db::create_transfer(db_path, key, acc, savings, 1000.0, "Pyaterochka cashback to savings".to_string(), Some(base_ts + 6 * day))
+        .expect("Failed to create transfer");
+    let broker = db::create_account(db_path, key, "Broker".to_string(), "brokerage".to_string())
+        .expect("Failed to create account");
+    let security = db::create_security(db_path, key, "X5".to_string(), None, "X5 Group".to_string(), "stock".to_string())
+        .expect("Failed to create security");
+    db::add_investment_transaction(db_path, key, broker, security, "buy".to_string(), 10.0, 25.0, 1.0, base_ts + 6 * day)
+        .expect("Failed to buy");
+
+    // Аналитика: расходы по контрагентам
+    let spending = db::get_spending_by_payee(db_path, key, None, None)
+        .expect("Failed to get spending by payee");
+    assert_eq!(spending.len(), 2);
+    assert_eq!(spending[0].payee_id, Some(pyaterochka));
+    assert_eq!(spending[0].payee_name.as_deref(), Some("Pyaterochka"));
+    assert_eq!(spending[0].total_spent, 950.0);
+    assert_eq!(spending[0].operation_count, 4);
+    assert_eq!(spending[1].payee_id, Some(perekrestok));
+    assert_eq!(spending[1].total_spent, 200.0);
+
+    let spending = db::get_spending_by_payee(db_path, key, Some(base_ts + 3 * day), Some(base_ts + 5 * day))
+        .expect("Failed to get spending by payee");
+    let total: f64 = spending.iter().map(|s| s.total_spent).sum();
+    assert_eq!(total, 500.0);
+    println!("✓ Spending grouped by payee");
+
+    println!("\n✅ Payee tests passed!");
+
+    let _ = fs::remove_file(db_path);
+}
@@ -0,0 +1,284 @@
+use fam_core_lib::db;
+use fam_core_lib::db::PlainTextFormat;
+use std::fs;
+
+const DAY: i64 = 86400;
+const T0: i64 = 1704103200; // 2024-01-01 10:00 UTC
+
+const GENERIC_BEANCOUNT: &str = r#"option "title" "Personal"
+plugin "beancount.plugins.auto_accounts"
+
+2024-03-01 open Assets:Checking EUR
+2024-03-01 open Expenses:Food
+2024-03-01 open Income:Salary
+
+2024-03-01 * "Employer" "March salary" #work
+  Assets:Checking   2,500.00 EUR
+  Income:Salary
+
+2024-03-02 ! "Bakery"
+  Assets:Checking  -12.50 EUR ; cash back
+  Expenses:Food     12.50 EUR
+
+2024-03-03 pad Assets:Checking Equity:Opening-Balances
+
+2024-03-05 balance Assets:Checking  2487.50 EUR
+"#;
+
+#[test]
+fn test_plain_text_export_import() {
+    let db_path = "/tmp/test_plain_text_accounting.db";
+    let beancount_path = "/tmp/test_plain_text_accounting_beancount.db";
+    let ledger_path = "/tmp/test_plain_text_accounting_ledger.db";
+    let key = "test_key_123";
+
+    for path in [db_path, beancount_path, ledger_path] {
+        let _ = fs::remove_file(path);
+        db::init_db(path, key).expect("Failed to init db");
+    }
+
+    let bank = db::create_account(db_path, key, "Bank".to_string(), "bank".to_string()).expect("Failed to create account");
+    let card = db::create_account(db_path, key, "Кредитная карта".to_string(), "credit_card".to_string())
+        .expect("Failed to create account");
+    let old = db::create_account(db_path, key, "Old deposit".to_string(), "deposit".to_string())
+        .expect("Failed to create account");
+    let groceries = db::create_category(db_path, key, "Продукты".to_string()).expect("Failed to create category");
+    let salary = db::create_category(db_path, key, "Salary".to_string()).expect("Failed to create category");
+
+    db::set_opening_balance(db_path, key, bank, 1000.0, T0).expect("Failed to set opening balance");
+    let paycheck = db::add_operation_at(db_path, key, bank, 5000.0, "Paycheck \"March\"".to_string(), T0 + DAY)
+        .expect("Failed to add operation");
+    db::set_operation_category(db_path, key, paycheck, Some(salary)).expect("Failed to set category");
+    let purchase = db::add_operation_at(db_path, key, card, 300.0, "Supermarket; weekly".to_string(), T0 + 2 * DAY)
+        .expect("Failed to add operation");
+    db::set_operation_category(db_path, key, purchase, Some(groceries)).expect("Failed to set category");
+    let fee = db::add_operation_at(db_path, key, bank, -50.0, "Bank fee".to_string(), T0 + 3 * DAY)
+        .expect("Failed to add operation");
+    let hypermarket = db::add_operation_at(db_path, key, bank, -400.0, "Hypermarket".to_string(), T0 + 3 * DAY + 3600)
+        .expect("Failed to add operation");
+    db::set_operation_splits(db_path, key, hypermarket, vec![
+        db::SplitLineInput { category_id: Some(groceries), amount: -250.0, memo: "Food".to_string() },
+        db::SplitLineInput { category_id: None, amount: -150.0, memo: "Household".to_string() },
+    ]).expect("Failed to split operation");
+    let repayment = db::create_transfer(db_path, key, bank, card, 300.0, "Card repayment".to_string(), Some(T0 + 4 * DAY))
+        .expect("Failed to create transfer");
+    let assertion = db::add_balance_assertion(db_path, key, bank, 5250.0, T0 + 5 * DAY).expect("Failed to add assertion");
+    db::add_balance_assertion(db_path, key, card, 0.0, T0 + 5 * DAY).expect("Failed to add assertion");
+    // Операция в тот же день после сверки
+    db::add_operation_at(db_path, key, bank, -20.0, "Coffee".to_string(), T0 + 5 * DAY + 3600)
+        .expect("Failed to add operation");
+
+    // Закрытый счёт не принимает операции позже закрытия
+    db::close_account(db_path, key, old, Some(T0 + 6 * DAY)).expect("Failed to close account");
+    let result = db::add_operation_at(db_path, key, old, 10.0, "Late".to_string(), T0 + 7 * DAY);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+    let result = db::close_account(db_path, key, old, None);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+    let result = db::close_account(db_path, key, bank, Some(T0));
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+    println!("✓ Closed account rejects later operations");
+
+    // Экспорт beancount
+    let beancount = db::export_plain_text(db_path, key, PlainTextFormat::Beancount, "RUB".to_string())
+        .expect("Failed to export beancount");
+    println!("{}", beancount);
+    assert!(beancount.contains("option \"operating_currency\" \"RUB\""));
+    assert!(beancount.contains(" open Assets:Bank RUB\n"));
+    assert!(beancount.contains(" open Liabilities:Кредитная-карта RUB\n"));
+    assert!(beancount.contains(&format!("  fam_account_id: {}\n", card)));
+    assert!(beancount.contains("  fam_name: \"Кредитная карта\"\n"));
+    assert!(beancount.contains(" open Expenses:Продукты RUB\n"));
+    assert!(beancount.contains(" open Income:Salary RUB\n"));
+    assert!(beancount.contains("* \"Paycheck \\\"March\\\"\"\n"));
+    assert!(beancount.contains("  Assets:Bank  5000.00 RUB\n"));
+    assert!(beancount.contains("  Income:Salary  -5000.00 RUB\n"));
+    assert!(beancount.contains("  Liabilities:Кредитная-карта  -300.00 RUB\n"));
+    assert!(beancount.contains("  Expenses:Uncategorized  50.00 RUB\n"));
+    assert!(beancount.contains("  Equity:Opening-Balances  -1000.00 RUB\n"));
+    assert!(beancount.contains(&format!("  fam_transfer_id: {}\n", repayment)));
+    assert!(beancount.contains(&format!("    fam_operation_id: {}\n", fee)));
+    assert!(beancount.contains("  Expenses:Продукты  250.00 RUB\n"));
+    assert!(beancount.contains("  Expenses:Uncategorized  150.00 RUB\n"));
+    assert!(beancount.contains("    fam_memo: \"Household\"\n"));
+    // beancount проверяет остаток на начало следующих суток - с учётом кофе после сверки
+    assert!(beancount.contains(" balance Assets:Bank  5230.00 RUB\n"));
+    assert!(beancount.contains(" close Assets:Old-deposit\n"));
+
+    let open_bank = beancount.find(" open Assets:Bank ").unwrap();
+    let balance_bank = beancount.find(" balance Assets:Bank ").unwrap();
+    let close_old = beancount.find(" close Assets:Old-deposit").unwrap();
+    assert!(open_bank < balance_bank && balance_bank < close_old, "Directives must be sorted by date");
+    println!("✓ beancount export written");
+
+    // Экспорт ledger
+    let ledger = db::export_plain_text(db_path, key, PlainTextFormat::Ledger, "RUB".to_string())
+        .expect("Failed to export ledger");
+    println!("{}", ledger);
+    assert!(ledger.contains("account Assets:Bank\n"));
+    assert!(ledger.contains(&format!("    ; fam_account_id: {}\n", bank)));
+    assert!(ledger.contains("    ; fam_closed_at: "));
+    assert!(ledger.contains("* Supermarket, weekly\n"));
+    assert!(ledger.contains("    Assets:Bank  0.00 RUB = 5250.00 RUB\n"));
+    assert!(ledger.contains("    ; fam_memo: Food\n"));
+    assert!(ledger.contains(&format!("    ; fam_balance_assertion_id: {}\n", assertion)));
+    println!("✓ ledger export written");
+
+    let result = db::export_plain_text(db_path, key, PlainTextFormat::Beancount, "rub".to_string());
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    // Обратный импорт в пустую БД сохраняет идентификаторы
+    for (path, format, content) in [
+        (beancount_path, PlainTextFormat::Beancount, &beancount),
+        (ledger_path, PlainTextFormat::Ledger, &ledger),
+    ] {
+        let imported = db::import_plain_text(path, key, format, content.as_bytes()).expect("Failed to import");
+        assert_eq!(imported.accounts_created, 3, "{:?}", format);
+        assert_eq!(imported.accounts_closed, 1);
+        assert_eq!(imported.categories_created, 2);
+        assert_eq!(imported.operations_created, 5);
+        assert_eq!(imported.transfers_created, 1);
+        assert_eq!(imported.opening_balances_set, 1);
+        assert_eq!(imported.assertions_created, 2);
+        assert_eq!(imported.skipped_count, 0);
+        assert!(imported.warnings.is_empty(), "{:?}", imported.warnings);
+
+        let mut source_accounts = db::list_accounts(db_path, key).unwrap();
+        let mut accounts = db::list_accounts(path, key).unwrap();
+        source_accounts.sort_by_key(|account| account.id);
+        accounts.sort_by_key(|account| account.id);
+        for (source, account) in source_accounts.iter().zip(&accounts) {
+            assert_eq!(source.id, account.id);
+            assert_eq!(source.name, account.name);
+            assert_eq!(source.acc_class, account.acc_class);
+            assert_eq!(source.acc_type, account.acc_type);
+            assert_eq!(source.closed_at, account.closed_at);
+        }
+
+        for account_id in [bank, card] {
+            let source = db::get_operations(db_path, key, account_id).unwrap();
+            let operations = db::get_operations(path, key, account_id).unwrap();
+            assert_eq!(source.len(), operations.len());
+            for (source, operation) in source.iter().zip(&operations) {
+                assert_eq!(source.id, operation.id);
+                assert_eq!(source.amount, operation.amount);
+                assert_eq!(source.ts, operation.ts);
+                assert_eq!(source.description, operation.description.replace("Supermarket, weekly", "Supermarket; weekly"));
+            }
+            assert_eq!(
+                db::get_account_balance(db_path, key, account_id).unwrap(),
+                db::get_account_balance(path, key, account_id).unwrap()
+            );
+        }
+
+        let categories = db::list_categories(path, key).unwrap();
+        let category_name = |operation_id: i64| {
+            let operation = db::get_operations(path, key, bank)
+                .unwrap()
+                .into_iter()
+                .chain(db::get_operations(path, key, card).unwrap())
+                .find(|operation| operation.id == operation_id)
+                .unwrap();
+            operation.category_id.map(|id| categories.iter().find(|c| c.id == id).unwrap().name.clone())
+        };
+        assert_eq!(category_name(paycheck).as_deref(), Some("Salary"));
+        assert_eq!(category_name(purchase).as_deref(), Some("Продукты"));
+        assert_eq!(category_name(fee), None);
+        assert_eq!(category_name(hypermarket), None);
+
+        let splits = db::get_operation_splits(path, key, hypermarket).unwrap();
+        let splits: Vec<_> = splits
+            .iter()
+            .map(|split| {
+                let category = split.category_id.map(|id| categories.iter().find(|c| c.id == id).unwrap().name.clone());
+                (category, split.amount, split.memo.as_str())
+            })
+            .collect();
+        assert_eq!(splits, vec![
+            (Some("Продукты".to_string()), -250.0, "Food"),
+            (None, -150.0, "Household"),
+        ]);
+
+        let transfers = db::list_transfers(path, key, None, None).unwrap();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].id, repayment);
+        assert_eq!((transfers[0].from_account_id, transfers[0].to_account_id), (bank, card));
+
+        let report = db::check_balance_assertions(path, key, None).unwrap();
+        assert_eq!(report.passed_count, 2);
+        assert!(report.results.iter().any(|r| {
+            r.assertion_id == assertion && r.ts == T0 + 5 * DAY && r.expected_balance == 5250.0
+        }));
+
+        let opening = db::get_opening_balance(path, key, bank).unwrap().expect("Opening balance not imported");
+        assert_eq!((opening.balance, opening.ts), (1000.0, T0));
+
+        // Повторный импорт ничего не меняет
+        let again = db::import_plain_text(path, key, format, content.as_bytes()).expect("Failed to re-import");
+        assert_eq!(again.accounts_created + again.operations_created + again.transfers_created, 0);
+        assert_eq!(again.assertions_created + again.opening_balances_set + again.accounts_closed, 0);
+        assert_eq!(again.skipped_count, 3 + 1 + 5 + 1 + 1 + 2);
+        println!("✓ {:?} round trip preserves ids and is idempotent", format);
+    }
+
+    // Файл beancount без метаданных: опущенная сумма, неподдерживаемые директивы
+    let imported = db::import_plain_text(beancount_path, key, PlainTextFormat::Beancount, GENERIC_BEANCOUNT.as_bytes())
+        .expect("Failed to import generic beancount");
+    assert_eq!(imported.accounts_created, 1);
+    assert_eq!(imported.categories_created, 1);
+    assert_eq!(imported.operations_created, 2);
+    assert_eq!(imported.assertions_created, 1);
+    assert_eq!(imported.warnings.len(), 1, "{:?}", imported.warnings);
+    assert!(imported.warnings[0].message.contains("pad"));
+
+    let checking = db::list_accounts(beancount_path, key)
+        .unwrap()
+        .into_iter()
+        .find(|account| account.name == "Checking")
+        .expect("Account not created");
+    assert_eq!(checking.acc_class, db::AccountClass::Asset);
+    let operations = db::get_operations(beancount_path, key, checking.id).unwrap();
+    assert_eq!(operations.len(), 2);
+    assert_eq!(operations[1].amount, 2500.0);
+    assert_eq!(operations[1].description, "Employer March salary");
+    assert_eq!(operations[0].amount, -12.5);
+    let report = db::check_balance_assertions(beancount_path, key, Some(checking.id)).unwrap();
+    assert_eq!(report.passed_count, 1);
+    println!("✓ Generic beancount imported");
+
+    // Закрытие и сверка по категориям пропускаются с предупреждением
+    let category_directives = "2024-03-06 close Expenses:Foo\n2024-03-06 balance Income:Bar  10.00 EUR\n";
+    let imported = db::import_plain_text(beancount_path, key, PlainTextFormat::Beancount, category_directives.as_bytes())
+        .expect("Failed to import category directives");
+    assert_eq!(imported.accounts_closed + imported.assertions_created + imported.categories_created, 0);
+    assert_eq!(imported.warnings.len(), 2, "{:?}", imported.warnings);
+    let warning_line = |account: &str| imported.warnings.iter().find(|w| w.message.contains(account)).map(|w| w.line_number);
+    assert_eq!(warning_line("Expenses:Foo"), Some(1));
+    assert_eq!(warning_line("Income:Bar"), Some(2));
+    println!("✓ Category close/balance directives skipped with warnings");
+
+    // Ошибки: несбалансированная транзакция откатывает весь файл
+    let unbalanced = "2024-03-01 open Assets:Wallet\n\n2024-03-02 * \"Broken\"\n  Assets:Wallet  10.00 EUR\n  Expenses:Food  -5.00 EUR\n";
+    let result = db::import_plain_text(ledger_path, key, PlainTextFormat::Beancount, unbalanced.as_bytes());
+    match result {
+        Err(db::DbError::ValidationError(message)) => assert!(message.starts_with("Line 3:"), "{}", message),
+        other => panic!("Unbalanced transaction accepted: {:?}", other),
+    }
+    assert!(db::list_accounts(ledger_path, key).unwrap().iter().all(|account| account.name != "Wallet"));
+
+    let assignment = "2024-03-01 * Reset\n    Assets:Bank  = 10 EUR\n";
+    let result = db::import_plain_text(ledger_path, key, PlainTextFormat::Ledger, assignment.as_bytes());
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    // Десятичная запятая не читается как разделитель тысяч
+    for amount in ["1,5 EUR", "-1.234,56 EUR"] {
+        let content = format!("2024-03-01 * \"Comma\"\n  Assets:Wallet  {}\n  Expenses:Food\n", amount);
+        let result = db::import_plain_text(ledger_path, key, PlainTextFormat::Beancount, content.as_bytes());
+        match result {
+            Err(db::DbError::ValidationError(message)) => assert!(message.contains("Invalid amount"), "{}", message),
+            other => panic!("Amount {} accepted: {:?}", amount, other),
+        }
+    }
+    println!("✓ Invalid files rejected");
+
+    println!("\n✅ Plain-text accounting export/import test passed!");
+}
@@ -0,0 +1,106 @@
+use fam_core_lib::db;
+use std::fs;
+
+#[test]
+fn test_rebalance_plan() {
+    let db_path = "/tmp/test_rebalance.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let bank = db::create_account(db_path, key, "Bank".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let deposit = db::create_account(db_path, key, "Deposit".to_string(), "deposit".to_string())
+        .expect("Failed to create account");
+    let cash = db::create_account(db_path, key, "Wallet".to_string(), "cash".to_string())
+        .expect("Failed to create account");
+    db::add_operation(db_path, key, bank, 6000.0, "Balance".to_string()).expect("Failed to add operation");
+    db::add_operation(db_path, key, deposit, 3000.0, "Balance".to_string()).expect("Failed to add operation");
+    db::add_operation(db_path, key, cash, 1000.0, "Balance".to_string()).expect("Failed to add operation");
+
+    // Без целей план не строится
+    assert!(matches!(db::get_rebalance_plan(db_path, key, None, false), Err(db::DbError::ValidationError(_))));
+
+    let bank_target = db::set_allocation_target(db_path, key, "bank".to_string(), 50.0, 5.0)
+        .expect("Failed to set target");
+    db::set_allocation_target(db_path, key, "deposit".to_string(), 40.0, 5.0).expect("Failed to set target");
+    db::set_allocation_target(db_path, key, "cash".to_string(), 10.0, 2.0).expect("Failed to set target");
+
+    // Сумма целей не больше 100%
+    let result = db::set_allocation_target(db_path, key, "cash".to_string(), 15.0, 2.0);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+    assert_eq!(db::list_allocation_targets(db_path, key).expect("Failed to list targets").len(), 3);
+    println!("✓ Allocation targets stored");
+
+    // Полный режим: bank 60% -> до верхней границы 55%, deposit 30% -> до нижней 35%
+    let plan = db::get_rebalance_plan(db_path, key, None, false).expect("Failed to get plan");
+    assert!(!plan.within_bands_before);
+    assert!(plan.within_bands_after);
+    let line = |plan: &db::RebalancePlan, asset_type: &str| {
+        plan.lines.iter().find(|l| l.asset_type == asset_type).map(|l| (l.amount, l.drift_percent)).unwrap()
+    };
+    assert_eq!(line(&plan, "bank").0, -500.0);
+    assert!((line(&plan, "bank").1.unwrap() - 10.0).abs() < 1e-9);
+    assert_eq!(line(&plan, "deposit").0, 500.0);
+    assert_eq!(line(&plan, "cash").0, 0.0);
+    assert_eq!(plan.transfers.len(), 1);
+    assert_eq!(plan.transfers[0].from_asset_type.as_deref(), Some("bank"));
+    assert_eq!(plan.transfers[0].to_asset_type, "deposit");
+    assert_eq!(plan.transfers[0].amount, 500.0);
+    println!("✓ Minimal transfer back into bands: bank -> deposit 500");
+
+    // Только взносы: 1000 идут в deposit, продаж нет
+    let plan = db::get_rebalance_plan(db_path, key, Some(1000.0), true).expect("Failed to get plan");
+    assert!(plan.lines.iter().all(|l| l.amount >= 0.0));
+    assert_eq!(line(&plan, "deposit").0, 1000.0);
+    assert_eq!(plan.transfers.len(), 1);
+    assert_eq!(plan.transfers[0].from_asset_type, None);
+    assert!(plan.within_bands_after);
+    assert_eq!(plan.unallocated, 0.0);
+
+    // Маленького взноса не хватает, чтобы вернуться в полосы
+    let plan = db::get_rebalance_plan(db_path, key, Some(100.0), true).expect("Failed to get plan");
+    assert_eq!(line(&plan, "deposit").0, 100.0);
+    assert!(!plan.within_bands_after);
+    println!("✓ Contributions-only mode");
+
+    // Широкая полоса у bank: продаётся превышение над целью, чтобы поднять deposit
+    let updated = db::set_allocation_target(db_path, key, "bank".to_string(), 50.0, 20.0)
+        .expect("Failed to set target");
+    assert_eq!(updated, bank_target);
+    let plan = db::get_rebalance_plan(db_path, key, None, false).expect("Failed to get plan");
+    assert_eq!(line(&plan, "bank").0, -500.0);
+    assert_eq!(line(&plan, "deposit").0, 500.0);
+    assert!(plan.within_bands_after);
+
+    // Группа без цели не ребалансируется
+    db::delete_allocation_target(db_path, key, "cash".to_string()).expect("Failed to delete target");
+    let plan = db::get_rebalance_plan(db_path, key, None, false).expect("Failed to get plan");
+    let cash_line = plan.lines.iter().find(|l| l.asset_type == "cash").unwrap();
+    assert_eq!((cash_line.target_percent, cash_line.amount), (None, 0.0));
+
+    let log = db::list_version_log(db_path, key, Some("allocation_target".to_string()), None)
+        .expect("Failed to list version log");
+    let actions: Vec<&str> = log.iter().map(|r| r.action.as_str()).collect();
+    assert_eq!(actions, vec!["delete", "update", "create", "create", "create"]);
+
+    // Цели на 80%: deposit выше полосы, но bank может принять только 1500,
+    // поэтому лишнее не продаётся и каждой продаже соответствует перевод
+    db::set_allocation_target(db_path, key, "deposit".to_string(), 10.0, 2.0).expect("Failed to set target");
+    db::set_allocation_target(db_path, key, "bank".to_string(), 70.0, 5.0).expect("Failed to set target");
+    let plan = db::get_rebalance_plan(db_path, key, None, false).expect("Failed to get plan");
+    assert_eq!(line(&plan, "bank").0, 1500.0);
+    assert_eq!(line(&plan, "deposit").0, -1500.0);
+    assert_eq!(plan.unallocated, 0.0);
+    let sold: f64 = plan.lines.iter().filter(|l| l.amount < 0.0).map(|l| -l.amount).sum();
+    let transferred: f64 = plan.transfers.iter().map(|t| t.amount).sum();
+    assert_eq!(sold, transferred);
+    assert!(!plan.within_bands_after);
+    println!("✓ Sales limited to what targeted groups can absorb");
+
+    let _ = fs::remove_file(db_path);
+    println!("✅ test_rebalance_plan passed");
+}
@@ -0,0 +1,150 @@
+use fam_core_lib::db;
+use std::fs;
+
+const DAY: i64 = 86400;
+
+#[test]
+fn test_statement_reconciliation_workflow() {
+    let db_path = "/tmp/test_reconciliation.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let acc = db::create_account(db_path, key, "Card".to_string(), "card".to_string())
+        .expect("Failed to create account");
+
+    let base = 1_700_000_000;
+
+    // Операции, введённые вручную
+    let op_coffee = db::add_operation_at(db_path, key, acc, -50.0, "COFFEE SHOP".to_string(), base + DAY)
+        .expect("Failed to add operation");
+    let op_grocery = db::add_operation_at(db_path, key, acc, -120.5, "Grocery Store".to_string(), base + 3 * DAY)
+        .expect("Failed to add operation");
+    let op_salary = db::add_operation_at(db_path, key, acc, 1000.0, "Salary ACME".to_string(), base + 5 * DAY)
+        .expect("Failed to add operation");
+    println!("✓ Added 3 backdated operations");
+
+    // Выписка банка: три известные операции и одна пропущенная комиссия
+    let lines = vec![
+        db::StatementLineInput { ts: base + 2 * DAY, amount: -50.0, description: "Coffee shop #12".to_string() },
+        db::StatementLineInput { ts: base + 3 * DAY, amount: -120.5, description: "GROCERY STORE MOSCOW".to_string() },
+        db::StatementLineInput { ts: base + 5 * DAY, amount: 1000.0, description: "ACME salary".to_string() },
+        db::StatementLineInput { ts: base + 7 * DAY, amount: -15.0, description: "Bank fee".to_string() },
+    ];
+
+    let session_id = db::start_reconciliation(
+        db_path, key, acc, "statement-2023-11.pdf".to_string(),
+        base, base + 8 * DAY, Some(814.5), lines,
+    ).expect("Failed to start reconciliation");
+
+    let matches = db::auto_match_statement(db_path, key, session_id, 0.01, 3)
+        .expect("Failed to auto-match");
+    assert_eq!(matches.len(), 3, "Three statement lines should find a matching operation");
+
+    let session = db::get_reconciliation(db_path, key, session_id).expect("Failed to get session");
+    let matched_ops: Vec<Option<i64>> = session.lines.iter().map(|l| l.operation_id).collect();
+    assert_eq!(matched_ops, vec![Some(op_coffee), Some(op_grocery), Some(op_salary), None]);
+    assert!(session.lines[..3].iter().all(|l| l.match_status == "suggested"));
+    println!("✓ Auto-match suggested the expected operations");
+
+    // Нельзя завершить сверку без подтверждения всех строк
+    let result = db::complete_reconciliation(db_path, key, session_id);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    for m in &matches {
+        db::confirm_statement_match(db_path, key, m.line_id, m.operation_id)
+            .expect("Failed to confirm match");
+    }
+
+    // Подтверждённая операция не может быть привязана ко второй строке
+    let fee_line = session.lines[3].id;
+    let result = db::confirm_statement_match(db_path, key, fee_line, op_coffee);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    let fee_op = db::create_operation_from_statement_line(db_path, key, fee_line)
+        .expect("Failed to create missing operation");
+    assert_eq!(db::get_operations(db_path, key, acc).expect("Failed to get operations").len(), 4);
+    println!("✓ Missing operation {} created from statement line", fee_op);
+
+    // Уже сопоставленную строку нельзя перепривязать к другой операции
+    let stray = db::add_operation_at(db_path, key, acc, -15.0, "Bank fee".to_string(), base + 9 * DAY)
+        .expect("Failed to add operation");
+    let result = db::confirm_statement_match(db_path, key, fee_line, stray);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+    let result = db::confirm_statement_match(db_path, key, matches[0].line_id, stray);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+    let session = db::get_reconciliation(db_path, key, session_id).expect("Failed to get session");
+    assert_eq!(session.lines[3].operation_id, Some(fee_op));
+    assert_eq!(session.lines[3].match_status, "created");
+    assert_eq!(session.lines[0].operation_id, Some(op_coffee));
+    println!("✓ Matched lines cannot be re-confirmed");
+
+    let session = db::complete_reconciliation(db_path, key, session_id)
+        .expect("Failed to complete reconciliation");
+    assert_eq!(session.status, "reconciled");
+    assert_eq!(session.ledger_balance, Some(814.5));
+    assert!(session.reconciled_at.is_some());
+    println!("✓ Period reconciled against {}", session.statement_name);
+
+    // Сверенную сессию нельзя изменить
+    let result = db::confirm_statement_match(db_path, key, fee_line, fee_op);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    let sessions = db::list_reconciliations(db_path, key, Some(acc)).expect("Failed to list sessions");
+    assert_eq!(sessions.len(), 1);
+
+    // Создание и завершение сверки записаны в подписанный version_log
+    let log = db::list_version_log(db_path, key, Some("reconciliation".to_string()), Some(session_id))
+        .expect("Failed to list version log");
+    assert_eq!(log.len(), 2);
+    assert_eq!(log[0].action, "reconcile");
+    assert!(log[0].payload.contains("statement-2023-11.pdf"));
+    assert!(log[0].payload.contains("Bank fee"));
+    for record in &log {
+        assert!(db::verify_version_signature(db_path, key, record.id).expect("Failed to verify"));
+    }
+
+    println!("\n✅ Reconciliation workflow tests passed!");
+
+    let _ = fs::remove_file(db_path);
+}
+
+#[test]
+fn test_reconciliation_closing_balance_mismatch() {
+    let db_path = "/tmp/test_reconciliation_mismatch.db";
+    let key = "test_key_123";
+
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let acc = db::create_account(db_path, key, "Bank".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+
+    let base = 1_700_000_000;
+    db::add_operation_at(db_path, key, acc, 200.0, "Deposit".to_string(), base + DAY)
+        .expect("Failed to add operation");
+
+    let session_id = db::start_reconciliation(
+        db_path, key, acc, "stmt".to_string(), base, base + 2 * DAY, Some(250.0),
+        vec![db::StatementLineInput { ts: base + DAY, amount: 200.0, description: "Deposit".to_string() }],
+    ).expect("Failed to start reconciliation");
+
+    let matches = db::auto_match_statement(db_path, key, session_id, 0.0, 0)
+        .expect("Failed to auto-match");
+    assert_eq!(matches.len(), 1);
+    db::confirm_statement_match(db_path, key, matches[0].line_id, matches[0].operation_id)
+        .expect("Failed to confirm match");
+
+    // Баланс по книге (200) не совпадает с остатком выписки (250)
+    let result = db::complete_reconciliation(db_path, key, session_id);
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    let session = db::get_reconciliation(db_path, key, session_id).expect("Failed to get session");
+    assert_eq!(session.status, "open");
+
+    let _ = fs::remove_file(db_path);
+}
@@ -0,0 +1,285 @@
+use fam_core_lib::db;
+use fam_core_lib::db::ImportLineStatus;
+use std::fs;
+
+const DAY: i64 = 86400;
+
+const CAMT053: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <BkToCstmrStmt>
+    <GrpHdr><MsgId>MSG-1</MsgId><CreDtTm>2024-04-01T06:00:00</CreDtTm></GrpHdr>
+    <Stmt>
+      <Id>STMT-2024-03</Id>
+      <Acct><Id><IBAN>DE89370400440532013000</IBAN></Id><Ccy>EUR</Ccy></Acct>
+      <Bal>
+        <Tp><CdOrPrtry><Cd>OPBD</Cd></CdOrPrtry></Tp>
+        <Amt Ccy="EUR">0.00</Amt><CdtDbtInd>CRDT</CdtDbtInd>
+        <Dt><Dt>2024-02-29</Dt></Dt>
+      </Bal>
+      <Bal>
+        <Tp><CdOrPrtry><Cd>CLBD</Cd></CdOrPrtry></Tp>
+        <Amt Ccy="EUR">1050.00</Amt><CdtDbtInd>CRDT</CdtDbtInd>
+        <Dt><Dt>2024-03-31</Dt></Dt>
+      </Bal>
+      <Ntry>
+        <Amt Ccy="EUR">1500.00</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><Dt>2024-03-01</Dt></BookgDt>
+        <ValDt><Dt>2024-02-29</Dt></ValDt>
+        <AcctSvcrRef>REF-1</AcctSvcrRef>
+        <BkTxCd/>
+        <NtryDtls><TxDtls>
+          <Refs><EndToEndId>E2E-INV-42</EndToEndId></Refs>
+          <RltdPties><Dbtr><Nm>ACME GmbH &amp; Co. KG</Nm></Dbtr></RltdPties>
+          <RmtInf><Ustrd>Invoice 42</Ustrd></RmtInf>
+        </TxDtls></NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">200.00</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts><Cd>BOOK</Cd></Sts>
+        <BookgDt><DtTm>2024-03-05T10:30:00+01:00</DtTm></BookgDt>
+        <ValDt><Dt>2024-03-05</Dt></ValDt>
+        <AcctSvcrRef>REF-2</AcctSvcrRef>
+        <NtryDtls><TxDtls>
+          <Refs><EndToEndId>NOTPROVIDED</EndToEndId></Refs>
+          <RltdPties>
+            <Dbtr><Nm>Account Owner</Nm></Dbtr>
+            <Cdtr><Nm>Stadtwerke</Nm></Cdtr>
+          </RltdPties>
+          <RmtInf><Ustrd>Strom</Ustrd><Ustrd>Maerz 2024</Ustrd></RmtInf>
+        </TxDtls></NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">999.00</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts>PDNG</Sts>
+        <BookgDt><Dt>2024-03-30</Dt></BookgDt>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">300.00</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><Dt>2024-03-10</Dt></BookgDt>
+        <AcctSvcrRef>REF-3</AcctSvcrRef>
+        <NtryDtls>
+          <TxDtls>
+            <AmtDtls><TxAmt><Amt Ccy="EUR">100.00</Amt></TxAmt></AmtDtls>
+            <RltdPties><Cdtr><Pty><Nm>Supplier One</Nm></Pty></Cdtr></RltdPties>
+            <RmtInf><Strd><CdtrRefInf><Ref>RF18539007547034</Ref></CdtrRefInf></Strd></RmtInf>
+          </TxDtls>
+          <TxDtls>
+            <AmtDtls><TxAmt><Amt Ccy="EUR">200.00</Amt></TxAmt></AmtDtls>
+            <RltdPties><Cdtr><Nm>Supplier Two</Nm></Cdtr></RltdPties>
+          </TxDtls>
+        </NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">50.00</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <RvslInd>true</RvslInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><Dt>2024-03-15</Dt></BookgDt>
+        <AcctSvcrRef>REF-4</AcctSvcrRef>
+        <AddtlNtryInf>Storno Lastschrift</AddtlNtryInf>
+      </Ntry>
+    </Stmt>
+  </BkToCstmrStmt>
+</Document>
+"#;
+
+const MT940: &str = "{1:F01BANKDEFFXXXX0000000000}{2:O9400000000000BANKDEFFXXXX00000000000000000000N}{4:
+:20:STMT-1
+:25:DE89370400440532013000
+:28C:1/1
+:60F:C240301EUR1000,00
+:61:2403010301C1500,00NTRFNONREF//BREF-1
+:86:166?00SEPA-GUTSCHRIFT?20EREF+E2E-777?21SVWZ+Rechnung
+ 2024-03?32ACME GMBH
+:61:240302D200,00NDDTKREF-9//BREF-2
+:86:/EREF/E2E-888//CNTP/DE02100100100006820101/PBNKDEFF/Stadtwerke AG/Berlin//REMI/USTD//Abschlag Maerz/
+:61:2403040305D45,50NMSCNONREF
+Kartenzahlung
+:86:Kartenzahlung Supermarkt
+Berlin
+:62F:C240305EUR2254,50
+-}
+{1:F01BANKDEFFXXXX0000000000}{2:O9400000000000BANKDEFFXXXX00000000000000000000N}{4:
+:20:STMT-2
+:25:DE89370400440532013000
+:28C:2/1
+:60F:C240305EUR2254,50
+:61:240306D4,50NMSCNONREF
+:86:Kaffee
+:62F:C240306EUR2250,00
+-}
+";
+
+const MT940_NONREF: &str = "{1:F01BANKDEFFXXXX0000000000}{2:O9400000000000BANKDEFFXXXX00000000000000000000N}{4:
+:20:STMT-3
+:25:DE89370400440532013000
+:28C:3/1
+:60F:C240306EUR100,00
+:61:240307D10,00NMSCNONREF//NONREF
+:86:Kaffee
+:61:240307D12,00NMSCNONREF//nonref
+:86:Baeckerei
+:61:240308D5,00NMSCNONREF//BATCH-1
+:86:Kiosk
+:61:240308D7,00NMSCNONREF//BATCH-1
+:86:Apotheke
+:62F:C240308EUR66,00
+-}
+";
+
+#[test]
+fn test_statement_formats() {
+    let db_path = "/tmp/test_statement_formats.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let giro = db::create_account(db_path, key, "Giro".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let business = db::create_account(db_path, key, "Business".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+
+    // camt.053
+    let preview = db::preview_camt053_import(db_path, key, giro, CAMT053.as_bytes()).expect("Failed to preview");
+    let lines = &preview.preview.lines;
+    assert!(preview.preview.errors.is_empty());
+    assert_eq!(lines.len(), 5);
+
+    assert_eq!(lines[0].amount, 1500.0);
+    assert_eq!(lines[0].external_id.as_deref(), Some("REF-1"));
+    assert_eq!(lines[0].end_to_end_id.as_deref(), Some("E2E-INV-42"));
+    assert_eq!(lines[0].counterparty.as_deref(), Some("ACME GmbH & Co. KG"));
+    assert_eq!(lines[0].remittance_info.as_deref(), Some("Invoice 42"));
+    assert_eq!(lines[0].description, "ACME GmbH & Co. KG Invoice 42");
+    assert_eq!(lines[0].ts - lines[0].value_ts.unwrap(), DAY);
+
+    assert_eq!(lines[1].amount, -200.0);
+    assert_eq!(lines[1].ts, 1709631000);
+    assert_eq!(lines[1].end_to_end_id, None);
+    assert_eq!(lines[1].counterparty.as_deref(), Some("Stadtwerke"));
+    assert_eq!(lines[1].remittance_info.as_deref(), Some("Strom Maerz 2024"));
+
+    // Пакетная запись разложена по TxDtls, отложенная запись пропущена
+    assert_eq!(lines[2].amount, -100.0);
+    assert_eq!(lines[2].external_id.as_deref(), Some("REF-3/1"));
+    assert_eq!(lines[2].counterparty.as_deref(), Some("Supplier One"));
+    assert_eq!(lines[2].remittance_info.as_deref(), Some("RF18539007547034"));
+    assert_eq!(lines[3].amount, -200.0);
+    assert_eq!(lines[3].external_id.as_deref(), Some("REF-3/2"));
+
+    // Сторно списания - поступление: направление задаёт CdtDbtInd
+    assert_eq!(lines[4].amount, 50.0);
+    assert_eq!(lines[4].description, "Reversal: Storno Lastschrift");
+
+    let check = preview.balance_check.as_ref().expect("Closing balance not parsed");
+    assert!(check.matches);
+    assert!((check.statement_balance - 1050.0).abs() < 1e-9);
+    println!("✓ camt.053 parsed with value/booking dates, parties, remittance and end-to-end IDs");
+
+    let imported = db::import_camt053(db_path, key, giro, "march.xml".to_string(), CAMT053.as_bytes())
+        .expect("Failed to import");
+    assert_eq!(imported.result.batch.source, "camt053");
+    assert_eq!(imported.result.batch.imported_count, 5);
+    assert!(imported.balance_check.unwrap().matches);
+    assert!((db::get_account_balance(db_path, key, giro).expect("Failed to get balance") - 1050.0).abs() < 1e-9);
+
+    let again = db::import_camt053(db_path, key, giro, "march.xml".to_string(), CAMT053.as_bytes())
+        .expect("Failed to import");
+    assert_eq!(again.result.batch.imported_count, 0);
+    assert_eq!(again.result.batch.duplicate_count, 5);
+    println!("✓ camt.053 imported idempotently");
+
+    // MT940: два сообщения по одному счёту
+    db::set_opening_balance(db_path, key, business, 1000.0, 1709200000).expect("Failed to set opening balance");
+    let preview = db::preview_mt940_import(db_path, key, business, MT940.as_bytes()).expect("Failed to preview");
+    let lines = &preview.preview.lines;
+    assert!(preview.preview.errors.is_empty());
+    assert_eq!(lines.len(), 4);
+
+    assert_eq!(lines[0].amount, 1500.0);
+    assert_eq!(lines[0].external_id.as_deref(), Some("BREF-1"));
+    assert_eq!(lines[0].counterparty.as_deref(), Some("ACME GMBH"));
+    assert_eq!(lines[0].remittance_info.as_deref(), Some("Rechnung 2024-03"));
+    assert_eq!(lines[0].end_to_end_id.as_deref(), Some("E2E-777"));
+    assert_eq!(lines[0].ts, lines[0].value_ts.unwrap());
+
+    assert_eq!(lines[1].amount, -200.0);
+    assert_eq!(lines[1].counterparty.as_deref(), Some("Stadtwerke AG"));
+    assert_eq!(lines[1].remittance_info.as_deref(), Some("Abschlag Maerz"));
+    assert_eq!(lines[1].end_to_end_id.as_deref(), Some("E2E-888"));
+    assert_eq!(lines[1].description, "Stadtwerke AG Abschlag Maerz");
+
+    assert_eq!(lines[2].amount, -45.5);
+    assert_eq!(lines[2].external_id, None);
+    assert_eq!(lines[2].end_to_end_id, None);
+    assert_eq!(lines[2].remittance_info.as_deref(), Some("Kartenzahlung Supermarkt Berlin"));
+    assert_eq!(lines[2].ts - lines[2].value_ts.unwrap(), DAY);
+    assert_eq!(lines[3].description, "Kaffee");
+
+    let check = preview.balance_check.as_ref().expect("Closing balance not parsed");
+    assert!((check.statement_balance - 2250.0).abs() < 1e-9);
+    assert!(check.matches);
+    println!("✓ MT940 parsed with structured and free-text :86:");
+
+    let imported = db::import_mt940(db_path, key, business, "mt940.sta".to_string(), MT940.as_bytes())
+        .expect("Failed to import");
+    assert_eq!(imported.result.batch.source, "mt940");
+    assert_eq!(imported.result.batch.imported_count, 4);
+    assert!(imported.balance_check.unwrap().matches);
+
+    let preview = db::preview_mt940_import(db_path, key, business, MT940.as_bytes()).expect("Failed to preview");
+    assert!(preview.preview.lines.iter().all(|line| line.status == ImportLineStatus::Duplicate));
+    println!("✓ MT940 imported idempotently");
+
+    // Подписанная запись пакета импорта содержит детали строк
+    let log = db::list_version_log(db_path, key, Some("import_batch".to_string()), None)
+        .expect("Failed to list version log");
+    let entry = log.iter().find(|entry| entry.entity_id == imported.result.batch.id).expect("Batch not logged");
+    assert!(entry.payload.contains("E2E-888"));
+    assert!(entry.payload.contains("\"value_ts\""));
+    assert!(db::verify_version_signature(db_path, key, entry.id).expect("Failed to verify"));
+    println!("✓ Import batch logged with a signature");
+
+    // NONREF и повторяющийся референс банка не идентифицируют строку
+    let cards = db::create_account(db_path, key, "Cards".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let preview = db::preview_mt940_import(db_path, key, cards, MT940_NONREF.as_bytes()).expect("Failed to preview");
+    let lines = &preview.preview.lines;
+    assert_eq!(lines.len(), 4);
+    assert!(lines.iter().all(|line| line.external_id.is_none()));
+    assert!(lines.iter().all(|line| line.status == ImportLineStatus::New));
+    let imported = db::import_mt940(db_path, key, cards, "nonref.sta".to_string(), MT940_NONREF.as_bytes())
+        .expect("Failed to import");
+    assert_eq!(imported.result.batch.imported_count, 4);
+    let preview = db::preview_mt940_import(db_path, key, cards, MT940_NONREF.as_bytes()).expect("Failed to preview");
+    assert!(preview.preview.lines.iter().all(|line| line.status == ImportLineStatus::Duplicate));
+    println!("✓ NONREF and repeated bank references fall back to content hashes");
+
+    // Ошибки
+    let two_accounts = MT940.replacen(":25:DE89370400440532013000", ":25:DE02100100100006820101", 1);
+    let result = db::preview_mt940_import(db_path, key, business, two_accounts.as_bytes());
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    let broken = MT940.replace(":61:240306D4,50NMSCNONREF", ":61:2403XXD4,50NMSCNONREF");
+    let preview = db::preview_mt940_import(db_path, key, business, broken.as_bytes()).expect("Failed to preview");
+    assert_eq!(preview.preview.errors.len(), 1);
+    let result = db::import_mt940(db_path, key, business, "broken.sta".to_string(), broken.as_bytes());
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    let result = db::preview_camt053_import(db_path, key, giro, MT940.as_bytes());
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+    let result = db::preview_mt940_import(db_path, key, giro, CAMT053.as_bytes());
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+    println!("✓ Invalid files rejected");
+
+    println!("\n✅ camt.053 and MT940 import test passed!");
+}
@@ -0,0 +1,114 @@
+use fam_core_lib::db;
+use std::fs;
+
+// 2022-01-01, 2022-01-10, 2023-03-01, 2023-06-01, 2023-07-03, 2023-08-01 (UTC)
+const JAN_01_2022: i64 = 1_640_995_200;
+const JAN_10_2022: i64 = 1_641_772_800;
+const MAR_01_2023: i64 = 1_677_628_800;
+const JUN_01_2023: i64 = 1_685_577_600;
+const JUL_03_2023: i64 = 1_688_342_400;
+const AUG_01_2023: i64 = 1_690_848_000;
+
+fn approx(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-6
+}
+
+#[test]
+fn test_tax_lots_and_realized_gains() {
+    let db_path = "/tmp/test_tax_lots.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let broker = db::create_account(db_path, key, "Broker".to_string(), "brokerage".to_string())
+        .expect("Failed to create account");
+    db::set_opening_balance(db_path, key, broker, 10000.0, JAN_01_2022)
+        .expect("Failed to set opening balance");
+
+    let sec = db::create_security(db_path, key, "SBER".to_string(), None, "Sberbank".to_string(), "stock".to_string())
+        .expect("Failed to create security");
+
+    // Два лота с разной себестоимостью (комиссия входит в себестоимость)
+    db::add_investment_transaction(db_path, key, broker, sec, "buy".to_string(), 10.0, 100.0, 10.0, JAN_10_2022)
+        .expect("Failed to buy lot 1");
+    db::add_investment_transaction(db_path, key, broker, sec, "buy".to_string(), 10.0, 120.0, 0.0, MAR_01_2023)
+        .expect("Failed to buy lot 2");
+
+    let lots = db::get_tax_lots(db_path, key, broker, Some(sec)).expect("Failed to get lots");
+    assert_eq!(lots.len(), 2);
+    assert!(approx(lots[0].cost_per_unit, 101.0));
+    assert!(approx(lots[1].cost_per_unit, 120.0));
+    let (lot1, lot2) = (lots[0].id, lots[1].id);
+    println!("✓ Two tax lots opened");
+
+    // FIFO (по умолчанию): списывается первый лот
+    db::add_investment_transaction(db_path, key, broker, sec, "sell".to_string(), 5.0, 150.0, 5.0, JUN_01_2023)
+        .expect("Failed to sell FIFO");
+
+    // LIFO: списывается последний лот
+    db::sell_security(db_path, key, broker, sec, 4.0, 130.0, 0.0, JUL_03_2023, "lifo".to_string(), None)
+        .expect("Failed to sell LIFO");
+
+    // Specific identification: сумма выбранных лотов должна совпадать с количеством
+    let result = db::sell_security(db_path, key, broker, sec, 3.0, 110.0, 0.0, AUG_01_2023, "specific".to_string(),
+        Some(vec![db::LotSelection { lot_id: lot1, quantity: 1.0 }]));
+    assert!(matches!(result, Err(db::DbError::ValidationError(_))));
+
+    db::sell_security(db_path, key, broker, sec, 3.0, 110.0, 0.0, AUG_01_2023, "specific".to_string(),
+        Some(vec![
+            db::LotSelection { lot_id: lot1, quantity: 2.0 },
+            db::LotSelection { lot_id: lot2, quantity: 1.0 },
+        ]))
+        .expect("Failed to sell specific lots");
+
+    let lots = db::get_tax_lots(db_path, key, broker, None).expect("Failed to get lots");
+    assert!(approx(lots[0].remaining_quantity, 3.0));
+    assert!(approx(lots[1].remaining_quantity, 5.0));
+    println!("✓ FIFO, LIFO and specific identification consumed the expected lots");
+
+    let report = db::get_realized_gains(db_path, key, 2023).expect("Failed to get realized gains");
+    for gain in &report.gains {
+        println!("  lot {} qty {} cost {:.2} proceeds {:.2} gain {:.2} days {} long {}",
+            gain.lot_id, gain.quantity, gain.cost_basis, gain.proceeds, gain.gain,
+            gain.holding_period_days, gain.long_term);
+    }
+    assert_eq!(report.gains.len(), 4);
+
+    // Продажа FIFO: 5 шт. из лота 1, выручка за вычетом комиссии
+    let fifo = &report.gains[0];
+    assert_eq!(fifo.lot_id, lot1);
+    assert!(approx(fifo.cost_basis, 505.0));
+    assert!(approx(fifo.proceeds, 745.0));
+    assert!(fifo.long_term);
+
+    let lifo = &report.gains[1];
+    assert_eq!(lifo.lot_id, lot2);
+    assert!(approx(lifo.gain, 40.0));
+    assert!(!lifo.long_term);
+    assert_eq!(lifo.holding_period_days, 124);
+
+    assert!(approx(report.total_proceeds, 1595.0));
+    assert!(approx(report.total_cost_basis, 1307.0));
+    assert!(approx(report.total_gain, 288.0));
+    assert!(approx(report.long_term_gain, 258.0));
+    assert!(approx(report.short_term_gain, 30.0));
+    println!("✓ Realized gains for 2023: {:.2}", report.total_gain);
+
+    let report_2022 = db::get_realized_gains(db_path, key, 2022).expect("Failed to get realized gains");
+    assert!(report_2022.gains.is_empty());
+
+    // Каждое списание лота записано в подписанный version_log
+    let log = db::list_version_log(db_path, key, Some("lot_assignment".to_string()), None)
+        .expect("Failed to list version log");
+    assert_eq!(log.len(), 4);
+    for record in &log {
+        assert!(db::verify_version_signature(db_path, key, record.id).expect("Failed to verify"));
+    }
+
+    println!("\n✅ Tax lot tests passed!");
+
+    let _ = fs::remove_file(db_path);
+}
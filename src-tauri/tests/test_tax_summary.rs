@@ -0,0 +1,151 @@
+use fam_core_lib::db;
+use std::fs;
+
+const DAY: i64 = 86400;
+
+// 2024-03-01 12:00 UTC - середина года при любом часовом поясе
+const BASE: i64 = 1709294400;
+
+#[test]
+fn test_tax_summary() {
+    let db_path = "/tmp/test_tax_summary.db";
+    let key = "test_key_123";
+
+    // Удаляем старую БД если есть
+    let _ = fs::remove_file(db_path);
+
+    db::init_db(db_path, key).expect("Failed to init db");
+
+    let checking = db::create_account(db_path, key, "Checking".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let savings = db::create_account(db_path, key, "Savings".to_string(), "bank".to_string())
+        .expect("Failed to create account");
+    let card = db::create_account(db_path, key, "Card".to_string(), "credit_card".to_string())
+        .expect("Failed to create account");
+    let broker = db::create_account(db_path, key, "Broker".to_string(), "brokerage".to_string())
+        .expect("Failed to create account");
+
+    let employer = db::create_payee(db_path, key, "Employer, Inc.".to_string()).expect("Failed to create payee");
+    let client = db::create_payee(db_path, key, "Client".to_string()).expect("Failed to create payee");
+    let charity = db::create_category(db_path, key, "Charity".to_string()).expect("Failed to create category");
+    let interest = db::create_category(db_path, key, "Interest".to_string()).expect("Failed to create category");
+    let groceries = db::create_category(db_path, key, "Groceries".to_string()).expect("Failed to create category");
+
+    db::set_category_tax_kind(db_path, key, charity, Some("deductible".to_string())).expect("Failed to set tax kind");
+    db::set_category_tax_kind(db_path, key, interest, Some("interest".to_string())).expect("Failed to set tax kind");
+    let invalid = db::set_category_tax_kind(db_path, key, groceries, Some("exempt".to_string()));
+    assert!(matches!(invalid, Err(db::DbError::ValidationError(_))));
+    let categories = db::list_categories(db_path, key).expect("Failed to list categories");
+    assert_eq!(categories.iter().find(|c| c.id == charity).unwrap().tax_kind.as_deref(), Some("deductible"));
+    assert_eq!(categories.iter().find(|c| c.id == groceries).unwrap().tax_kind, None);
+    println!("✓ Category tax kinds set");
+
+    let add = |account: i64, amount: f64, description: &str, ts: i64, payee: Option<i64>, category: Option<i64>| {
+        let id = db::add_operation_at(db_path, key, account, amount, description.to_string(), ts)
+            .expect("Failed to add operation");
+        if payee.is_some() {
+            db::set_operation_payee(db_path, key, id, payee).expect("Failed to set payee");
+        }
+        if category.is_some() {
+            db::set_operation_category(db_path, key, id, category).expect("Failed to set category");
+        }
+    };
+
+    // Доходы
+    add(checking, 5000.0, "Salary", BASE, Some(employer), None);
+    add(checking, 5000.0, "Salary", BASE + 30 * DAY, Some(employer), None);
+    add(checking, 1200.0, "Invoice", BASE + 10 * DAY, Some(client), None);
+    add(checking, 100.0, "Cash gift", BASE + 11 * DAY, None, None);
+    add(checking, 4000.0, "Salary", BASE - 365 * DAY, Some(employer), None);
+
+    // Проценты
+    add(savings, 30.0, "Interest", BASE + 31 * DAY, None, Some(interest));
+    add(savings, 30.5, "Interest", BASE + 61 * DAY, None, Some(interest));
+
+    // Вычитаемые расходы: со счёта, по карте и возврат
+    add(checking, -200.0, "Donation", BASE + 5 * DAY, None, Some(charity));
+    add(card, 150.0, "Donation", BASE + 6 * DAY, None, Some(charity));
+    add(checking, 50.0, "Donation refund", BASE + 7 * DAY, None, Some(charity));
+    add(checking, -80.0, "Food", BASE + 8 * DAY, None, Some(groceries));
+
+    // Внутренние перемещения не являются доходом
+    db::create_transfer(db_path, key, checking, savings, 1000.0, "Save".to_string(), Some(BASE + 12 * DAY))
+        .expect("Failed to transfer");
+
+    // Бумаги: продажа с доходом 200 и дивиденд 3
+    db::set_opening_balance(db_path, key, broker, 5000.0, BASE - DAY).expect("Failed to set opening balance");
+    let aapl = db::create_security(db_path, key, "AAPL".to_string(), None, "Apple".to_string(), "stock".to_string())
+        .expect("Failed to create security");
+    db::add_investment_transaction(db_path, key, broker, aapl, "buy".to_string(), 10.0, 100.0, 0.0, BASE + DAY)
+        .expect("Failed to buy");
+    db::add_investment_transaction(db_path, key, broker, aapl, "sell".to_string(), 4.0, 150.0, 0.0, BASE + 20 * DAY)
+        .expect("Failed to sell");
+    db::add_investment_transaction(db_path, key, broker, aapl, "dividend".to_string(), 6.0, 0.5, 0.0, BASE + 25 * DAY)
+        .expect("Failed to add dividend");
+
+    let export = db::generate_tax_summary(db_path, key, 2024).expect("Failed to generate tax summary");
+    let summary = &export.summary;
+    assert_eq!(summary.year, 2024);
+
+    assert_eq!(summary.income.len(), 3);
+    assert_eq!(summary.income[0].id, Some(employer));
+    assert!((summary.income[0].amount - 10000.0).abs() < 1e-9);
+    assert_eq!(summary.income[0].count, 2);
+    assert_eq!(summary.income[2].name, "Unassigned");
+    assert!((summary.total_income - 11300.0).abs() < 1e-9);
+    println!("✓ Income by source");
+
+    assert_eq!(summary.deductible_expenses.len(), 1);
+    assert_eq!(summary.deductible_expenses[0].id, Some(charity));
+    assert_eq!(summary.deductible_expenses[0].count, 3);
+    assert!((summary.total_deductible - 300.0).abs() < 1e-9);
+    println!("✓ Deductible expenses by category");
+
+    assert_eq!(summary.interest.len(), 1);
+    assert_eq!(summary.interest[0].id, Some(savings));
+    assert!((summary.total_interest - 60.5).abs() < 1e-9);
+    assert_eq!(summary.dividends.len(), 1);
+    assert_eq!(summary.dividends[0].name, "AAPL");
+    assert!((summary.total_dividends - 3.0).abs() < 1e-9);
+    assert!((summary.realized_gains.total_gain - 200.0).abs() < 1e-9);
+    println!("✓ Interest, dividends and realized gains");
+
+    // Выгрузка CSV
+    let lines: Vec<&str> = export.csv.content.lines().collect();
+    assert_eq!(lines[0], "section,id,name,amount,count");
+    assert!(lines.contains(&format!("income,{},\"Employer, Inc.\",10000.00,2", employer).as_str()));
+    assert!(lines.contains(&"income_total,,,11300.00,"));
+    assert!(lines.contains(&format!("deductible_expense,{},Charity,300.00,3", charity).as_str()));
+    assert!(lines.contains(&"interest_total,,,60.50,"));
+    assert!(lines.contains(&format!("realized_gain,{},AAPL,200.00,1", aapl).as_str()));
+    assert!(lines.contains(&"realized_gain_total,,,200.00,"));
+    println!("✓ CSV export");
+
+    // Выгрузка JSON
+    let parsed: serde_json::Value = serde_json::from_str(&export.json.content).expect("Invalid JSON");
+    assert_eq!(parsed["year"], 2024);
+    assert_eq!(parsed["total_income"], 11300.0);
+    println!("✓ JSON export");
+
+    // Подписи
+    assert_eq!(export.csv.format, "csv");
+    assert_eq!(export.json.format, "json");
+    assert_eq!(export.csv.signature.len(), 128);
+    assert_eq!(export.csv.public_key, export.json.public_key);
+    assert!(db::verify_signed_export(&export.csv).expect("Failed to verify"));
+    assert!(db::verify_signed_export(&export.json).expect("Failed to verify"));
+
+    let tampered = db::SignedExport {
+        content: export.csv.content.replace("11300.00", "1300.00"),
+        ..export.csv
+    };
+    assert!(!db::verify_signed_export(&tampered).expect("Failed to verify"));
+    println!("✓ Detached signatures verified");
+
+    // Год без операций
+    let empty = db::generate_tax_summary(db_path, key, 2020).expect("Failed to generate tax summary");
+    assert!(empty.summary.income.is_empty());
+    assert_eq!(empty.summary.total_income, 0.0);
+
+    println!("\n✅ Tax summary test passed!");
+}
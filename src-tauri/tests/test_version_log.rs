@@ -76,8 +76,10 @@ fn test_serialize_entity() {
     let account = db::Account {
         id: 123,
         name: "Тестовый счёт".to_string(),
+        acc_class: db::AccountClass::Asset,
         acc_type: "cash".to_string(),
         created_at: 1700000000,
+        closed_at: None,
     };
     
     let json = db::serialize_entity(&account).expect("Account serialization failed");
@@ -96,6 +98,9 @@ fn test_serialize_entity() {
         amount: 100.50,
         description: "Тестовая операция".to_string(),
         ts: 1700000100,
+        payee_id: None,
+        category_id: None,
+        notes: None,
     };
     
     let json = db::serialize_entity(&operation).expect("Operation serialization failed");
@@ -1,5 +1,5 @@
 import { invoke } from '@tauri-apps/api/core';
-import type { DbResult, Account, Operation, State, AssetAllocation, MasterKey, DerivedKey, CryptoConfig, ApiRequest, ApiResponse, VersionLogRecord, SignedVersion } from '../types/tauri';
+import type { DbResult, Account, AccountClass, Operation, State, NetWorth, HistoryInterval, NetWorthPoint, BalanceAggregation, BalanceBucket, AssetAllocation, AllocationTarget, RebalancePlan, Transfer, CashFlowGroupBy, CashFlow, RecurringItemInput, RecurringItem, AccountForecast, AnomalyKind, AnomalyStatus, AnomalyOptions, Anomaly, TaxCategoryKind, TaxSummaryExport, SignedExport, CsvImportOptions, ImportProfile, ImportPreview, ImportBatch, ImportResult, StatementImportPreview, StatementImportResult, PlainTextFormat, PlainTextImportResult, OpeningBalance, BalanceAssertion, BalanceAssertionReport, StatementLineInput, ReconciliationSession, StatementMatch, Security, SecurityAssetClass, SecurityPrice, InvestmentTransaction, InvestmentTransactionKind, Holding, LotMethod, LotSelection, TaxLot, RealizedGainsReport, InvestmentReturn, PortfolioReturns, Loan, LoanPayment, AmortizationRow, LoanPayoffProjection, Payee, PayeeRule, PayeeRuleKind, PayeeMerge, PayeeSpending, Category, Tag, CategorizationRuleInput, CategorizationRule, RuleChange, SplitLineInput, SplitLine, CategorySpending, OperationFilters, OperationSearchHit, OperationQuery, OperationPage, Attachment, AttachmentOwnerType, AttachmentContent, Goal, GoalProgress, MasterKey, DerivedKey, CryptoConfig, ApiRequest, ApiResponse, VersionLogRecord, SignedVersion } from '../types/tauri';
 
 // Utility commands
 export const app = {
@@ -58,14 +58,26 @@ export const db = {
 // API commands (high-level without path/key)
 export const api = {
   // Account management
-  async createAccount(name: string, accType: string): Promise<number> {
-    return await invoke('create_account', { name, accType });
+  async createAccount(name: string, accType: string, accClass?: AccountClass): Promise<number> {
+    return await invoke('create_account', {
+      name,
+      accType,
+      accClass: accClass !== undefined ? accClass : null
+    });
   },
 
   async listAccounts(): Promise<Account[]> {
     return await invoke('list_accounts');
   },
 
+  async setAccountClass(accountId: number, accClass: AccountClass, accType: string): Promise<void> {
+    return await invoke('set_account_class', { accountId, accClass, accType });
+  },
+
+  async closeAccount(accountId: number, ts?: number): Promise<void> {
+    return await invoke('close_account', { accountId, ts: ts !== undefined ? ts : null });
+  },
+
   // Operations management
   async addOperation(accountId: number, amount: number, description: string): Promise<number> {
     return await invoke('add_operation', { accountId, amount, description });
@@ -92,14 +104,520 @@ export const api = {
     return await invoke('get_net_worth');
   },
 
+  async getNetWorthBreakdown(): Promise<NetWorth> {
+    return await invoke('get_net_worth_breakdown');
+  },
+
+  async getNetWorthHistory(from: number, to: number, interval: HistoryInterval): Promise<NetWorthPoint[]> {
+    return await invoke('get_net_worth_history', { from, to, interval });
+  },
+
   async getBalanceHistory(accountId: number): Promise<State[]> {
     return await invoke('get_balance_history', { accountId });
   },
 
+  async getBalanceHistoryResampled(
+    accountId: number,
+    from: number,
+    to: number,
+    interval: HistoryInterval,
+    aggregation: BalanceAggregation
+  ): Promise<BalanceBucket[]> {
+    return await invoke('get_balance_history_resampled', { accountId, from, to, interval, aggregation });
+  },
+
   async getAssetAllocation(): Promise<AssetAllocation[]> {
     return await invoke('get_asset_allocation');
   },
 
+  async setAllocationTarget(assetType: string, targetPercent: number, tolerancePercent: number): Promise<number> {
+    return await invoke('set_allocation_target', { assetType, targetPercent, tolerancePercent });
+  },
+
+  async listAllocationTargets(): Promise<AllocationTarget[]> {
+    return await invoke('list_allocation_targets');
+  },
+
+  async deleteAllocationTarget(assetType: string): Promise<void> {
+    return await invoke('delete_allocation_target', { assetType });
+  },
+
+  async getRebalancePlan(contribution?: number, contributionsOnly: boolean = false): Promise<RebalancePlan> {
+    return await invoke('get_rebalance_plan', {
+      contribution: contribution !== undefined ? contribution : null,
+      contributionsOnly,
+    });
+  },
+
+  async createTransfer(
+    fromAccountId: number,
+    toAccountId: number,
+    amount: number,
+    description: string,
+    ts?: number
+  ): Promise<number> {
+    return await invoke('create_transfer', {
+      fromAccountId,
+      toAccountId,
+      amount,
+      description,
+      ts: ts !== undefined ? ts : null,
+    });
+  },
+
+  async listTransfers(fromTs?: number, toTs?: number): Promise<Transfer[]> {
+    return await invoke('list_transfers', {
+      fromTs: fromTs !== undefined ? fromTs : null,
+      toTs: toTs !== undefined ? toTs : null,
+    });
+  },
+
+  async getCashFlow(from: number, to: number, interval: HistoryInterval, groupBy: CashFlowGroupBy): Promise<CashFlow[]> {
+    return await invoke('get_cash_flow', { from, to, interval, groupBy });
+  },
+  async createRecurringItem(input: RecurringItemInput): Promise<number> {
+    return await invoke('create_recurring_item', { input });
+  },
+
+  async listRecurringItems(): Promise<RecurringItem[]> {
+    return await invoke('list_recurring_items');
+  },
+
+  async deleteRecurringItem(itemId: number): Promise<void> {
+    return await invoke('delete_recurring_item', { itemId });
+  },
+
+  async forecastBalances(horizonDays: number): Promise<AccountForecast[]> {
+    return await invoke('forecast_balances', { horizonDays });
+  },
+  async listAnomalies(options?: AnomalyOptions): Promise<Anomaly[]> {
+    return await invoke('list_anomalies', { options: options !== undefined ? options : null });
+  },
+
+  async setAnomalyStatus(operationId: number, kind: AnomalyKind, status: AnomalyStatus): Promise<void> {
+    return await invoke('set_anomaly_status', { operationId, kind, status });
+  },
+  async setCategoryTaxKind(categoryId: number, taxKind: TaxCategoryKind | null): Promise<void> {
+    return await invoke('set_category_tax_kind', { categoryId, taxKind });
+  },
+
+  async generateTaxSummary(year: number): Promise<TaxSummaryExport> {
+    return await invoke('generate_tax_summary', { year });
+  },
+
+  async verifySignedExport(exportDoc: SignedExport): Promise<boolean> {
+    return await invoke('verify_signed_export', { export: exportDoc });
+  },
+  async previewCsvImport(accountId: number, content: Uint8Array, options: CsvImportOptions): Promise<ImportPreview> {
+    return await invoke('preview_csv_import', { accountId, content: Array.from(content), options });
+  },
+
+  async importCsv(accountId: number, fileName: string, content: Uint8Array, options: CsvImportOptions): Promise<ImportResult> {
+    return await invoke('import_csv', { accountId, fileName, content: Array.from(content), options });
+  },
+  async previewOfxImport(accountId: number, content: Uint8Array): Promise<StatementImportPreview> {
+    return await invoke('preview_ofx_import', { accountId, content: Array.from(content) });
+  },
+
+  async importOfx(accountId: number, fileName: string, content: Uint8Array): Promise<StatementImportResult> {
+    return await invoke('import_ofx', { accountId, fileName, content: Array.from(content) });
+  },
+  async previewCamt053Import(accountId: number, content: Uint8Array): Promise<StatementImportPreview> {
+    return await invoke('preview_camt053_import', { accountId, content: Array.from(content) });
+  },
+
+  async importCamt053(accountId: number, fileName: string, content: Uint8Array): Promise<StatementImportResult> {
+    return await invoke('import_camt053', { accountId, fileName, content: Array.from(content) });
+  },
+
+  async previewMt940Import(accountId: number, content: Uint8Array): Promise<StatementImportPreview> {
+    return await invoke('preview_mt940_import', { accountId, content: Array.from(content) });
+  },
+
+  async importMt940(accountId: number, fileName: string, content: Uint8Array): Promise<StatementImportResult> {
+    return await invoke('import_mt940', { accountId, fileName, content: Array.from(content) });
+  },
+
+  async exportPlainText(format: PlainTextFormat, currency: string): Promise<string> {
+    return await invoke('export_plain_text', { format, currency });
+  },
+
+  async importPlainText(format: PlainTextFormat, content: Uint8Array): Promise<PlainTextImportResult> {
+    return await invoke('import_plain_text', { format, content: Array.from(content) });
+  },
+
+  async listImportBatches(accountId?: number): Promise<ImportBatch[]> {
+    return await invoke('list_import_batches', { accountId: accountId !== undefined ? accountId : null });
+  },
+
+  async saveImportProfile(name: string, options: CsvImportOptions): Promise<number> {
+    return await invoke('save_import_profile', { name, options });
+  },
+
+  async listImportProfiles(): Promise<ImportProfile[]> {
+    return await invoke('list_import_profiles');
+  },
+
+  async deleteImportProfile(profileId: number): Promise<void> {
+    return await invoke('delete_import_profile', { profileId });
+  },
+
+
+
+
+
+  // Opening balances and balance assertions
+  async setOpeningBalance(accountId: number, balance: number, ts: number): Promise<number> {
+    return await invoke('set_opening_balance', { accountId, balance, ts });
+  },
+
+  async getOpeningBalance(accountId: number): Promise<OpeningBalance | null> {
+    return await invoke('get_opening_balance', { accountId });
+  },
+
+  async addBalanceAssertion(accountId: number, expectedBalance: number, ts: number): Promise<number> {
+    return await invoke('add_balance_assertion', { accountId, expectedBalance, ts });
+  },
+
+  async listBalanceAssertions(accountId?: number): Promise<BalanceAssertion[]> {
+    return await invoke('list_balance_assertions', {
+      accountId: accountId !== undefined ? accountId : null
+    });
+  },
+
+  async checkBalanceAssertions(accountId?: number): Promise<BalanceAssertionReport> {
+    return await invoke('check_balance_assertions', {
+      accountId: accountId !== undefined ? accountId : null
+    });
+  },
+
+  // Statement reconciliation
+  async startReconciliation(
+    accountId: number,
+    statementName: string,
+    periodStart: number,
+    periodEnd: number,
+    closingBalance: number | null,
+    lines: StatementLineInput[],
+  ): Promise<number> {
+    return await invoke('start_reconciliation', { accountId, statementName, periodStart, periodEnd, closingBalance, lines });
+  },
+
+  async getReconciliation(sessionId: number): Promise<ReconciliationSession> {
+    return await invoke('get_reconciliation', { sessionId });
+  },
+
+  async listReconciliations(accountId?: number): Promise<ReconciliationSession[]> {
+    return await invoke('list_reconciliations', {
+      accountId: accountId !== undefined ? accountId : null
+    });
+  },
+
+  async autoMatchStatement(sessionId: number, amountTolerance: number, dateToleranceDays: number): Promise<StatementMatch[]> {
+    return await invoke('auto_match_statement', { sessionId, amountTolerance, dateToleranceDays });
+  },
+
+  async confirmStatementMatch(lineId: number, operationId: number): Promise<void> {
+    return await invoke('confirm_statement_match', { lineId, operationId });
+  },
+
+  async createOperationFromStatementLine(lineId: number): Promise<number> {
+    return await invoke('create_operation_from_statement_line', { lineId });
+  },
+
+  async completeReconciliation(sessionId: number): Promise<ReconciliationSession> {
+    return await invoke('complete_reconciliation', { sessionId });
+  },
+
+  // Securities and investment accounts
+  async createSecurity(ticker: string, isin: string | null, name: string, assetClass: SecurityAssetClass): Promise<number> {
+    return await invoke('create_security', { ticker, isin, name, assetClass });
+  },
+
+  async listSecurities(): Promise<Security[]> {
+    return await invoke('list_securities');
+  },
+
+  async addSecurityPrice(securityId: number, price: number, ts: number): Promise<number> {
+    return await invoke('add_security_price', { securityId, price, ts });
+  },
+
+  async getPriceHistory(securityId: number): Promise<SecurityPrice[]> {
+    return await invoke('get_price_history', { securityId });
+  },
+
+  async addInvestmentTransaction(
+    accountId: number,
+    securityId: number,
+    kind: InvestmentTransactionKind,
+    quantity: number,
+    price: number,
+    fee: number,
+    ts: number,
+  ): Promise<number> {
+    return await invoke('add_investment_transaction', { accountId, securityId, kind, quantity, price, fee, ts });
+  },
+
+  async getInvestmentTransactions(accountId: number): Promise<InvestmentTransaction[]> {
+    return await invoke('get_investment_transactions', { accountId });
+  },
+
+  async getHoldings(accountId?: number): Promise<Holding[]> {
+    return await invoke('get_holdings', {
+      accountId: accountId !== undefined ? accountId : null
+    });
+  },
+
+  // Tax lots and realized gains
+  async sellSecurity(
+    accountId: number,
+    securityId: number,
+    quantity: number,
+    price: number,
+    fee: number,
+    ts: number,
+    lotMethod: LotMethod,
+    lotSelection?: LotSelection[],
+  ): Promise<number> {
+    return await invoke('sell_security', {
+      accountId, securityId, quantity, price, fee, ts, lotMethod,
+      lotSelection: lotSelection !== undefined ? lotSelection : null
+    });
+  },
+
+  async getTaxLots(accountId: number, securityId?: number): Promise<TaxLot[]> {
+    return await invoke('get_tax_lots', {
+      accountId,
+      securityId: securityId !== undefined ? securityId : null
+    });
+  },
+
+  async getRealizedGains(year: number): Promise<RealizedGainsReport> {
+    return await invoke('get_realized_gains', { year });
+  },
+  async getInvestmentReturns(from: number, to: number): Promise<PortfolioReturns> {
+    return await invoke('get_investment_returns', { from, to });
+  },
+
+
+  // Loan commands
+  async createLoan(
+    accountId: number,
+    name: string,
+    principal: number,
+    annualRate: number,
+    termMonths: number,
+    startTs: number,
+    fundingAccountId?: number,
+  ): Promise<number> {
+    return await invoke('create_loan', {
+      accountId,
+      name,
+      principal,
+      annualRate,
+      termMonths,
+      startTs,
+      fundingAccountId: fundingAccountId !== undefined ? fundingAccountId : null,
+    });
+  },
+
+  async listLoans(): Promise<Loan[]> {
+    return await invoke('list_loans');
+  },
+
+  async recordLoanPayment(loanId: number, fromAccountId: number, extraPrincipal: number, ts: number): Promise<number> {
+    return await invoke('record_loan_payment', { loanId, fromAccountId, extraPrincipal, ts });
+  },
+
+  async getLoanPayments(loanId: number): Promise<LoanPayment[]> {
+    return await invoke('get_loan_payments', { loanId });
+  },
+
+  async getAmortizationSchedule(loanId: number, extraMonthly?: number): Promise<AmortizationRow[]> {
+    return await invoke('get_amortization_schedule', {
+      loanId,
+      extraMonthly: extraMonthly !== undefined ? extraMonthly : null
+    });
+  },
+
+  async getLoanPayoffProjection(loanId: number, extraMonthly: number): Promise<LoanPayoffProjection> {
+    return await invoke('get_loan_payoff_projection', { loanId, extraMonthly });
+  },
+
+  // Payee commands
+  async createPayee(name: string): Promise<number> {
+    return await invoke('create_payee', { name });
+  },
+
+  async listPayees(): Promise<Payee[]> {
+    return await invoke('list_payees');
+  },
+
+  async addPayeeRule(payeeId: number, kind: PayeeRuleKind, pattern: string, priority: number = 0): Promise<number> {
+    return await invoke('add_payee_rule', { payeeId, kind, pattern, priority });
+  },
+
+  async listPayeeRules(payeeId?: number): Promise<PayeeRule[]> {
+    return await invoke('list_payee_rules', {
+      payeeId: payeeId !== undefined ? payeeId : null
+    });
+  },
+
+  async deletePayeeRule(ruleId: number): Promise<void> {
+    return await invoke('delete_payee_rule', { ruleId });
+  },
+
+  async setOperationPayee(operationId: number, payeeId: number | null): Promise<void> {
+    return await invoke('set_operation_payee', { operationId, payeeId });
+  },
+
+  async applyPayeeRules(overwrite: boolean = false): Promise<number> {
+    return await invoke('apply_payee_rules', { overwrite });
+  },
+
+  async mergePayees(targetId: number, sourceIds: number[]): Promise<PayeeMerge[]> {
+    return await invoke('merge_payees', { targetId, sourceIds });
+  },
+
+  async getSpendingByPayee(fromTs?: number, toTs?: number): Promise<PayeeSpending[]> {
+    return await invoke('get_spending_by_payee', {
+      fromTs: fromTs !== undefined ? fromTs : null,
+      toTs: toTs !== undefined ? toTs : null
+    });
+  },
+
+  // Categorization commands
+  async createCategory(name: string): Promise<number> {
+    return await invoke('create_category', { name });
+  },
+
+  async listCategories(): Promise<Category[]> {
+    return await invoke('list_categories');
+  },
+
+  async createTag(name: string): Promise<number> {
+    return await invoke('create_tag', { name });
+  },
+
+  async listTags(): Promise<Tag[]> {
+    return await invoke('list_tags');
+  },
+
+  async getOperationTags(operationId: number): Promise<Tag[]> {
+    return await invoke('get_operation_tags', { operationId });
+  },
+
+  async addOperationTag(operationId: number, tagId: number): Promise<void> {
+    return await invoke('add_operation_tag', { operationId, tagId });
+  },
+
+  async removeOperationTag(operationId: number, tagId: number): Promise<void> {
+    return await invoke('remove_operation_tag', { operationId, tagId });
+  },
+
+  async setOperationCategory(operationId: number, categoryId: number | null): Promise<void> {
+    return await invoke('set_operation_category', { operationId, categoryId });
+  },
+
+  async createCategorizationRule(rule: CategorizationRuleInput): Promise<number> {
+    return await invoke('create_categorization_rule', { rule });
+  },
+
+  async updateCategorizationRule(ruleId: number, rule: CategorizationRuleInput): Promise<number> {
+    return await invoke('update_categorization_rule', { ruleId, rule });
+  },
+
+  async deleteCategorizationRule(ruleId: number): Promise<void> {
+    return await invoke('delete_categorization_rule', { ruleId });
+  },
+
+  async listCategorizationRules(): Promise<CategorizationRule[]> {
+    return await invoke('list_categorization_rules');
+  },
+
+  async applyCategorizationRules(fromTs: number, toTs: number, dryRun: boolean = true): Promise<RuleChange[]> {
+    return await invoke('apply_categorization_rules', { fromTs, toTs, dryRun });
+  },
+
+  // Split commands
+  async setOperationSplits(operationId: number, splits: SplitLineInput[]): Promise<SplitLine[]> {
+    return await invoke('set_operation_splits', { operationId, splits });
+  },
+
+  async getOperationSplits(operationId: number): Promise<SplitLine[]> {
+    return await invoke('get_operation_splits', { operationId });
+  },
+
+  async getSpendingByCategory(fromTs?: number, toTs?: number): Promise<CategorySpending[]> {
+    return await invoke('get_spending_by_category', {
+      fromTs: fromTs !== undefined ? fromTs : null,
+      toTs: toTs !== undefined ? toTs : null
+    });
+  },
+
+  // Search commands
+  async setOperationNotes(operationId: number, notes: string | null): Promise<void> {
+    return await invoke('set_operation_notes', { operationId, notes });
+  },
+
+  async searchOperations(query: string, filters?: OperationFilters, limit?: number): Promise<OperationSearchHit[]> {
+    return await invoke('search_operations', {
+      query,
+      filters: filters !== undefined ? filters : null,
+      limit: limit !== undefined ? limit : null
+    });
+  },
+
+  async queryOperations(query: OperationQuery = {}): Promise<OperationPage> {
+    return await invoke('query_operations', { query });
+  },
+
+  // Attachment commands
+  async addAttachment(
+    ownerType: AttachmentOwnerType,
+    ownerId: number,
+    fileName: string,
+    mimeType: string,
+    data: Uint8Array,
+  ): Promise<number> {
+    return await invoke('add_attachment', { ownerType, ownerId, fileName, mimeType, data: Array.from(data) });
+  },
+
+  async listAttachments(ownerType: AttachmentOwnerType, ownerId: number): Promise<Attachment[]> {
+    return await invoke('list_attachments', { ownerType, ownerId });
+  },
+
+  async getAttachment(attachmentId: number): Promise<AttachmentContent> {
+    return await invoke('get_attachment', { attachmentId });
+  },
+
+  async deleteAttachment(attachmentId: number): Promise<void> {
+    return await invoke('delete_attachment', { attachmentId });
+  },
+
+  // Goal commands
+  async createGoal(
+    name: string,
+    targetAmount: number,
+    targetTs: number,
+    accountIds: number[],
+    tagIds: number[] = [],
+  ): Promise<number> {
+    return await invoke('create_goal', { name, targetAmount, targetTs, accountIds, tagIds });
+  },
+
+  async listGoals(): Promise<Goal[]> {
+    return await invoke('list_goals');
+  },
+
+  async deleteGoal(goalId: number): Promise<void> {
+    return await invoke('delete_goal', { goalId });
+  },
+
+  async getGoalProgress(goalId: number): Promise<GoalProgress> {
+    return await invoke('get_goal_progress', { goalId });
+  },
+
   // Verification commands
   async verifyEntry(versionId: number): Promise<boolean> {
     return await invoke('verify_entry', { versionId });
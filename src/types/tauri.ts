@@ -8,11 +8,15 @@ export interface DbResult {
   message: string;
 }
 
+export type AccountClass = 'asset' | 'liability' | 'equity' | 'income' | 'expense';
+
 export interface Account {
   id: number;
   name: string;
+  class: AccountClass;
   type: string;
   created_at: number;
+  closed_at: number | null;
 }
 
 export interface Operation {
@@ -21,6 +25,9 @@ export interface Operation {
   amount: number;
   description: string;
   ts: number;
+  payee_id: number | null;
+  category_id: number | null;
+  notes: string | null;
 }
 
 export interface State {
@@ -30,12 +37,694 @@ export interface State {
   ts: number;
 }
 
+export interface NetWorth {
+  assets: number;
+  liabilities: number;
+  net_worth: number;
+}
+
+export type HistoryInterval = 'day' | 'week' | 'month' | 'quarter';
+
+export type BalanceAggregation = 'last' | 'ohlc' | 'average';
+
+export interface BalanceBucket {
+  ts: number;
+  value: number;
+  open: number | null;
+  high: number | null;
+  low: number | null;
+  close: number | null;
+  filled: boolean;
+}
+
+export interface NetWorthPoint {
+  ts: number;
+  assets: number;
+  liabilities: number;
+  net_worth: number;
+}
+
 export interface AssetAllocation {
   type: string;
   total_balance: number;
   account_count: number;
 }
 
+export interface AllocationTarget {
+  id: number;
+  asset_type: string;
+  target_percent: number;
+  tolerance_percent: number;
+  updated_at: number;
+}
+
+export interface RebalanceLine {
+  asset_type: string;
+  current_value: number;
+  current_percent: number;
+  target_percent: number | null;
+  tolerance_percent: number | null;
+  drift_percent: number | null;
+  amount: number;
+  projected_value: number;
+  projected_percent: number;
+}
+
+export interface RebalanceTransfer {
+  from_asset_type: string | null;
+  to_asset_type: string;
+  amount: number;
+}
+
+export interface RebalancePlan {
+  total_value: number;
+  contribution: number;
+  contributions_only: boolean;
+  lines: RebalanceLine[];
+  transfers: RebalanceTransfer[];
+  unallocated: number;
+  within_bands_before: boolean;
+  within_bands_after: boolean;
+}
+
+export interface Transfer {
+  id: number;
+  from_account_id: number;
+  to_account_id: number;
+  from_operation_id: number;
+  to_operation_id: number;
+  amount: number;
+  description: string;
+  ts: number;
+}
+
+export type CashFlowGroupBy = 'account' | 'category' | 'payee';
+
+export interface CashFlow {
+  ts: number;
+  group_id: number | null;
+  group_name: string | null;
+  income: number;
+  expenses: number;
+  net: number;
+  line_count: number;
+}
+
+export interface RecurringItemInput {
+  account_id: number;
+  amount: number;
+  description: string;
+  payee_id: number | null;
+  interval: HistoryInterval;
+  next_ts: number;
+  end_ts: number | null;
+}
+
+export interface RecurringItem extends RecurringItemInput {
+  id: number;
+  created_at: number;
+}
+
+export interface ForecastPoint {
+  ts: number;
+  expected: number;
+  lower: number;
+  upper: number;
+}
+
+export interface AccountForecast {
+  account_id: number;
+  current_balance: number;
+  daily_trend: number;
+  daily_volatility: number;
+  points: ForecastPoint[];
+  first_negative_ts: number | null;
+  next_income_ts: number | null;
+  negative_before_income: boolean;
+}
+
+export type AnomalyKind = 'outlier_amount' | 'duplicate' | 'new_payee_large';
+
+export type AnomalyStatus = 'open' | 'acknowledged' | 'dismissed';
+
+export interface AnomalyOptions {
+  from_ts?: number | null;
+  to_ts?: number | null;
+  duplicate_window_days?: number | null;
+  large_amount?: number | null;
+  include_dismissed?: boolean;
+}
+
+export interface Anomaly {
+  operation: Operation;
+  kind: AnomalyKind;
+  score: number;
+  related_operation_id: number | null;
+  reason: string;
+  status: AnomalyStatus;
+}
+
+export interface TaxSummaryLine {
+  id: number | null;
+  name: string;
+  amount: number;
+  count: number;
+}
+
+export interface TaxSummary {
+  year: number;
+  from_ts: number;
+  to_ts: number;
+  income: TaxSummaryLine[];
+  total_income: number;
+  deductible_expenses: TaxSummaryLine[];
+  total_deductible: number;
+  interest: TaxSummaryLine[];
+  total_interest: number;
+  dividends: TaxSummaryLine[];
+  total_dividends: number;
+  realized_gains: RealizedGainsReport;
+}
+
+export interface SignedExport {
+  format: string;
+  content: string;
+  signature: string;
+  public_key: string;
+}
+
+export interface TaxSummaryExport {
+  summary: TaxSummary;
+  csv: SignedExport;
+  json: SignedExport;
+}
+
+export type ImportEncoding = 'utf8' | 'cp1251';
+
+export type AmountSign = 'signed' | 'inverted';
+
+export interface CsvColumnMapping {
+  date: number;
+  description: number[];
+  amount: number | null;
+  debit: number | null;
+  credit: number | null;
+}
+
+export interface CsvImportOptions {
+  delimiter: string;
+  encoding?: ImportEncoding;
+  date_format: string;
+  decimal_separator: string;
+  has_header: boolean;
+  skip_rows?: number;
+  columns: CsvColumnMapping;
+  sign?: AmountSign;
+}
+
+export interface ImportProfile {
+  id: number;
+  name: string;
+  options: CsvImportOptions;
+  created_at: number;
+  updated_at: number;
+}
+
+export type ImportLineStatus = 'new' | 'duplicate';
+
+export interface ImportLine {
+  line_number: number;
+  ts: number;
+  amount: number;
+  description: string;
+  external_id: string | null;
+  value_ts: number | null;
+  counterparty: string | null;
+  remittance_info: string | null;
+  end_to_end_id: string | null;
+  import_hash: string;
+  status: ImportLineStatus;
+}
+
+export interface ImportLineError {
+  line_number: number;
+  message: string;
+}
+
+export interface ImportPreview {
+  account_id: number;
+  lines: ImportLine[];
+  errors: ImportLineError[];
+  new_count: number;
+  duplicate_count: number;
+  new_amount: number;
+}
+
+export interface ImportBatch {
+  id: number;
+  account_id: number;
+  source: string;
+  file_name: string;
+  file_sha256: string;
+  line_count: number;
+  imported_count: number;
+  duplicate_count: number;
+  imported_at: number;
+}
+
+export interface ImportResult {
+  batch: ImportBatch;
+  operation_ids: number[];
+  lines: ImportLine[];
+}
+
+export interface StatementBalanceCheck {
+  as_of: number;
+  statement_balance: number;
+  book_balance: number;
+  difference: number;
+  matches: boolean;
+}
+
+export interface StatementImportPreview {
+  preview: ImportPreview;
+  balance_check: StatementBalanceCheck | null;
+}
+
+export interface StatementImportResult {
+  result: ImportResult;
+  balance_check: StatementBalanceCheck | null;
+}
+
+export type PlainTextFormat = 'beancount' | 'ledger';
+
+export interface PlainTextImportResult {
+  accounts_created: number;
+  accounts_closed: number;
+  categories_created: number;
+  operations_created: number;
+  transfers_created: number;
+  opening_balances_set: number;
+  assertions_created: number;
+  skipped_count: number;
+  warnings: ImportLineError[];
+}
+
+export interface OpeningBalance {
+  id: number;
+  account_id: number;
+  balance: number;
+  ts: number;
+}
+
+export interface BalanceAssertion {
+  id: number;
+  account_id: number;
+  expected_balance: number;
+  ts: number;
+  created_at: number;
+}
+
+export interface BalanceAssertionResult {
+  assertion_id: number;
+  account_id: number;
+  ts: number;
+  expected_balance: number;
+  actual_balance: number;
+  difference: number;
+  passed: boolean;
+}
+
+export interface BalanceAssertionReport {
+  results: BalanceAssertionResult[];
+  first_failure: BalanceAssertionResult | null;
+  passed_count: number;
+  failed_count: number;
+}
+
+export interface StatementLineInput {
+  ts: number;
+  amount: number;
+  description: string;
+}
+
+export interface StatementLine {
+  id: number;
+  session_id: number;
+  ts: number;
+  amount: number;
+  description: string;
+  operation_id: number | null;
+  match_status: 'unmatched' | 'suggested' | 'confirmed' | 'created';
+}
+
+export interface ReconciliationSession {
+  id: number;
+  account_id: number;
+  statement_name: string;
+  period_start: number;
+  period_end: number;
+  closing_balance: number | null;
+  ledger_balance: number | null;
+  status: 'open' | 'reconciled';
+  created_at: number;
+  reconciled_at: number | null;
+  lines: StatementLine[];
+}
+
+export interface StatementMatch {
+  line_id: number;
+  operation_id: number;
+  score: number;
+}
+
+export type SecurityAssetClass = 'stock' | 'bond' | 'etf' | 'fund' | 'crypto' | 'commodity' | 'other';
+
+export interface Security {
+  id: number;
+  ticker: string;
+  isin: string | null;
+  name: string;
+  asset_class: SecurityAssetClass;
+  created_at: number;
+}
+
+export interface SecurityPrice {
+  id: number;
+  security_id: number;
+  price: number;
+  ts: number;
+}
+
+export type InvestmentTransactionKind = 'buy' | 'sell' | 'dividend';
+
+export interface InvestmentTransaction {
+  id: number;
+  account_id: number;
+  security_id: number;
+  kind: InvestmentTransactionKind;
+  quantity: number;
+  price: number;
+  fee: number;
+  operation_id: number;
+  ts: number;
+}
+
+export interface Holding {
+  account_id: number;
+  security_id: number;
+  ticker: string;
+  asset_class: SecurityAssetClass;
+  quantity: number;
+  price: number | null;
+  price_ts: number | null;
+  market_value: number;
+}
+
+export type LotMethod = 'fifo' | 'lifo' | 'specific';
+
+export interface TaxLot {
+  id: number;
+  account_id: number;
+  security_id: number;
+  transaction_id: number;
+  quantity: number;
+  remaining_quantity: number;
+  cost_per_unit: number;
+  acquired_ts: number;
+}
+
+export interface LotSelection {
+  lot_id: number;
+  quantity: number;
+}
+
+export interface RealizedGain {
+  sale_transaction_id: number;
+  lot_id: number;
+  account_id: number;
+  security_id: number;
+  ticker: string;
+  quantity: number;
+  acquired_ts: number;
+  disposed_ts: number;
+  holding_period_days: number;
+  long_term: boolean;
+  cost_basis: number;
+  proceeds: number;
+  gain: number;
+}
+
+export interface RealizedGainsReport {
+  year: number;
+  gains: RealizedGain[];
+  total_proceeds: number;
+  total_cost_basis: number;
+  total_gain: number;
+  short_term_gain: number;
+  long_term_gain: number;
+}
+
+export interface InvestmentReturn {
+  account_id: number | null;
+  from: number;
+  to: number;
+  start_value: number;
+  end_value: number;
+  net_contributions: number;
+  gain: number;
+  xirr: number | null;
+  xirr_error: string | null;
+  twr: number | null;
+  twr_annualized: number | null;
+  twr_error: string | null;
+}
+
+export interface PortfolioReturns {
+  accounts: InvestmentReturn[];
+  portfolio: InvestmentReturn;
+}
+
+export interface Loan {
+  id: number;
+  account_id: number;
+  name: string;
+  principal: number;
+  annual_rate: number;
+  term_months: number;
+  start_ts: number;
+  monthly_payment: number;
+  created_at: number;
+  funding_operation_id: number | null;
+}
+
+export interface LoanPayment {
+  id: number;
+  loan_id: number;
+  payment_number: number;
+  from_account_id: number;
+  principal: number;
+  interest: number;
+  extra_principal: number;
+  principal_operation_id: number;
+  interest_operation_id: number | null;
+  loan_operation_id: number;
+  ts: number;
+}
+
+export interface AmortizationRow {
+  payment_number: number;
+  due_ts: number;
+  payment: number;
+  principal: number;
+  interest: number;
+  extra_principal: number;
+  remaining_balance: number;
+}
+
+export interface LoanPayoffProjection {
+  loan_id: number;
+  outstanding_principal: number;
+  extra_monthly: number;
+  baseline_payoff_ts: number | null;
+  baseline_total_interest: number;
+  baseline_payments: number;
+  projected_payoff_ts: number | null;
+  projected_total_interest: number;
+  projected_payments: number;
+  months_saved: number;
+  interest_saved: number;
+  schedule: AmortizationRow[];
+}
+
+export type PayeeRuleKind = 'alias' | 'regex';
+
+export interface Payee {
+  id: number;
+  name: string;
+  created_at: number;
+}
+
+export interface PayeeRule {
+  id: number;
+  payee_id: number;
+  kind: PayeeRuleKind;
+  pattern: string;
+  priority: number;
+  created_at: number;
+}
+
+export interface PayeeMerge {
+  source_id: number;
+  target_id: number;
+  operations_moved: number;
+  rules_moved: number;
+}
+
+export interface PayeeSpending {
+  payee_id: number | null;
+  payee_name: string | null;
+  total_spent: number;
+  operation_count: number;
+}
+
+export type TaxCategoryKind = 'deductible' | 'interest';
+
+export interface Category {
+  id: number;
+  name: string;
+  created_at: number;
+  tax_kind: TaxCategoryKind | null;
+}
+
+export interface Tag {
+  id: number;
+  name: string;
+  created_at: number;
+}
+
+export interface CategorizationRuleInput {
+  name: string;
+  description_pattern: string | null;
+  account_id: number | null;
+  amount_gt: number | null;
+  amount_lt: number | null;
+  set_category_id: number | null;
+  set_payee_id: number | null;
+  add_tag_id: number | null;
+  priority: number;
+  enabled: boolean;
+}
+
+export interface CategorizationRule extends CategorizationRuleInput {
+  id: number;
+  version: number;
+  created_at: number;
+  updated_at: number;
+}
+
+export type RuleChangeField = 'category' | 'payee' | 'tag';
+
+export interface RuleChange {
+  rule_id: number;
+  rule_version: number;
+  operation_id: number;
+  field: RuleChangeField;
+  old_value: number | null;
+  new_value: number;
+}
+
+export interface SplitLineInput {
+  category_id: number | null;
+  amount: number;
+  memo: string;
+}
+
+export interface SplitLine extends SplitLineInput {
+  id: number;
+  operation_id: number;
+}
+
+export interface CategorySpending {
+  category_id: number | null;
+  category_name: string | null;
+  total_spent: number;
+  line_count: number;
+}
+
+export interface OperationFilters {
+  account_ids?: number[] | null;
+  from_ts?: number | null;
+  to_ts?: number | null;
+  min_amount?: number | null;
+  max_amount?: number | null;
+}
+
+export type OperationSort = 'ts_desc' | 'ts_asc' | 'amount_desc' | 'amount_asc';
+
+export interface OperationQuery extends OperationFilters {
+  text?: string | null;
+  sort?: OperationSort;
+  cursor?: string | null;
+  limit?: number | null;
+}
+
+export interface OperationPage {
+  operations: Operation[];
+  next_cursor: string | null;
+  total_count: number;
+  total_amount: number;
+}
+
+export interface OperationSearchHit {
+  operation: Operation;
+  rank: number;
+  snippet: string;
+}
+
+export type AttachmentOwnerType = 'operation' | 'account';
+
+export interface Attachment {
+  id: number;
+  owner_type: AttachmentOwnerType;
+  owner_id: number;
+  file_name: string;
+  mime_type: string;
+  size: number;
+  sha256: string;
+  created_at: number;
+}
+
+export interface AttachmentContent {
+  attachment: Attachment;
+  data: number[];
+  integrity_ok: boolean;
+}
+
+export interface Goal {
+  id: number;
+  name: string;
+  target_amount: number;
+  target_ts: number;
+  account_ids: number[];
+  tag_ids: number[];
+  created_at: number;
+}
+
+export type GoalStatus = 'reached' | 'on_track' | 'behind' | 'overdue';
+
+export interface GoalProgress {
+  goal_id: number;
+  current_amount: number;
+  target_amount: number;
+  remaining_amount: number;
+  months_remaining: number;
+  required_monthly_contribution: number;
+  recent_monthly_contribution: number;
+  projected_amount: number;
+  status: GoalStatus;
+}
+
 export interface VersionLogRecord {
   id: number;
   entity: string;